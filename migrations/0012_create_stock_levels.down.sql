@@ -0,0 +1,2 @@
+-- reverses the changes in 0012_create_stock_levels.up.sql
+DROP TABLE IF EXISTS stock_levels;
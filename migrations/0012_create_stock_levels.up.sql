@@ -0,0 +1,16 @@
+-- on hand and reserved quantities per item; available is derived as on_hand - reserved
+CREATE TABLE stock_levels
+(
+    id              serial PRIMARY KEY,
+    item_id         uuid        NOT NULL UNIQUE,
+    on_hand         integer     NOT NULL DEFAULT 0,
+    reserved        integer     NOT NULL DEFAULT 0,
+    created_by      text        NOT NULL,
+    created_at      timestamptz NOT NULL DEFAULT now(),
+    last_changed_by text        NOT NULL,
+    last_update     timestamptz NOT NULL DEFAULT now(),
+    CONSTRAINT fk_stock_levels_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id) ON DELETE CASCADE,
+    CONSTRAINT ck_stock_levels_on_hand_not_negative CHECK (on_hand >= 0),
+    CONSTRAINT ck_stock_levels_reserved_not_negative CHECK (reserved >= 0),
+    CONSTRAINT ck_stock_levels_reserved_within_on_hand CHECK (reserved <= on_hand)
+);
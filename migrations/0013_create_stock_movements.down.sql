@@ -0,0 +1,16 @@
+-- reverses the changes in 0013_create_stock_movements.up.sql
+ALTER TABLE stock_levels
+    ADD COLUMN on_hand integer NOT NULL DEFAULT 0;
+
+UPDATE stock_levels s
+SET on_hand = m.on_hand
+FROM (SELECT item_id, sum(quantity)::integer AS on_hand FROM stock_movements GROUP BY item_id) m
+WHERE m.item_id = s.item_id;
+
+ALTER TABLE stock_levels
+    ADD CONSTRAINT ck_stock_levels_on_hand_not_negative CHECK (on_hand >= 0),
+    ADD CONSTRAINT ck_stock_levels_reserved_within_on_hand CHECK (reserved <= on_hand);
+
+DROP TABLE IF EXISTS stock_movements;
+DROP FUNCTION IF EXISTS reject_stock_movement_changes();
+DROP TYPE IF EXISTS stock_movement_type;
@@ -0,0 +1,40 @@
+-- append-only ledger of stock changes; on hand is the sum of an item's movements
+CREATE TYPE stock_movement_type AS ENUM ('receipt', 'adjustment', 'sale', 'return', 'write_off');
+
+CREATE TABLE stock_movements
+(
+    id            serial PRIMARY KEY,
+    alt_id        uuid                NOT NULL DEFAULT gen_random_uuid() UNIQUE,
+    item_id       uuid                NOT NULL,
+    movement_type stock_movement_type NOT NULL,
+    quantity      integer             NOT NULL,
+    note          text,
+    created_by    text                NOT NULL,
+    created_at    timestamptz         NOT NULL DEFAULT now(),
+    CONSTRAINT fk_stock_movements_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id),
+    CONSTRAINT ck_stock_movements_quantity_not_zero CHECK (quantity <> 0)
+);
+
+CREATE INDEX idx_stock_movements_item_id ON stock_movements (item_id, id);
+
+CREATE FUNCTION reject_stock_movement_changes() RETURNS trigger AS
+$$
+BEGIN
+    RAISE EXCEPTION 'stock_movements is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_stock_movements_append_only
+    BEFORE UPDATE OR DELETE
+    ON stock_movements
+    FOR EACH ROW
+EXECUTE FUNCTION reject_stock_movement_changes();
+
+-- carry existing counts over as opening balances before on_hand stops being stored
+INSERT INTO stock_movements (item_id, movement_type, quantity, note, created_by, created_at)
+SELECT item_id, 'adjustment', on_hand, 'opening balance', last_changed_by, last_update
+FROM stock_levels
+WHERE on_hand <> 0;
+
+ALTER TABLE stock_levels
+    DROP COLUMN on_hand;
@@ -0,0 +1,17 @@
+-- reverses the changes in 0014_add_quantity_and_unit_price_to_invoices_items.up.sql
+CREATE TEMPORARY TABLE invoices_items_expanded AS
+SELECT invoice_id, item_id
+FROM invoices_items, generate_series(1, quantity);
+
+DELETE FROM invoices_items;
+
+ALTER TABLE invoices_items
+    DROP CONSTRAINT pk_invoices_items,
+    DROP COLUMN quantity,
+    DROP COLUMN unit_price;
+
+INSERT INTO invoices_items (invoice_id, item_id)
+SELECT invoice_id, item_id
+FROM invoices_items_expanded;
+
+DROP TABLE invoices_items_expanded;
@@ -0,0 +1,22 @@
+-- turn invoices_items into invoice lines: one row per (invoice, item) with a quantity and the price charged
+CREATE TEMPORARY TABLE invoices_items_collapsed AS
+SELECT invoice_id, item_id, count(*)::integer AS quantity
+FROM invoices_items
+GROUP BY invoice_id, item_id;
+
+DELETE FROM invoices_items;
+
+ALTER TABLE invoices_items
+    ADD COLUMN quantity   integer NOT NULL DEFAULT 1,
+    ADD COLUMN unit_price numeric NOT NULL,
+    ADD CONSTRAINT pk_invoices_items PRIMARY KEY (invoice_id, item_id),
+    ADD CONSTRAINT ck_invoices_items_quantity_positive CHECK (quantity > 0),
+    ADD CONSTRAINT ck_invoices_items_unit_price_not_negative CHECK (unit_price >= 0);
+
+-- existing lines never captured a price, so use the current catalogue price
+INSERT INTO invoices_items (invoice_id, item_id, quantity, unit_price)
+SELECT c.invoice_id, c.item_id, c.quantity, i.unit_price
+FROM invoices_items_collapsed c
+JOIN items i ON i.alt_id = c.item_id;
+
+DROP TABLE invoices_items_collapsed;
@@ -0,0 +1,6 @@
+-- reverses the changes in 0015_add_totals_breakdown_to_invoices.up.sql
+ALTER TABLE invoices
+    DROP COLUMN subtotal,
+    DROP COLUMN tax_rate,
+    DROP COLUMN tax,
+    DROP COLUMN discount;
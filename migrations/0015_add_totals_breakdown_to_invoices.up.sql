@@ -0,0 +1,18 @@
+-- invoice totals are computed from the invoice lines: total = max(subtotal - discount, 0) + tax
+ALTER TABLE invoices
+    ADD COLUMN subtotal numeric NOT NULL DEFAULT 0,
+    ADD COLUMN tax_rate numeric NOT NULL DEFAULT 0,
+    ADD COLUMN tax      numeric NOT NULL DEFAULT 0,
+    ADD COLUMN discount numeric NOT NULL DEFAULT 0,
+    ADD CONSTRAINT ck_invoices_tax_rate_range CHECK (tax_rate >= 0 AND tax_rate <= 1),
+    ADD CONSTRAINT ck_invoices_discount_not_negative CHECK (discount >= 0);
+
+-- invoices with lines are brought in line with them; invoices without lines keep the total they were given
+UPDATE invoices i
+SET subtotal = coalesce(l.subtotal, i.total),
+    total    = coalesce(l.subtotal, i.total)
+FROM invoices i2
+LEFT JOIN (SELECT invoice_id, sum(quantity * unit_price) AS subtotal
+           FROM invoices_items
+           GROUP BY invoice_id) l ON l.invoice_id = i2.alt_id
+WHERE i2.id = i.id;
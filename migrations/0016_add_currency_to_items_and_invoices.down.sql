@@ -0,0 +1,8 @@
+-- reverses the changes in 0016_add_currency_to_items_and_invoices.up.sql
+ALTER TABLE invoices
+    DROP COLUMN currency;
+
+ALTER TABLE items
+    DROP COLUMN currency;
+
+DROP TYPE currency_code;
@@ -0,0 +1,8 @@
+-- prices and invoice amounts are in the currency of their item or invoice; everything recorded so far was USD
+CREATE TYPE currency_code AS ENUM ('USD', 'EUR', 'GBP');
+
+ALTER TABLE items
+    ADD COLUMN currency currency_code NOT NULL DEFAULT 'USD';
+
+ALTER TABLE invoices
+    ADD COLUMN currency currency_code NOT NULL DEFAULT 'USD';
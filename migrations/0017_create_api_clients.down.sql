@@ -0,0 +1,2 @@
+-- reverses the changes in 0017_create_api_clients.up.sql
+DROP TABLE api_clients;
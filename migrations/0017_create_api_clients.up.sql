@@ -0,0 +1,14 @@
+-- credentials for consumers of the API; secrets are stored as argon2 PHC strings, never in plain text
+CREATE TABLE api_clients
+(
+    id              serial PRIMARY KEY,
+    alt_id          uuid        NOT NULL DEFAULT gen_random_uuid() UNIQUE,
+    client_id       text        NOT NULL UNIQUE,
+    name            text        NOT NULL,
+    secret_hash     text        NOT NULL,
+    revoked_at      timestamptz,
+    created_by      text        NOT NULL,
+    created_at      timestamptz NOT NULL DEFAULT now(),
+    last_changed_by text        NOT NULL,
+    last_update     timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,5 @@
+-- reverses the changes in 0018_add_scopes_to_api_clients.up.sql
+ALTER TABLE api_clients
+    DROP COLUMN scopes;
+
+DROP TYPE api_scope;
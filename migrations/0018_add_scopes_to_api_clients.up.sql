@@ -0,0 +1,16 @@
+-- what each API client may do; clients registered before scopes existed keep the full access they had
+CREATE TYPE api_scope AS ENUM (
+    'persons:read',
+    'persons:write',
+    'items:read',
+    'items:write',
+    'invoices:read',
+    'invoices:write',
+    'clients:admin'
+);
+
+ALTER TABLE api_clients
+    ADD COLUMN scopes api_scope[] NOT NULL DEFAULT enum_range(NULL::api_scope);
+
+ALTER TABLE api_clients
+    ALTER COLUMN scopes SET DEFAULT '{}';
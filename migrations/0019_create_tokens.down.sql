@@ -0,0 +1,4 @@
+-- reverses the changes in 0019_create_tokens.up.sql
+DROP TABLE refresh_tokens;
+
+DROP TABLE access_tokens;
@@ -0,0 +1,31 @@
+-- every access token issued is recorded by its jti so it can be revoked before it expires. Tokens obtained
+-- from the same client credentials grant, and everything refreshed from it, share a family.
+CREATE TABLE access_tokens
+(
+    jti        uuid PRIMARY KEY,
+    family     uuid        NOT NULL,
+    client_id  text        NOT NULL REFERENCES api_clients (client_id),
+    expires_at timestamptz NOT NULL,
+    revoked_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX access_tokens_family_idx ON access_tokens (family);
+CREATE INDEX access_tokens_client_id_idx ON access_tokens (client_id);
+
+-- refresh tokens are opaque; only their SHA-256 digest is stored. Each can be used once, and presenting a used
+-- one again revokes its whole family.
+CREATE TABLE refresh_tokens
+(
+    id         serial PRIMARY KEY,
+    token_hash text        NOT NULL UNIQUE,
+    family     uuid        NOT NULL,
+    client_id  text        NOT NULL REFERENCES api_clients (client_id),
+    expires_at timestamptz NOT NULL,
+    used_at    timestamptz,
+    revoked_at timestamptz,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX refresh_tokens_family_idx ON refresh_tokens (family);
+CREATE INDEX refresh_tokens_client_id_idx ON refresh_tokens (client_id);
@@ -0,0 +1,8 @@
+-- reverses the changes in 0020_create_audit_log.up.sql
+DROP TABLE audit_log;
+
+DROP FUNCTION reject_audit_log_changes();
+
+DROP TYPE audit_action;
+
+DROP TYPE audit_resource;
@@ -0,0 +1,34 @@
+-- full change history of persons, items and invoices. Each row holds the record as it was before and after one
+-- change; before is null for a create and after is null for a delete. There is deliberately no foreign key to the
+-- record so its history outlives it.
+CREATE TYPE audit_resource AS ENUM ('person', 'item', 'invoice');
+
+CREATE TYPE audit_action AS ENUM ('create', 'update', 'delete');
+
+CREATE TABLE audit_log
+(
+    id            serial PRIMARY KEY,
+    resource_type audit_resource NOT NULL,
+    resource_id   uuid           NOT NULL,
+    action        audit_action   NOT NULL,
+    actor         text           NOT NULL,
+    before        jsonb,
+    after         jsonb,
+    created_at    timestamptz    NOT NULL DEFAULT now(),
+    CONSTRAINT ck_audit_log_has_snapshot CHECK (before IS NOT NULL OR after IS NOT NULL)
+);
+
+CREATE INDEX idx_audit_log_resource ON audit_log (resource_type, resource_id, id);
+
+CREATE FUNCTION reject_audit_log_changes() RETURNS trigger AS
+$$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_audit_log_append_only
+    BEFORE UPDATE OR DELETE
+    ON audit_log
+    FOR EACH ROW
+EXECUTE FUNCTION reject_audit_log_changes();
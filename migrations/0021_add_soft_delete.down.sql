@@ -0,0 +1,16 @@
+-- reverses the changes in 0021_add_soft_delete.up.sql
+-- enum values cannot be dropped, so audit_action keeps 'restore'
+ALTER TABLE invoices
+    DROP CONSTRAINT fk_invoices_user_id;
+ALTER TABLE invoices
+    ADD CONSTRAINT fk_invoices_user_id FOREIGN KEY (user_id) REFERENCES persons (alt_id) ON DELETE CASCADE;
+ALTER TABLE invoices_items
+    DROP CONSTRAINT fk_invoices_items_invoice_alt_id,
+    DROP CONSTRAINT fk_invoices_items_item_alt_id;
+ALTER TABLE invoices_items
+    ADD CONSTRAINT fk_invoices_items_invoice_alt_id FOREIGN KEY (invoice_id) REFERENCES invoices (alt_id) ON DELETE CASCADE,
+    ADD CONSTRAINT fk_invoices_items_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id) ON DELETE CASCADE;
+
+ALTER TABLE invoices DROP COLUMN deleted_at;
+ALTER TABLE items DROP COLUMN deleted_at;
+ALTER TABLE persons DROP COLUMN deleted_at;
@@ -0,0 +1,18 @@
+-- persons, items and invoices are soft deleted so invoice history survives them
+ALTER TABLE persons ADD COLUMN deleted_at timestamptz;
+ALTER TABLE items ADD COLUMN deleted_at timestamptz;
+ALTER TABLE invoices ADD COLUMN deleted_at timestamptz;
+
+-- rows are no longer removed by the API, so refuse a hard delete that would take lines or invoices with it
+ALTER TABLE invoices_items
+    DROP CONSTRAINT fk_invoices_items_invoice_alt_id,
+    DROP CONSTRAINT fk_invoices_items_item_alt_id;
+ALTER TABLE invoices_items
+    ADD CONSTRAINT fk_invoices_items_invoice_alt_id FOREIGN KEY (invoice_id) REFERENCES invoices (alt_id),
+    ADD CONSTRAINT fk_invoices_items_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id);
+ALTER TABLE invoices
+    DROP CONSTRAINT fk_invoices_user_id;
+ALTER TABLE invoices
+    ADD CONSTRAINT fk_invoices_user_id FOREIGN KEY (user_id) REFERENCES persons (alt_id);
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'restore';
@@ -0,0 +1,13 @@
+-- reverses the changes in 0022_add_invoice_status.up.sql
+ALTER TABLE invoices
+    ADD COLUMN paid boolean NOT NULL DEFAULT false;
+
+-- a refunded invoice had been paid before it was refunded
+UPDATE invoices
+SET paid = true
+WHERE status IN ('paid', 'refunded');
+
+ALTER TABLE invoices
+    DROP COLUMN status;
+
+DROP TYPE invoice_status;
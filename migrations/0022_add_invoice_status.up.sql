@@ -0,0 +1,13 @@
+-- invoices move through draft -> issued -> paid -> refunded, or are voided before they are paid
+CREATE TYPE invoice_status AS ENUM ('draft', 'issued', 'paid', 'void', 'refunded');
+
+ALTER TABLE invoices
+    ADD COLUMN status invoice_status NOT NULL DEFAULT 'draft';
+
+-- an invoice could only be marked paid once it had been sent, so paid ones were issued; the rest are still drafts
+UPDATE invoices
+SET status = 'paid'
+WHERE paid;
+
+ALTER TABLE invoices
+    DROP COLUMN paid;
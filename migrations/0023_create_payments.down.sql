@@ -0,0 +1,8 @@
+-- reverses the changes in 0023_create_payments.up.sql
+ALTER TABLE invoices
+    DROP COLUMN amount_paid;
+
+DROP TRIGGER trg_payments_append_only ON payments;
+DROP FUNCTION reject_payment_changes();
+DROP TABLE payments;
+DROP TYPE payment_method;
@@ -0,0 +1,43 @@
+-- append-only record of money received against an invoice; an invoice can be settled by several payments
+CREATE TYPE payment_method AS ENUM ('cash', 'card', 'bank_transfer', 'cheque', 'other');
+
+CREATE TABLE payments
+(
+    id         serial PRIMARY KEY,
+    alt_id     uuid           NOT NULL DEFAULT gen_random_uuid() UNIQUE,
+    invoice_id uuid           NOT NULL,
+    amount     numeric        NOT NULL,
+    currency   currency_code  NOT NULL,
+    method     payment_method NOT NULL,
+    reference  text,
+    paid_at    timestamptz    NOT NULL DEFAULT now(),
+    created_by text           NOT NULL,
+    created_at timestamptz    NOT NULL DEFAULT now(),
+    CONSTRAINT fk_payments_invoice_alt_id FOREIGN KEY (invoice_id) REFERENCES invoices (alt_id),
+    CONSTRAINT ck_payments_amount_positive CHECK (amount > 0)
+);
+
+CREATE INDEX idx_payments_invoice_id ON payments (invoice_id, id);
+
+CREATE FUNCTION reject_payment_changes() RETURNS trigger AS
+$$
+BEGIN
+    RAISE EXCEPTION 'payments is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_payments_append_only
+    BEFORE UPDATE OR DELETE
+    ON payments
+    FOR EACH ROW
+EXECUTE FUNCTION reject_payment_changes();
+
+-- kept in step with the invoice's payments so the balance is available wherever the invoice is read
+ALTER TABLE invoices
+    ADD COLUMN amount_paid numeric NOT NULL DEFAULT 0,
+    ADD CONSTRAINT ck_invoices_amount_paid_not_negative CHECK (amount_paid >= 0);
+
+-- invoices marked paid before payments were recorded are taken as paid in full
+UPDATE invoices
+SET amount_paid = total
+WHERE status IN ('paid', 'refunded');
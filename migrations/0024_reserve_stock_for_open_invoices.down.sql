@@ -0,0 +1,10 @@
+-- reverses the changes in 0024_reserve_stock_for_open_invoices.up.sql
+UPDATE stock_levels s
+SET reserved = greatest(s.reserved - r.reserved, 0)
+FROM (SELECT ii.item_id, sum(ii.quantity) AS reserved
+      FROM invoices_items ii
+               JOIN invoices v ON v.alt_id = ii.invoice_id
+      WHERE v.status IN ('draft', 'issued')
+        AND v.deleted_at IS NULL
+      GROUP BY ii.item_id) r
+WHERE r.item_id = s.item_id;
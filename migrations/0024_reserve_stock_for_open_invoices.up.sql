@@ -0,0 +1,12 @@
+-- lines on draft and issued invoices now hold a reservation; carry the existing ones over
+INSERT INTO stock_levels (item_id, reserved, created_by, last_changed_by)
+SELECT ii.item_id, sum(ii.quantity), 'system', 'system'
+FROM invoices_items ii
+         JOIN invoices v ON v.alt_id = ii.invoice_id
+WHERE v.status IN ('draft', 'issued')
+  AND v.deleted_at IS NULL
+GROUP BY ii.item_id
+ON CONFLICT (item_id) DO UPDATE
+    SET reserved        = stock_levels.reserved + EXCLUDED.reserved,
+        last_changed_by = EXCLUDED.last_changed_by,
+        last_update     = now();
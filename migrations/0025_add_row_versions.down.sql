@@ -0,0 +1,14 @@
+-- reverses the changes in 0025_add_row_versions.up.sql
+DROP TRIGGER IF EXISTS trg_invoices_version ON invoices;
+DROP TRIGGER IF EXISTS trg_items_version ON items;
+DROP TRIGGER IF EXISTS trg_persons_version ON persons;
+DROP FUNCTION IF EXISTS bump_row_version();
+
+ALTER TABLE invoices
+    DROP COLUMN version;
+
+ALTER TABLE items
+    DROP COLUMN version;
+
+ALTER TABLE persons
+    DROP COLUMN version;
@@ -0,0 +1,35 @@
+-- every change to a person, item or invoice bumps its version, which clients send back in If-Match
+ALTER TABLE persons
+    ADD COLUMN version bigint NOT NULL DEFAULT 1;
+
+ALTER TABLE items
+    ADD COLUMN version bigint NOT NULL DEFAULT 1;
+
+ALTER TABLE invoices
+    ADD COLUMN version bigint NOT NULL DEFAULT 1;
+
+CREATE FUNCTION bump_row_version() RETURNS trigger AS
+$$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_persons_version
+    BEFORE UPDATE
+    ON persons
+    FOR EACH ROW
+EXECUTE FUNCTION bump_row_version();
+
+CREATE TRIGGER trg_items_version
+    BEFORE UPDATE
+    ON items
+    FOR EACH ROW
+EXECUTE FUNCTION bump_row_version();
+
+CREATE TRIGGER trg_invoices_version
+    BEFORE UPDATE
+    ON invoices
+    FOR EACH ROW
+EXECUTE FUNCTION bump_row_version();
@@ -0,0 +1,2 @@
+-- reverses the changes in 0026_create_idempotency_keys.up.sql
+DROP TABLE idempotency_keys;
@@ -0,0 +1,17 @@
+-- the response to a POST sent with an Idempotency-Key, so a retry with the same key gets it back instead of
+-- repeating the request. A key is held with no response while its first request runs, and is free again once
+-- expires_at passes. The response's headers, e.g. its ETag and Location, are kept as [name, value] pairs.
+CREATE TABLE idempotency_keys
+(
+    client_id        text        NOT NULL REFERENCES api_clients (client_id),
+    key              text        NOT NULL,
+    request_hash     text        NOT NULL,
+    response_status  smallint,
+    response_body    bytea,
+    response_headers jsonb       NOT NULL DEFAULT '[]',
+    expires_at       timestamptz NOT NULL,
+    created_at       timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (client_id, key)
+);
+
+CREATE INDEX idempotency_keys_expires_at_idx ON idempotency_keys (expires_at);
@@ -0,0 +1,5 @@
+-- reverses the changes in 0027_add_item_search.up.sql
+DROP INDEX IF EXISTS items_search_idx;
+
+ALTER TABLE items
+    DROP COLUMN IF EXISTS search;
@@ -0,0 +1,8 @@
+-- keyword search over items: the name weighs more than the description when ranking matches
+ALTER TABLE items
+    ADD COLUMN search tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', name), 'A') ||
+        setweight(to_tsvector('english', coalesce(description, '')), 'B')
+        ) STORED;
+
+CREATE INDEX items_search_idx ON items USING gin (search);
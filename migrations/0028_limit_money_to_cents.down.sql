@@ -0,0 +1,16 @@
+-- reverses the changes in 0028_limit_money_to_cents.up.sql
+ALTER TABLE payments
+    ALTER COLUMN amount TYPE numeric;
+
+ALTER TABLE invoices_items
+    ALTER COLUMN unit_price TYPE numeric;
+
+ALTER TABLE invoices
+    ALTER COLUMN total TYPE numeric,
+    ALTER COLUMN subtotal TYPE numeric,
+    ALTER COLUMN tax TYPE numeric,
+    ALTER COLUMN discount TYPE numeric,
+    ALTER COLUMN amount_paid TYPE numeric;
+
+ALTER TABLE items
+    ALTER COLUMN unit_price TYPE numeric;
@@ -0,0 +1,17 @@
+-- every currency we sell in is counted in cents, so amounts are kept to two decimal places; tax_rate is a fraction
+-- and keeps its precision
+ALTER TABLE items
+    ALTER COLUMN unit_price TYPE numeric(12, 2);
+
+ALTER TABLE invoices
+    ALTER COLUMN total TYPE numeric(12, 2),
+    ALTER COLUMN subtotal TYPE numeric(12, 2),
+    ALTER COLUMN tax TYPE numeric(12, 2),
+    ALTER COLUMN discount TYPE numeric(12, 2),
+    ALTER COLUMN amount_paid TYPE numeric(12, 2);
+
+ALTER TABLE invoices_items
+    ALTER COLUMN unit_price TYPE numeric(12, 2);
+
+ALTER TABLE payments
+    ALTER COLUMN amount TYPE numeric(12, 2);
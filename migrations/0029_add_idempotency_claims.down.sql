@@ -0,0 +1,3 @@
+-- reverses the changes in 0029_add_idempotency_claims.up.sql
+ALTER TABLE idempotency_keys
+    DROP COLUMN claim_id;
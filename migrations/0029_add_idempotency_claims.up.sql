@@ -0,0 +1,7 @@
+-- the request holding a key, so that only it can store the response or free the key. Keys held when this runs get
+-- an id no request has, so their requests' responses are not kept and their retries run again once the hold ends.
+ALTER TABLE idempotency_keys
+    ADD COLUMN claim_id uuid NOT NULL DEFAULT gen_random_uuid();
+
+ALTER TABLE idempotency_keys
+    ALTER COLUMN claim_id DROP DEFAULT;
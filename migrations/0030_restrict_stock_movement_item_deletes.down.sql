@@ -0,0 +1,5 @@
+-- reverses the changes in 0030_restrict_stock_movement_item_deletes.up.sql
+ALTER TABLE stock_movements
+    DROP CONSTRAINT fk_stock_movements_item_alt_id;
+ALTER TABLE stock_movements
+    ADD CONSTRAINT fk_stock_movements_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id);
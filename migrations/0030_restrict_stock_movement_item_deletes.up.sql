@@ -0,0 +1,6 @@
+-- items are only ever soft deleted, and the ledger must outlive them: refuse a hard delete of an item with stock
+-- movements rather than let it take its history with it
+ALTER TABLE stock_movements
+    DROP CONSTRAINT fk_stock_movements_item_alt_id;
+ALTER TABLE stock_movements
+    ADD CONSTRAINT fk_stock_movements_item_alt_id FOREIGN KEY (item_id) REFERENCES items (alt_id) ON DELETE RESTRICT;
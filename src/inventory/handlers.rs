@@ -1,13 +1,24 @@
+use crate::inventory::model::{ExpectedVersion, Invoice, Item, Person};
 use crate::inventory::services::ServiceError;
+use async_trait::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::{ETAG, IF_MATCH};
+use axum::http::request::Parts;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use serde::Serialize;
 use serde_json::json;
 
+pub mod api_client;
+pub mod audit;
+pub mod idempotency;
 pub mod invoice;
 pub mod item;
+pub mod payment;
 pub mod person;
 pub mod status;
+pub mod stock;
 
 impl IntoResponse for ServiceError {
     fn into_response(self) -> Response {
@@ -16,8 +27,17 @@ impl IntoResponse for ServiceError {
             ServiceError::InvalidUuid(e) => (StatusCode::BAD_REQUEST, e),
             ServiceError::InputValidationError(e) => (StatusCode::BAD_REQUEST, e),
             ServiceError::UniqueViolation(e) => (StatusCode::CONFLICT, e),
+            ServiceError::ForeignKeyViolation(e) => (StatusCode::CONFLICT, e),
             ServiceError::UnexpectedError(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
             ServiceError::InvalidPrice(e) => (StatusCode::BAD_REQUEST, e),
+            ServiceError::InsufficientStock(e) => (StatusCode::CONFLICT, e),
+            ServiceError::CurrencyMismatch(e) => (StatusCode::UNPROCESSABLE_ENTITY, e),
+            ServiceError::InvalidStatus(e) => (StatusCode::CONFLICT, e),
+            ServiceError::ExceedsBalance(e) => (StatusCode::CONFLICT, e),
+            ServiceError::PreconditionFailed(e) => (StatusCode::PRECONDITION_FAILED, e),
+            ServiceError::PreconditionRequired(e) => (StatusCode::PRECONDITION_REQUIRED, e),
+            ServiceError::IdempotencyKeyInUse(e) => (StatusCode::CONFLICT, e),
+            ServiceError::IdempotencyKeyReused(e) => (StatusCode::UNPROCESSABLE_ENTITY, e),
             ServiceError::Other(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
         };
         let body = Json(json!({
@@ -27,3 +47,152 @@ impl IntoResponse for ServiceError {
         (status, body).into_response()
     }
 }
+
+/// The versions of a record a client will accept changing, taken from the `ETag`s it sends back
+/// in `If-Match`, or any version for `*`. Updates require it so that one client can't overwrite
+/// another's change unseen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IfMatch(pub ExpectedVersion);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, ServiceError> {
+        let value = parts.headers.get(IF_MATCH).ok_or_else(|| {
+            ServiceError::PreconditionRequired(
+                "If-Match must be set to the ETag of the record being changed".to_string(),
+            )
+        })?;
+        value
+            .to_str()
+            .ok()
+            .and_then(parse_if_match)
+            .map(IfMatch)
+            .ok_or_else(|| {
+                ServiceError::PreconditionFailed(format!(
+                    "If-Match {:?} is not an ETag returned by this service",
+                    value
+                ))
+            })
+    }
+}
+
+/// `*`, or a comma-separated list of strong ETags. Weak ETags are refused, as `If-Match` compares
+/// them strongly and so they never match.
+fn parse_if_match(value: &str) -> Option<ExpectedVersion> {
+    if value.trim() == "*" {
+        return Some(ExpectedVersion::Any);
+    }
+    value
+        .split(',')
+        .map(|etag| {
+            etag.trim()
+                .strip_prefix('"')?
+                .strip_suffix('"')?
+                .parse()
+                .ok()
+        })
+        .collect::<Option<Vec<i64>>>()
+        .map(ExpectedVersion::OneOf)
+}
+
+/// A record whose version is sent as its `ETag`
+pub trait Versioned {
+    fn version(&self) -> i64;
+}
+
+impl Versioned for Person {
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+impl Versioned for Item {
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+impl Versioned for Invoice {
+    fn version(&self) -> i64 {
+        self.version
+    }
+}
+
+/// A single record as JSON, with its version in the `ETag` header for the client to send back
+/// in `If-Match` when it changes the record
+#[derive(Debug)]
+pub struct Tagged<T>(pub T);
+
+impl<T> IntoResponse for Tagged<T>
+where
+    T: Versioned + Serialize,
+{
+    fn into_response(self) -> Response {
+        let etag = format!("\"{}\"", self.0.version());
+        ([(ETAG, etag)], Json(self.0)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn if_match(value: Option<&str>) -> Result<IfMatch, ServiceError> {
+        let mut request = Request::builder();
+        if let Some(value) = value {
+            request = request.header(IF_MATCH, value);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        IfMatch::from_request_parts(&mut parts, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_if_match() {
+        assert_eq!(
+            if_match(Some("\"3\"")).await.unwrap(),
+            IfMatch(ExpectedVersion::OneOf(vec![3]))
+        );
+        assert_eq!(
+            if_match(Some("\"3\", \"12\"")).await.unwrap(),
+            IfMatch(ExpectedVersion::OneOf(vec![3, 12]))
+        );
+        assert_eq!(
+            if_match(Some("*")).await.unwrap(),
+            IfMatch(ExpectedVersion::Any)
+        );
+        assert!(matches!(
+            if_match(None).await,
+            Err(ServiceError::PreconditionRequired(_))
+        ));
+        // a weak ETag never matches, even next to a strong one
+        assert!(matches!(
+            if_match(Some("W/\"12\"")).await,
+            Err(ServiceError::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            if_match(Some("\"3\", W/\"12\"")).await,
+            Err(ServiceError::PreconditionFailed(_))
+        ));
+        assert!(matches!(
+            if_match(Some("\"3\",")).await,
+            Err(ServiceError::PreconditionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_tagged_sets_etag() {
+        let item = Item {
+            version: 7,
+            ..Item::default()
+        };
+        let response = Tagged(item).into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ETAG], "\"7\"");
+    }
+}
@@ -0,0 +1,321 @@
+use crate::inventory::model::{
+    ApiClient, ApiClientCredentials, ApiError, AuditInfo, CreateApiClientRequest, RevokedTokens,
+    Scope,
+};
+use crate::inventory::services::ServiceError;
+use crate::jwt::Claims;
+use crate::AppContext;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use tracing::instrument;
+use utoipa::OpenApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_api_clients,
+        create_api_client,
+        rotate_api_client_secret,
+        revoke_api_client,
+        revoke_api_client_tokens
+    ),
+    components(schemas(
+        ApiClient,
+        ApiClientCredentials,
+        CreateApiClientRequest,
+        RevokedTokens,
+        Scope,
+        ApiError,
+        AuditInfo
+    ))
+)]
+pub struct ApiClientApi;
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "",
+    summary = "Get all API clients",
+    description = "Get every API client, including revoked ones. Secrets are never returned. Requires the clients:admin scope",
+    params(
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "API clients returned", body=[ApiClient]),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Token lacks the clients:admin scope", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_api_clients(
+    claims: Claims,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Vec<ApiClient>>, ServiceError> {
+    app_context
+        .api_client_service
+        .list_clients()
+        .await
+        .map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "",
+    summary = "Create an API client",
+    description = "Issue credentials for a new API client. The secret is only returned in this response. Requires the clients:admin scope",
+    request_body = CreateApiClientRequest,
+    params(
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 201, description = "API client created", body=ApiClientCredentials),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Token lacks the clients:admin scope", body=ApiError),
+       (status = 409, description = "Client id already in use", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn create_api_client(
+    claims: Claims,
+    State(app_context): State<AppContext>,
+    Json(create_api_client_request): Json<CreateApiClientRequest>,
+) -> Result<(StatusCode, Json<ApiClientCredentials>), ServiceError> {
+    app_context
+        .api_client_service
+        .create_client(create_api_client_request, claims.sub)
+        .await
+        .map(|credentials| (StatusCode::CREATED, Json(credentials)))
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "/{client_id}/secret",
+    summary = "Rotate an API client's secret",
+    description = "Replace the secret of an API client that has not been revoked. The old secret stops working immediately and the new one is only returned in this response. Requires the clients:admin scope",
+    params(
+       ("client_id", Path, description="The client id of the API client"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Secret rotated", body=ApiClientCredentials),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Token lacks the clients:admin scope", body=ApiError),
+       (status = 404, description = "Not Found or revoked", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn rotate_api_client_secret(
+    claims: Claims,
+    Path(client_id): Path<String>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<ApiClientCredentials>, ServiceError> {
+    app_context
+        .api_client_service
+        .rotate_secret(client_id, claims.sub)
+        .await
+        .map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    delete,
+    path = "/{client_id}",
+    summary = "Revoke an API client",
+    description = "Revoke an API client so no further tokens are issued to it and the ones it holds stop working. The client is kept for auditing. Requires the clients:admin scope",
+    params(
+       ("client_id", Path, description="The client id of the API client"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "API client revoked", body=ApiClient),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Token lacks the clients:admin scope", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn revoke_api_client(
+    claims: Claims,
+    Path(client_id): Path<String>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<ApiClient>, ServiceError> {
+    app_context
+        .api_client_service
+        .revoke_client(client_id, claims.sub)
+        .await
+        .map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    delete,
+    path = "/{client_id}/tokens",
+    summary = "Log out an API client",
+    description = "Revoke every access and refresh token issued to an API client without revoking the client, so it has to authorize again with its credentials. Requires the clients:admin scope",
+    params(
+       ("client_id", Path, description="The client id of the API client"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Tokens revoked", body=RevokedTokens),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Token lacks the clients:admin scope", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn revoke_api_client_tokens(
+    claims: Claims,
+    Path(client_id): Path<String>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<RevokedTokens>, ServiceError> {
+    let revoked = app_context
+        .token_service
+        .revoke_client_tokens(client_id.clone())
+        .await?;
+    Ok(Json(RevokedTokens { client_id, revoked }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::model::{
+        ApiClient, ApiClientCredentials, CreateApiClientRequest, RevokedTokens, Scope,
+    };
+    use crate::inventory::services::api_client::MockApiClientService;
+    use crate::inventory::services::invoice::MockInvoiceService;
+    use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::person::MockPersonService;
+    use crate::inventory::services::token::MockTokenService;
+    use crate::inventory::services::ServiceError;
+    use crate::test_helpers::{mock_claims, test_app_context};
+    use crate::AppContext;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::Json;
+    use mockall::predicate::eq;
+    use std::sync::Arc;
+
+    fn app_context_with_api_client_service(
+        mock_api_client_service: MockApiClientService,
+    ) -> AppContext {
+        AppContext {
+            api_client_service: Arc::new(mock_api_client_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        }
+    }
+
+    fn credentials() -> ApiClientCredentials {
+        ApiClientCredentials {
+            client_id: "reporting".to_string(),
+            client_secret: "s3cret".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_api_clients() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_list_clients()
+            .returning(|| Box::pin(async { Ok(vec![ApiClient::default()]) }));
+        let app_context = app_context_with_api_client_service(mock_api_client_service);
+        let result = super::get_api_clients(mock_claims(), State(app_context)).await;
+        assert_eq!(result.unwrap().0.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_create_api_client() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_create_client()
+            .withf(|_, actor| actor == "test")
+            .returning(|_, _| Box::pin(async { Ok(credentials()) }));
+        let app_context = app_context_with_api_client_service(mock_api_client_service);
+        let result = super::create_api_client(
+            mock_claims(),
+            State(app_context),
+            Json(CreateApiClientRequest {
+                client_id: "reporting".to_string(),
+                name: "Reporting team".to_string(),
+                scopes: vec![Scope::ItemsRead],
+            }),
+        )
+        .await;
+        let (status, Json(created)) = result.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created, credentials());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_api_client_secret() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_rotate_secret()
+            .with(eq("reporting".to_string()), eq("test".to_string()))
+            .returning(|_, _| Box::pin(async { Ok(credentials()) }));
+        let app_context = app_context_with_api_client_service(mock_api_client_service);
+        let result = super::rotate_api_client_secret(
+            mock_claims(),
+            Path("reporting".to_string()),
+            State(app_context),
+        )
+        .await;
+        assert_eq!(result.unwrap().0, credentials());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_api_client_not_found() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_revoke_client()
+            .returning(|_, _| {
+                Box::pin(async { Err(ServiceError::NotFound("not found".to_string())) })
+            });
+        let app_context = app_context_with_api_client_service(mock_api_client_service);
+        let result = super::revoke_api_client(
+            mock_claims(),
+            Path("nobody".to_string()),
+            State(app_context),
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_api_client_tokens() {
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service
+            .expect_revoke_client_tokens()
+            .with(eq("reporting".to_string()))
+            .returning(|_| Box::pin(async { Ok(3) }));
+        let app_context = AppContext {
+            token_service: Arc::new(mock_token_service),
+            ..app_context_with_api_client_service(MockApiClientService::new())
+        };
+        let result = super::revoke_api_client_tokens(
+            mock_claims(),
+            Path("reporting".to_string()),
+            State(app_context),
+        )
+        .await;
+        assert_eq!(
+            result.unwrap().0,
+            RevokedTokens {
+                client_id: "reporting".to_string(),
+                revoked: 3,
+            }
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use crate::inventory::model::page::Page;
+use crate::inventory::model::{ApiError, AuditAction, AuditEntry, AuditResource, Pagination};
+use crate::inventory::services::ServiceError;
+use crate::jwt::Claims;
+use crate::AppContext;
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use tracing::instrument;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_person_history),
+    components(schemas(AuditEntry, AuditResource, AuditAction, ApiError))
+)]
+pub struct PersonHistoryApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_item_history),
+    components(schemas(AuditEntry, AuditResource, AuditAction, ApiError))
+)]
+pub struct ItemHistoryApi;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_invoice_history),
+    components(schemas(AuditEntry, AuditResource, AuditAction, ApiError))
+)]
+pub struct InvoiceHistoryApi;
+
+async fn get_history(
+    app_context: AppContext,
+    resource_type: AuditResource,
+    id: Uuid,
+    maybe_pagination_query: Option<Query<Pagination>>,
+) -> Result<Json<Page<AuditEntry>>, ServiceError> {
+    let pagination = maybe_pagination_query.map(|pagination_query| pagination_query.0);
+    app_context
+        .audit_service
+        .get_history(resource_type, id, pagination)
+        .await
+        .map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/history",
+    summary = "Get the change history of a person",
+    description = "Get every create, update and delete of a person, oldest first, with who made it and the person before and after. History is kept after the person is deleted",
+    params(
+       ("id", Path, description="Person Id - UUID"),
+       Pagination,
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "History returned", body=Page<AuditEntry>),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "No history for this person", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_person_history(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    maybe_pagination_query: Option<Query<Pagination>>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Page<AuditEntry>>, ServiceError> {
+    get_history(
+        app_context,
+        AuditResource::Person,
+        id,
+        maybe_pagination_query,
+    )
+    .await
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/history",
+    summary = "Get the change history of an item",
+    description = "Get every create, update and delete of an item, oldest first, with who made it and the item before and after. History is kept after the item is deleted",
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       Pagination,
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "History returned", body=Page<AuditEntry>),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "No history for this item", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_item_history(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    maybe_pagination_query: Option<Query<Pagination>>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Page<AuditEntry>>, ServiceError> {
+    get_history(app_context, AuditResource::Item, id, maybe_pagination_query).await
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/history",
+    summary = "Get the change history of an invoice",
+    description = "Get every create, update and delete of an invoice, oldest first, with who made it and the invoice before and after. Adding or removing a line is recorded as an update of the invoice's totals. History is kept after the invoice is deleted",
+    params(
+       ("id", Path, description="Invoice id (uuid)"),
+       Pagination,
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "History returned", body=Page<AuditEntry>),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "No history for this invoice", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_invoice_history(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    maybe_pagination_query: Option<Query<Pagination>>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Page<AuditEntry>>, ServiceError> {
+    get_history(
+        app_context,
+        AuditResource::Invoice,
+        id,
+        maybe_pagination_query,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::{AuditAction, AuditEntry, AuditResource, Pagination};
+    use crate::inventory::services::audit::MockAuditService;
+    use crate::inventory::services::invoice::MockInvoiceService;
+    use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::person::MockPersonService;
+    use crate::inventory::services::ServiceError;
+    use crate::test_helpers::{first_item_uuid, first_person_uuid, mock_claims, test_app_context};
+    use crate::AppContext;
+    use axum::extract::{Path, Query, State};
+    use mockall::predicate::eq;
+    use std::sync::Arc;
+
+    fn app_context_with_audit_service(mock_audit_service: MockAuditService) -> AppContext {
+        AppContext {
+            audit_service: Arc::new(mock_audit_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_item_history() {
+        let entry = AuditEntry {
+            resource_type: AuditResource::Item,
+            resource_id: first_item_uuid().to_string(),
+            action: AuditAction::Create,
+            ..AuditEntry::default()
+        };
+        let cloned_entry = entry.clone();
+        let pagination = Pagination {
+            cursor: Some("Mw".to_string()),
+            page_size: Some(5),
+        };
+        let mut mock_audit_service = MockAuditService::new();
+        mock_audit_service
+            .expect_get_history()
+            .with(
+                eq(AuditResource::Item),
+                eq(first_item_uuid()),
+                eq(Some(pagination.clone())),
+            )
+            .returning(move |_, _, _| {
+                let page = Page {
+                    items: vec![cloned_entry.clone()],
+                    ..Page::default()
+                };
+                Box::pin(async move { Ok(page) })
+            });
+        let app_context = app_context_with_audit_service(mock_audit_service);
+        let result = super::get_item_history(
+            mock_claims(),
+            Path(first_item_uuid()),
+            Some(Query(pagination)),
+            State(app_context),
+        )
+        .await;
+        assert_eq!(result.unwrap().0.items, vec![entry]);
+    }
+
+    #[tokio::test]
+    async fn test_get_person_history_not_found() {
+        let mut mock_audit_service = MockAuditService::new();
+        mock_audit_service
+            .expect_get_history()
+            .with(eq(AuditResource::Person), eq(first_person_uuid()), eq(None))
+            .returning(|_, _, _| {
+                Box::pin(async { Err(ServiceError::NotFound("not found".to_string())) })
+            });
+        let app_context = app_context_with_audit_service(mock_audit_service);
+        let result = super::get_person_history(
+            mock_claims(),
+            Path(first_person_uuid()),
+            None,
+            State(app_context),
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+}
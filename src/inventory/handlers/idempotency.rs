@@ -0,0 +1,236 @@
+use crate::inventory::model::{IdempotencyClaim, IdempotentResponse};
+use crate::inventory::services::ServiceError;
+use crate::AppContext;
+use async_trait::async_trait;
+use axum::body::{to_bytes, Body};
+use axum::extract::FromRequestParts;
+use axum::http::header::CONTENT_LENGTH;
+use axum::http::request::Parts;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use tracing::error;
+use uuid::Uuid;
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Set on a response that was replayed for a retry rather than produced by running the request
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// The `Idempotency-Key` header of a POST the client may retry, if it sent one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IdempotencyKey(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IdempotencyKey
+where
+    S: Send + Sync,
+{
+    type Rejection = ServiceError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, ServiceError> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY) else {
+            return Ok(IdempotencyKey(None));
+        };
+        value
+            .to_str()
+            .map(|key| IdempotencyKey(Some(key.to_string())))
+            .map_err(|_| {
+                ServiceError::InputValidationError(
+                    "Idempotency-Key must be printable ASCII".to_string(),
+                )
+            })
+    }
+}
+
+/// Runs `handle` on `request` once for each idempotency key. A retry with the same key gets the
+/// response to the first request back without running it again. Only successful responses are
+/// kept; after a failure the key is given up, so a retry with it runs the request again.
+pub(crate) async fn idempotent<R, F, Fut, T>(
+    app_context: &AppContext,
+    client_id: &str,
+    IdempotencyKey(key): IdempotencyKey,
+    operation: &str,
+    request: R,
+    handle: F,
+) -> Response
+where
+    R: Serialize,
+    F: FnOnce(R) -> Fut,
+    Fut: Future<Output = Result<T, ServiceError>>,
+    T: IntoResponse,
+{
+    let Some(key) = key else {
+        return handle(request).await.into_response();
+    };
+    let idempotency_service = &app_context.idempotency_service;
+    let claim = IdempotencyClaim {
+        client_id: client_id.to_string(),
+        key,
+        request_hash: request_hash(operation, &request),
+        claim_id: Uuid::new_v4(),
+    };
+    match idempotency_service.begin(claim.clone()).await {
+        Ok(Some(response)) => return replay(response),
+        Ok(None) => {}
+        Err(e) => return e.into_response(),
+    }
+
+    let response = handle(request).await.into_response();
+    if !response.status().is_success() {
+        if let Err(e) = idempotency_service.release(claim.clone()).await {
+            error!("Failed to release idempotency key {}: {:?}", claim.key, e);
+        }
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            return ServiceError::UnexpectedError(format!("Failed to read response: {}", e))
+                .into_response()
+        }
+    };
+    let stored = IdempotentResponse {
+        status: parts.status.as_u16(),
+        // the length is set again from the body when the response is replayed
+        headers: parts
+            .headers
+            .iter()
+            .filter(|(name, _)| **name != CONTENT_LENGTH)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: body.to_vec(),
+    };
+    if let Err(e) = idempotency_service.complete(claim.clone(), stored).await {
+        error!(
+            "Failed to store the response for idempotency key {}: {:?}",
+            claim.key, e
+        );
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Tells requests apart, so that a key can't be reused for a different request
+fn request_hash(operation: &str, request: &impl Serialize) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(operation.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(serde_json::to_vec(request).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+/// The kept response, with the headers it was first sent with
+fn replay(response: IdempotentResponse) -> Response {
+    let mut replayed = Response::new(Body::from(response.body));
+    *replayed.status_mut() = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+    let headers = replayed.headers_mut();
+    for (name, value) in response.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    replayed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::services::idempotency::MockIdempotencyService;
+    use crate::inventory::services::invoice::MockInvoiceService;
+    use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::person::MockPersonService;
+    use crate::test_helpers::test_app_context;
+    use axum::http::header::{CONTENT_TYPE, ETAG, LOCATION};
+    use axum::Json;
+    use std::sync::{Arc, Mutex};
+
+    fn app_context(mock_idempotency_service: MockIdempotencyService) -> AppContext {
+        AppContext {
+            idempotency_service: Arc::new(mock_idempotency_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        }
+    }
+
+    fn key() -> IdempotencyKey {
+        IdempotencyKey(Some("key-1".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_replay_keeps_headers() {
+        let kept = Arc::new(Mutex::new(None));
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service
+            .expect_begin()
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(None) }));
+        let kept_by_complete = kept.clone();
+        mock_idempotency_service
+            .expect_complete()
+            .times(1)
+            .returning(move |_, response| {
+                *kept_by_complete.lock().unwrap() = Some(response);
+                Box::pin(async { Ok(()) })
+            });
+        let original = idempotent(
+            &app_context(mock_idempotency_service),
+            "foo",
+            key(),
+            "create_item",
+            "request",
+            |_| async {
+                Ok((
+                    StatusCode::CREATED,
+                    [(ETAG, "\"1\""), (LOCATION, "/api/v1/items/1")],
+                    Json("created"),
+                ))
+            },
+        )
+        .await;
+
+        // the retry gets the kept response back without running the request
+        let kept_response = kept.lock().unwrap().clone().unwrap();
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service
+            .expect_begin()
+            .times(1)
+            .returning(move |_| {
+                let response = kept_response.clone();
+                Box::pin(async move { Ok(Some(response)) })
+            });
+        mock_idempotency_service.expect_complete().never();
+        let replayed = idempotent(
+            &app_context(mock_idempotency_service),
+            "foo",
+            key(),
+            "create_item",
+            "request",
+            |_| async {
+                Err::<StatusCode, _>(ServiceError::UnexpectedError("ran twice".to_string()))
+            },
+        )
+        .await;
+
+        assert_eq!(replayed.status(), original.status());
+        assert_eq!(replayed.headers()[IDEMPOTENT_REPLAYED], "true");
+        let mut replayed_headers = replayed.headers().clone();
+        replayed_headers.remove(IDEMPOTENT_REPLAYED);
+        assert_eq!(&replayed_headers, original.headers());
+        assert_eq!(replayed_headers[ETAG], "\"1\"");
+        assert_eq!(replayed_headers[LOCATION], "/api/v1/items/1");
+        assert_eq!(replayed_headers[CONTENT_TYPE], "application/json");
+        let original_body = to_bytes(original.into_body(), usize::MAX).await.unwrap();
+        let replayed_body = to_bytes(replayed.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(replayed_body, original_body);
+    }
+}
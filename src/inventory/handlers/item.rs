@@ -1,19 +1,44 @@
+use crate::inventory::handlers::idempotency::{idempotent, IdempotencyKey};
+use crate::inventory::handlers::{IfMatch, Tagged};
+use crate::inventory::model::money::{Currency, Money};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::sort::{ItemSortField, SortQuery};
 use crate::inventory::model::{
-    ApiError, AuditInfo, CreateItemRequest, DeleteResults, Item, Pagination, UpdateItemRequest,
+    ApiError, AuditInfo, CreateItemRequest, DeleteResults, IncludeDeletedQuery, IncludeTotalQuery,
+    Item, ItemSearchQuery, Pagination, SearchMatch, UpdateItemRequest,
 };
 use crate::inventory::services::ServiceError;
 use crate::jwt::Claims;
 use crate::AppContext;
 use axum::extract::{Path, Query, State};
+use axum::response::Response;
 use axum::Json;
+use serde_json::Value;
 use tracing::instrument;
 use utoipa::OpenApi;
 use uuid::Uuid;
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(get_items, get_item_by_id, create_item, update_item, delete_item),
-    components(schemas(Item, CreateItemRequest, UpdateItemRequest, ApiError, AuditInfo))
+    paths(
+        get_items,
+        get_item_by_id,
+        create_item,
+        update_item,
+        patch_item,
+        delete_item,
+        restore_item
+    ),
+    components(schemas(
+        Item,
+        SearchMatch,
+        CreateItemRequest,
+        UpdateItemRequest,
+        Money,
+        Currency,
+        ApiError,
+        AuditInfo
+    ))
 )]
 pub struct ItemApi;
 
@@ -23,13 +48,17 @@ pub struct ItemApi;
     get,
     path = "",
     summary = "Get all items",
-    description = "Get all items in the inventory",
+    description = "Get all items in the inventory, or those matching the search filters. Items found by keyword (q) are returned most relevant first, with the matching words highlighted in search_match. Deleted items are left out unless include_deleted is set",
     params(
        Pagination,
+       ItemSearchQuery,
+       ("include_deleted", Query, description="Include deleted items"),
+       ("include_total", Query, description="Count the matching items across all pages"),
+       ("sort" = Option<String>, Query, description="Fields to sort by, comma separated and descending when prefixed with `-`: name, unit_price, created_at, last_update. Overrides the order by relevance", example="name,-unit_price"),
        ("Authorization", Header, description="Bearer token")
     ),
     responses(
-       (status = 200, description = "Items returned", body=[Item]),
+       (status = 200, description = "Items returned", body=Page<Item>),
        (status = 400, description = "Bad Request", body=ApiError),
        (status = 401, description = "Unauthorized", body=ApiError),
        (status = 403, description = "Forbidden", body=ApiError),
@@ -40,16 +69,25 @@ pub struct ItemApi;
 pub async fn get_items(
     claims: Claims,
     maybe_pagination_query: Option<Query<Pagination>>,
+    maybe_include_deleted_query: Option<Query<IncludeDeletedQuery>>,
+    maybe_include_total_query: Option<Query<IncludeTotalQuery>>,
+    Query(search): Query<ItemSearchQuery>,
+    Query(SortQuery { sort }): Query<SortQuery<ItemSortField>>,
     State(app_context): State<AppContext>,
-) -> Result<Json<Vec<Item>>, ServiceError> {
-    let pagination = if let Some(pagination_query) = maybe_pagination_query {
-        Some(pagination_query.0)
-    } else {
-        None
-    };
+) -> Result<Json<Page<Item>>, ServiceError> {
+    let pagination = maybe_pagination_query.map(|pagination_query| pagination_query.0);
+    let include_deleted = maybe_include_deleted_query.is_some_and(|query| query.0.include_deleted);
+    let include_total = maybe_include_total_query.is_some_and(|query| query.0.include_total);
+    if !search.is_empty() {
+        return app_context
+            .item_service
+            .search_items(search, pagination, sort, include_deleted, include_total)
+            .await
+            .map(Json);
+    }
     app_context
         .item_service
-        .get_all_items(pagination)
+        .get_all_items(pagination, sort, include_deleted, include_total)
         .await
         .map(Json)
 }
@@ -60,13 +98,15 @@ pub async fn get_items(
     get,
     path = "/{id}",
     summary = "Get item by ID",
-    description = "Get an item by its unique identifier (uuid)",
+    description = "Get an item by its unique identifier (uuid). A deleted item is only returned when include_deleted is set",
     params(
        ("id", Path, description="The unique identifier of the item"),
+       ("include_deleted", Query, description="Return the item even if deleted"),
        ("Authorization", Header, description="Bearer token")
     ),
     responses(
-       (status = 200, description = "Item returned", body=Item),
+       (status = 200, description = "Item returned", body=Item,
+        headers(("ETag" = String, description = "The item's version, to send back in If-Match when changing it"))),
        (status = 400, description = "Bad Request", body=ApiError),
        (status = 401, description = "Unauthorized", body=ApiError),
        (status = 403, description = "Forbidden", body=ApiError),
@@ -77,9 +117,15 @@ pub async fn get_items(
 pub async fn get_item_by_id(
     claims: Claims,
     Path(id): Path<Uuid>,
+    maybe_include_deleted_query: Option<Query<IncludeDeletedQuery>>,
     State(app_context): State<AppContext>,
-) -> Result<Json<Item>, ServiceError> {
-    app_context.item_service.get_item_by_id(id).await.map(Json)
+) -> Result<Tagged<Item>, ServiceError> {
+    let include_deleted = maybe_include_deleted_query.is_some_and(|query| query.0.include_deleted);
+    app_context
+        .item_service
+        .get_item_by_id(id, include_deleted)
+        .await
+        .map(Tagged)
 }
 
 #[axum_macros::debug_handler]
@@ -91,6 +137,7 @@ pub async fn get_item_by_id(
     description = "Create a new item in the inventory",
     request_body = CreateItemRequest,
     params(
+       ("Idempotency-Key", Header, description="Optional. A retry with the same key returns the first response instead of repeating the request"),
        ("Authorization", Header, description="Bearer token")
     ),
     responses(
@@ -98,19 +145,32 @@ pub async fn get_item_by_id(
        (status = 400, description = "Bad Request", body=ApiError),
        (status = 401, description = "Unauthorized", body=ApiError),
        (status = 403, description = "Forbidden", body=ApiError),
+       (status = 409, description = "A request with the same Idempotency-Key is still running", body=ApiError),
+       (status = 422, description = "The Idempotency-Key was used for a different request", body=ApiError),
        (status = 500, description = "Internal Server Error", body=ApiError)
     )
 )]
 pub async fn create_item(
     claims: Claims,
+    idempotency_key: IdempotencyKey,
     State(app_context): State<AppContext>,
     Json(create_item_request): Json<CreateItemRequest>,
-) -> Result<Json<Item>, ServiceError> {
-    app_context
-        .item_service
-        .create_item(create_item_request)
-        .await
-        .map(Json)
+) -> Response {
+    idempotent(
+        &app_context,
+        &claims.sub,
+        idempotency_key,
+        "create_item",
+        create_item_request,
+        |request| async {
+            app_context
+                .item_service
+                .create_item(request, claims.sub.clone())
+                .await
+                .map(Json)
+        },
+    )
+    .await
 }
 
 #[axum_macros::debug_handler]
@@ -119,38 +179,84 @@ pub async fn create_item(
     put,
     path = "/{id}",
     summary = "Update an item",
-    description = "Update an existing item in the inventory",
+    description = "Update an existing item in the inventory. If-Match must carry the ETag the item was read with, and the update is refused if the item has changed since",
     request_body = UpdateItemRequest,
     params(
        ("id", Path, description="The unique identifier of the item"),
+       ("If-Match", Header, description="The ETag of the item being updated"),
        ("Authorization", Header, description="Bearer token")
     ),
     responses(
-       (status = 200, description = "Item updated", body=Item),
+       (status = 200, description = "Item updated", body=Item,
+        headers(("ETag" = String, description = "The item's new version"))),
        (status = 400, description = "Bad Request", body=ApiError),
        (status = 401, description = "Unauthorized", body=ApiError),
        (status = 403, description = "Forbidden", body=ApiError),
        (status = 404, description = "Not Found", body=ApiError),
+       (status = 412, description = "The item has changed since the ETag in If-Match was read", body=ApiError),
+       (status = 428, description = "If-Match is missing", body=ApiError),
        (status = 500, description = "Internal Server Error", body=ApiError)
     )
 )]
 pub async fn update_item(
     claims: Claims,
     Path(id): Path<String>,
+    IfMatch(version): IfMatch,
     State(app_context): State<AppContext>,
-    Json(update_item_request): Json<UpdateItemRequest>,
-) -> Result<Json<Item>, ServiceError> {
-    if id != update_item_request.id.to_string() {
+    Json(mut update_item_request): Json<UpdateItemRequest>,
+) -> Result<Tagged<Item>, ServiceError> {
+    if id != update_item_request.id {
         return Err(ServiceError::InputValidationError(format!(
             "ID in path does not match ID in request. path: {}, request: {}",
             id, update_item_request.id
         )));
     }
+    update_item_request.version = version;
     app_context
         .item_service
-        .update_item(update_item_request)
+        .update_item(update_item_request, claims.sub)
         .await
-        .map(Json)
+        .map(Tagged)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    summary = "Change some fields of an item",
+    description = "Applies a JSON Merge Patch (RFC 7396) of UpdateItemRequest to an item: fields left out of the patch are kept. If-Match must carry the ETag the item was read with, and the patch is refused if the item has changed since",
+    request_body(content = Object, content_type = "application/merge-patch+json",
+                 description = "The fields to change, e.g. {\"unit_price\": {\"amount\": \"9.99\"}}"),
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       ("If-Match", Header, description="The ETag of the item being changed"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Item updated", body=Item,
+        headers(("ETag" = String, description = "The item's new version"))),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 412, description = "The item has changed since the ETag in If-Match was read", body=ApiError),
+       (status = 428, description = "If-Match is missing", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn patch_item(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    IfMatch(version): IfMatch,
+    State(app_context): State<AppContext>,
+    Json(patch): Json<Value>,
+) -> Result<Tagged<Item>, ServiceError> {
+    app_context
+        .item_service
+        .patch_item(id, patch, version, claims.sub)
+        .await
+        .map(Tagged)
 }
 
 #[axum_macros::debug_handler]
@@ -159,7 +265,7 @@ pub async fn update_item(
     delete,
     path = "/{id}",
     summary = "Delete an item",
-    description = "Delete an item from the inventory",
+    description = "Delete an item from the inventory. The item is kept, marked as deleted, so invoices that list it still resolve, and can be restored",
     params(
        ("id", Path, description="The unique identifier of the item"),
        ("Authorization", Header, description="Bearer token")
@@ -178,18 +284,66 @@ pub async fn delete_item(
     Path(id): Path<Uuid>,
     State(app_context): State<AppContext>,
 ) -> Result<Json<DeleteResults>, ServiceError> {
-    app_context.item_service.delete_item(id).await.map(Json)
+    app_context
+        .item_service
+        .delete_item(id, claims.sub)
+        .await
+        .map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "/{id}/restore",
+    summary = "Restore a deleted item",
+    description = "Undo the delete of an item",
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Item restored", body=Item),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "No deleted item with this id", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn restore_item(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Item>, ServiceError> {
+    app_context
+        .item_service
+        .restore_item(id, claims.sub)
+        .await
+        .map(Json)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::inventory::model::{Item, Pagination};
+    use crate::inventory::handlers::idempotency::IdempotencyKey;
+    use crate::inventory::handlers::IfMatch;
+    use crate::inventory::model::money::{Currency, Money};
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::sort::SortQuery;
+    use crate::inventory::model::{
+        ExpectedVersion, IncludeDeletedQuery, IncludeTotalQuery, Item, ItemSearchQuery, Pagination,
+    };
     use crate::inventory::services::invoice::MockInvoiceService;
     use crate::inventory::services::item::MockItemService;
     use crate::inventory::services::person::MockPersonService;
     use crate::jwt::Claims;
-    use crate::test_helpers::{first_item_uuid, test_app_context, FIRST_ITEM_UUID};
+    use crate::test_helpers::{
+        body_to_string, first_item_uuid, mock_claims, test_app_context, FIRST_ITEM_UUID,
+    };
     use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
+    use bigdecimal::BigDecimal;
+    use mockall::predicate::eq;
 
     #[tokio::test]
     async fn test_get_items() {
@@ -198,16 +352,22 @@ mod tests {
             id: "1".to_string(),
             name: "Item 1".to_string(),
             description: "Item 1 Description".to_string(),
-            unit_price: 100.0,
+            unit_price: Money::new(BigDecimal::from(100), Currency::USD),
             audit_info: Default::default(),
+            stock: None,
+            version: 1,
+            search_match: None,
         };
         let cloned_item = expected_item.clone();
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_all_items()
-            .returning(move |_| {
-                let cloned_item = cloned_item.clone();
-                Box::pin(async move { Ok(vec![cloned_item]) })
+            .returning(move |_, _, _, _| {
+                let page = Page {
+                    items: vec![cloned_item.clone()],
+                    ..Page::default()
+                };
+                Box::pin(async move { Ok(page) })
             });
         let app_context = test_app_context(
             MockPersonService::new(),
@@ -215,9 +375,18 @@ mod tests {
             MockInvoiceService::new(),
         );
         let no_pagination: Option<Query<Pagination>> = None;
-        let result = super::get_items(Claims::default(), no_pagination, State(app_context)).await;
+        let result = super::get_items(
+            Claims::default(),
+            no_pagination,
+            None,
+            None,
+            Query(ItemSearchQuery::default()),
+            Query(SortQuery::default()),
+            State(app_context),
+        )
+        .await;
         assert!(result.is_ok());
-        let items = result.unwrap().0;
+        let items = result.unwrap().0.items;
         assert_eq!(items.len(), 1);
         assert_eq!(items[0], expected_item);
     }
@@ -229,14 +398,17 @@ mod tests {
             id: "1".to_string(),
             name: "Item 1".to_string(),
             description: "Item 1 Description".to_string(),
-            unit_price: 100.0,
+            unit_price: Money::new(BigDecimal::from(100), Currency::USD),
             audit_info: Default::default(),
+            stock: None,
+            version: 1,
+            search_match: None,
         };
         let cloned_item = expected_item.clone();
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_item_by_id()
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_item = cloned_item.clone();
                 Box::pin(async move { Ok(cloned_item) })
             });
@@ -248,6 +420,7 @@ mod tests {
         let result = super::get_item_by_id(
             Claims::default(),
             Path(first_item_uuid()),
+            None,
             State(app_context),
         )
         .await;
@@ -263,33 +436,41 @@ mod tests {
             id: "1".to_string(),
             name: "Test Item".to_string(),
             description: "Test Item Description".to_string(),
-            unit_price: 100.0,
+            unit_price: Money::new(BigDecimal::from(100), Currency::USD),
             audit_info: Default::default(),
+            stock: None,
+            version: 1,
+            search_match: None,
         };
         let cloned_item = expected_item.clone();
         let mut mock_item_service = MockItemService::new();
-        mock_item_service.expect_create_item().returning(move |_| {
-            let cloned_item = cloned_item.clone();
-            Box::pin(async move { Ok(cloned_item) })
-        });
+        mock_item_service
+            .expect_create_item()
+            .withf(|_, actor| actor == "test")
+            .returning(move |_, _| {
+                let cloned_item = cloned_item.clone();
+                Box::pin(async move { Ok(cloned_item) })
+            });
         let app_context = test_app_context(
             MockPersonService::new(),
             mock_item_service,
             MockInvoiceService::new(),
         );
         let result = super::create_item(
-            Claims::default(),
+            mock_claims(),
+            IdempotencyKey(None),
             State(app_context),
             axum::Json(super::CreateItemRequest {
                 name: "Test Item".to_string(),
                 description: "Test Item Description".to_string(),
-                unit_price: 100.0,
+                unit_price: Money::new(BigDecimal::from(100), Currency::USD),
                 created_by: "testuser".to_string(),
             }),
         )
         .await;
-        assert!(result.is_ok());
-        let item = result.unwrap().0;
+        assert_eq!(result.status(), StatusCode::OK);
+        let body = body_to_string(result.into_body()).await.unwrap();
+        let item: Item = serde_json::from_str(&body).unwrap();
         assert_eq!(item, expected_item);
     }
 
@@ -300,30 +481,40 @@ mod tests {
             id: FIRST_ITEM_UUID.to_string(),
             name: "Updated Item".to_string(),
             description: "Updated Item Description".to_string(),
-            unit_price: 200.0,
+            unit_price: Money::new(BigDecimal::from(200), Currency::USD),
             audit_info: Default::default(),
+            stock: None,
+            version: 1,
+            search_match: None,
         };
         let cloned_item = expected_item.clone();
         let mut mock_item_service = MockItemService::new();
-        mock_item_service.expect_update_item().returning(move |_| {
-            let cloned_item = cloned_item.clone();
-            Box::pin(async move { Ok(cloned_item) })
-        });
+        mock_item_service
+            .expect_update_item()
+            .withf(|request, actor| {
+                request.version == ExpectedVersion::OneOf(vec![1]) && actor == "test"
+            })
+            .returning(move |_, _| {
+                let cloned_item = cloned_item.clone();
+                Box::pin(async move { Ok(cloned_item) })
+            });
         let app_context = test_app_context(
             MockPersonService::new(),
             mock_item_service,
             MockInvoiceService::new(),
         );
         let result = super::update_item(
-            Claims::default(),
+            mock_claims(),
             Path(FIRST_ITEM_UUID.to_string()),
+            IfMatch(ExpectedVersion::OneOf(vec![1])),
             State(app_context),
             axum::Json(super::UpdateItemRequest {
                 id: first_item_uuid().to_string(),
                 name: "Updated Item".to_string(),
                 description: "Updated Item Description".to_string(),
-                unit_price: 200.0,
+                unit_price: Money::new(BigDecimal::from(200), Currency::USD),
                 changed_by: "testuser".to_string(),
+                version: ExpectedVersion::Any,
             }),
         )
         .await;
@@ -332,6 +523,44 @@ mod tests {
         assert_eq!(item, expected_item);
     }
 
+    #[tokio::test]
+    async fn test_patch_item() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service
+            .expect_patch_item()
+            .withf(|id, patch, version, actor| {
+                *id == first_item_uuid()
+                    && patch["name"] == "Patched Item"
+                    && *version == ExpectedVersion::OneOf(vec![1])
+                    && actor == "test"
+            })
+            .returning(|_, _, _, _| {
+                Box::pin(async {
+                    Ok(Item {
+                        name: "Patched Item".to_string(),
+                        version: 2,
+                        ..Item::default()
+                    })
+                })
+            });
+        let app_context = test_app_context(
+            MockPersonService::new(),
+            mock_item_service,
+            MockInvoiceService::new(),
+        );
+        let result = super::patch_item(
+            mock_claims(),
+            Path(first_item_uuid()),
+            IfMatch(ExpectedVersion::OneOf(vec![1])),
+            State(app_context),
+            axum::Json(serde_json::json!({"name": "Patched Item"})),
+        )
+        .await;
+        let item = result.unwrap().0;
+        assert_eq!(item.name, "Patched Item");
+        assert_eq!(item.version, 2);
+    }
+
     #[tokio::test]
     async fn test_delete_item() {
         let expected_result = super::DeleteResults {
@@ -340,21 +569,20 @@ mod tests {
         };
         let cloned_result = expected_result.clone();
         let mut mock_item_service = MockItemService::new();
-        mock_item_service.expect_delete_item().returning(move |_| {
-            let cloned_result = cloned_result.clone();
-            Box::pin(async move { Ok(cloned_result) })
-        });
+        mock_item_service
+            .expect_delete_item()
+            .withf(|_, deleted_by| deleted_by == "test")
+            .returning(move |_, _| {
+                let cloned_result = cloned_result.clone();
+                Box::pin(async move { Ok(cloned_result) })
+            });
         let app_context = test_app_context(
             MockPersonService::new(),
             mock_item_service,
             MockInvoiceService::new(),
         );
-        let result = super::delete_item(
-            Claims::default(),
-            Path(first_item_uuid()),
-            State(app_context),
-        )
-        .await;
+        let result =
+            super::delete_item(mock_claims(), Path(first_item_uuid()), State(app_context)).await;
         assert!(result.is_ok());
         let delete_results = result.unwrap().0;
         assert_eq!(delete_results, expected_result);
@@ -367,16 +595,22 @@ mod tests {
             id: "1".to_string(),
             name: "Item 1".to_string(),
             description: "Item 1 Description".to_string(),
-            unit_price: 100.0,
+            unit_price: Money::new(BigDecimal::from(100), Currency::USD),
             audit_info: Default::default(),
+            stock: None,
+            version: 1,
+            search_match: None,
         };
         let cloned_item = expected_item.clone();
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_all_items()
-            .returning(move |_| {
-                let cloned_item = cloned_item.clone();
-                Box::pin(async move { Ok(vec![cloned_item]) })
+            .returning(move |_, _, _, _| {
+                let page = Page {
+                    items: vec![cloned_item.clone()],
+                    ..Page::default()
+                };
+                Box::pin(async move { Ok(page) })
             });
         let app_context = test_app_context(
             MockPersonService::new(),
@@ -386,15 +620,108 @@ mod tests {
         let result = super::get_items(
             Claims::default(),
             Some(axum::extract::Query(super::Pagination {
-                last_id: None,
-                page_size: 10,
+                cursor: None,
+                page_size: Some(10),
             })),
+            None,
+            None,
+            Query(ItemSearchQuery::default()),
+            Query(SortQuery::default()),
             State(app_context),
         )
         .await;
         assert!(result.is_ok());
-        let items = result.unwrap().0;
+        let items = result.unwrap().0.items;
         assert_eq!(items.len(), 1);
         assert_eq!(items[0], expected_item);
     }
+
+    #[tokio::test]
+    async fn test_search_items() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_get_all_items().never();
+        mock_item_service
+            .expect_search_items()
+            .withf(|search, pagination, _, include_deleted, include_total| {
+                search.q.as_deref() == Some("blue widget")
+                    && search.max_price == Some(BigDecimal::from(20))
+                    && pagination.is_none()
+                    && !include_deleted
+                    && *include_total
+            })
+            .returning(|_, _, _, _, _| {
+                Box::pin(async {
+                    Ok(Page {
+                        items: vec![Item::default()],
+                        total: Some(1),
+                        ..Page::default()
+                    })
+                })
+            });
+        let app_context = test_app_context(
+            MockPersonService::new(),
+            mock_item_service,
+            MockInvoiceService::new(),
+        );
+        let result = super::get_items(
+            Claims::default(),
+            None,
+            None,
+            Some(Query(IncludeTotalQuery {
+                include_total: true,
+            })),
+            Query(ItemSearchQuery {
+                q: Some("blue widget".to_string()),
+                max_price: Some(BigDecimal::from(20)),
+                ..ItemSearchQuery::default()
+            }),
+            Query(SortQuery::default()),
+            State(app_context),
+        )
+        .await;
+        let page = result.unwrap().0;
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.total, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_get_deleted_item_by_id() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service
+            .expect_get_item_by_id()
+            .with(eq(first_item_uuid()), eq(true))
+            .returning(|_, _| Box::pin(async { Ok(Item::default()) }));
+        let app_context = test_app_context(
+            MockPersonService::new(),
+            mock_item_service,
+            MockInvoiceService::new(),
+        );
+        let result = super::get_item_by_id(
+            mock_claims(),
+            Path(first_item_uuid()),
+            Some(Query(IncludeDeletedQuery {
+                include_deleted: true,
+            })),
+            State(app_context),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_restore_item() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service
+            .expect_restore_item()
+            .withf(|id, restored_by| *id == first_item_uuid() && restored_by == "test")
+            .returning(|_, _| Box::pin(async { Ok(Item::default()) }));
+        let app_context = test_app_context(
+            MockPersonService::new(),
+            mock_item_service,
+            MockInvoiceService::new(),
+        );
+        let result =
+            super::restore_item(mock_claims(), Path(first_item_uuid()), State(app_context)).await;
+        assert!(result.is_ok());
+    }
 }
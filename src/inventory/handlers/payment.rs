@@ -0,0 +1,219 @@
+use crate::inventory::handlers::idempotency::{idempotent, IdempotencyKey};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::{ApiError, CreatePaymentRequest, Pagination, Payment, PaymentMethod};
+use crate::inventory::services::ServiceError;
+use crate::jwt::Claims;
+use crate::AppContext;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::Json;
+use tracing::instrument;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(create_payment, get_payments),
+    components(schemas(Payment, CreatePaymentRequest, PaymentMethod, ApiError))
+)]
+pub struct PaymentApi;
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "/{id}/payments",
+    summary = "Record a payment against an invoice",
+    description = "Record a full or partial payment against an issued invoice. The invoice is marked paid once its balance reaches zero",
+    request_body = CreatePaymentRequest,
+    params(
+       ("id", Path, description="The unique identifier of the invoice"),
+       ("Idempotency-Key", Header, description="Optional. A retry with the same key returns the first response instead of repeating the request"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 201, description = "Payment recorded", body=Payment),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 409, description = "Invoice is not issued, the payment exceeds its balance, or a request with the same Idempotency-Key is still running", body=ApiError),
+       (status = 422, description = "Payment currency differs from the invoice currency, or the Idempotency-Key was used for a different request", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn create_payment(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    idempotency_key: IdempotencyKey,
+    State(app_context): State<AppContext>,
+    Json(create_payment_request): Json<CreatePaymentRequest>,
+) -> Result<Response, ServiceError> {
+    if id != create_payment_request.invoice_id {
+        return Err(ServiceError::InputValidationError(format!(
+            "ID in path does not match ID in request. path: {}, request: {}",
+            id, create_payment_request.invoice_id
+        )));
+    }
+    let response = idempotent(
+        &app_context,
+        &claims.sub,
+        idempotency_key,
+        "create_payment",
+        create_payment_request,
+        |request| async {
+            app_context
+                .payment_service
+                .record_payment(request, claims.sub.clone())
+                .await
+                .map(|payment| (StatusCode::CREATED, Json(payment)))
+        },
+    )
+    .await;
+    Ok(response)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/payments",
+    summary = "Get the payments recorded against an invoice",
+    description = "Get an invoice's payments, oldest first",
+    params(
+       ("id", Path, description="The unique identifier of the invoice"),
+       Pagination,
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Payments returned", body=Page<Payment>),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_payments(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    maybe_pagination_query: Option<Query<Pagination>>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Page<Payment>>, ServiceError> {
+    let pagination = maybe_pagination_query.map(|pagination_query| pagination_query.0);
+    app_context
+        .payment_service
+        .get_payments(id, pagination)
+        .await
+        .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::handlers::idempotency::IdempotencyKey;
+    use crate::inventory::model::money::{Currency, Money};
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::{CreatePaymentRequest, Payment, PaymentMethod};
+    use crate::inventory::services::invoice::MockInvoiceService;
+    use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::payment::MockPaymentService;
+    use crate::inventory::services::person::MockPersonService;
+    use crate::inventory::services::ServiceError;
+    use crate::test_helpers::{body_to_string, first_invoice_uuid, mock_claims, test_app_context};
+    use crate::AppContext;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::Json;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn app_context_with_payment_service(mock_payment_service: MockPaymentService) -> AppContext {
+        AppContext {
+            payment_service: Arc::new(mock_payment_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        }
+    }
+
+    fn create_payment_request(invoice_id: Uuid) -> CreatePaymentRequest {
+        CreatePaymentRequest {
+            invoice_id,
+            amount: Money::new("40.00".parse().unwrap(), Currency::USD),
+            method: PaymentMethod::Card,
+            ..CreatePaymentRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_payment() {
+        let expected_payment = Payment {
+            invoice_id: first_invoice_uuid().to_string(),
+            amount: Money::new("40.00".parse().unwrap(), Currency::USD),
+            ..Payment::default()
+        };
+        let cloned_payment = expected_payment.clone();
+        let mut mock_payment_service = MockPaymentService::new();
+        mock_payment_service
+            .expect_record_payment()
+            .withf(|_, actor| actor == "test")
+            .returning(move |_, _| {
+                let cloned_payment = cloned_payment.clone();
+                Box::pin(async move { Ok(cloned_payment) })
+            });
+        let app_context = app_context_with_payment_service(mock_payment_service);
+        let result = super::create_payment(
+            mock_claims(),
+            Path(first_invoice_uuid()),
+            IdempotencyKey(None),
+            State(app_context),
+            Json(create_payment_request(first_invoice_uuid())),
+        )
+        .await;
+        let response = result.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = body_to_string(response.into_body()).await.unwrap();
+        let payment: Payment = serde_json::from_str(&body).unwrap();
+        assert_eq!(payment, expected_payment);
+    }
+
+    #[tokio::test]
+    async fn test_create_payment_mismatched_ids() {
+        let app_context = app_context_with_payment_service(MockPaymentService::new());
+        let result = super::create_payment(
+            mock_claims(),
+            Path(Uuid::new_v4()),
+            IdempotencyKey(None),
+            State(app_context),
+            Json(create_payment_request(first_invoice_uuid())),
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_payments() {
+        let mut mock_payment_service = MockPaymentService::new();
+        mock_payment_service
+            .expect_get_payments()
+            .returning(|_, _| {
+                Box::pin(async {
+                    Ok(Page {
+                        items: vec![Payment::default()],
+                        ..Page::default()
+                    })
+                })
+            });
+        let app_context = app_context_with_payment_service(mock_payment_service);
+        let result = super::get_payments(
+            mock_claims(),
+            Path(first_invoice_uuid()),
+            None,
+            State(app_context),
+        )
+        .await;
+        assert_eq!(result.unwrap().0.items.len(), 1);
+    }
+}
@@ -1,16 +1,33 @@
-use crate::inventory::model::{ApiError, CreatePersonRequest, Pagination, Person};
+use crate::inventory::handlers::idempotency::{idempotent, IdempotencyKey};
+use crate::inventory::handlers::{IfMatch, Tagged};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::sort::{PersonSortField, SortQuery};
+use crate::inventory::model::{
+    ApiError, CreatePersonRequest, IncludeDeletedQuery, IncludeTotalQuery, Pagination, Person,
+    UpdatePersonRequest,
+};
 use crate::inventory::services::ServiceError;
 use crate::jwt::Claims;
 use crate::{inventory, AppContext};
 use axum::extract::{Path, Query, State};
+use axum::response::Response;
 use axum::Json;
+use serde_json::Value;
 use tracing::{debug, instrument};
 use utoipa::OpenApi;
 use uuid::Uuid;
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(get_persons, get_person_by_id, create_person, delete_person),
+    paths(
+        get_persons,
+        get_person_by_id,
+        create_person,
+        update_person,
+        patch_person,
+        delete_person,
+        restore_person
+    ),
     components(schemas(
         inventory::model::CreatePersonRequest,
         inventory::model::UpdatePersonRequest,
@@ -26,13 +43,16 @@ pub struct PersonApi;
     get,
     path = "",
     summary = "Get a list of persons",
-    description = "Returns a list of persons",
+    description = "Returns a page of persons. Deleted persons are left out unless include_deleted is set",
     params(
         Pagination,
+        ("include_deleted", Query, description="Include deleted persons"),
+        ("include_total", Query, description="Count the persons across all pages"),
+        ("sort" = Option<String>, Query, description="Fields to sort by, comma separated and descending when prefixed with `-`: name, email, created_at, last_update", example="name,-created_at"),
         ("Authorization", Header, description="Bearer token"),
     ),
     responses(
-        (status = 200, description = "Returns a list of persons", body=[Person]),
+        (status = 200, description = "Returns a page of persons", body=Page<Person>),
         (status = 400, description = "Bad request", body=ApiError),
         (status = 401, description = "Unauthorized", body=ApiError),
         (status = 403, description = "Forbidden", body=ApiError),
@@ -43,13 +63,23 @@ pub struct PersonApi;
 pub async fn get_persons(
     claims: Claims,
     pagination: Option<Query<Pagination>>,
+    include_deleted: Option<Query<IncludeDeletedQuery>>,
+    include_total: Option<Query<IncludeTotalQuery>>,
+    Query(SortQuery { sort }): Query<SortQuery<PersonSortField>>,
     State(app_context): State<AppContext>,
-) -> Result<Json<Vec<Person>>, ServiceError> {
-    let Query(pagination) = pagination.unwrap_or_default();
+) -> Result<Json<Page<Person>>, ServiceError> {
+    let pagination = pagination.map(|pagination_query| pagination_query.0);
+    let Query(include_deleted) = include_deleted.unwrap_or_default();
+    let Query(include_total) = include_total.unwrap_or_default();
     debug!("Claims: {:?}", claims);
     app_context
         .person_service
-        .get_persons(pagination.last_id, pagination.page_size) // TODO - refactor service interface to accept Option<Pagination>
+        .get_persons(
+            pagination,
+            sort,
+            include_deleted.include_deleted,
+            include_total.include_total,
+        )
         .await
         .map(Json)
 }
@@ -66,23 +96,37 @@ pub async fn get_persons(
             (status = 401, description = "Unauthorized", body = ApiError),
             (status = 403, description = "Forbidden", body = ApiError),
             (status = 404, description = "Not found", body = ApiError),
+            (status = 409, description = "A request with the same Idempotency-Key is still running", body = ApiError),
+            (status = 422, description = "The Idempotency-Key was used for a different request", body = ApiError),
             (status = 500, description = "Internal server error", body = ApiError),
         ),
         params(
+            ("Idempotency-Key", Header, description="Optional. A retry with the same key returns the first response instead of repeating the request"),
             ("Authorization", Header, description="Bearer token"),
         ),
 )]
 pub async fn create_person(
     claims: Claims,
+    idempotency_key: IdempotencyKey,
     State(app_context): State<AppContext>,
     Json(person): Json<CreatePersonRequest>,
-) -> Result<Json<Person>, ServiceError> {
+) -> Response {
     debug!("Claims: {:?}", claims);
-    app_context
-        .person_service
-        .create_person(person)
-        .await
-        .map(Json)
+    idempotent(
+        &app_context,
+        &claims.sub,
+        idempotency_key,
+        "create_person",
+        person,
+        |person| async {
+            app_context
+                .person_service
+                .create_person(person, claims.sub.clone())
+                .await
+                .map(Json)
+        },
+    )
+    .await
 }
 
 #[axum_macros::debug_handler]
@@ -91,7 +135,7 @@ pub async fn create_person(
     delete,
     path = "/{id}",
     summary = "Remove a specific person",
-    description = "Removes a specific person",
+    description = "Removes a specific person. The person is kept, marked as deleted, so their invoices still resolve, and can be restored",
     params(
         ("Authorization", Header, description="Bearer token"),
         ("id" = Uuid, Path, description = "Person Id - UUID"),
@@ -111,7 +155,11 @@ pub async fn delete_person(
     State(app_context): State<AppContext>,
 ) -> Result<Json<()>, ServiceError> {
     debug!("Claims: {:?}", claims);
-    app_context.person_service.delete_person(id).await.map(Json)
+    app_context
+        .person_service
+        .delete_person(id, claims.sub)
+        .await
+        .map(Json)
 }
 
 #[axum_macros::debug_handler]
@@ -120,13 +168,15 @@ pub async fn delete_person(
     get,
     path = "/{id}",
     summary = "Get a specific person",
-    description = "Returns a specific person identified by the id",
+    description = "Returns a specific person identified by the id. A deleted person is only returned when include_deleted is set",
     params(
         ("id" = Uuid, Path, description = "Person Id - UUID"),
+        ("include_deleted", Query, description="Return the person even if deleted"),
         ("Authorization", Header, description="Bearer token"),
     ),
     responses(
-        (status = 200, description = "Returns a list of persons", body=Person),
+        (status = 200, description = "Returns a list of persons", body=Person,
+         headers(("ETag" = String, description = "The person's version, to send back in If-Match when changing them"))),
         (status = 400, description = "Bad request", body=ApiError),
         (status = 401, description = "Unauthorized", body=ApiError),
         (status = 403, description = "Forbidden", body=ApiError),
@@ -134,22 +184,151 @@ pub async fn delete_person(
     ),
 )]
 pub async fn get_person_by_id(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    include_deleted: Option<Query<IncludeDeletedQuery>>,
+    State(app_context): State<AppContext>,
+) -> Result<Tagged<Person>, ServiceError> {
+    let Query(include_deleted) = include_deleted.unwrap_or_default();
+    debug!("Claims: {:?}", claims);
+    app_context
+        .person_service
+        .get_person(id, include_deleted.include_deleted)
+        .await
+        .map(Tagged)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    put,
+    path = "/{id}",
+    summary = "Update a person",
+    description = "Replaces the name and email of a person. If-Match must carry the ETag the person was read with, and the update is refused if the person has changed since",
+    request_body = UpdatePersonRequest,
+    params(
+        ("id" = Uuid, Path, description = "Person Id - UUID"),
+        ("If-Match", Header, description = "The ETag of the person being updated"),
+        ("Authorization", Header, description="Bearer token"),
+    ),
+    responses(
+        (status = 200, description = "Person updated", body=Person,
+         headers(("ETag" = String, description = "The person's new version"))),
+        (status = 400, description = "Bad request", body=ApiError),
+        (status = 401, description = "Unauthorized", body=ApiError),
+        (status = 403, description = "Forbidden", body=ApiError),
+        (status = 404, description = "Not found", body=ApiError),
+        (status = 412, description = "The person has changed since the ETag in If-Match was read", body=ApiError),
+        (status = 428, description = "If-Match is missing", body=ApiError),
+        (status = 500, description = "Internal server error", body=ApiError),
+    ),
+)]
+pub async fn update_person(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    IfMatch(version): IfMatch,
+    State(app_context): State<AppContext>,
+    Json(mut update_person_request): Json<UpdatePersonRequest>,
+) -> Result<Tagged<Person>, ServiceError> {
+    if id.to_string() != update_person_request.id {
+        return Err(ServiceError::InputValidationError(format!(
+            "ID in path does not match ID in request. path: {}, request: {}",
+            id, update_person_request.id
+        )));
+    }
+    update_person_request.version = version;
+    app_context
+        .person_service
+        .update_person(update_person_request, claims.sub)
+        .await
+        .map(Tagged)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    patch,
+    path = "/{id}",
+    summary = "Change some fields of a person",
+    description = "Applies a JSON Merge Patch (RFC 7396) of UpdatePersonRequest to a person: fields left out of the patch are kept. If-Match must carry the ETag the person was read with, and the patch is refused if the person has changed since",
+    request_body(content = Object, content_type = "application/merge-patch+json",
+                 description = "The fields to change, e.g. {\"email\": \"new@example.com\"}"),
+    params(
+        ("id" = Uuid, Path, description = "Person Id - UUID"),
+        ("If-Match", Header, description = "The ETag of the person being changed"),
+        ("Authorization", Header, description="Bearer token"),
+    ),
+    responses(
+        (status = 200, description = "Person updated", body=Person,
+         headers(("ETag" = String, description = "The person's new version"))),
+        (status = 400, description = "Bad request", body=ApiError),
+        (status = 401, description = "Unauthorized", body=ApiError),
+        (status = 403, description = "Forbidden", body=ApiError),
+        (status = 404, description = "Not found", body=ApiError),
+        (status = 412, description = "The person has changed since the ETag in If-Match was read", body=ApiError),
+        (status = 428, description = "If-Match is missing", body=ApiError),
+        (status = 500, description = "Internal server error", body=ApiError),
+    ),
+)]
+pub async fn patch_person(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    IfMatch(version): IfMatch,
+    State(app_context): State<AppContext>,
+    Json(patch): Json<Value>,
+) -> Result<Tagged<Person>, ServiceError> {
+    app_context
+        .person_service
+        .patch_person(id, patch, version, claims.sub)
+        .await
+        .map(Tagged)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "/{id}/restore",
+    summary = "Restore a deleted person",
+    description = "Undoes the delete of a person",
+    params(
+        ("Authorization", Header, description="Bearer token"),
+        ("id" = Uuid, Path, description = "Person Id - UUID"),
+    ),
+    responses(
+        (status = 200, description = "Person restored", body=Person),
+        (status = 400, description = "Bad request", body=ApiError),
+        (status = 401, description = "Unauthorized", body=ApiError),
+        (status = 403, description = "Forbidden", body=ApiError),
+        (status = 404, description = "No deleted person with this id", body=ApiError),
+        (status = 500, description = "Internal server error", body=ApiError),
+    ),
+)]
+pub async fn restore_person(
     claims: Claims,
     Path(id): Path<Uuid>,
     State(app_context): State<AppContext>,
 ) -> Result<Json<Person>, ServiceError> {
     debug!("Claims: {:?}", claims);
-    app_context.person_service.get_person(id).await.map(Json)
+    app_context
+        .person_service
+        .restore_person(id, claims.sub)
+        .await
+        .map(Json)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::inventory::model::Pagination;
+    use crate::inventory::handlers::idempotency::IdempotencyKey;
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::sort::SortQuery;
+    use crate::inventory::model::{ExpectedVersion, Pagination};
     use crate::inventory::services::invoice::MockInvoiceService;
     use crate::inventory::services::item::MockItemService;
     use crate::inventory::services::person::MockPersonService;
-    use crate::test_helpers::mock_claims;
+    use crate::test_helpers::{body_to_string, mock_claims};
     use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
     use axum::Json;
 
     #[tokio::test]
@@ -164,20 +343,28 @@ mod tests {
             name: "John".to_string(),
             email: "test@test.com".to_string(),
             audit_info: Default::default(),
+            version: 1,
         };
         let cloned_expected_person = expected_person.clone();
         let mut mock_person_service = MockPersonService::new();
-        mock_person_service.expect_get_person().returning(move |_| {
-            let cloned_person = expected_person.clone();
-            Box::pin(async move { Ok(cloned_person) })
-        });
+        mock_person_service
+            .expect_get_person()
+            .returning(move |_, _| {
+                let cloned_person = expected_person.clone();
+                Box::pin(async move { Ok(cloned_person) })
+            });
         let app_context = test_app_context(
             mock_person_service,
             MockItemService::new(),
             MockInvoiceService::new(),
         );
-        let result =
-            super::get_person_by_id(mock_claims(), Path(Uuid::new_v4()), State(app_context)).await;
+        let result = super::get_person_by_id(
+            mock_claims(),
+            Path(Uuid::new_v4()),
+            None,
+            State(app_context),
+        )
+        .await;
         assert!(result.is_ok());
         let person = result.unwrap().0;
         assert_eq!(person, cloned_expected_person);
@@ -196,6 +383,7 @@ mod tests {
                 name: "John".to_string(),
                 email: "john@test.com".to_string(),
                 audit_info: Default::default(),
+                version: 1,
             },
             Person {
                 seq: 2,
@@ -203,15 +391,19 @@ mod tests {
                 name: "Jane".to_string(),
                 email: "jane@test.com".to_string(),
                 audit_info: Default::default(),
+                version: 1,
             },
         ];
         let cloned_expected_persons = expected_persons.clone();
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_get_persons()
-            .returning(move |_, _| {
-                let cloned_persons = expected_persons.clone();
-                Box::pin(async move { Ok(cloned_persons) })
+            .returning(move |_, _, _, _| {
+                let page = Page {
+                    items: expected_persons.clone(),
+                    ..Page::default()
+                };
+                Box::pin(async move { Ok(page) })
             });
         let app_context = test_app_context(
             mock_person_service,
@@ -219,9 +411,17 @@ mod tests {
             MockInvoiceService::new(),
         );
         let maybe_pagination = Some(Query(Pagination::default()));
-        let result = super::get_persons(mock_claims(), maybe_pagination, State(app_context)).await;
+        let result = super::get_persons(
+            mock_claims(),
+            maybe_pagination,
+            None,
+            None,
+            Query(SortQuery::default()),
+            State(app_context),
+        )
+        .await;
         assert!(result.is_ok());
-        let persons = result.unwrap().0;
+        let persons = result.unwrap().0.items;
         assert_eq!(persons, cloned_expected_persons);
     }
 
@@ -242,12 +442,14 @@ mod tests {
             name: person.name.clone(),
             email: person.email.clone(),
             audit_info: Default::default(),
+            version: 1,
         };
         let cloned_expected_person = expected_person.clone();
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_create_person()
-            .returning(move |_| {
+            .withf(|_, actor| actor == "test")
+            .returning(move |_, _| {
                 let cloned_person = expected_person.clone();
                 Box::pin(async move { Ok(cloned_person) })
             });
@@ -256,12 +458,121 @@ mod tests {
             MockItemService::new(),
             MockInvoiceService::new(),
         );
-        let result = super::create_person(mock_claims(), State(app_context), Json(person)).await;
-        assert!(result.is_ok());
-        let person = result.unwrap().0;
+        let result = super::create_person(
+            mock_claims(),
+            IdempotencyKey(None),
+            State(app_context),
+            Json(person),
+        )
+        .await;
+        assert_eq!(result.status(), StatusCode::OK);
+        let body = body_to_string(result.into_body()).await.unwrap();
+        let person: Person = serde_json::from_str(&body).unwrap();
         assert_eq!(person, cloned_expected_person);
     }
 
+    #[tokio::test]
+    async fn test_update_person() {
+        use crate::inventory::handlers::IfMatch;
+        use crate::inventory::model::{Person, UpdatePersonRequest};
+        use crate::test_helpers::test_app_context;
+        use uuid::Uuid;
+
+        let uuid = Uuid::new_v4();
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_update_person()
+            .withf(|request, actor| {
+                request.version == ExpectedVersion::OneOf(vec![2]) && actor == "test"
+            })
+            .returning(move |request, _| {
+                Box::pin(async move {
+                    Ok(Person {
+                        id: request.id,
+                        version: 3,
+                        ..Person::default()
+                    })
+                })
+            });
+        let app_context = test_app_context(
+            mock_person_service,
+            MockItemService::new(),
+            MockInvoiceService::new(),
+        );
+        let request = UpdatePersonRequest {
+            id: uuid.to_string(),
+            name: "Updated Person".to_string(),
+            email: "updated@example.com".to_string(),
+            changed_by: String::new(),
+            version: ExpectedVersion::Any,
+        };
+        let result = super::update_person(
+            mock_claims(),
+            Path(uuid),
+            IfMatch(ExpectedVersion::OneOf(vec![2])),
+            State(app_context),
+            Json(request.clone()),
+        )
+        .await;
+        let person = result.unwrap().0;
+        assert_eq!(person.id, uuid.to_string());
+        assert_eq!(person.version, 3);
+
+        // the id in the body must match the one in the path
+        let app_context = test_app_context(
+            MockPersonService::new(),
+            MockItemService::new(),
+            MockInvoiceService::new(),
+        );
+        let result = super::update_person(
+            mock_claims(),
+            Path(Uuid::new_v4()),
+            IfMatch(ExpectedVersion::OneOf(vec![2])),
+            State(app_context),
+            Json(request),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(crate::inventory::services::ServiceError::InputValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_patch_person() {
+        use crate::inventory::handlers::IfMatch;
+        use crate::inventory::model::Person;
+        use crate::test_helpers::test_app_context;
+        use serde_json::json;
+        use uuid::Uuid;
+
+        let uuid = Uuid::new_v4();
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_patch_person()
+            .withf(move |id, patch, version, actor| {
+                *id == uuid
+                    && patch == &json!({"email": "patched@example.com"})
+                    && *version == ExpectedVersion::OneOf(vec![2])
+                    && actor == "test"
+            })
+            .returning(|_, _, _, _| Box::pin(async { Ok(Person::default()) }));
+        let app_context = test_app_context(
+            mock_person_service,
+            MockItemService::new(),
+            MockInvoiceService::new(),
+        );
+        let result = super::patch_person(
+            mock_claims(),
+            Path(uuid),
+            IfMatch(ExpectedVersion::OneOf(vec![2])),
+            State(app_context),
+            Json(json!({"email": "patched@example.com"})),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_delete_person() {
         use crate::test_helpers::test_app_context;
@@ -270,7 +581,8 @@ mod tests {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_delete_person()
-            .returning(move |_| Box::pin(async move { Ok(()) }));
+            .withf(|_, deleted_by| deleted_by == "test")
+            .returning(move |_, _| Box::pin(async move { Ok(()) }));
         let app_context = test_app_context(
             mock_person_service,
             MockItemService::new(),
@@ -288,21 +600,28 @@ mod tests {
         use uuid::Uuid;
 
         let mut mock_person_service = MockPersonService::new();
-        mock_person_service.expect_get_person().returning(move |_| {
-            Box::pin(async move { Err(ServiceError::NotFound("Mock NotFound".to_string())) })
-        });
+        mock_person_service
+            .expect_get_person()
+            .returning(move |_, _| {
+                Box::pin(async move { Err(ServiceError::NotFound("Mock NotFound".to_string())) })
+            });
         let app_context = test_app_context(
             mock_person_service,
             MockItemService::new(),
             MockInvoiceService::new(),
         );
-        let result =
-            super::get_person_by_id(mock_claims(), Path(Uuid::new_v4()), State(app_context)).await;
+        let result = super::get_person_by_id(
+            mock_claims(),
+            Path(Uuid::new_v4()),
+            None,
+            State(app_context),
+        )
+        .await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            ServiceError::NotFound(msg) => assert!(true, "Expected NotFound: msg: {}", msg),
-            _ => assert!(false, "Expected NotFound"),
+            ServiceError::NotFound(msg) => assert_eq!(msg, "Mock NotFound"),
+            _ => panic!("Expected NotFound"),
         }
     }
 }
@@ -0,0 +1,294 @@
+use crate::inventory::handlers::idempotency::{idempotent, IdempotencyKey};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::{
+    ApiError, CreateStockMovementRequest, MovementType, Pagination, StockLevel, StockMovement,
+};
+use crate::inventory::services::ServiceError;
+use crate::jwt::Claims;
+use crate::AppContext;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::Json;
+use tracing::instrument;
+use utoipa::OpenApi;
+use uuid::Uuid;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_item_stock, create_stock_movement, get_stock_movements),
+    components(schemas(
+        StockLevel,
+        StockMovement,
+        CreateStockMovementRequest,
+        MovementType,
+        ApiError
+    ))
+)]
+pub struct StockApi;
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/stock",
+    summary = "Get stock levels for an item",
+    description = "Get the on hand, reserved and available quantities for an item. On hand is the sum of the item's stock movements",
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Stock levels returned", body=StockLevel),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_item_stock(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<StockLevel>, ServiceError> {
+    app_context.stock_service.get_stock(id).await.map(Json)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    post,
+    path = "/{id}/stock/movements",
+    summary = "Record a stock movement",
+    description = "Append a receipt, adjustment, sale, return or write-off to an item's stock ledger",
+    request_body = CreateStockMovementRequest,
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       ("Idempotency-Key", Header, description="Optional. A retry with the same key returns the first response instead of repeating the request"),
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 201, description = "Stock movement recorded", body=StockMovement),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 404, description = "Not Found", body=ApiError),
+       (status = 409, description = "Movement would take on hand below reserved, or a request with the same Idempotency-Key is still running", body=ApiError),
+       (status = 422, description = "The Idempotency-Key was used for a different request", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn create_stock_movement(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    idempotency_key: IdempotencyKey,
+    State(app_context): State<AppContext>,
+    Json(create_stock_movement_request): Json<CreateStockMovementRequest>,
+) -> Result<Response, ServiceError> {
+    if id != create_stock_movement_request.item_id {
+        return Err(ServiceError::InputValidationError(format!(
+            "ID in path does not match ID in request. path: {}, request: {}",
+            id, create_stock_movement_request.item_id
+        )));
+    }
+    let response = idempotent(
+        &app_context,
+        &claims.sub,
+        idempotency_key,
+        "create_stock_movement",
+        create_stock_movement_request,
+        |request| async {
+            app_context
+                .stock_service
+                .record_movement(request, claims.sub.clone())
+                .await
+                .map(|movement| (StatusCode::CREATED, Json(movement)))
+        },
+    )
+    .await;
+    Ok(response)
+}
+
+#[axum_macros::debug_handler]
+#[instrument]
+#[utoipa::path(
+    get,
+    path = "/{id}/stock/movements",
+    summary = "Get stock movement history for an item",
+    description = "Get an item's stock movements, oldest first",
+    params(
+       ("id", Path, description="The unique identifier of the item"),
+       Pagination,
+       ("Authorization", Header, description="Bearer token")
+    ),
+    responses(
+       (status = 200, description = "Stock movements returned", body=Page<StockMovement>),
+       (status = 400, description = "Bad Request", body=ApiError),
+       (status = 401, description = "Unauthorized", body=ApiError),
+       (status = 403, description = "Forbidden", body=ApiError),
+       (status = 500, description = "Internal Server Error", body=ApiError)
+    )
+)]
+pub async fn get_stock_movements(
+    claims: Claims,
+    Path(id): Path<Uuid>,
+    maybe_pagination_query: Option<Query<Pagination>>,
+    State(app_context): State<AppContext>,
+) -> Result<Json<Page<StockMovement>>, ServiceError> {
+    let pagination = maybe_pagination_query.map(|pagination_query| pagination_query.0);
+    app_context
+        .stock_service
+        .get_movements(id, pagination)
+        .await
+        .map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::handlers::idempotency::IdempotencyKey;
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::{
+        CreateStockMovementRequest, MovementType, StockLevel, StockMovement,
+    };
+    use crate::inventory::services::invoice::MockInvoiceService;
+    use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::person::MockPersonService;
+    use crate::inventory::services::stock::MockStockService;
+    use crate::inventory::services::ServiceError;
+    use crate::test_helpers::{body_to_string, first_item_uuid, mock_claims, test_app_context};
+    use crate::AppContext;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::Json;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn app_context_with_stock_service(mock_stock_service: MockStockService) -> AppContext {
+        AppContext {
+            stock_service: Arc::new(mock_stock_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        }
+    }
+
+    fn create_stock_level(item_id: Uuid) -> StockLevel {
+        StockLevel {
+            item_id: item_id.to_string(),
+            on_hand: 10,
+            reserved: 3,
+            available: 7,
+            changed_by: Some("testuser".to_string()),
+            updated_at: None,
+        }
+    }
+
+    fn create_movement_request(item_id: Uuid) -> CreateStockMovementRequest {
+        CreateStockMovementRequest {
+            item_id,
+            movement_type: MovementType::Receipt,
+            quantity: 10,
+            note: Some("PO-1001".to_string()),
+            created_by: "testuser".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_item_stock() {
+        let expected_stock = create_stock_level(first_item_uuid());
+        let cloned_stock = expected_stock.clone();
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service.expect_get_stock().returning(move |_| {
+            let cloned_stock = cloned_stock.clone();
+            Box::pin(async move { Ok(cloned_stock) })
+        });
+        let app_context = app_context_with_stock_service(mock_stock_service);
+        let result =
+            super::get_item_stock(mock_claims(), Path(first_item_uuid()), State(app_context)).await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().0, expected_stock);
+    }
+
+    #[tokio::test]
+    async fn test_get_item_stock_not_found() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service.expect_get_stock().returning(|_| {
+            Box::pin(async { Err(ServiceError::NotFound("not found".to_string())) })
+        });
+        let app_context = app_context_with_stock_service(mock_stock_service);
+        let result =
+            super::get_item_stock(mock_claims(), Path(Uuid::new_v4()), State(app_context)).await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_stock_movement() {
+        let expected_movement = StockMovement {
+            item_id: first_item_uuid().to_string(),
+            quantity: 10,
+            ..StockMovement::default()
+        };
+        let cloned_movement = expected_movement.clone();
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service
+            .expect_record_movement()
+            .withf(|_, actor| actor == "test")
+            .returning(move |_, _| {
+                let cloned_movement = cloned_movement.clone();
+                Box::pin(async move { Ok(cloned_movement) })
+            });
+        let app_context = app_context_with_stock_service(mock_stock_service);
+        let result = super::create_stock_movement(
+            mock_claims(),
+            Path(first_item_uuid()),
+            IdempotencyKey(None),
+            State(app_context),
+            Json(create_movement_request(first_item_uuid())),
+        )
+        .await;
+        let response = result.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = body_to_string(response.into_body()).await.unwrap();
+        let movement: StockMovement = serde_json::from_str(&body).unwrap();
+        assert_eq!(movement, expected_movement);
+    }
+
+    #[tokio::test]
+    async fn test_create_stock_movement_mismatched_ids() {
+        let app_context = app_context_with_stock_service(MockStockService::new());
+        let result = super::create_stock_movement(
+            mock_claims(),
+            Path(Uuid::new_v4()),
+            IdempotencyKey(None),
+            State(app_context),
+            Json(create_movement_request(first_item_uuid())),
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_stock_movements() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service.expect_get_movements().returning(|_, _| {
+            Box::pin(async {
+                Ok(Page {
+                    items: vec![StockMovement::default()],
+                    ..Page::default()
+                })
+            })
+        });
+        let app_context = app_context_with_stock_service(mock_stock_service);
+        let result = super::get_stock_movements(
+            mock_claims(),
+            Path(first_item_uuid()),
+            None,
+            State(app_context),
+        )
+        .await;
+        assert_eq!(result.unwrap().0.items.len(), 1);
+    }
+}
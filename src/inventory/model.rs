@@ -1,22 +1,27 @@
+use crate::inventory::model::money::{
+    amount_between, decimal_between, decimal_string, positive_amount, Currency, Money,
+};
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use garde::Validate;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize, IntoParams, Clone, Copy, PartialEq, Serialize, ToSchema)]
-pub struct Pagination {
-    pub last_id: Option<i32>,
-    pub page_size: i64,
-}
+pub mod money;
+pub mod page;
+pub mod sort;
 
-impl Default for Pagination {
-    fn default() -> Self {
-        Pagination {
-            last_id: None,
-            page_size: 10,
-        }
-    }
+/// Which page of a list to return. A list is read from the start, then page by page by passing
+/// back the `next_cursor` of each page
+#[derive(Debug, Default, Deserialize, IntoParams, Clone, PartialEq, Serialize, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// The `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// Results per page, 10 unless set and at most 100
+    pub page_size: Option<i64>,
 }
 #[derive(
     Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Validate, ToSchema,
@@ -26,10 +31,43 @@ pub struct CreatePersonRequest {
     pub name: String,
     #[garde(email)]
     pub email: String,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub created_by: String,
 }
 
+/// The versions of a record a change may be made against, from the caller's `If-Match` header
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub enum ExpectedVersion {
+    /// Whichever version is current, as for `If-Match: *`
+    #[default]
+    Any,
+    /// One of these, as for `If-Match: "3", "4"`
+    OneOf(Vec<i64>),
+}
+
+impl ExpectedVersion {
+    pub fn matches(&self, version: i64) -> bool {
+        match self {
+            ExpectedVersion::Any => true,
+            ExpectedVersion::OneOf(versions) => versions.contains(&version),
+        }
+    }
+}
+
+impl Display for ExpectedVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectedVersion::Any => write!(f, "any version"),
+            ExpectedVersion::OneOf(versions) => {
+                let versions: Vec<String> = versions.iter().map(i64::to_string).collect();
+                write!(f, "version {}", versions.join(" or "))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Validate, ToSchema)]
 pub struct UpdatePersonRequest {
     #[garde(skip)] // TODO - improve this
@@ -38,8 +76,15 @@ pub struct UpdatePersonRequest {
     pub name: String,
     #[garde(email)]
     pub email: String,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub changed_by: String,
+    /// Taken from the caller's `If-Match` header; the update is refused if the record has
+    /// changed since this version was read
+    #[garde(skip)]
+    #[serde(skip)]
+    pub version: ExpectedVersion,
 }
 
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
@@ -48,6 +93,9 @@ pub struct AuditInfo {
     pub created_at: DateTime<Utc>,
     pub changed_by: String,
     pub updated_at: DateTime<Utc>,
+    /// Only set on deleted records, which are returned when `include_deleted` is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
@@ -57,6 +105,8 @@ pub struct Person {
     pub name: String,
     pub email: String,
     pub audit_info: AuditInfo,
+    /// Goes up by one with every change; also returned as the `ETag` of a single record
+    pub version: i64,
 }
 
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
@@ -71,21 +121,17 @@ pub struct DeleteResults {
     pub deleted: bool,
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
-pub struct PersonList {
-    pub persons: Vec<Person>,
-    pub total: i32,
-}
-
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateItemRequest {
     #[garde(length(min = 3, max = 255))]
     pub name: String,
     #[garde(skip)]
     pub description: String,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub unit_price: f64,
+    #[garde(custom(amount_between(0, 1000000)))]
+    pub unit_price: Money,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub created_by: String,
 }
 
@@ -97,10 +143,17 @@ pub struct UpdateItemRequest {
     pub name: String,
     #[garde(skip)]
     pub description: String,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub unit_price: f64,
+    #[garde(custom(amount_between(0, 1000000)))]
+    pub unit_price: Money,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub changed_by: String,
+    /// Taken from the caller's `If-Match` header; the update is refused if the record has
+    /// changed since this version was read
+    #[garde(skip)]
+    #[serde(skip)]
+    pub version: ExpectedVersion,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, Validate)]
@@ -113,36 +166,237 @@ pub struct Item {
     pub name: String,
     #[garde(skip)]
     pub description: String,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub unit_price: f64,
+    #[garde(custom(amount_between(0, 1000000)))]
+    pub unit_price: Money,
     #[garde(skip)]
     pub audit_info: AuditInfo,
+    /// Goes up by one with every change; also returned as the `ETag` of a single record
+    #[garde(skip)]
+    pub version: i64,
+    #[garde(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock: Option<StockLevel>,
+    /// Only set on items found by a keyword search
+    #[garde(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_match: Option<SearchMatch>,
+}
+
+/// How an item matched the keywords it was searched for
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchMatch {
+    /// Higher is more relevant; only comparable between the results of one search
+    pub rank: f32,
+    /// The HTML-escaped name with the matching words wrapped in `<mark>` tags
+    pub name: String,
+    /// Up to two HTML-escaped extracts of the description around the matching words, wrapped in
+    /// `<mark>` tags and separated by ` ... `
+    pub description: String,
+}
+
+/// Narrows a list of items; an item must match every filter given
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ItemSearchQuery {
+    /// Keywords to find in the name or description, in web search syntax: `"a phrase"`,
+    /// `-excluded` and `or` are understood. Matches are returned most relevant first
+    pub q: Option<String>,
+    /// Lowest unit price, inclusive
+    #[serde(default, with = "decimal_string::option")]
+    #[param(value_type = Option<String>, example = "10.00")]
+    pub min_price: Option<BigDecimal>,
+    /// Highest unit price, inclusive
+    #[serde(default, with = "decimal_string::option")]
+    #[param(value_type = Option<String>, example = "99.99")]
+    pub max_price: Option<BigDecimal>,
+    /// Only items created by this client
+    pub created_by: Option<String>,
+}
+
+impl ItemSearchQuery {
+    /// True if no filter is set, so every item matches
+    pub fn is_empty(&self) -> bool {
+        self.q.is_none()
+            && self.min_price.is_none()
+            && self.max_price.is_none()
+            && self.created_by.is_none()
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct StockLevel {
+    pub item_id: String,
+    pub on_hand: i32,
+    pub reserved: i32,
+    pub available: i32,
+    pub changed_by: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "stock_movement_type", rename_all = "snake_case")]
+pub enum MovementType {
+    #[default]
+    Receipt,
+    Adjustment,
+    Sale,
+    Return,
+    WriteOff,
+}
+
+impl MovementType {
+    /// Whether a movement of this type adds to (true) or takes from (false) on hand stock.
+    /// Adjustments carry their own sign and return None.
+    pub fn is_inbound(&self) -> Option<bool> {
+        match self {
+            MovementType::Receipt | MovementType::Return => Some(true),
+            MovementType::Sale | MovementType::WriteOff => Some(false),
+            MovementType::Adjustment => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct StockMovement {
+    pub seq: i32,
+    pub id: String,
+    pub item_id: String,
+    pub movement_type: MovementType,
+    /// Signed change to on hand stock
+    pub quantity: i32,
+    pub note: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(
+    Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Validate, ToSchema,
+)]
+pub struct CreateStockMovementRequest {
+    #[garde(skip)]
+    pub item_id: Uuid,
+    #[garde(skip)]
+    pub movement_type: MovementType,
+    /// Units moved. Positive for receipts, sales, returns and write-offs; adjustments are signed.
+    #[garde(range(min = -1000000, max = 1000000))]
+    pub quantity: i32,
+    #[garde(length(max = 255))]
+    pub note: Option<String>,
+    /// Taken from the caller's token, never from the request body
+    #[garde(skip)]
+    #[serde(skip)]
+    pub created_by: String,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateInvoiceRequest {
     #[garde(skip)]
     pub user_id: Uuid,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub total: f64,
+    /// Currency of the invoice; every line, the discount and the total must be in it
+    #[garde(skip)]
+    #[serde(default)]
+    pub currency: Currency,
+    /// Optional check value; the request is rejected if it differs from the computed total
+    #[garde(inner(custom(amount_between(0, 1000000))))]
+    #[serde(default)]
+    pub total: Option<Money>,
+    /// Fraction of the discounted subtotal charged as tax, e.g. "0.2" for 20%
+    #[garde(custom(decimal_between(0, 1)))]
+    #[serde(default, with = "decimal_string")]
+    #[schema(value_type = String, example = "0.2")]
+    pub tax_rate: BigDecimal,
+    #[garde(custom(amount_between(0, 1000000)))]
+    #[serde(default)]
+    pub discount: Money,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub created_by: String,
+    /// Items to put on the invoice, a unit for every time an item is listed. The invoice is only
+    /// created if every line can be added
     #[garde(skip)]
+    #[serde(default)]
     pub items: Vec<Uuid>,
-    #[garde(skip)]
-    pub paid: bool,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, Validate)]
 pub struct UpdateInvoiceRequest {
     #[garde(skip)]
     pub id: Uuid,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub total: f64,
-    #[garde(skip)]
-    pub paid: bool,
+    /// Optional check value; the request is rejected if it differs from the computed total
+    #[garde(inner(custom(amount_between(0, 1000000))))]
+    #[serde(default)]
+    pub total: Option<Money>,
+    /// Leave out to keep the invoice's current tax rate
+    #[garde(inner(custom(decimal_between(0, 1))))]
+    #[serde(default, with = "decimal_string::option")]
+    #[schema(value_type = Option<String>, example = "0.2")]
+    pub tax_rate: Option<BigDecimal>,
+    /// Leave out to keep the invoice's current discount
+    #[garde(inner(custom(amount_between(0, 1000000))))]
+    #[serde(default)]
+    pub discount: Option<Money>,
+    /// Taken from the caller's token, never from the request body
     #[garde(skip)]
+    #[serde(skip)]
     pub changed_by: String,
+    /// Taken from the caller's `If-Match` header; the update is refused if the record has
+    /// changed since this version was read
+    #[garde(skip)]
+    #[serde(skip)]
+    pub version: ExpectedVersion,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "invoice_status", rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    /// Lines, tax rate and discount can still be changed
+    #[default]
+    Draft,
+    /// Sent to the customer; the invoice can no longer be changed
+    Issued,
+    Paid,
+    Void,
+    Refunded,
+}
+
+impl InvoiceStatus {
+    /// Whether an invoice in this status may move to `next`. Drafts are issued or voided,
+    /// issued invoices are paid or voided, and paid invoices can only be refunded.
+    pub fn can_transition_to(&self, next: InvoiceStatus) -> bool {
+        matches!(
+            (self, next),
+            (InvoiceStatus::Draft, InvoiceStatus::Issued)
+                | (InvoiceStatus::Draft, InvoiceStatus::Void)
+                | (InvoiceStatus::Issued, InvoiceStatus::Paid)
+                | (InvoiceStatus::Issued, InvoiceStatus::Void)
+                | (InvoiceStatus::Paid, InvoiceStatus::Refunded)
+        )
+    }
+
+    /// Whether an invoice in this status keeps its lines' quantities reserved. Reservations are
+    /// taken out of stock when the invoice is paid and released when it is voided.
+    pub fn holds_stock(&self) -> bool {
+        matches!(self, InvoiceStatus::Draft | InvoiceStatus::Issued)
+    }
+}
+
+impl Display for InvoiceStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvoiceStatus::Draft => write!(f, "draft"),
+            InvoiceStatus::Issued => write!(f, "issued"),
+            InvoiceStatus::Paid => write!(f, "paid"),
+            InvoiceStatus::Void => write!(f, "void"),
+            InvoiceStatus::Refunded => write!(f, "refunded"),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema, Validate)]
@@ -153,14 +407,100 @@ pub struct Invoice {
     pub id: String,
     #[garde(skip)]
     pub user_id: String,
-    #[garde(range(min = 0.0, max = 1000000.0))]
-    pub total: f64,
     #[garde(skip)]
-    pub paid: bool,
+    pub currency: Currency,
+    /// Sum of the line subtotals
+    #[garde(skip)]
+    pub subtotal: Money,
+    #[garde(skip)]
+    pub discount: Money,
+    #[garde(skip)]
+    #[serde(with = "decimal_string")]
+    #[schema(value_type = String, example = "0.2")]
+    pub tax_rate: BigDecimal,
+    #[garde(skip)]
+    pub tax: Money,
+    /// Subtotal less discount, plus tax
+    #[garde(custom(amount_between(0, 1000000)))]
+    pub total: Money,
+    /// Sum of the payments recorded against the invoice
+    #[garde(skip)]
+    pub amount_paid: Money,
+    /// Total less amount paid
+    #[garde(skip)]
+    pub balance: Money,
+    #[garde(skip)]
+    pub status: InvoiceStatus,
     #[garde(skip)]
     pub audit_info: AuditInfo,
+    /// Goes up by one with every change; also returned as the `ETag` of a single record
     #[garde(skip)]
-    pub items: Vec<Item>,
+    pub version: i64,
+    #[garde(skip)]
+    pub items: Vec<InvoiceLine>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InvoiceLine {
+    pub item_id: String,
+    pub name: String,
+    pub description: String,
+    pub quantity: i32,
+    /// Price per unit captured when the item was added to the invoice
+    pub unit_price: Money,
+    pub subtotal: Money,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "payment_method", rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Cash,
+    #[default]
+    Card,
+    BankTransfer,
+    Cheque,
+    Other,
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct Payment {
+    pub seq: i32,
+    pub id: String,
+    pub invoice_id: String,
+    pub amount: Money,
+    pub method: PaymentMethod,
+    /// Card authorisation, transfer reference, cheque number and the like
+    pub reference: Option<String>,
+    /// When the money was received
+    pub paid_at: DateTime<Utc>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(
+    Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Validate, ToSchema,
+)]
+pub struct CreatePaymentRequest {
+    #[garde(skip)]
+    pub invoice_id: Uuid,
+    /// More than zero, in the invoice currency and no more than its outstanding balance
+    #[garde(custom(positive_amount(1000000)))]
+    pub amount: Money,
+    #[garde(skip)]
+    pub method: PaymentMethod,
+    #[garde(length(max = 255))]
+    pub reference: Option<String>,
+    /// Leave out for a payment received now
+    #[garde(skip)]
+    #[serde(default)]
+    pub paid_at: Option<DateTime<Utc>>,
+    /// Taken from the caller's token, never from the request body
+    #[garde(skip)]
+    #[serde(skip)]
+    pub created_by: String,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -174,8 +514,230 @@ pub struct WithItemsQuery {
     pub with_items: bool,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IncludeDeletedQuery {
+    pub include_deleted: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct IncludeTotalQuery {
+    pub include_total: bool,
+}
+
+/// Narrows a list of invoices; an invoice must match every filter given
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InvoiceFilter {
+    pub status: Option<InvoiceStatus>,
+    /// Only invoices whose payments cover the total (true) or that still have a balance (false)
+    pub paid: Option<bool>,
+    /// Created at or after this time, as RFC 3339
+    pub created_from: Option<DateTime<Utc>>,
+    /// Created before this time, as RFC 3339
+    pub created_to: Option<DateTime<Utc>>,
+    /// Lowest total, inclusive
+    #[serde(default, with = "decimal_string::option")]
+    #[param(value_type = Option<String>, example = "100.00")]
+    pub min_total: Option<BigDecimal>,
+    /// Highest total, inclusive
+    #[serde(default, with = "decimal_string::option")]
+    #[param(value_type = Option<String>, example = "999.99")]
+    pub max_total: Option<BigDecimal>,
+    /// Only invoices for this person
+    pub user_id: Option<Uuid>,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct InvoiceItemRequest {
     pub invoice_id: Uuid,
     pub item_id: Uuid,
+    /// Units to add; adding an item already on the invoice increases its quantity
+    #[serde(default = "default_line_quantity")]
+    pub quantity: i32,
+}
+
+fn default_line_quantity() -> i32 {
+    1
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct ApiClient {
+    pub seq: i32,
+    pub id: String,
+    pub client_id: String,
+    pub name: String,
+    /// Set once the client has been revoked; revoked clients can no longer be issued tokens
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// What tokens issued to the client allow it to do
+    pub scopes: Vec<Scope>,
+    pub audit_info: AuditInfo,
+}
+
+/// A permission carried in an access token. Read scopes cover GET requests; write scopes cover
+/// creating, changing and deleting.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type)]
+#[sqlx(type_name = "api_scope")]
+pub enum Scope {
+    #[serde(rename = "persons:read")]
+    #[sqlx(rename = "persons:read")]
+    PersonsRead,
+    #[serde(rename = "persons:write")]
+    #[sqlx(rename = "persons:write")]
+    PersonsWrite,
+    #[serde(rename = "items:read")]
+    #[sqlx(rename = "items:read")]
+    ItemsRead,
+    #[serde(rename = "items:write")]
+    #[sqlx(rename = "items:write")]
+    ItemsWrite,
+    #[serde(rename = "invoices:read")]
+    #[sqlx(rename = "invoices:read")]
+    InvoicesRead,
+    #[serde(rename = "invoices:write")]
+    #[sqlx(rename = "invoices:write")]
+    InvoicesWrite,
+    #[serde(rename = "clients:admin")]
+    #[sqlx(rename = "clients:admin")]
+    ClientsAdmin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 7] = [
+        Scope::PersonsRead,
+        Scope::PersonsWrite,
+        Scope::ItemsRead,
+        Scope::ItemsWrite,
+        Scope::InvoicesRead,
+        Scope::InvoicesWrite,
+        Scope::ClientsAdmin,
+    ];
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Scope::PersonsRead => "persons:read",
+            Scope::PersonsWrite => "persons:write",
+            Scope::ItemsRead => "items:read",
+            Scope::ItemsWrite => "items:write",
+            Scope::InvoicesRead => "invoices:read",
+            Scope::InvoicesWrite => "invoices:write",
+            Scope::ClientsAdmin => "clients:admin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(
+    Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, Validate, ToSchema,
+)]
+pub struct CreateApiClientRequest {
+    #[garde(length(min = 3, max = 64), custom(client_id_characters))]
+    pub client_id: String,
+    /// Who the credentials are for, e.g. the consuming team
+    #[garde(length(min = 3, max = 100))]
+    pub name: String,
+    #[garde(length(min = 1))]
+    pub scopes: Vec<Scope>,
+}
+
+fn client_id_characters(client_id: &str, _: &()) -> garde::Result {
+    if client_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+    {
+        Ok(())
+    } else {
+        Err(garde::Error::new(
+            "may only contain letters, digits, '_', '.' and '-'",
+        ))
+    }
+}
+
+/// A client's secret is only ever returned here, when it is created or rotated
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct ApiClientCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// The client a refresh token was redeemed for, with the scopes it holds now, and the token family
+/// the replacement tokens join
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct RedeemedRefreshToken {
+    pub client_id: String,
+    pub scopes: Vec<Scope>,
+    pub family: Uuid,
+}
+
+/// The response to the first request sent with an idempotency key, replayed to its retries
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct IdempotentResponse {
+    pub status: u16,
+    /// Name and value of each header, in the order they were sent
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// A request's hold on an idempotency key. Only the request holding the key can keep a response
+/// for it or give it up, so a request that outlived its hold can't touch the key once another
+/// request has taken it over.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdempotencyClaim {
+    pub client_id: String,
+    pub key: String,
+    pub request_hash: String,
+    /// Drawn afresh by each request
+    pub claim_id: Uuid,
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct RevokedTokens {
+    pub client_id: String,
+    /// Access and refresh tokens that were still valid and have now been revoked
+    pub revoked: i64,
+}
+
+/// The kinds of record whose changes are kept in the audit log
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "audit_resource", rename_all = "snake_case")]
+pub enum AuditResource {
+    #[default]
+    Person,
+    Item,
+    Invoice,
+}
+
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, Hash, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    #[default]
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+/// One change to a record, with the record as it was before and after it
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub seq: i32,
+    pub resource_type: AuditResource,
+    pub resource_id: String,
+    pub action: AuditAction,
+    /// Client id of the token that made the change
+    pub actor: String,
+    /// Absent for creates
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<serde_json::Value>,
+    /// Deletes show the record with `deleted_at` set
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
 }
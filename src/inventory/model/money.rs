@@ -0,0 +1,235 @@
+use bigdecimal::{BigDecimal, Zero};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use utoipa::ToSchema;
+
+/// ISO 4217 code of a currency we sell in
+#[derive(
+    Debug, Default, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[sqlx(type_name = "currency_code")]
+pub enum Currency {
+    #[default]
+    USD,
+    EUR,
+    GBP,
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Currency::USD => write!(f, "USD"),
+            Currency::EUR => write!(f, "EUR"),
+            Currency::GBP => write!(f, "GBP"),
+        }
+    }
+}
+
+/// An exact decimal amount in a currency. Amounts are carried as `BigDecimal` all the way from
+/// the database to the API and serialized as strings so no precision is lost to floats.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, ToSchema)]
+pub struct Money {
+    #[serde(with = "decimal_string")]
+    #[schema(value_type = String, example = "12.50")]
+    pub amount: BigDecimal,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: BigDecimal, currency: Currency) -> Self {
+        Money { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money::new(BigDecimal::from(0), currency)
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount.to_plain_string(), self.currency)
+    }
+}
+
+/// Every currency we sell in is counted in cents
+const MAX_DECIMAL_PLACES: i64 = 2;
+
+/// garde rule for `#[garde(custom(amount_between(0, 1000000)))]` on a `Money` field.
+pub fn amount_between(min: i64, max: i64) -> impl Fn(&Money, &()) -> garde::Result {
+    let between = decimal_between(min, max);
+    move |money, ctx| {
+        between(&money.amount, ctx)?;
+        check_cents(money)
+    }
+}
+
+/// garde rule for `#[garde(custom(positive_amount(1000000)))]` on a `Money` field that can't be
+/// zero.
+pub fn positive_amount(max: i64) -> impl Fn(&Money, &()) -> garde::Result {
+    let zero = BigDecimal::zero();
+    move |money, ctx| {
+        if money.amount <= zero {
+            Err(garde::Error::new("not greater than 0"))
+        } else {
+            decimal_between(0, max)(&money.amount, ctx)?;
+            check_cents(money)
+        }
+    }
+}
+
+fn check_cents(money: &Money) -> garde::Result {
+    if money.amount.normalized().fractional_digit_count() > MAX_DECIMAL_PLACES {
+        Err(garde::Error::new(format!(
+            "more than {} decimal places",
+            MAX_DECIMAL_PLACES
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// garde rule for `#[garde(custom(decimal_between(0, 1)))]` on a `BigDecimal` field.
+pub fn decimal_between(min: i64, max: i64) -> impl Fn(&BigDecimal, &()) -> garde::Result {
+    let (lower, upper) = (BigDecimal::from(min), BigDecimal::from(max));
+    move |value, _| {
+        if value < &lower {
+            Err(garde::Error::new(format!("lower than {}", min)))
+        } else if value > &upper {
+            Err(garde::Error::new(format!("greater than {}", max)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Serializes a `BigDecimal` as a plain decimal string. Deserializes from a string or, for
+/// convenience, a JSON number, which is read via its shortest representation so `0.1` stays `0.1`.
+pub mod decimal_string {
+    use bigdecimal::BigDecimal;
+    use serde::de::{Error, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt::Formatter;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(value: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_plain_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigDecimal, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+
+    struct DecimalVisitor;
+
+    impl Visitor<'_> for DecimalVisitor {
+        type Value = BigDecimal;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("a decimal number or a string containing one")
+        }
+
+        fn visit_i64<E: Error>(self, value: i64) -> Result<BigDecimal, E> {
+            Ok(BigDecimal::from(value))
+        }
+
+        fn visit_u64<E: Error>(self, value: u64) -> Result<BigDecimal, E> {
+            Ok(BigDecimal::from(value))
+        }
+
+        fn visit_f64<E: Error>(self, value: f64) -> Result<BigDecimal, E> {
+            self.visit_str(&value.to_string())
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<BigDecimal, E> {
+            BigDecimal::from_str(value.trim())
+                .map_err(|_| E::custom(format!("invalid decimal: {}", value)))
+        }
+    }
+
+    pub mod option {
+        use bigdecimal::BigDecimal;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<BigDecimal>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<BigDecimal>, D::Error> {
+            #[derive(Deserialize)]
+            struct Wrapper(#[serde(with = "super")] BigDecimal);
+            Option::<Wrapper>::deserialize(deserializer).map(|wrapper| wrapper.map(|w| w.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_serialize_amount_as_string() {
+        let money = Money::new(BigDecimal::from_str("12.50").unwrap(), Currency::USD);
+        let json = serde_json::to_string(&money).unwrap();
+        assert_eq!(json, r#"{"amount":"12.50","currency":"USD"}"#);
+    }
+
+    #[test]
+    fn test_deserialize_amount_from_string_or_number() {
+        let money: Money = serde_json::from_str(r#"{"amount":"0.10","currency":"USD"}"#).unwrap();
+        assert_eq!(money.amount, BigDecimal::from_str("0.1").unwrap());
+        let money: Money = serde_json::from_str(r#"{"amount":0.1,"currency":"USD"}"#).unwrap();
+        assert_eq!(money.amount, BigDecimal::from_str("0.1").unwrap());
+    }
+
+    #[test]
+    fn test_deserialize_currency() {
+        let money: Money = serde_json::from_str(r#"{"amount":"9.99","currency":"GBP"}"#).unwrap();
+        assert_eq!(money.currency, Currency::GBP);
+        assert_eq!(money.to_string(), "9.99 GBP");
+        let result = serde_json::from_str::<Money>(r#"{"amount":"9.99","currency":"JPY"}"#);
+        assert!(result.is_err());
+        let result = serde_json::from_str::<Money>(r#"{"amount":"9.99"}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_invalid_amount() {
+        let result = serde_json::from_str::<Money>(r#"{"amount":"ten","currency":"USD"}"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_amount_between() {
+        let rule = amount_between(0, 100);
+        assert!(rule(&Money::zero(Currency::USD), &()).is_ok());
+        let negative = Money::new(BigDecimal::from(-1), Currency::USD);
+        assert!(rule(&negative, &()).is_err());
+        let too_large = Money::new(BigDecimal::from_str("100.01").unwrap(), Currency::USD);
+        assert!(rule(&too_large, &()).is_err());
+        let fraction_of_a_cent = Money::new(BigDecimal::from_str("12.345").unwrap(), Currency::USD);
+        assert!(rule(&fraction_of_a_cent, &()).is_err());
+        let trailing_zero = Money::new(BigDecimal::from_str("12.340").unwrap(), Currency::USD);
+        assert!(rule(&trailing_zero, &()).is_ok());
+    }
+
+    #[test]
+    fn test_positive_amount() {
+        let rule = positive_amount(100);
+        assert!(rule(&Money::zero(Currency::USD), &()).is_err());
+        let smallest = Money::new(BigDecimal::from_str("0.01").unwrap(), Currency::USD);
+        assert!(rule(&smallest, &()).is_ok());
+        let too_large = Money::new(BigDecimal::from_str("100.01").unwrap(), Currency::USD);
+        assert!(rule(&too_large, &()).is_err());
+        let fraction_of_a_cent = Money::new(BigDecimal::from_str("0.005").unwrap(), Currency::USD);
+        assert!(rule(&fraction_of_a_cent, &()).is_err());
+    }
+}
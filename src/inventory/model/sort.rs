@@ -0,0 +1,264 @@
+use bigdecimal::BigDecimal;
+use chrono::DateTime;
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+/// How the values of a sort field are compared, and so how a cursor's copy of them is read back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortType {
+    Text,
+    Numeric,
+    Timestamp,
+}
+
+impl SortType {
+    /// The Postgres type a cursor value is cast to before it's compared with the column
+    pub fn sql_type(self) -> &'static str {
+        match self {
+            SortType::Text => "text",
+            SortType::Numeric => "numeric",
+            SortType::Timestamp => "timestamptz",
+        }
+    }
+
+    /// True if `value` can be compared with a column of this type
+    pub fn accepts(self, value: &str) -> bool {
+        match self {
+            SortType::Text => true,
+            SortType::Numeric => BigDecimal::from_str(value).is_ok(),
+            SortType::Timestamp => DateTime::parse_from_rfc3339(value).is_ok(),
+        }
+    }
+}
+
+/// A field a list can be sorted by. The variants of each implementing enum are the whitelist of
+/// names accepted in `sort`, so only their columns ever reach a query
+pub trait SortField:
+    Debug + Clone + Copy + PartialEq + Serialize + DeserializeOwned + Send + Sync
+{
+    fn column(self) -> &'static str;
+    fn sort_type(self) -> SortType;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersonSortField {
+    Name,
+    Email,
+    CreatedAt,
+    LastUpdate,
+}
+
+impl SortField for PersonSortField {
+    fn column(self) -> &'static str {
+        match self {
+            PersonSortField::Name => "name",
+            PersonSortField::Email => "email",
+            PersonSortField::CreatedAt => "created_at",
+            PersonSortField::LastUpdate => "last_update",
+        }
+    }
+
+    fn sort_type(self) -> SortType {
+        match self {
+            PersonSortField::Name | PersonSortField::Email => SortType::Text,
+            PersonSortField::CreatedAt | PersonSortField::LastUpdate => SortType::Timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSortField {
+    Name,
+    UnitPrice,
+    CreatedAt,
+    LastUpdate,
+}
+
+impl SortField for ItemSortField {
+    fn column(self) -> &'static str {
+        match self {
+            ItemSortField::Name => "name",
+            ItemSortField::UnitPrice => "unit_price",
+            ItemSortField::CreatedAt => "created_at",
+            ItemSortField::LastUpdate => "last_update",
+        }
+    }
+
+    fn sort_type(self) -> SortType {
+        match self {
+            ItemSortField::Name => SortType::Text,
+            ItemSortField::UnitPrice => SortType::Numeric,
+            ItemSortField::CreatedAt | ItemSortField::LastUpdate => SortType::Timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceSortField {
+    CreatedAt,
+    LastUpdate,
+    Total,
+    AmountPaid,
+}
+
+impl SortField for InvoiceSortField {
+    fn column(self) -> &'static str {
+        match self {
+            InvoiceSortField::CreatedAt => "created_at",
+            InvoiceSortField::LastUpdate => "last_update",
+            InvoiceSortField::Total => "total",
+            InvoiceSortField::AmountPaid => "amount_paid",
+        }
+    }
+
+    fn sort_type(self) -> SortType {
+        match self {
+            InvoiceSortField::CreatedAt | InvoiceSortField::LastUpdate => SortType::Timestamp,
+            InvoiceSortField::Total | InvoiceSortField::AmountPaid => SortType::Numeric,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey<F> {
+    pub field: F,
+    pub descending: bool,
+}
+
+/// The order of a list, most significant key first. Rows that tie on every key, or every row
+/// when there are no keys, are ordered by id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sort<F>(pub Vec<SortKey<F>>);
+
+impl<F> Default for Sort<F> {
+    fn default() -> Self {
+        Sort(Vec::new())
+    }
+}
+
+impl<F> Sort<F> {
+    pub fn keys(&self) -> &[SortKey<F>] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<F: SortField> FromStr for Sort<F> {
+    type Err = String;
+
+    /// Reads a comma separated list of field names, each prefixed with `-` to sort it descending
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut keys: Vec<SortKey<F>> = Vec::new();
+        for name in value.split(',').filter(|name| !name.is_empty()) {
+            let (name, descending) = match name.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let field = F::deserialize(StrDeserializer::<ValueError>::new(name))
+                .map_err(|e| format!("Invalid sort field: {}", e))?;
+            if keys.iter().any(|key| key.field == field) {
+                return Err(format!("Sort field given more than once: {}", name));
+            }
+            keys.push(SortKey { field, descending });
+        }
+        Ok(Sort(keys))
+    }
+}
+
+impl<F: SortField> Display for Sort<F> {
+    /// Writes the sort back out the way `from_str` reads it
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, key) in self.keys().iter().enumerate() {
+            let name = serde_json::to_value(key.field).map_err(|_| std::fmt::Error)?;
+            write!(
+                f,
+                "{}{}{}",
+                if i > 0 { "," } else { "" },
+                if key.descending { "-" } else { "" },
+                name.as_str().ok_or(std::fmt::Error)?
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de, F: SortField> Deserialize<'de> for Sort<F> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// The `sort` query parameter of a list, e.g. `sort=name,-created_at`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound = "F: SortField")]
+pub struct SortQuery<F> {
+    #[serde(default)]
+    pub sort: Sort<F>,
+}
+
+impl<F> Default for SortQuery<F> {
+    fn default() -> Self {
+        SortQuery {
+            sort: Sort::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sort() {
+        let sort: Sort<ItemSortField> = "name,-unit_price".parse().unwrap();
+        assert_eq!(
+            sort.keys(),
+            [
+                SortKey {
+                    field: ItemSortField::Name,
+                    descending: false,
+                },
+                SortKey {
+                    field: ItemSortField::UnitPrice,
+                    descending: true,
+                },
+            ]
+        );
+        assert!("".parse::<Sort<ItemSortField>>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_display_sort() {
+        let sort: Sort<InvoiceSortField> = "-amount_paid,created_at".parse().unwrap();
+        assert_eq!(sort.to_string(), "-amount_paid,created_at");
+        assert_eq!(Sort::<InvoiceSortField>::default().to_string(), "");
+    }
+
+    #[test]
+    fn test_parse_sort_invalid() {
+        // only whitelisted fields, and each of them once
+        let result = "name,-password".parse::<Sort<PersonSortField>>();
+        assert!(result.unwrap_err().contains("unknown variant `password`"));
+        assert!("total".parse::<Sort<ItemSortField>>().is_err());
+        assert!("name,-name".parse::<Sort<PersonSortField>>().is_err());
+    }
+
+    #[test]
+    fn test_sort_type_accepts() {
+        assert!(SortType::Numeric.accepts("10.50"));
+        assert!(!SortType::Numeric.accepts("ten"));
+        assert!(SortType::Timestamp.accepts("2024-01-01T00:00:00.123456Z"));
+        assert!(!SortType::Timestamp.accepts("yesterday"));
+    }
+}
@@ -1,9 +1,19 @@
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::sort::{Sort, SortField};
 use crate::inventory::model::DeleteResults;
 use sqlx::postgres::PgQueryResult;
+use sqlx::{Postgres, QueryBuilder};
 
+pub mod api_client;
+pub mod audit;
+pub mod idempotency;
 pub mod invoice;
 pub mod item;
+pub mod payment;
 pub mod person;
+pub mod stock;
+pub mod token;
+pub mod unit_of_work;
 
 #[derive(Debug)]
 pub enum RepoError {
@@ -11,6 +21,13 @@ pub enum RepoError {
     InvalidUuid(String),
     Other(String),
     UniqueViolation(String),
+    /// A row is still referenced by another, e.g. an item by its stock movements
+    ForeignKeyViolation(String),
+    InsufficientStock(String),
+    CurrencyMismatch(String),
+    InvalidStatus(String),
+    ExceedsBalance(String),
+    VersionMismatch(String),
 }
 
 impl From<sqlx::Error> for RepoError {
@@ -20,6 +37,8 @@ impl From<sqlx::Error> for RepoError {
             sqlx::Error::Database(err) => {
                 if err.is_unique_violation() {
                     RepoError::UniqueViolation(err.to_string())
+                } else if err.is_foreign_key_violation() {
+                    RepoError::ForeignKeyViolation(err.to_string())
                 } else {
                     RepoError::Other(err.to_string())
                 }
@@ -37,3 +56,54 @@ impl From<PgQueryResult> for DeleteResults {
         }
     }
 }
+
+/// Finishes a list query, which must end in its WHERE clause, with the rows after `seek` in `sort`
+/// order. Each key is compared with the cursor's copy of its value, cast back to the column's
+/// type, and rows that tie on every key are ordered by id.
+pub(crate) fn push_seek<F: SortField>(
+    query: &mut QueryBuilder<'_, Postgres>,
+    sort: &Sort<F>,
+    seek: &Seek,
+) {
+    if let Some(after) = &seek.after {
+        // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ... OR (k1 = v1 AND ... AND id > after.id)
+        query.push(" AND (");
+        for i in 0..=sort.keys().len() {
+            if i > 0 {
+                query.push(" OR ");
+            }
+            query.push("(");
+            for (key, value) in sort.keys().iter().zip(&after.keys).take(i) {
+                push_key(query, key.field, " = ", value);
+                query.push(" AND ");
+            }
+            match sort.keys().get(i).zip(after.keys.get(i)) {
+                Some((key, value)) => {
+                    let op = if key.descending { " < " } else { " > " };
+                    push_key(query, key.field, op, value);
+                }
+                None => {
+                    query.push("id > ").push_bind(after.id);
+                }
+            }
+            query.push(")");
+        }
+        query.push(")");
+    }
+    query.push(" ORDER BY ");
+    for key in sort.keys() {
+        query
+            .push(key.field.column())
+            .push(if key.descending { " DESC, " } else { " ASC, " });
+    }
+    query.push("id LIMIT ").push_bind(seek.limit);
+}
+
+fn push_key<F: SortField>(query: &mut QueryBuilder<'_, Postgres>, field: F, op: &str, value: &str) {
+    query
+        .push(field.column())
+        .push(op)
+        .push_bind(value.to_string())
+        .push("::")
+        .push(field.sort_type().sql_type());
+}
@@ -0,0 +1,167 @@
+use crate::inventory::model::Scope;
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use sqlx::{FromRow, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, FromRow)]
+pub struct ApiClientRow {
+    pub id: i32,
+    pub alt_id: Uuid,
+    pub client_id: String,
+    pub name: String,
+    pub secret_hash: String,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub scopes: Vec<Scope>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub last_changed_by: String,
+    pub last_update: DateTime<Utc>,
+}
+
+#[async_trait]
+#[automock]
+pub trait ApiClientRepository: Debug {
+    async fn create(
+        &self,
+        client_id: &str,
+        name: &str,
+        secret_hash: &str,
+        scopes: &[Scope],
+        created_by: &str,
+    ) -> Result<ApiClientRow, RepoError>;
+    async fn get_all(&self) -> Result<Vec<ApiClientRow>, RepoError>;
+    async fn get_by_client_id(&self, client_id: &str) -> Result<ApiClientRow, RepoError>;
+    /// Replaces the secret of a client that has not been revoked
+    async fn update_secret(
+        &self,
+        client_id: &str,
+        secret_hash: &str,
+        changed_by: &str,
+    ) -> Result<ApiClientRow, RepoError>;
+    /// Revokes a client; revoking an already revoked client keeps the original revocation time
+    async fn revoke(&self, client_id: &str, changed_by: &str) -> Result<ApiClientRow, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct ApiClientRepositoryImpl {
+    pool: PgPool,
+}
+
+impl ApiClientRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ApiClientRepository for ApiClientRepositoryImpl {
+    #[instrument(skip(secret_hash))]
+    async fn create(
+        &self,
+        client_id: &str,
+        name: &str,
+        secret_hash: &str,
+        scopes: &[Scope],
+        created_by: &str,
+    ) -> Result<ApiClientRow, RepoError> {
+        let result = sqlx::query_as!(
+            ApiClientRow,
+            r#"
+            INSERT INTO api_clients (client_id, name, secret_hash, scopes, created_by, last_changed_by)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            RETURNING id, alt_id, client_id, name, secret_hash, revoked_at, scopes as "scopes: Vec<Scope>",
+                      created_by, created_at, last_changed_by, last_update
+            "#,
+            client_id,
+            name,
+            secret_hash,
+            scopes as &[Scope],
+            created_by
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn get_all(&self) -> Result<Vec<ApiClientRow>, RepoError> {
+        let result = sqlx::query_as!(
+            ApiClientRow,
+            r#"
+            SELECT id, alt_id, client_id, name, secret_hash, revoked_at, scopes as "scopes: Vec<Scope>",
+                   created_by, created_at, last_changed_by, last_update
+            FROM api_clients
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn get_by_client_id(&self, client_id: &str) -> Result<ApiClientRow, RepoError> {
+        let result = sqlx::query_as!(
+            ApiClientRow,
+            r#"
+            SELECT id, alt_id, client_id, name, secret_hash, revoked_at, scopes as "scopes: Vec<Scope>",
+                   created_by, created_at, last_changed_by, last_update
+            FROM api_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument(skip(secret_hash))]
+    async fn update_secret(
+        &self,
+        client_id: &str,
+        secret_hash: &str,
+        changed_by: &str,
+    ) -> Result<ApiClientRow, RepoError> {
+        let result = sqlx::query_as!(
+            ApiClientRow,
+            r#"
+            UPDATE api_clients
+            SET secret_hash = $2, last_changed_by = $3, last_update = now()
+            WHERE client_id = $1 AND revoked_at IS NULL
+            RETURNING id, alt_id, client_id, name, secret_hash, revoked_at, scopes as "scopes: Vec<Scope>",
+                      created_by, created_at, last_changed_by, last_update
+            "#,
+            client_id,
+            secret_hash,
+            changed_by
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn revoke(&self, client_id: &str, changed_by: &str) -> Result<ApiClientRow, RepoError> {
+        let result = sqlx::query_as!(
+            ApiClientRow,
+            r#"
+            UPDATE api_clients
+            SET revoked_at = coalesce(revoked_at, now()), last_changed_by = $2, last_update = now()
+            WHERE client_id = $1
+            RETURNING id, alt_id, client_id, name, secret_hash, revoked_at, scopes as "scopes: Vec<Scope>",
+                      created_by, created_at, last_changed_by, last_update
+            "#,
+            client_id,
+            changed_by
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+}
@@ -0,0 +1,157 @@
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::{AuditAction, AuditResource, ExpectedVersion};
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use serde_json::Value;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, FromRow)]
+pub struct AuditRow {
+    pub id: i32,
+    pub resource_type: AuditResource,
+    pub resource_id: Uuid,
+    pub action: AuditAction,
+    pub actor: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+#[automock]
+pub trait AuditRepository: Debug {
+    /// Changes to one record, oldest first
+    async fn get_history(
+        &self,
+        resource_type: AuditResource,
+        resource_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<AuditRow>, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct AuditRepositoryImpl {
+    pool: PgPool,
+}
+
+impl AuditRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl AuditRepository for AuditRepositoryImpl {
+    #[instrument]
+    async fn get_history(
+        &self,
+        resource_type: AuditResource,
+        resource_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<AuditRow>, RepoError> {
+        let result = sqlx::query_as!(
+            AuditRow,
+            r#"
+            SELECT id, resource_type as "resource_type: AuditResource", resource_id,
+                   action as "action: AuditAction", actor, before, after, created_at
+            FROM audit_log
+            WHERE resource_type = $1
+              AND resource_id = $2
+              AND id > coalesce($3, 0)
+            ORDER BY id
+            LIMIT $4
+            "#,
+            resource_type as AuditResource,
+            resource_id,
+            seek.after.as_ref().map(|cursor| cursor.id),
+            seek.limit
+        )
+        .fetch_all(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+}
+
+/// The record as it is now, as JSON, or None if it does not exist. The row is locked so that
+/// nothing can change it between this snapshot and the change being audited.
+pub(crate) async fn snapshot(
+    conn: &mut PgConnection,
+    resource_type: AuditResource,
+    resource_id: Uuid,
+) -> Result<Option<Value>, RepoError> {
+    let result =
+        match resource_type {
+            AuditResource::Person => sqlx::query_scalar!(
+                r#"SELECT to_jsonb(p) as "snapshot!" FROM persons p WHERE alt_id = $1 FOR UPDATE"#,
+                resource_id
+            )
+            .fetch_optional(conn)
+            .await,
+            AuditResource::Item => sqlx::query_scalar!(
+                // the search column is derived from the name and description
+                r#"SELECT to_jsonb(i) - 'search' as "snapshot!" FROM items i WHERE alt_id = $1 FOR UPDATE"#,
+                resource_id
+            )
+            .fetch_optional(conn)
+            .await,
+            AuditResource::Invoice => sqlx::query_scalar!(
+                r#"SELECT to_jsonb(v) as "snapshot!" FROM invoices v WHERE alt_id = $1 FOR UPDATE"#,
+                resource_id
+            )
+            .fetch_optional(conn)
+            .await,
+        };
+    result.map_err(RepoError::from)
+}
+
+/// Refuses a change made against an out of date copy of a record: `expected` is the version the
+/// caller last read, and `current` the record's snapshot, which holds the row lock so the version
+/// can't move before the change is written. Deleted records are left for the update to report as
+/// not found.
+pub(crate) fn check_version(
+    current: &Value,
+    expected: &ExpectedVersion,
+    resource_id: Uuid,
+) -> Result<(), RepoError> {
+    let version = current["version"].as_i64().unwrap_or_default();
+    if current["deleted_at"].is_null() && !expected.matches(version) {
+        return Err(RepoError::VersionMismatch(format!(
+            "{} is at version {} but the change was made against {}",
+            resource_id, version, expected
+        )));
+    }
+    Ok(())
+}
+
+/// Appends a change to the audit log. Call it on the same transaction as the change so the two
+/// are committed or rolled back together.
+pub(crate) async fn record(
+    conn: &mut PgConnection,
+    resource_type: AuditResource,
+    resource_id: Uuid,
+    action: AuditAction,
+    actor: &str,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), RepoError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_log (resource_type, resource_id, action, actor, before, after)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        resource_type as AuditResource,
+        resource_id,
+        action as AuditAction,
+        actor,
+        before,
+        after
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use crate::inventory::model::IdempotencyClaim;
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use serde_json::Value;
+use sqlx::{Connection, FromRow, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+
+#[derive(Clone, Debug, FromRow)]
+pub struct IdempotencyKeyRow {
+    pub client_id: String,
+    pub key: String,
+    pub request_hash: String,
+    /// Not set until the first request with the key has finished
+    pub response_status: Option<i16>,
+    pub response_body: Option<Vec<u8>>,
+    /// `[name, value]` pairs; empty until the first request with the key has finished
+    pub response_headers: Value,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+#[automock]
+pub trait IdempotencyRepository: Debug {
+    /// Holds the claimed key for its request until `hold_until`. None if the key was free, or had
+    /// expired, and is now held; otherwise the key as the earlier request with it left it.
+    async fn claim(
+        &self,
+        claim: &IdempotencyClaim,
+        hold_until: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyRow>, RepoError>;
+    /// Stores the response to the request holding the claimed key, to be replayed until
+    /// `expires_at`. Does nothing if the key is no longer held by that request.
+    async fn complete(
+        &self,
+        claim: &IdempotencyClaim,
+        response_status: i16,
+        response_body: &[u8],
+        response_headers: &Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepoError>;
+    /// Frees a key whose request did not succeed, so it can be retried with the same key. Does
+    /// nothing if the key is no longer held by that request.
+    async fn release(&self, claim: &IdempotencyClaim) -> Result<(), RepoError>;
+}
+
+#[derive(Debug)]
+pub struct IdempotencyRepositoryImpl {
+    pool: PgPool,
+}
+
+impl IdempotencyRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryImpl {
+    #[instrument]
+    async fn claim(
+        &self,
+        claim: &IdempotencyClaim,
+        hold_until: DateTime<Utc>,
+    ) -> Result<Option<IdempotencyKeyRow>, RepoError> {
+        let mut conn = self.pool.acquire().await?;
+        let mut tx = conn.begin().await?;
+        // expired keys are forgotten, which also frees this one if it was used long enough ago
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE client_id = $1 AND expires_at <= now()
+            "#,
+            claim.client_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let claimed = sqlx::query!(
+            r#"
+            INSERT INTO idempotency_keys (client_id, key, request_hash, claim_id, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (client_id, key) DO NOTHING
+            "#,
+            claim.client_id,
+            claim.key,
+            claim.request_hash,
+            claim.claim_id,
+            hold_until
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        let earlier = if claimed {
+            None
+        } else {
+            let row = sqlx::query_as!(
+                IdempotencyKeyRow,
+                r#"
+                SELECT client_id, key, request_hash, response_status, response_body, response_headers, expires_at, created_at
+                FROM idempotency_keys
+                WHERE client_id = $1 AND key = $2
+                "#,
+                claim.client_id,
+                claim.key
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            Some(row)
+        };
+        tx.commit().await?;
+        Ok(earlier)
+    }
+
+    #[instrument(skip(response_body, response_headers))]
+    async fn complete(
+        &self,
+        claim: &IdempotencyClaim,
+        response_status: i16,
+        response_body: &[u8],
+        response_headers: &Value,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepoError> {
+        sqlx::query!(
+            r#"
+            UPDATE idempotency_keys
+            SET response_status = $5, response_body = $6, response_headers = $7, expires_at = $8
+            WHERE client_id = $1 AND key = $2 AND request_hash = $3 AND claim_id = $4
+            "#,
+            claim.client_id,
+            claim.key,
+            claim.request_hash,
+            claim.claim_id,
+            response_status,
+            response_body,
+            response_headers,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn release(&self, claim: &IdempotencyClaim) -> Result<(), RepoError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE client_id = $1 AND key = $2 AND request_hash = $3 AND claim_id = $4
+              AND response_status IS NULL
+            "#,
+            claim.client_id,
+            claim.key,
+            claim.request_hash,
+            claim.claim_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
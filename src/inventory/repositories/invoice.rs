@@ -1,12 +1,17 @@
+use crate::inventory::model::money::Currency;
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::sort::{InvoiceSortField, Sort};
 use crate::inventory::model::{
-    CreateInvoiceRequest, DeleteResults, Pagination, UpdateInvoiceRequest,
+    AuditAction, AuditResource, CreateInvoiceRequest, DeleteResults, InvoiceFilter, InvoiceStatus,
+    UpdateInvoiceRequest,
 };
-use crate::inventory::repositories::RepoError;
+use crate::inventory::repositories::unit_of_work::Db;
+use crate::inventory::repositories::{audit, push_seek, stock, RepoError};
 use async_trait::async_trait;
-use bigdecimal::{BigDecimal, FromPrimitive};
+use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
 use mockall::automock;
-use sqlx::FromRow;
+use sqlx::{Connection, FromRow, Postgres, QueryBuilder};
 use std::fmt::Debug;
 use tracing::instrument;
 use uuid::Uuid;
@@ -16,18 +21,28 @@ pub struct InvoiceRow {
     pub id: i32,
     pub alt_id: Uuid,
     pub user_id: Uuid,
+    pub currency: Currency,
+    pub subtotal: BigDecimal,
+    pub tax_rate: BigDecimal,
+    pub tax: BigDecimal,
+    pub discount: BigDecimal,
     pub total: BigDecimal,
-    pub paid: bool,
+    pub amount_paid: BigDecimal,
+    pub status: InvoiceStatus,
     pub created_by: String,
     pub created_at: DateTime<Utc>,
     pub last_changed_by: String,
     pub last_update: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i64,
 }
 
 #[derive(Clone, Debug, FromRow)]
 pub struct InvoiceItemRow {
     pub invoice_id: Uuid,
     pub item_id: Uuid,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
 }
 
 #[derive(Clone, Debug, FromRow)]
@@ -35,124 +50,313 @@ pub struct InvoiceWithItemRow {
     pub id: i32,
     pub alt_id: Uuid,
     pub user_id: Uuid,
+    pub currency: Currency,
+    pub subtotal: BigDecimal,
+    pub tax_rate: BigDecimal,
+    pub tax: BigDecimal,
+    pub discount: BigDecimal,
     pub total: BigDecimal,
-    pub paid: bool,
+    pub amount_paid: BigDecimal,
+    pub status: InvoiceStatus,
     pub created_by: String,
     pub created_at: DateTime<Utc>,
     pub last_changed_by: String,
     pub last_update: DateTime<Utc>,
-    pub item_alt_id: Uuid,
-    pub item_name: String,
-    pub item_description: String,
-    pub item_unit_price: BigDecimal,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i64,
+    /// The line fields are not set on the one row returned for an invoice without lines
+    pub item_alt_id: Option<Uuid>,
+    pub item_name: Option<String>,
+    pub item_description: Option<String>,
+    pub quantity: Option<i32>,
+    pub unit_price: Option<BigDecimal>,
+    pub line_subtotal: Option<BigDecimal>,
+}
+
+/// A line for a new invoice, priced at the item's price when the invoice is created
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewInvoiceLine {
+    pub item_id: Uuid,
+    pub quantity: i32,
+    pub unit_price: BigDecimal,
+}
+
+/// Invoice pricing as computed by the service from the invoice's lines
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InvoiceTotals {
+    pub subtotal: BigDecimal,
+    pub tax_rate: BigDecimal,
+    pub tax: BigDecimal,
+    pub discount: BigDecimal,
+    pub total: BigDecimal,
 }
 
 #[async_trait]
 #[automock]
 pub trait InvoiceRepository: Debug {
-    async fn create(&self, invoice: CreateInvoiceRequest) -> Result<InvoiceRow, RepoError>;
+    /// Prices the lines a new invoice in `currency` would get for `items`: one line per item,
+    /// with a unit for every time it is listed, at the item's current price. Fails with NotFound
+    /// for an unknown or deleted item and CurrencyMismatch for one priced in another currency.
+    async fn price_lines(
+        &self,
+        currency: Currency,
+        items: &[Uuid],
+    ) -> Result<Vec<NewInvoiceLine>, RepoError>;
+    /// Creates an invoice and its lines in one transaction, reserving each line's quantity.
+    /// Nothing is written if any line can't be added.
+    async fn create(
+        &self,
+        invoice: CreateInvoiceRequest,
+        lines: Vec<NewInvoiceLine>,
+        totals: InvoiceTotals,
+    ) -> Result<InvoiceRow, RepoError>;
+    /// Invoices matching every filter set in `filter`, in `sort` order with ties broken by id, or
+    /// by id alone when `sort` is empty
     async fn get_all_invoices(
         &self,
-        maybe_pagination: Option<Pagination>,
+        filter: &InvoiceFilter,
+        sort: &Sort<InvoiceSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<InvoiceRow>, RepoError>;
+    /// How many invoices match `filter` across all pages
+    async fn count_invoices(
+        &self,
+        filter: &InvoiceFilter,
+        include_deleted: bool,
+    ) -> Result<i64, RepoError>;
     async fn get_by_id(&self, id: i32) -> Result<InvoiceRow, RepoError>;
-    async fn get_by_uuid(&self, alt_id: Uuid) -> Result<InvoiceRow, RepoError>;
-    async fn get_with_items(&self, id: Uuid) -> Result<Vec<InvoiceWithItemRow>, RepoError>;
-    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<InvoiceRow>, RepoError>;
-    async fn update(&self, invoice: UpdateInvoiceRequest) -> Result<InvoiceRow, RepoError>;
-    async fn delete(&self, id: Uuid) -> Result<DeleteResults, RepoError>;
-    async fn add_item(&self, invoice_item: InvoiceItemRow) -> Result<InvoiceItemRow, RepoError>;
-    async fn remove_item(&self, invoice_item: InvoiceItemRow) -> Result<DeleteResults, RepoError>;
+    async fn get_by_uuid(
+        &self,
+        alt_id: Uuid,
+        include_deleted: bool,
+    ) -> Result<InvoiceRow, RepoError>;
+    /// One row per line of the invoice, or a single row without line fields when it has none.
+    /// No rows if the invoice doesn't exist.
+    async fn get_with_items(
+        &self,
+        id: Uuid,
+        include_deleted: bool,
+    ) -> Result<Vec<InvoiceWithItemRow>, RepoError>;
+    async fn update(
+        &self,
+        invoice: UpdateInvoiceRequest,
+        totals: InvoiceTotals,
+    ) -> Result<InvoiceRow, RepoError>;
+    async fn update_totals(
+        &self,
+        id: Uuid,
+        totals: InvoiceTotals,
+        changed_by: &str,
+    ) -> Result<InvoiceRow, RepoError>;
+    /// Marks an invoice as deleted, keeping its lines but releasing what they reserved
+    async fn delete(&self, id: Uuid, deleted_by: &str) -> Result<DeleteResults, RepoError>;
+    async fn restore(&self, id: Uuid, restored_by: &str) -> Result<InvoiceRow, RepoError>;
+    /// Moves an invoice from `from` to `to`, failing with InvalidStatus if it is no longer in
+    /// `from`. Which moves are allowed is up to the caller. Paying an invoice takes its reserved
+    /// stock out of stock, voiding it releases the reservations.
+    async fn set_status(
+        &self,
+        id: Uuid,
+        from: InvoiceStatus,
+        to: InvoiceStatus,
+        changed_by: &str,
+    ) -> Result<InvoiceRow, RepoError>;
+    /// Adds `quantity` units of an item to an invoice at the item's current price, or increases
+    /// the quantity of the existing line, keeping the price captured when it was first added.
+    /// The added quantity is reserved, failing with InsufficientStock if it isn't available.
+    async fn add_item(
+        &self,
+        invoice_id: Uuid,
+        item_id: Uuid,
+        quantity: i32,
+        changed_by: &str,
+    ) -> Result<InvoiceItemRow, RepoError>;
+    /// Removes a line from an invoice and releases the quantity it reserved.
+    async fn remove_item(
+        &self,
+        invoice_id: Uuid,
+        item_id: Uuid,
+        changed_by: &str,
+    ) -> Result<DeleteResults, RepoError>;
     async fn get_items(&self, invoice_id: Uuid) -> Result<Vec<InvoiceItemRow>, RepoError>;
 }
 
 #[derive(Debug)]
 pub struct InvoiceRepositoryImpl {
-    pool: sqlx::PgPool,
+    pub db: Db,
 }
 
 impl InvoiceRepositoryImpl {
-    pub async fn new(pool: sqlx::PgPool) -> Self {
-        Self { pool }
+    pub async fn new(db: impl Into<Db>) -> Self {
+        Self { db: db.into() }
     }
 }
 
 #[async_trait]
 impl InvoiceRepository for InvoiceRepositoryImpl {
     #[instrument]
-    async fn create(&self, invoice: CreateInvoiceRequest) -> Result<InvoiceRow, RepoError> {
-        let total = BigDecimal::from_f64(invoice.total).unwrap();
+    async fn price_lines(
+        &self,
+        currency: Currency,
+        items: &[Uuid],
+    ) -> Result<Vec<NewInvoiceLine>, RepoError> {
+        let prices = sqlx::query!(
+            r#"
+            SELECT alt_id, unit_price, currency as "currency: Currency"
+            FROM items
+            WHERE alt_id = ANY($1) AND deleted_at IS NULL
+            "#,
+            items
+        )
+        .fetch_all(&mut *self.db.acquire().await?)
+        .await?;
+
+        let mut lines: Vec<NewInvoiceLine> = Vec::new();
+        for item_id in items {
+            if let Some(line) = lines.iter_mut().find(|line| line.item_id == *item_id) {
+                line.quantity += 1;
+                continue;
+            }
+            let price = prices
+                .iter()
+                .find(|price| price.alt_id == *item_id)
+                .ok_or_else(|| {
+                    RepoError::NotFound(format!("Item with id {} not found", item_id))
+                })?;
+            check_item_currency(*item_id, price.currency, currency)?;
+            lines.push(NewInvoiceLine {
+                item_id: *item_id,
+                quantity: 1,
+                unit_price: price.unit_price.clone(),
+            });
+        }
+        Ok(lines)
+    }
+
+    #[instrument]
+    async fn create(
+        &self,
+        invoice: CreateInvoiceRequest,
+        mut lines: Vec<NewInvoiceLine>,
+        totals: InvoiceTotals,
+    ) -> Result<InvoiceRow, RepoError> {
+        // items and their stock levels are locked in item order, as every other caller does
+        lines.sort_by_key(|line| line.item_id);
         let now = Utc::now();
-        // TODO - add items to invoice
-        let result = sqlx::query_as!(
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let row = sqlx::query_as!(
             InvoiceRow,
             r#"
-            INSERT INTO invoices (user_id, total, paid, created_by, created_at, last_changed_by, last_update)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            RETURNING id, alt_id, total, paid, created_by, created_at, last_changed_by, last_update, user_id
+            INSERT INTO invoices (user_id, currency, subtotal, tax_rate, tax, discount, total, created_by, created_at, last_changed_by, last_update)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
             "#,
             invoice.user_id,
-            total,
-            invoice.paid,
+            invoice.currency as Currency,
+            totals.subtotal,
+            totals.tax_rate,
+            totals.tax,
+            totals.discount,
+            totals.total,
             invoice.created_by,
             now,
             invoice.created_by,
             now
         )
-            .fetch_one(&self.pool)
-            .await;
+            .fetch_one(&mut *tx)
+            .await?;
 
-        result.map_err(RepoError::from)
+        // an item deleted or repriced in another currency since the lines were priced fails the
+        // whole invoice; returning early drops the transaction, which rolls the header back
+        for line in &lines {
+            let item_currency = sqlx::query_scalar!(
+                r#"
+                SELECT currency as "currency: Currency"
+                FROM items
+                WHERE alt_id = $1 AND deleted_at IS NULL
+                FOR SHARE
+                "#,
+                line.item_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| {
+                RepoError::NotFound(format!("Item with id {} not found", line.item_id))
+            })?;
+            check_item_currency(line.item_id, item_currency, row.currency)?;
+            stock::reserve(&mut tx, line.item_id, line.quantity, &invoice.created_by).await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO invoices_items (invoice_id, item_id, quantity, unit_price)
+                VALUES ($1, $2, $3, $4)
+                "#,
+                row.alt_id,
+                line.item_id,
+                line.quantity,
+                line.unit_price
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, row.alt_id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            row.alt_id,
+            AuditAction::Create,
+            &invoice.created_by,
+            None,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     #[instrument]
     async fn get_all_invoices(
         &self,
-        maybe_pagination: Option<Pagination>,
+        filter: &InvoiceFilter,
+        sort: &Sort<InvoiceSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<InvoiceRow>, RepoError> {
-        let result = if let Some(pagination) = maybe_pagination {
-            if let Some(last_id) = pagination.last_id {
-                sqlx::query_as!(
-                    InvoiceRow,
-                    r#"
-                    SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
-                    FROM invoices
-                    WHERE id > $1
-                    ORDER BY id ASC
-                    LIMIT $2
-                    "#,
-                    last_id,
-                    pagination.page_size
-                )
-                    .fetch_all(&self.pool)
-                    .await
-            } else {
-                sqlx::query_as!(
-                    InvoiceRow,
-                    r#"
-                    SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
-                    FROM invoices
-                    ORDER BY id ASC
-                    LIMIT $1
-                    "#,
-                    pagination.page_size
-                )
-                    .fetch_all(&self.pool)
-                    .await
-            }
-        } else {
-            sqlx::query_as!(
-                InvoiceRow,
-                r#"
-                SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
-                FROM invoices
-                ORDER BY id ASC
-                LIMIT 10
-                "#,
-            )
-                .fetch_all(&self.pool)
-                .await
-        };
+        let mut query = QueryBuilder::new(
+            r#"
+            SELECT id, alt_id, user_id, currency, subtotal, tax_rate, tax, discount, total, amount_paid, status, created_by, created_at, last_changed_by, last_update, deleted_at, version
+            FROM invoices
+            WHERE ("#,
+        );
+        query
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        push_filters(&mut query, filter);
+        push_seek(&mut query, sort, seek);
+        let result = query
+            .build_query_as::<InvoiceRow>()
+            .fetch_all(&mut *self.db.acquire().await?)
+            .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn count_invoices(
+        &self,
+        filter: &InvoiceFilter,
+        include_deleted: bool,
+    ) -> Result<i64, RepoError> {
+        let mut query = QueryBuilder::new("SELECT count(*) FROM invoices WHERE (");
+        query
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        push_filters(&mut query, filter);
+        let result = query
+            .build_query_scalar::<i64>()
+            .fetch_one(&mut *self.db.acquire().await?)
+            .await;
         result.map_err(RepoError::from)
     }
 
@@ -161,155 +365,419 @@ impl InvoiceRepository for InvoiceRepositoryImpl {
         let result = sqlx::query_as!(
             InvoiceRow,
             r#"
-            SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
+            SELECT id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
             FROM invoices
-            WHERE id = $1
+            WHERE id = $1 AND deleted_at IS NULL
             "#,
             id
         )
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.db.acquire().await?)
             .await;
 
         result.map_err(RepoError::from)
     }
 
     #[instrument]
-    async fn get_by_uuid(&self, alt_id: Uuid) -> Result<InvoiceRow, RepoError> {
+    async fn get_by_uuid(
+        &self,
+        alt_id: Uuid,
+        include_deleted: bool,
+    ) -> Result<InvoiceRow, RepoError> {
         let result = sqlx::query_as!(
             InvoiceRow,
             r#"
-            SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
+            SELECT id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
             FROM invoices
-            WHERE alt_id = $1
+            WHERE alt_id = $1 AND ($2 OR deleted_at IS NULL)
             "#,
-            alt_id
+            alt_id,
+            include_deleted
         )
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *self.db.acquire().await?)
             .await;
         result.map_err(RepoError::from)
     }
 
     #[instrument]
-    async fn get_with_items(&self, id: Uuid) -> Result<Vec<InvoiceWithItemRow>, RepoError> {
+    async fn get_with_items(
+        &self,
+        id: Uuid,
+        include_deleted: bool,
+    ) -> Result<Vec<InvoiceWithItemRow>, RepoError> {
         let result = sqlx::query_as!(
             InvoiceWithItemRow,
             r#"
-            SELECT i.id, i.alt_id, i.user_id, i.total, i.paid, i.created_by, i.created_at, i.last_changed_by, i.last_update,
-            ii.item_id as item_alt_id, it.name as item_name, it.description as item_description, it.unit_price as item_unit_price
+            SELECT i.id, i.alt_id, i.user_id, i.currency as "currency: Currency", i.subtotal, i.tax_rate, i.tax, i.discount, i.total, i.amount_paid, i.status as "status: InvoiceStatus", i.created_by, i.created_at, i.last_changed_by, i.last_update, i.deleted_at, i.version,
+            ii.item_id as "item_alt_id?", it.name as "item_name?", it.description as "item_description?",
+            ii.quantity as "quantity?", ii.unit_price as "unit_price?", ii.quantity * ii.unit_price as "line_subtotal?"
             FROM invoices i
-            JOIN invoices_items ii ON i.alt_id = ii.invoice_id
-            JOIN items it ON ii.item_id = it.alt_id
-            WHERE i.alt_id = $1
+            LEFT JOIN invoices_items ii ON i.alt_id = ii.invoice_id
+            LEFT JOIN items it ON ii.item_id = it.alt_id
+            WHERE i.alt_id = $1 AND ($2 OR i.deleted_at IS NULL)
             "#,
-            id
+            id,
+            include_deleted
         )
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *self.db.acquire().await?)
             .await;
         result.map_err(RepoError::from)
     }
 
     #[instrument]
-    async fn find_by_user_id(&self, user_id: Uuid) -> Result<Vec<InvoiceRow>, RepoError> {
-        let result = sqlx::query_as!(
+    async fn update(
+        &self,
+        invoice: UpdateInvoiceRequest,
+        totals: InvoiceTotals,
+    ) -> Result<InvoiceRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, invoice.id)
+            .await?
+            .ok_or_else(|| {
+                RepoError::NotFound(format!("Invoice with id {} not found", invoice.id))
+            })?;
+        audit::check_version(&before, &invoice.version, invoice.id)?;
+        let row = sqlx::query_as!(
             InvoiceRow,
             r#"
-            SELECT id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
-            FROM invoices
-            WHERE user_id = $1
+            UPDATE invoices
+            SET subtotal = $1, tax_rate = $2, tax = $3, discount = $4, total = $5,
+                last_changed_by = $6, last_update = now()
+            WHERE alt_id = $7 AND deleted_at IS NULL AND status = 'draft'
+            RETURNING id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
             "#,
-            user_id
+            totals.subtotal,
+            totals.tax_rate,
+            totals.tax,
+            totals.discount,
+            totals.total,
+            invoice.changed_by,
+            invoice.id
         )
-            .fetch_all(&self.pool)
-            .await;
-        result.map_err(RepoError::from)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| {
+                RepoError::NotFound(format!("Draft invoice with id {} not found", invoice.id))
+            })?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, invoice.id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            invoice.id,
+            AuditAction::Update,
+            &invoice.changed_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     #[instrument]
-    async fn update(&self, invoice: UpdateInvoiceRequest) -> Result<InvoiceRow, RepoError> {
-        let total = BigDecimal::from_f64(invoice.total).unwrap();
+    async fn update_totals(
+        &self,
+        id: Uuid,
+        totals: InvoiceTotals,
+        changed_by: &str,
+    ) -> Result<InvoiceRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, id)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", id)))?;
         let row = sqlx::query_as!(
             InvoiceRow,
             r#"
             UPDATE invoices
-            SET total = $1, paid = $2, last_changed_by = $3, last_update = now()
-            WHERE alt_id = $4
-            RETURNING id, alt_id, user_id, total, paid, created_by, created_at, last_changed_by, last_update
+            SET subtotal = $1, tax_rate = $2, tax = $3, discount = $4, total = $5,
+                last_changed_by = $6, last_update = now()
+            WHERE alt_id = $7 AND deleted_at IS NULL
+            RETURNING id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
             "#,
-            total,
-            invoice.paid,
-            invoice.changed_by,
-            invoice.id
+            totals.subtotal,
+            totals.tax_rate,
+            totals.tax,
+            totals.discount,
+            totals.total,
+            changed_by,
+            id
         )
-            .fetch_one(&self.pool)
-            .await;
-        row.map_err(RepoError::from)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", id)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            id,
+            AuditAction::Update,
+            changed_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     #[instrument]
-    async fn delete(&self, id: Uuid) -> Result<DeleteResults, RepoError> {
-        let result = sqlx::query!(
+    async fn delete(&self, id: Uuid, deleted_by: &str) -> Result<DeleteResults, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, id)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", id)))?;
+        let status = sqlx::query_scalar!(
             r#"
-            DELETE FROM invoices
-            WHERE alt_id = $1
+            SELECT status as "status: InvoiceStatus"
+            FROM invoices
+            WHERE alt_id = $1 AND deleted_at IS NULL
             "#,
             id
         )
-            .execute(&self.pool)
-            .await;
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", id)))?;
+        let pg_result = sqlx::query!(
+            r#"
+            UPDATE invoices
+            SET deleted_at = now(), last_changed_by = $2, last_update = now()
+            WHERE alt_id = $1 AND deleted_at IS NULL
+            "#,
+            id,
+            deleted_by
+        )
+        .execute(&mut *tx)
+        .await?;
+        if status.holds_stock() {
+            stock::release_invoice(&mut tx, id, deleted_by).await?;
+        }
 
-        match result {
-            Ok(pg_result) => {
-                if pg_result.rows_affected() == 0 {
-                    Err(RepoError::NotFound(format!(
-                        "Invoice with id {} not found",
-                        id
-                    )))
-                } else {
-                    let mut delete_results = DeleteResults::from(pg_result);
-                    delete_results.id = id.to_string();
-                    Ok(delete_results)
-                }
-            }
-            Err(e) => Err(RepoError::Other(e.to_string())),
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            id,
+            AuditAction::Delete,
+            deleted_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        let mut delete_results = DeleteResults::from(pg_result);
+        delete_results.id = id.to_string();
+        Ok(delete_results)
+    }
+
+    #[instrument]
+    async fn restore(&self, id: Uuid, restored_by: &str) -> Result<InvoiceRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        let row = sqlx::query_as!(
+            InvoiceRow,
+            r#"
+            UPDATE invoices
+            SET deleted_at = NULL, last_changed_by = $2, last_update = now()
+            WHERE alt_id = $1 AND deleted_at IS NOT NULL
+            RETURNING id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
+            "#,
+            id,
+            restored_by
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Deleted invoice with id {} not found", id)))?;
+        // the lines reserve their stock again, which fails if it has since been sold elsewhere
+        if row.status.holds_stock() {
+            stock::reserve_invoice(&mut tx, id, restored_by).await?;
+        }
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            id,
+            AuditAction::Restore,
+            restored_by,
+            before,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    #[instrument]
+    async fn set_status(
+        &self,
+        id: Uuid,
+        from: InvoiceStatus,
+        to: InvoiceStatus,
+        changed_by: &str,
+    ) -> Result<InvoiceRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        let current = sqlx::query_scalar!(
+            r#"
+            SELECT status as "status: InvoiceStatus"
+            FROM invoices
+            WHERE alt_id = $1 AND deleted_at IS NULL
+            "#,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", id)))?;
+        // the snapshot holds the row lock, so the status can't change between here and the update
+        if current != from {
+            return Err(RepoError::InvalidStatus(format!(
+                "invoice {} is {}, not {}",
+                id, current, from
+            )));
+        }
+        let row = sqlx::query_as!(
+            InvoiceRow,
+            r#"
+            UPDATE invoices
+            SET status = $2, last_changed_by = $3, last_update = now()
+            WHERE alt_id = $1
+            RETURNING id, alt_id, user_id, currency as "currency: Currency", subtotal, tax_rate, tax, discount, total, amount_paid, status as "status: InvoiceStatus", created_by, created_at, last_changed_by, last_update, deleted_at, version
+            "#,
+            id,
+            to as InvoiceStatus,
+            changed_by
+        )
+            .fetch_one(&mut *tx)
+            .await?;
+        if from.holds_stock() && to == InvoiceStatus::Paid {
+            stock::commit_invoice(&mut tx, id, changed_by).await?;
+        } else if from.holds_stock() && !to.holds_stock() {
+            stock::release_invoice(&mut tx, id, changed_by).await?;
         }
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            id,
+            AuditAction::Update,
+            changed_by,
+            before,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     #[instrument]
-    async fn add_item(&self, invoice_item: InvoiceItemRow) -> Result<InvoiceItemRow, RepoError> {
+    async fn add_item(
+        &self,
+        invoice_id: Uuid,
+        item_id: Uuid,
+        quantity: i32,
+        changed_by: &str,
+    ) -> Result<InvoiceItemRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+
+        // an unknown or deleted invoice or item selects nothing and comes back as NotFound; the
+        // rows are locked so neither currency nor the invoice's status can change before the line
+        // is written. The invoice is locked for update as its totals are rewritten next, which a
+        // share lock held by a concurrent change to the same invoice would deadlock
+        let currencies = sqlx::query!(
+            r#"
+            SELECT v.currency as "invoice_currency: Currency", it.currency as "item_currency: Currency",
+                   v.status as "invoice_status: InvoiceStatus"
+            FROM invoices v, items it
+            WHERE v.alt_id = $1 AND it.alt_id = $2
+              AND v.deleted_at IS NULL AND it.deleted_at IS NULL
+            FOR UPDATE OF v
+            FOR SHARE OF it
+            "#,
+            invoice_id,
+            item_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        check_draft(invoice_id, currencies.invoice_status)?;
+        check_item_currency(
+            item_id,
+            currencies.item_currency,
+            currencies.invoice_currency,
+        )?;
+        stock::reserve(&mut tx, item_id, quantity, changed_by).await?;
+
         let row = sqlx::query_as!(
             InvoiceItemRow,
             r#"
-            INSERT INTO invoices_items (invoice_id, item_id)
-            VALUES ($1, $2)
-            RETURNING invoice_id, item_id
+            INSERT INTO invoices_items (invoice_id, item_id, quantity, unit_price)
+            SELECT $1, alt_id, $3, unit_price
+            FROM items
+            WHERE alt_id = $2
+            ON CONFLICT (invoice_id, item_id) DO UPDATE
+                SET quantity = invoices_items.quantity + EXCLUDED.quantity
+            RETURNING invoice_id, item_id, quantity, unit_price
             "#,
-            invoice_item.invoice_id,
-            invoice_item.item_id
+            invoice_id,
+            item_id,
+            quantity
         )
-            .fetch_one(&self.pool)
-            .await;
-        row.map_err(RepoError::from)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(row)
     }
 
     #[instrument]
-    async fn remove_item(&self, invoice_item: InvoiceItemRow) -> Result<DeleteResults, RepoError> {
-        let result = sqlx::query!(
+    async fn remove_item(
+        &self,
+        invoice_id: Uuid,
+        item_id: Uuid,
+        changed_by: &str,
+    ) -> Result<DeleteResults, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        // locked for update ahead of the totals rewrite, as in add_item
+        let status = sqlx::query_scalar!(
+            r#"
+            SELECT status as "status: InvoiceStatus"
+            FROM invoices
+            WHERE alt_id = $1 AND deleted_at IS NULL
+            FOR UPDATE
+            "#,
+            invoice_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| RepoError::NotFound(format!("Invoice with id {} not found", invoice_id)))?;
+        check_draft(invoice_id, status)?;
+        let removed = sqlx::query_scalar!(
             r#"
             DELETE FROM invoices_items
             WHERE invoice_id = $1 AND item_id = $2
+            RETURNING quantity
             "#,
-            invoice_item.invoice_id,
-            invoice_item.item_id
+            invoice_id,
+            item_id
         )
-            .execute(&self.pool)
-            .await;
-        result
-            .map(DeleteResults::from)
-            .map(|mut r| {
-                r.id = invoice_item.item_id.to_string();
-                r
-            })
-            .map_err(RepoError::from)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(quantity) = removed {
+            stock::release(&mut tx, item_id, quantity, changed_by).await?;
+        }
+        tx.commit().await?;
+        Ok(DeleteResults {
+            id: item_id.to_string(),
+            deleted: removed.is_some(),
+        })
     }
 
     #[instrument]
@@ -317,14 +785,66 @@ impl InvoiceRepository for InvoiceRepositoryImpl {
         let result = sqlx::query_as!(
             InvoiceItemRow,
             r#"
-            SELECT invoice_id, item_id
+            SELECT invoice_id, item_id, quantity, unit_price
             FROM invoices_items
             WHERE invoice_id = $1
             "#,
             invoice_id
         )
-            .fetch_all(&self.pool)
-            .await;
+        .fetch_all(&mut *self.db.acquire().await?)
+        .await;
         result.map_err(RepoError::from)
     }
 }
+
+/// Lines can only be added to or removed from a draft; anything later has been sent out.
+fn check_draft(invoice_id: Uuid, status: InvoiceStatus) -> Result<(), RepoError> {
+    if status != InvoiceStatus::Draft {
+        return Err(RepoError::InvalidStatus(format!(
+            "invoice {} is {}; lines can only be changed while it is a draft",
+            invoice_id, status
+        )));
+    }
+    Ok(())
+}
+
+/// Every line of an invoice is priced in the invoice's currency.
+fn check_item_currency(
+    item_id: Uuid,
+    item_currency: Currency,
+    invoice_currency: Currency,
+) -> Result<(), RepoError> {
+    if item_currency != invoice_currency {
+        return Err(RepoError::CurrencyMismatch(format!(
+            "item {} is priced in {} but the invoice is in {}",
+            item_id, item_currency, invoice_currency
+        )));
+    }
+    Ok(())
+}
+
+/// Adds the `filter` conditions to a query over invoices, which must end in its WHERE clause, so
+/// that a page and its total always agree.
+fn push_filters(query: &mut QueryBuilder<'_, Postgres>, filter: &InvoiceFilter) {
+    if let Some(status) = filter.status {
+        query.push(" AND status = ").push_bind(status);
+    }
+    if let Some(paid) = filter.paid {
+        query.push(" AND (amount_paid >= total) = ").push_bind(paid);
+    }
+    if let Some(created_from) = filter.created_from {
+        query.push(" AND created_at >= ").push_bind(created_from);
+    }
+    if let Some(created_to) = filter.created_to {
+        query.push(" AND created_at < ").push_bind(created_to);
+    }
+    if let Some(min_total) = &filter.min_total {
+        query.push(" AND total >= ").push_bind(min_total.clone());
+    }
+    if let Some(max_total) = &filter.max_total {
+        query.push(" AND total <= ").push_bind(max_total.clone());
+    }
+    if let Some(user_id) = filter.user_id {
+        query.push(" AND user_id = ").push_bind(user_id);
+    }
+}
@@ -1,11 +1,16 @@
-use crate::inventory::model::{CreateItemRequest, Pagination, UpdateItemRequest};
-use crate::inventory::repositories::RepoError;
+use crate::inventory::model::money::Currency;
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::sort::{ItemSortField, Sort};
+use crate::inventory::model::{
+    AuditAction, AuditResource, CreateItemRequest, ItemSearchQuery, UpdateItemRequest,
+};
+use crate::inventory::repositories::unit_of_work::Db;
+use crate::inventory::repositories::{audit, push_seek, RepoError};
 use async_trait::async_trait;
-use bigdecimal::FromPrimitive;
 use chrono::{DateTime, Utc};
 use mockall::automock;
 use sqlx::types::BigDecimal;
-use sqlx::{FromRow, PgPool};
+use sqlx::{Connection, FromRow, Postgres, QueryBuilder};
 use std::fmt::Debug;
 use uuid::Uuid;
 
@@ -16,10 +21,58 @@ pub struct ItemRow {
     pub name: String,
     pub description: String,
     pub unit_price: BigDecimal,
+    pub currency: Currency,
     pub created_by: String,
     pub created_at: DateTime<Utc>,
     pub last_changed_by: String,
     pub last_update: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i64,
+}
+
+/// An item found by `search_items`, with how well it matched when searched by keyword
+#[derive(Debug, Clone, FromRow)]
+pub struct ItemSearchRow {
+    pub id: i32,
+    pub alt_id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub unit_price: BigDecimal,
+    pub currency: Currency,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub last_changed_by: String,
+    pub last_update: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i64,
+    /// The remaining fields are only set when the search has keywords
+    pub rank: Option<f32>,
+    /// HTML-escaped, with the matching words wrapped in `<mark>` tags
+    pub name_highlight: Option<String>,
+    pub description_highlight: Option<String>,
+}
+
+// Postgres marks the matching words with these private use characters rather than the tags
+// themselves, so the text around them can be escaped before they're swapped for `<mark>`
+const HIGHLIGHT_START: char = '\u{E000}';
+const HIGHLIGHT_STOP: char = '\u{E001}';
+
+/// HTML-escapes a `ts_headline` extract and turns its highlight markers into `<mark>` tags
+fn mark_matches(headline: &str) -> String {
+    let mut marked = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            '&' => marked.push_str("&amp;"),
+            '<' => marked.push_str("&lt;"),
+            '>' => marked.push_str("&gt;"),
+            '"' => marked.push_str("&quot;"),
+            '\'' => marked.push_str("&#39;"),
+            HIGHLIGHT_START => marked.push_str("<mark>"),
+            HIGHLIGHT_STOP => marked.push_str("</mark>"),
+            c => marked.push(c),
+        }
+    }
+    marked
 }
 
 #[async_trait]
@@ -27,23 +80,43 @@ pub struct ItemRow {
 pub trait ItemRepository: Debug {
     async fn get_all_items(
         &self,
-        maybe_pagination: Option<Pagination>,
+        sort: &Sort<ItemSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<ItemRow>, RepoError>;
+    /// Items matching every filter in `search`, in `sort` order. Without one they come by
+    /// relevance when the search has keywords, otherwise by id
+    async fn search_items(
+        &self,
+        search: &ItemSearchQuery,
+        sort: &Sort<ItemSortField>,
+        seek: &Seek,
+        include_deleted: bool,
+    ) -> Result<Vec<ItemSearchRow>, RepoError>;
+    /// How many items match `search` across all pages
+    async fn count_items(
+        &self,
+        search: &ItemSearchQuery,
+        include_deleted: bool,
+    ) -> Result<i64, RepoError>;
     async fn get_item_by_id(&self, id: i32) -> Result<ItemRow, RepoError>;
-    async fn get_item_by_uuid(&self, id: Uuid) -> Result<ItemRow, RepoError>;
+    async fn get_item_by_uuid(&self, id: Uuid, include_deleted: bool)
+        -> Result<ItemRow, RepoError>;
     async fn create_item(&self, item: &CreateItemRequest) -> Result<ItemRow, RepoError>;
     async fn update_item(&self, item: &UpdateItemRequest) -> Result<ItemRow, RepoError>;
-    async fn delete_item(&self, id: Uuid) -> Result<ItemRow, RepoError>;
+    /// Marks an item as deleted; it is kept so invoice lines that reference it still resolve
+    async fn delete_item(&self, id: Uuid, deleted_by: &str) -> Result<ItemRow, RepoError>;
+    async fn restore_item(&self, id: Uuid, restored_by: &str) -> Result<ItemRow, RepoError>;
 }
 
 #[derive(Debug)]
 pub struct ItemRepositoryImpl {
-    pub db: PgPool,
+    pub db: Db,
 }
 
 impl ItemRepositoryImpl {
-    pub async fn new(db: PgPool) -> ItemRepositoryImpl {
-        ItemRepositoryImpl { db }
+    pub async fn new(db: impl Into<Db>) -> ItemRepositoryImpl {
+        ItemRepositoryImpl { db: db.into() }
     }
 }
 
@@ -51,50 +124,131 @@ impl ItemRepositoryImpl {
 impl ItemRepository for ItemRepositoryImpl {
     async fn get_all_items(
         &self,
-        maybe_pagination: Option<Pagination>,
+        sort: &Sort<ItemSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<ItemRow>, RepoError> {
-        let result = if let Some(pagination) = maybe_pagination {
-            if let Some(last_id) = pagination.last_id {
-                sqlx::query_as!(
-                    ItemRow,
-                    r#"
-                        SELECT id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
-                        FROM items
-                        WHERE id > $1
-                        ORDER BY id
-                        LIMIT $2
-                    "#,
-                    last_id,
-                    pagination.page_size,
-                )
-                    .fetch_all(&self.db)
-                    .await
-            } else {
-                sqlx::query_as!(
-                    ItemRow,
+        let mut query = QueryBuilder::new(
+            r#"
+                SELECT id, alt_id, name, description, unit_price, currency, created_by, created_at, last_changed_by, last_update, deleted_at, version
+                FROM items
+                WHERE ("#,
+        );
+        query
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        push_seek(&mut query, sort, seek);
+        let result = query
+            .build_query_as::<ItemRow>()
+            .fetch_all(&mut *self.db.acquire().await?)
+            .await;
+        result.map_err(RepoError::from)
+    }
+
+    async fn search_items(
+        &self,
+        search: &ItemSearchQuery,
+        sort: &Sort<ItemSortField>,
+        seek: &Seek,
+        include_deleted: bool,
+    ) -> Result<Vec<ItemSearchRow>, RepoError> {
+        let mut query = QueryBuilder::new(
+            r#"
+                SELECT id, alt_id, name, description, unit_price, currency, created_by, created_at, last_changed_by, last_update, deleted_at, version,
+            "#,
+        );
+        match &search.q {
+            Some(q) => {
+                let selectors = format!(
+                    "StartSel=\"{}\", StopSel=\"{}\"",
+                    HIGHLIGHT_START, HIGHLIGHT_STOP
+                );
+                query
+                    .push(" ts_rank(search, query) AS rank, ts_headline('english', name, query, ")
+                    .push_bind(format!("{}, HighlightAll=true", selectors))
+                    .push(") AS name_highlight, ts_headline('english', description, query, ")
+                    .push_bind(format!(
+                        "{}, MaxFragments=2, MinWords=5, MaxWords=20",
+                        selectors
+                    ))
+                    .push(
+                        r#") AS description_highlight
+                        FROM items, websearch_to_tsquery('english', "#,
+                    )
+                    .push_bind(q.clone())
+                    .push(") query WHERE search @@ query");
+            }
+            None => {
+                query.push(
                     r#"
-                        SELECT id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
-                        FROM items
-                        ORDER BY id
-                        LIMIT $1
-                    "#,
-                    pagination.page_size,
-                )
-                    .fetch_all(&self.db)
-                    .await
+                    NULL::real AS rank, NULL::text AS name_highlight, NULL::text AS description_highlight
+                    FROM items
+                    WHERE TRUE"#,
+                );
+            }
+        }
+        push_filters(&mut query, search);
+        query
+            .push(" AND (")
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        if search.q.is_some() && sort.is_empty() {
+            // ranked most relevant first, ties by id, so the next page starts at the first item
+            // ordered after the cursor's rank and id
+            if let Some(after) = &seek.after {
+                query
+                    .push(" AND (ts_rank(search, query), -id) < (")
+                    .push_bind(after.rank)
+                    .push("::real, -")
+                    .push_bind(after.id)
+                    .push(")");
             }
+            query
+                .push(" ORDER BY ts_rank(search, query) DESC, id LIMIT ")
+                .push_bind(seek.limit);
         } else {
-            sqlx::query_as!(
-                ItemRow,
-                r#"
-                    SELECT id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
-                    FROM items
-                    ORDER BY id
-                "#
-            )
-                .fetch_all(&self.db)
-                .await
-        };
+            push_seek(&mut query, sort, seek);
+        }
+        let rows = query
+            .build_query_as::<ItemSearchRow>()
+            .fetch_all(&mut *self.db.acquire().await?)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ItemSearchRow {
+                name_highlight: row.name_highlight.as_deref().map(mark_matches),
+                description_highlight: row.description_highlight.as_deref().map(mark_matches),
+                ..row
+            })
+            .collect())
+    }
+
+    async fn count_items(
+        &self,
+        search: &ItemSearchQuery,
+        include_deleted: bool,
+    ) -> Result<i64, RepoError> {
+        let mut query = QueryBuilder::new("SELECT count(*) FROM items");
+        match &search.q {
+            Some(q) => {
+                query
+                    .push(", websearch_to_tsquery('english', ")
+                    .push_bind(q.clone())
+                    .push(") query WHERE search @@ query");
+            }
+            None => {
+                query.push(" WHERE TRUE");
+            }
+        }
+        push_filters(&mut query, search);
+        query
+            .push(" AND (")
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        let result = query
+            .build_query_scalar::<i64>()
+            .fetch_one(&mut *self.db.acquire().await?)
+            .await;
         result.map_err(RepoError::from)
     }
 
@@ -102,91 +256,206 @@ impl ItemRepository for ItemRepositoryImpl {
         let result = sqlx::query_as!(
             ItemRow,
             r#"
-                SELECT id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
+                SELECT id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
                 FROM items
-                WHERE id = $1
+                WHERE id = $1 AND deleted_at IS NULL
             "#,
             id,
         )
-            .fetch_one(&self.db)
+            .fetch_one(&mut *self.db.acquire().await?)
             .await;
         result.map_err(RepoError::from)
     }
 
-    async fn get_item_by_uuid(&self, id: Uuid) -> Result<ItemRow, RepoError> {
+    async fn get_item_by_uuid(
+        &self,
+        id: Uuid,
+        include_deleted: bool,
+    ) -> Result<ItemRow, RepoError> {
         let result = sqlx::query_as!(
             ItemRow,
             r#"
-                SELECT id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
+                SELECT id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
                 FROM items
-                WHERE alt_id = $1
+                WHERE alt_id = $1 AND ($2 OR deleted_at IS NULL)
             "#,
             id,
+            include_deleted,
         )
-            .fetch_one(&self.db)
+            .fetch_one(&mut *self.db.acquire().await?)
             .await;
         result.map_err(RepoError::from)
     }
 
     async fn create_item(&self, item: &CreateItemRequest) -> Result<ItemRow, RepoError> {
-        let result = sqlx::query_as!(
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let row = sqlx::query_as!(
             ItemRow,
             r#"
-                INSERT INTO items (alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-                RETURNING id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
+                INSERT INTO items (alt_id, name, description, unit_price, currency, created_by, created_at, last_changed_by, last_update)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                RETURNING id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
             "#,
             Uuid::new_v4(),
             item.name,
             item.description,
-            BigDecimal::from_f64(item.unit_price),
+            item.unit_price.amount,
+            item.unit_price.currency as Currency,
             item.created_by,
             Utc::now(),
             item.created_by,
             Utc::now(),
         )
-            .fetch_one(&self.db)
-            .await;
-        result.map_err(RepoError::from)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Item, row.alt_id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Item,
+            row.alt_id,
+            AuditAction::Create,
+            &item.created_by,
+            None,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     async fn update_item(&self, item: &UpdateItemRequest) -> Result<ItemRow, RepoError> {
-        if let Ok(uuid) = Uuid::parse_str(&item.id) {
-            let result = sqlx::query_as!(
-                ItemRow,
-                r#"
-                    UPDATE items
-                    SET name = $1, description = $2, unit_price = $3, last_changed_by = $4, last_update = $5
-                    WHERE alt_id = $6
-                    RETURNING id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
-                "#,
-                item.name,
-                item.description,
-                BigDecimal::from_f64(item.unit_price),
-                item.changed_by,
-                Utc::now(),
-                uuid,
-            )
-                .fetch_one(&self.db)
-                .await;
-            result.map_err(RepoError::from)
-        } else {
-            return Err(RepoError::InvalidUuid(item.clone().id));
-        }
+        let uuid =
+            Uuid::parse_str(&item.id).map_err(|_| RepoError::InvalidUuid(item.id.clone()))?;
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Item, uuid)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Item with id {} not found", uuid)))?;
+        audit::check_version(&before, &item.version, uuid)?;
+        let row = sqlx::query_as!(
+            ItemRow,
+            r#"
+                UPDATE items
+                SET name = $1, description = $2, unit_price = $3, currency = $4, last_changed_by = $5, last_update = $6
+                WHERE alt_id = $7 AND deleted_at IS NULL
+                RETURNING id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
+            "#,
+            item.name,
+            item.description,
+            item.unit_price.amount,
+            item.unit_price.currency as Currency,
+            item.changed_by,
+            Utc::now(),
+            uuid,
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Item with id {} not found", uuid)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Item, uuid).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Item,
+            uuid,
+            AuditAction::Update,
+            &item.changed_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
-    async fn delete_item(&self, id: Uuid) -> Result<ItemRow, RepoError> {
-        let result = sqlx::query_as!(
+    async fn delete_item(&self, id: Uuid, deleted_by: &str) -> Result<ItemRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Item, id)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Item with id {} not found", id)))?;
+        let row = sqlx::query_as!(
             ItemRow,
             r#"
-                DELETE FROM items
-                WHERE alt_id = $1
-                RETURNING id, alt_id, name, description, unit_price, created_by, created_at, last_changed_by, last_update
+                UPDATE items
+                SET deleted_at = now(), last_changed_by = $2, last_update = now()
+                WHERE alt_id = $1 AND deleted_at IS NULL
+                RETURNING id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
             "#,
             id,
+            deleted_by,
         )
-            .fetch_one(&self.db)
-            .await;
-        result.map_err(RepoError::from)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Item with id {} not found", id)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Item, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Item,
+            id,
+            AuditAction::Delete,
+            deleted_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    async fn restore_item(&self, id: Uuid, restored_by: &str) -> Result<ItemRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Item, id).await?;
+        let row = sqlx::query_as!(
+            ItemRow,
+            r#"
+                UPDATE items
+                SET deleted_at = NULL, last_changed_by = $2, last_update = now()
+                WHERE alt_id = $1 AND deleted_at IS NOT NULL
+                RETURNING id, alt_id, name, description, unit_price, currency as "currency: Currency", created_by, created_at, last_changed_by, last_update, deleted_at, version
+            "#,
+            id,
+            restored_by,
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Deleted item with id {} not found", id)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Item, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Item,
+            id,
+            AuditAction::Restore,
+            restored_by,
+            before,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+}
+
+/// Adds the price and creator conditions of `search` to a query over items, which must end in its
+/// WHERE clause, so that a page and its total always agree.
+fn push_filters(query: &mut QueryBuilder<'_, Postgres>, search: &ItemSearchQuery) {
+    if let Some(min_price) = &search.min_price {
+        query
+            .push(" AND unit_price >= ")
+            .push_bind(min_price.clone());
+    }
+    if let Some(max_price) = &search.max_price {
+        query
+            .push(" AND unit_price <= ")
+            .push_bind(max_price.clone());
+    }
+    if let Some(created_by) = &search.created_by {
+        query
+            .push(" AND created_by = ")
+            .push_bind(created_by.clone());
     }
 }
@@ -0,0 +1,179 @@
+use crate::inventory::model::money::Currency;
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::{
+    AuditAction, AuditResource, CreatePaymentRequest, InvoiceStatus, PaymentMethod,
+};
+use crate::inventory::repositories::{audit, stock, RepoError};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use sqlx::{FromRow, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct PaymentRow {
+    pub id: i32,
+    pub alt_id: Uuid,
+    pub invoice_id: Uuid,
+    pub amount: BigDecimal,
+    pub currency: Currency,
+    pub method: PaymentMethod,
+    pub reference: Option<String>,
+    pub paid_at: DateTime<Utc>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+#[automock]
+pub trait PaymentRepository: Debug {
+    /// Records a payment against an issued invoice and adds it to the invoice's amount paid,
+    /// marking the invoice paid and taking its reserved stock once nothing is outstanding.
+    async fn record_payment(&self, payment: &CreatePaymentRequest)
+        -> Result<PaymentRow, RepoError>;
+    async fn get_payments(
+        &self,
+        invoice_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<PaymentRow>, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct PaymentRepositoryImpl {
+    pool: PgPool,
+}
+
+impl PaymentRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PaymentRepository for PaymentRepositoryImpl {
+    #[instrument]
+    async fn record_payment(
+        &self,
+        payment: &CreatePaymentRequest,
+    ) -> Result<PaymentRow, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        // the snapshot locks the invoice, so concurrent payments can't jointly overpay it
+        let before = audit::snapshot(&mut tx, AuditResource::Invoice, payment.invoice_id).await?;
+        let invoice = sqlx::query!(
+            r#"
+            SELECT currency as "currency: Currency", status as "status: InvoiceStatus",
+                   total - amount_paid as "balance!"
+            FROM invoices
+            WHERE alt_id = $1 AND deleted_at IS NULL
+            "#,
+            payment.invoice_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            RepoError::NotFound(format!("Invoice with id {} not found", payment.invoice_id))
+        })?;
+        if invoice.status != InvoiceStatus::Issued {
+            return Err(RepoError::InvalidStatus(format!(
+                "invoice {} is {}; payments can only be taken against issued invoices",
+                payment.invoice_id, invoice.status
+            )));
+        }
+        if payment.amount.currency != invoice.currency {
+            return Err(RepoError::CurrencyMismatch(format!(
+                "payment is in {} but invoice {} is in {}",
+                payment.amount.currency, payment.invoice_id, invoice.currency
+            )));
+        }
+        if payment.amount.amount > invoice.balance {
+            return Err(RepoError::ExceedsBalance(format!(
+                "payment of {} is more than the {} outstanding on invoice {}",
+                payment.amount.amount.to_plain_string(),
+                invoice.balance.to_plain_string(),
+                payment.invoice_id
+            )));
+        }
+
+        let row = sqlx::query_as!(
+            PaymentRow,
+            r#"
+            INSERT INTO payments (invoice_id, amount, currency, method, reference, paid_at, created_by)
+            VALUES ($1, $2, $3, $4, $5, coalesce($6, now()), $7)
+            RETURNING id, alt_id, invoice_id, amount, currency as "currency: Currency",
+                      method as "method: PaymentMethod", reference, paid_at, created_by, created_at
+            "#,
+            payment.invoice_id,
+            payment.amount.amount,
+            payment.amount.currency as Currency,
+            payment.method as PaymentMethod,
+            payment.reference,
+            payment.paid_at,
+            payment.created_by
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let status = sqlx::query_scalar!(
+            r#"
+            UPDATE invoices
+            SET amount_paid = amount_paid + $2,
+                status = CASE WHEN amount_paid + $2 >= total THEN 'paid' ELSE status END,
+                last_changed_by = $3, last_update = now()
+            WHERE alt_id = $1
+            RETURNING status as "status: InvoiceStatus"
+            "#,
+            payment.invoice_id,
+            payment.amount.amount,
+            payment.created_by
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if status == InvoiceStatus::Paid {
+            stock::commit_invoice(&mut tx, payment.invoice_id, &payment.created_by).await?;
+        }
+
+        let after = audit::snapshot(&mut tx, AuditResource::Invoice, payment.invoice_id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Invoice,
+            payment.invoice_id,
+            AuditAction::Update,
+            &payment.created_by,
+            before,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    #[instrument]
+    async fn get_payments(
+        &self,
+        invoice_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<PaymentRow>, RepoError> {
+        let result = sqlx::query_as!(
+            PaymentRow,
+            r#"
+            SELECT id, alt_id, invoice_id, amount, currency as "currency: Currency",
+                   method as "method: PaymentMethod", reference, paid_at, created_by, created_at
+            FROM payments
+            WHERE invoice_id = $1
+              AND id > coalesce($2, 0)
+            ORDER BY id
+            LIMIT $3
+            "#,
+            invoice_id,
+            seek.after.as_ref().map(|cursor| cursor.id),
+            seek.limit
+        )
+        .fetch_all(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+}
@@ -1,10 +1,15 @@
-use crate::inventory::model::{CreatePersonRequest, UpdatePersonRequest};
-use crate::inventory::repositories::RepoError;
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::sort::{PersonSortField, Sort};
+use crate::inventory::model::{
+    AuditAction, AuditResource, CreatePersonRequest, UpdatePersonRequest,
+};
+use crate::inventory::repositories::unit_of_work::Db;
+use crate::inventory::repositories::{audit, push_seek, RepoError};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use mockall::automock;
 use sqlx::types::Uuid;
-use sqlx::PgPool;
+use sqlx::{Connection, QueryBuilder};
 use std::fmt::Debug;
 
 #[derive(sqlx::FromRow, Debug, Clone)]
@@ -17,6 +22,8 @@ pub struct PersonRow {
     pub created_at: DateTime<Utc>,
     pub last_changed_by: String,
     pub last_update: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i64,
 }
 
 #[async_trait]
@@ -24,24 +31,32 @@ pub struct PersonRow {
 pub trait PersonRepository: Debug {
     async fn get_all_persons(
         &self,
-        last_id: Option<i32>,
-        page_size: i64,
+        sort: &Sort<PersonSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<PersonRow>, RepoError>;
+    async fn count_persons(&self, include_deleted: bool) -> Result<i64, RepoError>;
     async fn get_person_by_id(&self, id: i32) -> Result<PersonRow, RepoError>;
-    async fn get_person_by_uuid(&self, id: Uuid) -> Result<PersonRow, RepoError>;
+    async fn get_person_by_uuid(
+        &self,
+        id: Uuid,
+        include_deleted: bool,
+    ) -> Result<PersonRow, RepoError>;
     async fn create_person(&self, person: &CreatePersonRequest) -> Result<PersonRow, RepoError>;
     async fn update_person(&self, person: &UpdatePersonRequest) -> Result<PersonRow, RepoError>;
-    async fn delete_person(&self, id: Uuid) -> Result<PersonRow, RepoError>;
+    /// Marks a person as deleted; they are kept so their invoices still resolve
+    async fn delete_person(&self, id: Uuid, deleted_by: &str) -> Result<PersonRow, RepoError>;
+    async fn restore_person(&self, id: Uuid, restored_by: &str) -> Result<PersonRow, RepoError>;
 }
 
 #[derive(Debug)]
 pub struct PersonRepositoryImpl {
-    pub db: PgPool,
+    pub db: Db,
 }
 
 impl PersonRepositoryImpl {
-    pub async fn new(db: PgPool) -> PersonRepositoryImpl {
-        PersonRepositoryImpl { db }
+    pub async fn new(db: impl Into<Db>) -> PersonRepositoryImpl {
+        PersonRepositoryImpl { db: db.into() }
     }
 }
 
@@ -49,56 +64,52 @@ impl PersonRepositoryImpl {
 impl PersonRepository for PersonRepositoryImpl {
     async fn get_all_persons(
         &self,
-        last_id: Option<i32>,
-        page_size: i64,
+        sort: &Sort<PersonSortField>,
+        seek: &Seek,
+        include_deleted: bool,
     ) -> Result<Vec<PersonRow>, RepoError> {
-        let result = if let Some(id) = last_id {
-            sqlx::query_as!(
-                PersonRow,
-                r#"
-                    SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
-                    FROM persons
-                    WHERE id > $1
-                    ORDER BY id
-                    LIMIT $2
-                    "#,
-                id,
-                page_size
-            )
-                .fetch_all(&self.db)
-                .await
-        } else {
-            sqlx::query_as!(
-                PersonRow,
-                r#"
-                    SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
-                    FROM persons
-                    ORDER BY id
-                    LIMIT $1
-                    "#,
-                page_size
-            )
-                .fetch_all(&self.db)
-                .await
-        };
+        let mut query = QueryBuilder::new(
+            r#"
+                SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
+                FROM persons
+                WHERE ("#,
+        );
+        query
+            .push_bind(include_deleted)
+            .push(" OR deleted_at IS NULL)");
+        push_seek(&mut query, sort, seek);
+        let result = query
+            .build_query_as::<PersonRow>()
+            .fetch_all(&mut *self.db.acquire().await?)
+            .await;
+        result.map_err(RepoError::from)
+    }
 
-        match result {
-            Ok(rows) => Ok(rows),
-            Err(e) => Err(RepoError::Other(e.to_string())),
-        }
+    async fn count_persons(&self, include_deleted: bool) -> Result<i64, RepoError> {
+        let result = sqlx::query_scalar!(
+            r#"
+                SELECT count(*) as "count!"
+                FROM persons
+                WHERE $1 OR deleted_at IS NULL
+                "#,
+            include_deleted
+        )
+        .fetch_one(&mut *self.db.acquire().await?)
+        .await;
+        result.map_err(RepoError::from)
     }
 
     async fn get_person_by_id(&self, id: i32) -> Result<PersonRow, RepoError> {
         let result = sqlx::query_as!(
             PersonRow,
             r#"
-                SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
+                SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
                 FROM persons
-                WHERE id = $1
+                WHERE id = $1 AND deleted_at IS NULL
                 "#,
             id
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *self.db.acquire().await?)
         .await;
 
         match result {
@@ -107,17 +118,22 @@ impl PersonRepository for PersonRepositoryImpl {
         }
     }
 
-    async fn get_person_by_uuid(&self, id: Uuid) -> Result<PersonRow, RepoError> {
+    async fn get_person_by_uuid(
+        &self,
+        id: Uuid,
+        include_deleted: bool,
+    ) -> Result<PersonRow, RepoError> {
         let result = sqlx::query_as!(
             PersonRow,
             r#"
-                SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
+                SELECT id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
                 FROM persons
-                WHERE alt_id = $1
+                WHERE alt_id = $1 AND ($2 OR deleted_at IS NULL)
                 "#,
-            id
+            id,
+            include_deleted
         )
-        .fetch_one(&self.db)
+        .fetch_one(&mut *self.db.acquire().await?)
         .await;
 
         match result {
@@ -127,70 +143,146 @@ impl PersonRepository for PersonRepositoryImpl {
     }
 
     async fn create_person(&self, person: &CreatePersonRequest) -> Result<PersonRow, RepoError> {
-        let result = sqlx::query_as!(
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let row = sqlx::query_as!(
             PersonRow,
             r#"
                 INSERT INTO persons (name, email, created_by)
                 VALUES ($1, $2, $3)
-                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
+                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
                 "#,
             person.name,
             person.email,
             person.created_by
         )
-            .fetch_one(&self.db)
-            .await;
+            .fetch_one(&mut *tx)
+            .await?;
 
-        match result {
-            Ok(row) => Ok(row),
-            Err(e) => Err(RepoError::from(e)),
-        }
+        let after = audit::snapshot(&mut tx, AuditResource::Person, row.alt_id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Person,
+            row.alt_id,
+            AuditAction::Create,
+            &person.created_by,
+            None,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
     async fn update_person(&self, person: &UpdatePersonRequest) -> Result<PersonRow, RepoError> {
-        if let Ok(uuid) = Uuid::parse_str(&person.id) {
-            let result = sqlx::query_as!(
-                PersonRow,
-                r#"
-                    UPDATE persons
-                    SET name = $1, email = $2, last_changed_by = $3, last_update = $4
-                    WHERE alt_id = $5
-                    RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
-                    "#,
-                person.name,
-                person.email,
-                person.changed_by,
-                Utc::now(),
-                uuid
-            )
-                .fetch_one(&self.db)
-                .await;
-
-            match result {
-                Ok(row) => Ok(row),
-                Err(e) => Err(RepoError::from(e)),
-            }
-        } else {
-            Err(RepoError::InvalidUuid(person.id.clone()))
-        }
+        let uuid =
+            Uuid::parse_str(&person.id).map_err(|_| RepoError::InvalidUuid(person.id.clone()))?;
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Person, uuid)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Person with id {} not found", uuid)))?;
+        audit::check_version(&before, &person.version, uuid)?;
+        let row = sqlx::query_as!(
+            PersonRow,
+            r#"
+                UPDATE persons
+                SET name = $1, email = $2, last_changed_by = $3, last_update = $4
+                WHERE alt_id = $5 AND deleted_at IS NULL
+                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
+                "#,
+            person.name,
+            person.email,
+            person.changed_by,
+            Utc::now(),
+            uuid
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Person with id {} not found", uuid)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Person, uuid).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Person,
+            uuid,
+            AuditAction::Update,
+            &person.changed_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 
-    async fn delete_person(&self, id: Uuid) -> Result<PersonRow, RepoError> {
-        let result = sqlx::query_as!(
+    async fn delete_person(&self, id: Uuid, deleted_by: &str) -> Result<PersonRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Person, id)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Person with id {} not found", id)))?;
+        let row = sqlx::query_as!(
             PersonRow,
             r#"
-                DELETE FROM persons
-                WHERE alt_id = $1
-                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update
+                UPDATE persons
+                SET deleted_at = now(), last_changed_by = $2, last_update = now()
+                WHERE alt_id = $1 AND deleted_at IS NULL
+                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
                 "#,
-            id
+            id,
+            deleted_by
         )
-            .fetch_one(&self.db)
-            .await;
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Person with id {} not found", id)))?;
 
-        match result {
-            Ok(row) => Ok(row),
-            Err(e) => Err(RepoError::from(e)),
-        }
+        let after = audit::snapshot(&mut tx, AuditResource::Person, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Person,
+            id,
+            AuditAction::Delete,
+            deleted_by,
+            Some(before),
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    async fn restore_person(&self, id: Uuid, restored_by: &str) -> Result<PersonRow, RepoError> {
+        let mut conn = self.db.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let before = audit::snapshot(&mut tx, AuditResource::Person, id).await?;
+        let row = sqlx::query_as!(
+            PersonRow,
+            r#"
+                UPDATE persons
+                SET deleted_at = NULL, last_changed_by = $2, last_update = now()
+                WHERE alt_id = $1 AND deleted_at IS NOT NULL
+                RETURNING id, alt_id, name, email, created_by, created_at, last_changed_by, last_update, deleted_at, version
+                "#,
+            id,
+            restored_by
+        )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RepoError::NotFound(format!("Deleted person with id {} not found", id)))?;
+
+        let after = audit::snapshot(&mut tx, AuditResource::Person, id).await?;
+        audit::record(
+            &mut tx,
+            AuditResource::Person,
+            id,
+            AuditAction::Restore,
+            restored_by,
+            before,
+            after,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(row)
     }
 }
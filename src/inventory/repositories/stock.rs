@@ -0,0 +1,366 @@
+use crate::inventory::model::page::Seek;
+use crate::inventory::model::{CreateStockMovementRequest, MovementType};
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct StockRow {
+    pub item_id: Uuid,
+    pub on_hand: i32,
+    pub reserved: i32,
+    pub available: i32,
+    pub last_changed_by: Option<String>,
+    pub last_update: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct StockMovementRow {
+    pub id: i32,
+    pub alt_id: Uuid,
+    pub item_id: Uuid,
+    pub movement_type: MovementType,
+    pub quantity: i32,
+    pub note: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+#[automock]
+pub trait StockRepository: Debug {
+    async fn get_stock(&self, item_id: Uuid) -> Result<StockRow, RepoError>;
+    /// Appends a movement to the ledger. `movement.quantity` must already carry its sign.
+    async fn record_movement(
+        &self,
+        movement: &CreateStockMovementRequest,
+    ) -> Result<StockMovementRow, RepoError>;
+    async fn get_movements(
+        &self,
+        item_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<StockMovementRow>, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct StockRepositoryImpl {
+    pool: PgPool,
+}
+
+impl StockRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl StockRepository for StockRepositoryImpl {
+    #[instrument]
+    async fn get_stock(&self, item_id: Uuid) -> Result<StockRow, RepoError> {
+        // on hand is always derived from the ledger; items without movements report zero stock
+        let result = sqlx::query_as!(
+            StockRow,
+            r#"
+            SELECT i.alt_id as item_id,
+                   m.on_hand as "on_hand!",
+                   coalesce(s.reserved, 0) as "reserved!",
+                   m.on_hand - coalesce(s.reserved, 0) as "available!",
+                   s.last_changed_by as "last_changed_by?",
+                   s.last_update as "last_update?"
+            FROM items i
+            LEFT JOIN stock_levels s ON s.item_id = i.alt_id
+            CROSS JOIN LATERAL (
+                SELECT coalesce(sum(quantity), 0)::integer as on_hand
+                FROM stock_movements
+                WHERE item_id = i.alt_id
+            ) m
+            WHERE i.alt_id = $1
+            "#,
+            item_id
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn record_movement(
+        &self,
+        movement: &CreateStockMovementRequest,
+    ) -> Result<StockMovementRow, RepoError> {
+        let mut tx = self.pool.begin().await?;
+
+        let level = lock_level(&mut tx, movement.item_id, &movement.created_by).await?;
+        let new_on_hand = level.on_hand + movement.quantity;
+        if new_on_hand < level.reserved {
+            return Err(RepoError::InsufficientStock(format!(
+                "movement of {} would leave {} on hand with {} reserved",
+                movement.quantity, new_on_hand, level.reserved
+            )));
+        }
+
+        let row = sqlx::query_as!(
+            StockMovementRow,
+            r#"
+            INSERT INTO stock_movements (item_id, movement_type, quantity, note, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, alt_id, item_id, movement_type as "movement_type: MovementType",
+                      quantity, note, created_by, created_at
+            "#,
+            movement.item_id,
+            movement.movement_type as MovementType,
+            movement.quantity,
+            movement.note,
+            movement.created_by
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE stock_levels
+            SET last_changed_by = $2, last_update = now()
+            WHERE item_id = $1
+            "#,
+            movement.item_id,
+            movement.created_by
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(row)
+    }
+
+    #[instrument]
+    async fn get_movements(
+        &self,
+        item_id: Uuid,
+        seek: &Seek,
+    ) -> Result<Vec<StockMovementRow>, RepoError> {
+        let result = sqlx::query_as!(
+            StockMovementRow,
+            r#"
+            SELECT id, alt_id, item_id, movement_type as "movement_type: MovementType",
+                   quantity, note, created_by, created_at
+            FROM stock_movements
+            WHERE item_id = $1
+              AND id > coalesce($2, 0)
+            ORDER BY id
+            LIMIT $3
+            "#,
+            item_id,
+            seek.after.as_ref().map(|cursor| cursor.id),
+            seek.limit
+        )
+        .fetch_all(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+}
+
+#[derive(Debug)]
+struct LockedLevel {
+    on_hand: i32,
+    reserved: i32,
+}
+
+/// Locks an item's stock level, creating it on first use. The stock_levels row doubles as a
+/// per-item lock, so concurrent changes are serialised and cannot jointly take on hand below what
+/// is reserved or reserve more than is on hand.
+async fn lock_level(
+    conn: &mut PgConnection,
+    item_id: Uuid,
+    actor: &str,
+) -> Result<LockedLevel, RepoError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO stock_levels (item_id, created_by, last_changed_by)
+        SELECT alt_id, $2, $2
+        FROM items
+        WHERE alt_id = $1
+        ON CONFLICT (item_id) DO NOTHING
+        "#,
+        item_id,
+        actor
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let level = sqlx::query_as!(
+        LockedLevel,
+        r#"
+        SELECT (SELECT coalesce(sum(quantity), 0)::integer
+                FROM stock_movements
+                WHERE item_id = s.item_id) as "on_hand!",
+               s.reserved
+        FROM stock_levels s
+        WHERE s.item_id = $1
+        FOR UPDATE
+        "#,
+        item_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(level)
+}
+
+/// Sets `quantity` units of an item aside, failing with InsufficientStock if fewer are available.
+pub(crate) async fn reserve(
+    conn: &mut PgConnection,
+    item_id: Uuid,
+    quantity: i32,
+    actor: &str,
+) -> Result<(), RepoError> {
+    let level = lock_level(conn, item_id, actor).await?;
+    let available = level.on_hand - level.reserved;
+    if available < quantity {
+        return Err(RepoError::InsufficientStock(format!(
+            "{} of item {} requested but only {} available",
+            quantity, item_id, available
+        )));
+    }
+    sqlx::query!(
+        r#"
+        UPDATE stock_levels
+        SET reserved = reserved + $2, last_changed_by = $3, last_update = now()
+        WHERE item_id = $1
+        "#,
+        item_id,
+        quantity,
+        actor
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Hands `quantity` reserved units of an item back to available stock, failing with
+/// InsufficientStock if fewer are reserved.
+pub(crate) async fn release(
+    conn: &mut PgConnection,
+    item_id: Uuid,
+    quantity: i32,
+    actor: &str,
+) -> Result<(), RepoError> {
+    let level = lock_level(conn, item_id, actor).await?;
+    unreserve(conn, item_id, &level, quantity, actor).await
+}
+
+/// Takes `quantity` units off an item's locked reservation. Every line reserved its quantity
+/// when it was written, so releasing more than is reserved means a line was released twice.
+async fn unreserve(
+    conn: &mut PgConnection,
+    item_id: Uuid,
+    level: &LockedLevel,
+    quantity: i32,
+    actor: &str,
+) -> Result<(), RepoError> {
+    if level.reserved < quantity {
+        return Err(RepoError::InsufficientStock(format!(
+            "{} of item {} to be released but only {} reserved",
+            quantity, item_id, level.reserved
+        )));
+    }
+    sqlx::query!(
+        r#"
+        UPDATE stock_levels
+        SET reserved = reserved - $2, last_changed_by = $3, last_update = now()
+        WHERE item_id = $1
+        "#,
+        item_id,
+        quantity,
+        actor
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Reserves the quantity of every line on an invoice.
+pub(crate) async fn reserve_invoice(
+    conn: &mut PgConnection,
+    invoice_id: Uuid,
+    actor: &str,
+) -> Result<(), RepoError> {
+    for line in invoice_lines(conn, invoice_id).await? {
+        reserve(conn, line.item_id, line.quantity, actor).await?;
+    }
+    Ok(())
+}
+
+/// Releases the quantity reserved for every line on an invoice.
+pub(crate) async fn release_invoice(
+    conn: &mut PgConnection,
+    invoice_id: Uuid,
+    actor: &str,
+) -> Result<(), RepoError> {
+    for line in invoice_lines(conn, invoice_id).await? {
+        release(conn, line.item_id, line.quantity, actor).await?;
+    }
+    Ok(())
+}
+
+/// Takes the quantity reserved for every line on a paid invoice out of stock, recording a sale
+/// movement per line. Fails with InsufficientStock if a line's quantity is no longer on hand.
+pub(crate) async fn commit_invoice(
+    conn: &mut PgConnection,
+    invoice_id: Uuid,
+    actor: &str,
+) -> Result<(), RepoError> {
+    let note = format!("invoice {}", invoice_id);
+    for line in invoice_lines(conn, invoice_id).await? {
+        let level = lock_level(conn, line.item_id, actor).await?;
+        if level.on_hand < line.quantity {
+            return Err(RepoError::InsufficientStock(format!(
+                "{} of item {} sold but only {} on hand",
+                line.quantity, line.item_id, level.on_hand
+            )));
+        }
+        unreserve(conn, line.item_id, &level, line.quantity, actor).await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO stock_movements (item_id, movement_type, quantity, note, created_by)
+            VALUES ($1, 'sale', $2, $3, $4)
+            "#,
+            line.item_id,
+            -line.quantity,
+            note,
+            actor
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct InvoiceLine {
+    item_id: Uuid,
+    quantity: i32,
+}
+
+/// An invoice's lines in item order, so that every caller locks stock levels in the same order.
+async fn invoice_lines(
+    conn: &mut PgConnection,
+    invoice_id: Uuid,
+) -> Result<Vec<InvoiceLine>, RepoError> {
+    let lines = sqlx::query_as!(
+        InvoiceLine,
+        r#"
+        SELECT item_id, quantity
+        FROM invoices_items
+        WHERE invoice_id = $1
+        ORDER BY item_id
+        "#,
+        invoice_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(lines)
+}
@@ -0,0 +1,253 @@
+use crate::inventory::model::Scope;
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use sqlx::{FromRow, PgPool};
+use std::fmt::Debug;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, FromRow)]
+pub struct RefreshTokenRow {
+    pub id: i32,
+    pub token_hash: String,
+    pub family: Uuid,
+    pub client_id: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, FromRow)]
+pub struct RedeemedRefreshTokenRow {
+    pub client_id: String,
+    pub scopes: Vec<Scope>,
+    pub family: Uuid,
+}
+
+#[async_trait]
+#[automock]
+pub trait TokenRepository: Debug {
+    async fn record_access_token(
+        &self,
+        jti: Uuid,
+        family: Uuid,
+        client_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepoError>;
+    /// True if the token was issued by us, has not been revoked and its client has not been revoked
+    async fn is_access_token_active(&self, jti: Uuid) -> Result<bool, RepoError>;
+    async fn create_refresh_token(
+        &self,
+        token_hash: &str,
+        family: Uuid,
+        client_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RefreshTokenRow, RepoError>;
+    async fn get_refresh_token(&self, token_hash: &str) -> Result<RefreshTokenRow, RepoError>;
+    /// Marks a refresh token as used. Fails with NotFound unless the token is unused, unrevoked,
+    /// unexpired and belongs to a client that has not been revoked.
+    async fn use_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<RedeemedRefreshTokenRow, RepoError>;
+    /// Revokes every access and refresh token in a family, returning how many were still valid
+    async fn revoke_family(&self, family: Uuid) -> Result<i64, RepoError>;
+    /// Revokes every access and refresh token of a client, returning how many were still valid
+    async fn revoke_client_tokens(&self, client_id: &str) -> Result<i64, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct TokenRepositoryImpl {
+    pool: PgPool,
+}
+
+impl TokenRepositoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TokenRepository for TokenRepositoryImpl {
+    #[instrument]
+    async fn record_access_token(
+        &self,
+        jti: Uuid,
+        family: Uuid,
+        client_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), RepoError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO access_tokens (jti, family, client_id, expires_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            jti,
+            family,
+            client_id,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    #[instrument]
+    async fn is_access_token_active(&self, jti: Uuid) -> Result<bool, RepoError> {
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM access_tokens t
+                JOIN api_clients c ON c.client_id = t.client_id
+                WHERE t.jti = $1
+                  AND t.revoked_at IS NULL
+                  AND c.revoked_at IS NULL
+            ) as "active!"
+            "#,
+            jti
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument(skip(token_hash))]
+    async fn create_refresh_token(
+        &self,
+        token_hash: &str,
+        family: Uuid,
+        client_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<RefreshTokenRow, RepoError> {
+        let result = sqlx::query_as!(
+            RefreshTokenRow,
+            r#"
+            INSERT INTO refresh_tokens (token_hash, family, client_id, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, token_hash, family, client_id, expires_at, used_at, revoked_at, created_at
+            "#,
+            token_hash,
+            family,
+            client_id,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument(skip(token_hash))]
+    async fn get_refresh_token(&self, token_hash: &str) -> Result<RefreshTokenRow, RepoError> {
+        let result = sqlx::query_as!(
+            RefreshTokenRow,
+            r#"
+            SELECT id, token_hash, family, client_id, expires_at, used_at, revoked_at, created_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument(skip(token_hash))]
+    async fn use_refresh_token(
+        &self,
+        token_hash: &str,
+    ) -> Result<RedeemedRefreshTokenRow, RepoError> {
+        // a single conditional update so two concurrent refreshes cannot both redeem the token
+        let result = sqlx::query_as!(
+            RedeemedRefreshTokenRow,
+            r#"
+            UPDATE refresh_tokens t
+            SET used_at = now()
+            FROM api_clients c
+            WHERE t.token_hash = $1
+              AND t.used_at IS NULL
+              AND t.revoked_at IS NULL
+              AND t.expires_at > now()
+              AND c.client_id = t.client_id
+              AND c.revoked_at IS NULL
+            RETURNING t.client_id, c.scopes as "scopes: Vec<Scope>", t.family
+            "#,
+            token_hash
+        )
+        .fetch_one(&self.pool)
+        .await;
+        result.map_err(RepoError::from)
+    }
+
+    #[instrument]
+    async fn revoke_family(&self, family: Uuid) -> Result<i64, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let access_tokens = sqlx::query!(
+            r#"
+            UPDATE access_tokens
+            SET revoked_at = now()
+            WHERE family = $1 AND revoked_at IS NULL AND expires_at > now()
+            "#,
+            family
+        )
+        .execute(&mut *tx)
+        .await?;
+        let refresh_tokens = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = now()
+            WHERE family = $1 AND revoked_at IS NULL AND used_at IS NULL AND expires_at > now()
+            "#,
+            family
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((access_tokens.rows_affected() + refresh_tokens.rows_affected()) as i64)
+    }
+
+    #[instrument]
+    async fn revoke_client_tokens(&self, client_id: &str) -> Result<i64, RepoError> {
+        let mut tx = self.pool.begin().await?;
+        let client = sqlx::query!(
+            r#"
+            SELECT client_id FROM api_clients WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if client.is_none() {
+            return Err(RepoError::NotFound(format!(
+                "No API client with id {}",
+                client_id
+            )));
+        }
+        let access_tokens = sqlx::query!(
+            r#"
+            UPDATE access_tokens
+            SET revoked_at = now()
+            WHERE client_id = $1 AND revoked_at IS NULL AND expires_at > now()
+            "#,
+            client_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        let refresh_tokens = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked_at = now()
+            WHERE client_id = $1 AND revoked_at IS NULL AND used_at IS NULL AND expires_at > now()
+            "#,
+            client_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((access_tokens.rows_affected() + refresh_tokens.rows_affected()) as i64)
+    }
+}
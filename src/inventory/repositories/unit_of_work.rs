@@ -0,0 +1,159 @@
+use crate::inventory::repositories::invoice::{InvoiceRepository, InvoiceRepositoryImpl};
+use crate::inventory::repositories::item::{ItemRepository, ItemRepositoryImpl};
+use crate::inventory::repositories::person::{PersonRepository, PersonRepositoryImpl};
+use crate::inventory::repositories::RepoError;
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::pool::PoolConnection;
+use sqlx::{PgConnection, PgPool, Postgres, Transaction};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::instrument;
+
+/// Where a repository runs its queries: straight against the pool, or inside the transaction of
+/// a unit of work. A repository's own transactions become savepoints inside a unit of work, so a
+/// step that fails is undone without losing the steps before it.
+#[derive(Clone)]
+pub enum Db {
+    Pool(PgPool),
+    Unit(Arc<Mutex<Option<Transaction<'static, Postgres>>>>),
+}
+
+impl Db {
+    /// A connection to run queries on, or to begin a transaction on. Inside a unit of work this
+    /// is the unit's transaction, held until the connection is dropped.
+    pub(crate) async fn acquire(&self) -> Result<DbConnection<'_>, RepoError> {
+        match self {
+            Db::Pool(pool) => Ok(DbConnection::Pool(Box::new(pool.acquire().await?))),
+            Db::Unit(tx) => {
+                let guard = tx.lock().await;
+                if guard.is_none() {
+                    return Err(RepoError::Other(
+                        "unit of work has already been committed".to_string(),
+                    ));
+                }
+                Ok(DbConnection::Unit(guard))
+            }
+        }
+    }
+}
+
+impl From<PgPool> for Db {
+    fn from(pool: PgPool) -> Self {
+        Db::Pool(pool)
+    }
+}
+
+impl Debug for Db {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Db::Pool(pool) => f.debug_tuple("Pool").field(pool).finish(),
+            Db::Unit(_) => f.write_str("Unit"),
+        }
+    }
+}
+
+pub(crate) enum DbConnection<'a> {
+    Pool(Box<PoolConnection<Postgres>>),
+    Unit(MutexGuard<'a, Option<Transaction<'static, Postgres>>>),
+}
+
+impl Deref for DbConnection<'_> {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DbConnection::Pool(conn) => conn,
+            // acquire never hands out a guard over a finished unit
+            DbConnection::Unit(guard) => guard.as_ref().expect("unit of work is open"),
+        }
+    }
+}
+
+impl DerefMut for DbConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DbConnection::Pool(conn) => conn,
+            DbConnection::Unit(guard) => guard.as_mut().expect("unit of work is open"),
+        }
+    }
+}
+
+/// Repositories sharing one transaction. Nothing they write is visible to anyone else until
+/// `commit`; a unit of work dropped without committing is rolled back.
+#[async_trait]
+#[automock]
+pub trait UnitOfWork: Debug + Send + Sync {
+    fn persons(&self) -> Arc<dyn PersonRepository + Send + Sync>;
+    fn items(&self) -> Arc<dyn ItemRepository + Send + Sync>;
+    fn invoices(&self) -> Arc<dyn InvoiceRepository + Send + Sync>;
+    async fn commit(&self) -> Result<(), RepoError>;
+}
+
+#[async_trait]
+#[automock]
+pub trait UnitOfWorkFactory: Debug + Send + Sync {
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork>, RepoError>;
+}
+
+#[derive(Debug)]
+pub struct UnitOfWorkFactoryImpl {
+    pool: PgPool,
+}
+
+impl UnitOfWorkFactoryImpl {
+    pub async fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UnitOfWorkFactory for UnitOfWorkFactoryImpl {
+    #[instrument]
+    async fn begin(&self) -> Result<Box<dyn UnitOfWork>, RepoError> {
+        let tx = self.pool.begin().await?;
+        Ok(Box::new(UnitOfWorkImpl {
+            db: Db::Unit(Arc::new(Mutex::new(Some(tx)))),
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct UnitOfWorkImpl {
+    db: Db,
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkImpl {
+    fn persons(&self) -> Arc<dyn PersonRepository + Send + Sync> {
+        Arc::new(PersonRepositoryImpl {
+            db: self.db.clone(),
+        })
+    }
+
+    fn items(&self) -> Arc<dyn ItemRepository + Send + Sync> {
+        Arc::new(ItemRepositoryImpl {
+            db: self.db.clone(),
+        })
+    }
+
+    fn invoices(&self) -> Arc<dyn InvoiceRepository + Send + Sync> {
+        Arc::new(InvoiceRepositoryImpl {
+            db: self.db.clone(),
+        })
+    }
+
+    #[instrument]
+    async fn commit(&self) -> Result<(), RepoError> {
+        let Db::Unit(tx) = &self.db else {
+            unreachable!("a unit of work always runs in a transaction");
+        };
+        let tx = tx.lock().await.take().ok_or_else(|| {
+            RepoError::Other("unit of work has already been committed".to_string())
+        })?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
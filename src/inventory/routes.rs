@@ -1,8 +1,11 @@
 use crate::inventory::handlers::status::{healthz, livenessz, readyz};
-use crate::inventory::handlers::{invoice, item, person};
+use crate::inventory::handlers::{api_client, audit, invoice, item, payment, person, stock};
+use crate::inventory::model::Scope;
+use crate::jwt::RequiredScope;
 use crate::AppContext;
 use axum::http::{HeaderValue, Method};
-use axum::Router;
+use axum::routing::{delete, get, post, MethodRouter};
+use axum::{Extension, Router};
 use tower_http::cors::CorsLayer;
 use utoipa::OpenApi;
 
@@ -10,16 +13,29 @@ use utoipa::OpenApi;
 #[openapi(
     nest(
         (path = "/v1/api/persons", api=person::PersonApi),
+        (path = "/v1/api/persons", api=audit::PersonHistoryApi),
         (path = "/v1/api/items", api=item::ItemApi),
-        (path = "/v1/api/invoices", api=invoice::InvoiceApi)
+        (path = "/v1/api/items", api=stock::StockApi),
+        (path = "/v1/api/items", api=audit::ItemHistoryApi),
+        (path = "/v1/api/invoices", api=invoice::InvoiceApi),
+        (path = "/v1/api/invoices", api=payment::PaymentApi),
+        (path = "/v1/api/invoices", api=audit::InvoiceHistoryApi),
+        (path = "/v1/api/clients", api=api_client::ApiClientApi)
     )
 )]
 pub struct ApiDoc;
+
+/// Only lets requests through to the handlers in `method_router` if their token carries `scope`
+fn scoped(method_router: MethodRouter<AppContext>, scope: Scope) -> MethodRouter<AppContext> {
+    method_router.route_layer(Extension(RequiredScope(scope)))
+}
+
 pub fn person_routes() -> Router<AppContext> {
     Router::new()
         .route(
             "/",
-            axum::routing::get(person::get_persons).post(person::create_person),
+            scoped(get(person::get_persons), Scope::PersonsRead)
+                .merge(scoped(post(person::create_person), Scope::PersonsWrite)),
         )
         .layer(
             CorsLayer::new()
@@ -28,12 +44,31 @@ pub fn person_routes() -> Router<AppContext> {
         )
         .route(
             "/:id",
-            axum::routing::get(person::get_person_by_id).delete(person::delete_person),
+            scoped(get(person::get_person_by_id), Scope::PersonsRead).merge(scoped(
+                delete(person::delete_person)
+                    .put(person::update_person)
+                    .patch(person::patch_person),
+                Scope::PersonsWrite,
+            )),
+        )
+        .route(
+            "/:id/history",
+            scoped(get(audit::get_person_history), Scope::PersonsRead),
+        )
+        .route(
+            "/:id/restore",
+            scoped(post(person::restore_person), Scope::PersonsWrite),
         )
         .layer(
             CorsLayer::new()
                 .allow_origin("*".parse::<HeaderValue>().unwrap())
-                .allow_methods([Method::GET, Method::DELETE]),
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::DELETE,
+                    Method::PUT,
+                    Method::PATCH,
+                ]),
         )
 }
 
@@ -41,7 +76,8 @@ pub fn item_routes() -> Router<AppContext> {
     Router::new()
         .route(
             "/",
-            axum::routing::get(item::get_items).post(item::create_item),
+            scoped(get(item::get_items), Scope::ItemsRead)
+                .merge(scoped(post(item::create_item), Scope::ItemsWrite)),
         )
         .layer(
             CorsLayer::new()
@@ -50,14 +86,47 @@ pub fn item_routes() -> Router<AppContext> {
         )
         .route(
             "/:id",
-            axum::routing::get(item::get_item_by_id)
-                .delete(item::delete_item)
-                .put(item::update_item),
+            scoped(get(item::get_item_by_id), Scope::ItemsRead).merge(scoped(
+                delete(item::delete_item)
+                    .put(item::update_item)
+                    .patch(item::patch_item),
+                Scope::ItemsWrite,
+            )),
+        )
+        .route(
+            "/:id/stock",
+            scoped(get(stock::get_item_stock), Scope::ItemsRead),
+        )
+        .route(
+            "/:id/history",
+            scoped(get(audit::get_item_history), Scope::ItemsRead),
+        )
+        .route(
+            "/:id/restore",
+            scoped(post(item::restore_item), Scope::ItemsWrite),
         )
         .layer(
             CorsLayer::new()
                 .allow_origin("*".parse::<HeaderValue>().unwrap())
-                .allow_methods([Method::GET, Method::DELETE]),
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::DELETE,
+                    Method::PUT,
+                    Method::PATCH,
+                ]),
+        )
+        .route(
+            "/:id/stock/movements",
+            scoped(get(stock::get_stock_movements), Scope::ItemsRead).merge(scoped(
+                post(stock::create_stock_movement),
+                Scope::ItemsWrite,
+            )),
+        )
+        .layer(
+            CorsLayer::new()
+                .allow_origin("*".parse::<HeaderValue>().unwrap())
+                .allow_methods([Method::GET, Method::POST]),
         )
 }
 
@@ -65,7 +134,8 @@ fn invoice_routes() -> Router<AppContext> {
     Router::new()
         .route(
             "/",
-            axum::routing::get(invoice::get_invoices).post(invoice::create_invoice),
+            scoped(get(invoice::get_invoices), Scope::InvoicesRead)
+                .merge(scoped(post(invoice::create_invoice), Scope::InvoicesWrite)),
         )
         .layer(
             CorsLayer::new()
@@ -74,26 +144,87 @@ fn invoice_routes() -> Router<AppContext> {
         )
         .route(
             "/:id",
-            axum::routing::get(invoice::get_invoice_by_id)
-                .delete(invoice::delete_invoice)
-                .put(invoice::update_invoice),
+            scoped(get(invoice::get_invoice_by_id), Scope::InvoicesRead).merge(scoped(
+                delete(invoice::delete_invoice)
+                    .put(invoice::update_invoice)
+                    .patch(invoice::patch_invoice),
+                Scope::InvoicesWrite,
+            )),
         )
         .route(
             "/:id/items",
-            axum::routing::post(invoice::add_invoice_items),
+            scoped(post(invoice::add_invoice_items), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/history",
+            scoped(get(audit::get_invoice_history), Scope::InvoicesRead),
+        )
+        .route(
+            "/:id/restore",
+            scoped(post(invoice::restore_invoice), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/issue",
+            scoped(post(invoice::issue_invoice), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/pay",
+            scoped(post(invoice::pay_invoice), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/void",
+            scoped(post(invoice::void_invoice), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/refund",
+            scoped(post(invoice::refund_invoice), Scope::InvoicesWrite),
+        )
+        .route(
+            "/:id/payments",
+            scoped(get(payment::get_payments), Scope::InvoicesRead)
+                .merge(scoped(post(payment::create_payment), Scope::InvoicesWrite)),
         )
         .route(
             "/:invoice_id/items/:item_id",
-            axum::routing::delete(invoice::remove_invoice_item),
+            scoped(delete(invoice::remove_invoice_item), Scope::InvoicesWrite),
         )
         .route(
             "/users/:id",
-            axum::routing::get(invoice::get_invoices_by_user),
+            scoped(get(invoice::get_invoices_by_user), Scope::InvoicesRead),
         )
         .layer(
             CorsLayer::new()
                 .allow_origin("*".parse::<HeaderValue>().unwrap())
-                .allow_methods([Method::GET, Method::DELETE]),
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::DELETE,
+                    Method::PUT,
+                    Method::PATCH,
+                ]),
+        )
+}
+
+fn api_client_routes() -> Router<AppContext> {
+    Router::new()
+        .route(
+            "/",
+            get(api_client::get_api_clients).post(api_client::create_api_client),
+        )
+        .route("/:client_id", delete(api_client::revoke_api_client))
+        .route(
+            "/:client_id/secret",
+            post(api_client::rotate_api_client_secret),
+        )
+        .route(
+            "/:client_id/tokens",
+            delete(api_client::revoke_api_client_tokens),
+        )
+        .route_layer(Extension(RequiredScope(Scope::ClientsAdmin)))
+        .layer(
+            CorsLayer::new()
+                .allow_origin("*".parse::<HeaderValue>().unwrap())
+                .allow_methods([Method::GET, Method::POST, Method::DELETE]),
         )
 }
 
@@ -109,6 +240,7 @@ fn all_routes() -> Router<AppContext> {
         .nest("/persons", person_routes())
         .nest("/items", item_routes())
         .nest("/invoices", invoice_routes())
+        .nest("/clients", api_client_routes())
 }
 
 fn v1_routes() -> Router<AppContext> {
@@ -123,18 +255,34 @@ pub(crate) fn api_routes_with_status_routes() -> Router<AppContext> {
 
 #[cfg(test)]
 mod tests {
+    use crate::inventory::model::money::{Currency, Money};
+    use crate::inventory::model::page::Page;
+    use crate::inventory::model::sort::{PersonSortField, SortKey};
     use crate::inventory::model::{
-        CreateInvoiceRequest, CreateItemRequest, CreatePersonRequest, DeleteResults,
-        InvoiceItemRequest, Item, Person, UpdateInvoiceRequest, UpdateItemRequest,
+        AuditResource, CreateInvoiceRequest, CreateItemRequest, CreatePaymentRequest,
+        CreatePersonRequest, CreateStockMovementRequest, DeleteResults, Invoice,
+        InvoiceItemRequest, InvoiceStatus, Item, MovementType, Payment, Person, Scope, StockLevel,
+        StockMovement, UpdateInvoiceRequest, UpdateItemRequest, UpdatePersonRequest,
     };
+    use crate::inventory::model::{ExpectedVersion, IdempotentResponse};
     use crate::inventory::routes::{api_routes_with_status_routes, item_routes, person_routes};
+    use crate::inventory::services::audit::MockAuditService;
+    use crate::inventory::services::idempotency::MockIdempotencyService;
     use crate::inventory::services::invoice::MockInvoiceService;
     use crate::inventory::services::item::MockItemService;
+    use crate::inventory::services::payment::MockPaymentService;
     use crate::inventory::services::person::MockPersonService;
-    use crate::test_helpers::{mock_token, test_app_context};
+    use crate::inventory::services::stock::MockStockService;
+    use crate::inventory::services::ServiceError;
+    use crate::test_helpers::{
+        body_to_string, first_invoice_uuid, first_item_uuid, first_person_uuid, mock_token,
+        mock_token_with_scopes, test_app_context,
+    };
+    use crate::AppContext;
     use axum::body::Body;
     use axum::http::Request;
     use axum::{http, Router};
+    use std::sync::Arc;
     use tower::ServiceExt;
     use uuid::Uuid;
 
@@ -165,6 +313,35 @@ mod tests {
         app(mock_person_service, mock_item_service, mock_invoice_service).await
     }
 
+    async fn app_with_live_mock_idempotency_service(
+        mock_item_service: MockItemService,
+        mock_idempotency_service: MockIdempotencyService,
+    ) -> Router {
+        Router::new()
+            .nest("/items", item_routes())
+            .with_state(AppContext {
+                idempotency_service: Arc::new(mock_idempotency_service),
+                ..test_app_context(
+                    MockPersonService::new(),
+                    mock_item_service,
+                    MockInvoiceService::new(),
+                )
+            })
+    }
+
+    fn create_item_request_with_key(key: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/items")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .header("Idempotency-Key", key)
+            .method(http::Method::POST)
+            .body(Body::from(
+                serde_json::to_string(&CreateItemRequest::default()).unwrap(),
+            ))
+            .unwrap()
+    }
+
     async fn app_v1(
         mock_person_service: MockPersonService,
         mock_item_service: MockItemService,
@@ -199,12 +376,54 @@ mod tests {
         app_v1(mock_person_service, mock_item_service, mock_invoice_service).await
     }
 
+    async fn app_v1_with_live_mock_stock_service(mock_stock_service: MockStockService) -> Router {
+        api_routes_with_status_routes().with_state(AppContext {
+            stock_service: Arc::new(mock_stock_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        })
+    }
+
+    async fn app_v1_with_live_mock_payment_service(
+        mock_payment_service: MockPaymentService,
+    ) -> Router {
+        api_routes_with_status_routes().with_state(AppContext {
+            payment_service: Arc::new(mock_payment_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        })
+    }
+
+    async fn app_v1_with_live_mock_audit_service(mock_audit_service: MockAuditService) -> Router {
+        api_routes_with_status_routes().with_state(AppContext {
+            audit_service: Arc::new(mock_audit_service),
+            ..test_app_context(
+                MockPersonService::new(),
+                MockItemService::new(),
+                MockInvoiceService::new(),
+            )
+        })
+    }
+
     #[tokio::test]
     async fn test_person_routes_get_all() {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_get_persons()
-            .returning(|_, _| Box::pin(async move { Ok(vec![Person::default()]) }));
+            .returning(|_, _, _, _| {
+                Box::pin(async move {
+                    Ok(Page {
+                        items: vec![Person::default()],
+                        ..Page::default()
+                    })
+                })
+            });
         let app = app_with_live_mock_person_service(mock_person_service).await;
         let request = Request::builder()
             .uri("/persons")
@@ -216,12 +435,57 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_person_routes_get_all_sorted() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_get_persons()
+            .withf(|_, sort, _, _| {
+                sort.keys()
+                    == [
+                        SortKey {
+                            field: PersonSortField::Name,
+                            descending: false,
+                        },
+                        SortKey {
+                            field: PersonSortField::CreatedAt,
+                            descending: true,
+                        },
+                    ]
+            })
+            .returning(|_, _, _, _| Box::pin(async move { Ok(Page::default()) }));
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri("/persons?sort=name,-created_at")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_person_routes_get_all_unknown_sort_field() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service.expect_get_persons().never();
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri("/persons?sort=-password")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_person_routes_get_by_id() {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_get_person()
-            .returning(|_| Box::pin(async move { Ok(Person::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Person::default()) }));
         let app = app_with_live_mock_person_service(mock_person_service).await;
         let request = Request::builder()
             .uri("/persons/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
@@ -238,7 +502,7 @@ mod tests {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_create_person()
-            .returning(|_| Box::pin(async move { Ok(Person::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Person::default()) }));
         let new_person = CreatePersonRequest::default();
         let app = app_with_live_mock_person_service(mock_person_service).await;
         let request = Request::builder()
@@ -252,12 +516,105 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_person_routes_create_ignores_audit_fields_in_body() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_create_person()
+            .withf(|person, created_by| person.created_by.is_empty() && created_by == "foo")
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(Person::default()) }));
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri("/persons")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(
+                r#"{"name":"Mallory","email":"mallory@example.com","created_by":"alice"}"#,
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_person_routes_update() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_update_person()
+            .withf(|request, actor| {
+                request.version == ExpectedVersion::OneOf(vec![1]) && actor == "foo"
+            })
+            .returning(|_, _| {
+                Box::pin(async move {
+                    Ok(Person {
+                        version: 2,
+                        ..Person::default()
+                    })
+                })
+            });
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let uuid = Uuid::new_v4();
+        let update_person_request = UpdatePersonRequest {
+            id: uuid.to_string(),
+            name: "Updated Person".to_string(),
+            email: "updated@example.com".to_string(),
+            changed_by: String::new(),
+            version: ExpectedVersion::Any,
+        };
+        let request = Request::builder()
+            .uri(format!("/persons/{}", uuid))
+            .method(http::Method::PUT)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"1\"")
+            .body(Body::from(
+                serde_json::to_string(&update_person_request).unwrap(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers()[http::header::ETAG], "\"2\"");
+    }
+
+    #[tokio::test]
+    async fn test_person_routes_patch() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_patch_person()
+            .withf(|_, patch, version, _| {
+                patch["email"] == "patched@example.com"
+                    && *version == ExpectedVersion::OneOf(vec![1])
+            })
+            .returning(|_, _, _, _| {
+                Box::pin(async move {
+                    Ok(Person {
+                        version: 2,
+                        ..Person::default()
+                    })
+                })
+            });
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri(format!("/persons/{}", Uuid::new_v4()))
+            .method(http::Method::PATCH)
+            .header(http::header::CONTENT_TYPE, "application/merge-patch+json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"1\"")
+            .body(Body::from(r#"{"email": "patched@example.com"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers()[http::header::ETAG], "\"2\"");
+    }
+
     #[tokio::test]
     async fn test_person_routes_delete() {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_delete_person()
-            .returning(|_| Box::pin(async move { Ok(()) }));
+            .returning(|_, _| Box::pin(async move { Ok(()) }));
 
         let app = app_with_live_mock_person_service(mock_person_service).await;
         let request = Request::builder()
@@ -270,12 +627,76 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_person_routes_delete_without_write_scope() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service.expect_delete_person().never();
+
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri("/persons/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
+            .method(http::Method::DELETE)
+            .header(
+                http::header::AUTHORIZATION,
+                mock_token_with_scopes(vec![Scope::PersonsRead]),
+            )
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_person_routes_get_by_id_with_read_scope() {
+        let mut mock_person_service = MockPersonService::new();
+        mock_person_service
+            .expect_get_person()
+            .returning(|_, _| Box::pin(async move { Ok(Person::default()) }));
+        let app = app_with_live_mock_person_service(mock_person_service).await;
+        let request = Request::builder()
+            .uri("/persons/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
+            .header(
+                http::header::AUTHORIZATION,
+                mock_token_with_scopes(vec![Scope::PersonsRead]),
+            )
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_clients_route_requires_admin_scope() {
+        let app = app_v1(
+            MockPersonService::new(),
+            MockItemService::new(),
+            MockInvoiceService::new(),
+        )
+        .await;
+        let request = Request::builder()
+            .uri("/api/v1/clients")
+            .header(
+                http::header::AUTHORIZATION,
+                mock_token_with_scopes(vec![
+                    Scope::PersonsWrite,
+                    Scope::ItemsWrite,
+                    Scope::InvoicesWrite,
+                ]),
+            )
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
     #[tokio::test]
     async fn test_api_v1_get_person_by_id_route() {
         let mut mock_person_service = MockPersonService::new();
         mock_person_service
             .expect_get_person()
-            .returning(|_| Box::pin(async move { Ok(Person::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Person::default()) }));
 
         let app = app_v1_with_live_mock_person_service(mock_person_service).await;
         let request = Request::builder()
@@ -293,7 +714,7 @@ mod tests {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_all_items()
-            .returning(|_| Box::pin(async move { Ok(vec![]) }));
+            .returning(|_, _, _, _| Box::pin(async move { Ok(Page::default()) }));
         let app = app_v1_with_live_mock_item_service(mock_item_service).await;
         let request = Request::builder()
             .uri("/api/v1/items")
@@ -310,7 +731,7 @@ mod tests {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_all_items()
-            .returning(|_| Box::pin(async move { Ok(vec![]) }));
+            .returning(|_, _, _, _| Box::pin(async move { Ok(Page::default()) }));
         let app = app_with_live_mock_item_service(mock_item_service).await;
         let request = Request::builder()
             .uri("/items")
@@ -322,12 +743,54 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_search_items_route() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_get_all_items().never();
+        mock_item_service
+            .expect_search_items()
+            .withf(|search, pagination, _, include_deleted, _| {
+                search.q.as_deref() == Some("blue widget")
+                    && search.min_price == Some("10.50".parse().unwrap())
+                    && search.created_by.as_deref() == Some("warehouse")
+                    && pagination
+                        .as_ref()
+                        .is_some_and(|pagination| pagination.page_size == Some(5))
+                    && !include_deleted
+            })
+            .returning(|_, _, _, _, _| Box::pin(async move { Ok(Page::default()) }));
+        let app = app_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri("/items?q=blue%20widget&min_price=10.50&created_by=warehouse&page_size=5")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_search_items_route_invalid_price() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_search_items().never();
+        let app = app_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri("/items?max_price=cheap")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn test_create_item_route() {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_create_item()
-            .returning(|_| Box::pin(async move { Ok(Item::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Item::default()) }));
         let app = app_with_live_mock_item_service(mock_item_service).await;
         let create_item_request = CreateItemRequest::default();
         let request = Request::builder()
@@ -348,7 +811,15 @@ mod tests {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_update_item()
-            .returning(|_| Box::pin(async move { Ok(Item::default()) }));
+            .withf(|request, _| request.version == ExpectedVersion::OneOf(vec![3]))
+            .returning(|_, _| {
+                Box::pin(async move {
+                    Ok(Item {
+                        version: 4,
+                        ..Item::default()
+                    })
+                })
+            });
         let app = app_with_live_mock_item_service(mock_item_service).await;
         let mut update_item_request = UpdateItemRequest::default();
         let uuid = Uuid::new_v4();
@@ -357,6 +828,7 @@ mod tests {
             .uri(format!("/items/{}", uuid.clone()))
             .header(http::header::CONTENT_TYPE, "application/json")
             .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"3\"")
             .method(http::Method::PUT)
             .body(Body::from(
                 serde_json::to_string(&update_item_request).unwrap(),
@@ -364,6 +836,175 @@ mod tests {
             .unwrap();
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers()[http::header::ETAG], "\"4\"");
+    }
+
+    #[tokio::test]
+    async fn test_update_item_route_requires_if_match() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_update_item().never();
+        let app = app_with_live_mock_item_service(mock_item_service).await;
+        let mut update_item_request = UpdateItemRequest::default();
+        let uuid = Uuid::new_v4();
+        update_item_request.id = uuid.to_string();
+        let request = Request::builder()
+            .uri(format!("/items/{}", uuid.clone()))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::PUT)
+            .body(Body::from(
+                serde_json::to_string(&update_item_request).unwrap(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn test_patch_item_route_requires_if_match() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_patch_item().never();
+        let app = app_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri(format!("/items/{}", Uuid::new_v4()))
+            .header(http::header::CONTENT_TYPE, "application/merge-patch+json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::PATCH)
+            .body(Body::from(r#"{"name": "Patched Item"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_patch_invoice_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service
+            .expect_patch_invoice()
+            .withf(|_, patch, version, _| {
+                patch["tax_rate"] == "0.2" && *version == ExpectedVersion::OneOf(vec![3])
+            })
+            .returning(|_, _, _, _| Box::pin(async move { Ok(Invoice::default()) }));
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let request = Request::builder()
+            .uri(format!("/api/v1/invoices/{}", Uuid::new_v4()))
+            .header(http::header::CONTENT_TYPE, "application/merge-patch+json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"3\"")
+            .method(http::Method::PATCH)
+            .body(Body::from(r#"{"tax_rate": "0.2"}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_create_item_route_stores_response_for_idempotency_key() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service
+            .expect_create_item()
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(Item::default()) }));
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service
+            .expect_begin()
+            .withf(|claim| claim.client_id == "foo" && claim.key == "key-1")
+            .returning(|_| Box::pin(async { Ok(None) }));
+        mock_idempotency_service
+            .expect_complete()
+            .withf(|claim, response| claim.key == "key-1" && response.status == 200)
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(()) }));
+        mock_idempotency_service.expect_release().never();
+        let app =
+            app_with_live_mock_idempotency_service(mock_item_service, mock_idempotency_service)
+                .await;
+        let response = app
+            .oneshot(create_item_request_with_key("key-1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert!(response.headers().get("idempotent-replayed").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_item_route_replays_response_for_idempotency_key() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_create_item().never();
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service.expect_begin().returning(|_| {
+            Box::pin(async {
+                Ok(Some(IdempotentResponse {
+                    status: 200,
+                    headers: vec![("content-type".to_string(), "application/json".to_string())],
+                    body: br#"{"name":"replayed"}"#.to_vec(),
+                }))
+            })
+        });
+        mock_idempotency_service.expect_complete().never();
+        let app =
+            app_with_live_mock_idempotency_service(mock_item_service, mock_idempotency_service)
+                .await;
+        let response = app
+            .oneshot(create_item_request_with_key("key-1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers()["idempotent-replayed"], "true");
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+        let body = body_to_string(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"name":"replayed"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_create_item_route_releases_idempotency_key_on_failure() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_create_item().returning(|_, _| {
+            Box::pin(async { Err(ServiceError::InputValidationError("bad".to_string())) })
+        });
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service
+            .expect_begin()
+            .returning(|_| Box::pin(async { Ok(None) }));
+        mock_idempotency_service.expect_complete().never();
+        mock_idempotency_service
+            .expect_release()
+            .withf(|claim| claim.client_id == "foo" && claim.key == "key-1")
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(()) }));
+        let app =
+            app_with_live_mock_idempotency_service(mock_item_service, mock_idempotency_service)
+                .await;
+        let response = app
+            .oneshot(create_item_request_with_key("key-1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_create_item_route_with_reused_idempotency_key() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_create_item().never();
+        let mut mock_idempotency_service = MockIdempotencyService::new();
+        mock_idempotency_service.expect_begin().returning(|_| {
+            Box::pin(async {
+                Err(ServiceError::IdempotencyKeyReused(
+                    "used for another request".to_string(),
+                ))
+            })
+        });
+        let app =
+            app_with_live_mock_idempotency_service(mock_item_service, mock_idempotency_service)
+                .await;
+        let response = app
+            .oneshot(create_item_request_with_key("key-1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
     }
 
     #[tokio::test]
@@ -371,7 +1012,7 @@ mod tests {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_delete_item()
-            .returning(|_| Box::pin(async move { Ok(DeleteResults::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(DeleteResults::default()) }));
         let app = app_with_live_mock_item_service(mock_item_service).await;
         let uuid = Uuid::new_v4();
         let request = Request::builder()
@@ -386,13 +1027,37 @@ mod tests {
 
     #[tokio::test]
     async fn test_api_v1_get_item_by_id_route() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_get_item_by_id().returning(|_, _| {
+            Box::pin(async move {
+                Ok(Item {
+                    version: 2,
+                    ..Item::default()
+                })
+            })
+        });
+        let app = app_v1_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri("/api/v1/items/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.headers()[http::header::ETAG], "\"2\"");
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_get_deleted_item_by_id_route() {
         let mut mock_item_service = MockItemService::new();
         mock_item_service
             .expect_get_item_by_id()
-            .returning(|_| Box::pin(async move { Ok(Item::default()) }));
+            .withf(|_, include_deleted| *include_deleted)
+            .returning(|_, _| Box::pin(async move { Ok(Item::default()) }));
         let app = app_v1_with_live_mock_item_service(mock_item_service).await;
         let request = Request::builder()
-            .uri("/api/v1/items/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
+            .uri("/api/v1/items/2b1b425e-dee2-4227-8d94-f470a0ce0cd0?include_deleted=true")
             .header(http::header::AUTHORIZATION, mock_token())
             .method(http::Method::GET)
             .body(Body::empty())
@@ -401,6 +1066,42 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_api_v1_restore_item_route() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service
+            .expect_restore_item()
+            .withf(|_, restored_by| restored_by == "foo")
+            .returning(|_, _| Box::pin(async move { Ok(Item::default()) }));
+        let app = app_v1_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri("/api/v1/items/2b1b425e-dee2-4227-8d94-f470a0ce0cd0/restore")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_restore_item_route_requires_write_scope() {
+        let mut mock_item_service = MockItemService::new();
+        mock_item_service.expect_restore_item().never();
+        let app = app_v1_with_live_mock_item_service(mock_item_service).await;
+        let request = Request::builder()
+            .uri("/api/v1/items/2b1b425e-dee2-4227-8d94-f470a0ce0cd0/restore")
+            .header(
+                http::header::AUTHORIZATION,
+                mock_token_with_scopes(vec![Scope::ItemsRead]),
+            )
+            .method(http::Method::POST)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
     #[tokio::test]
     async fn test_update_item_mismatched_ids() {
         let mock_item_service = MockItemService::new();
@@ -412,6 +1113,7 @@ mod tests {
             .uri(format!("/items/{}", Uuid::new_v4()))
             .header(http::header::CONTENT_TYPE, "application/json")
             .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"1\"")
             .method(http::Method::PUT)
             .body(Body::from(
                 serde_json::to_string(&update_item_request).unwrap(),
@@ -426,7 +1128,7 @@ mod tests {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_get_invoice()
-            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request = Request::builder()
             .uri("/api/v1/invoices/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
@@ -443,7 +1145,7 @@ mod tests {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_get_invoices_for_user()
-            .returning(|_| Box::pin(async move { Ok(vec![]) }));
+            .returning(|_, _, _, _, _, _| Box::pin(async move { Ok(Page::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request = Request::builder()
             .uri("/api/v1/invoices/users/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
@@ -460,7 +1162,7 @@ mod tests {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_create_invoice()
-            .returning(|_| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let create_request = CreateInvoiceRequest::default();
         let request = Request::builder()
@@ -479,7 +1181,7 @@ mod tests {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_delete_invoice()
-            .returning(|_| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request = Request::builder()
             .uri("/api/v1/invoices/2b1b425e-dee2-4227-8d94-f470a0ce0cd0")
@@ -491,16 +1193,74 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_api_v1_invoice_status_routes() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service
+            .expect_issue_invoice()
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(Invoice::default()) }));
+        mock_invoice_service
+            .expect_pay_invoice()
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(Invoice::default()) }));
+        mock_invoice_service
+            .expect_void_invoice()
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(Invoice::default()) }));
+        mock_invoice_service
+            .expect_refund_invoice()
+            .times(1)
+            .withf(|_, changed_by| changed_by == "foo")
+            .returning(|_, _| Box::pin(async move { Ok(Invoice::default()) }));
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        for action in ["issue", "pay", "void", "refund"] {
+            let request = Request::builder()
+                .uri(format!(
+                    "/api/v1/invoices/2b1b425e-dee2-4227-8d94-f470a0ce0cd0/{}",
+                    action
+                ))
+                .header(http::header::AUTHORIZATION, mock_token())
+                .method(http::Method::POST)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), http::StatusCode::OK, "{}", action);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_pay_draft_invoice_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service.expect_pay_invoice().returning(|_, _| {
+            Box::pin(async move {
+                Err(ServiceError::InvalidStatus(
+                    "invoice is draft and cannot be moved to paid".to_string(),
+                ))
+            })
+        });
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let request = Request::builder()
+            .uri("/api/v1/invoices/2b1b425e-dee2-4227-8d94-f470a0ce0cd0/pay")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+    }
+
     #[tokio::test]
     async fn test_api_v1_add_invoice_items_route() {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_add_item_to_invoice()
-            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _, _, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request_body = InvoiceItemRequest {
             item_id: Uuid::new_v4(),
             invoice_id: Uuid::new_v4(),
+            quantity: 1,
         };
         let request = Request::builder()
             .uri(format!(
@@ -516,16 +1276,45 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_api_v1_add_invoice_items_currency_mismatch_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service
+            .expect_add_item_to_invoice()
+            .returning(|_, _, _, _| {
+                Box::pin(async move { Err(ServiceError::CurrencyMismatch("EUR".to_string())) })
+            });
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let request_body = InvoiceItemRequest {
+            item_id: Uuid::new_v4(),
+            invoice_id: Uuid::new_v4(),
+            quantity: 1,
+        };
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/invoices/{}/items",
+                request_body.invoice_id
+            ))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(serde_json::to_string(&request_body).unwrap()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
     #[tokio::test]
     async fn test_api_v1_remove_invoice_item_route() {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_remove_item_from_invoice()
-            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request_body = InvoiceItemRequest {
             item_id: Uuid::new_v4(),
             invoice_id: Uuid::new_v4(),
+            quantity: 1,
         };
         let request = Request::builder()
             .uri(format!(
@@ -546,13 +1335,14 @@ mod tests {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_update_invoice()
-            .returning(|_| Box::pin(async move { Ok(Default::default()) }));
+            .returning(|_, _| Box::pin(async move { Ok(Default::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let update_request = UpdateInvoiceRequest::default();
         let request = Request::builder()
             .uri(format!("/api/v1/invoices/{}", update_request.id.clone()))
             .header(http::header::CONTENT_TYPE, "application/json")
             .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"1\"")
             .method(http::Method::PUT)
             .body(Body::from(serde_json::to_string(&update_request).unwrap()))
             .unwrap();
@@ -560,12 +1350,38 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_api_v1_update_invoice_stale_version_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service
+            .expect_update_invoice()
+            .returning(|_, _| {
+                Box::pin(async move {
+                    Err(ServiceError::PreconditionFailed(
+                        "invoice has changed".to_string(),
+                    ))
+                })
+            });
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let update_request = UpdateInvoiceRequest::default();
+        let request = Request::builder()
+            .uri(format!("/api/v1/invoices/{}", update_request.id.clone()))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .header(http::header::IF_MATCH, "\"1\"")
+            .method(http::Method::PUT)
+            .body(Body::from(serde_json::to_string(&update_request).unwrap()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::PRECONDITION_FAILED);
+    }
+
     #[tokio::test]
     async fn test_api_v1_get_invoices_route() {
         let mut mock_invoice_service = MockInvoiceService::new();
         mock_invoice_service
             .expect_list_all_invoices()
-            .returning(|_| Box::pin(async move { Ok(vec![]) }));
+            .returning(|_, _, _, _, _| Box::pin(async move { Ok(Page::default()) }));
         let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
         let request = Request::builder()
             .uri("/api/v1/invoices")
@@ -576,4 +1392,266 @@ mod tests {
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), http::StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn test_api_v1_get_invoices_filtered_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service
+            .expect_list_all_invoices()
+            .withf(|filter, pagination, _, _, _| {
+                filter.status == Some(InvoiceStatus::Issued)
+                    && filter.paid == Some(false)
+                    && filter.created_from == Some("2024-01-01T00:00:00Z".parse().unwrap())
+                    && filter.min_total == Some("99.50".parse().unwrap())
+                    && filter.user_id == Some(first_person_uuid())
+                    && pagination
+                        .as_ref()
+                        .is_none_or(|p| p.cursor.is_none() && p.page_size.is_none())
+            })
+            .returning(|_, _, _, _, _| Box::pin(async move { Ok(Page::default()) }));
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/invoices?status=issued&paid=false&created_from=2024-01-01T00:00:00Z&min_total=99.50&user_id={}",
+                first_person_uuid()
+            ))
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_get_invoices_invalid_status_route() {
+        let mut mock_invoice_service = MockInvoiceService::new();
+        mock_invoice_service.expect_list_all_invoices().never();
+        let app = app_v1_with_live_mock_invoice_service(mock_invoice_service).await;
+        let request = Request::builder()
+            .uri("/api/v1/invoices?status=overdue")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_get_item_stock_route() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service
+            .expect_get_stock()
+            .returning(|_| Box::pin(async move { Ok(StockLevel::default()) }));
+        let app = app_v1_with_live_mock_stock_service(mock_stock_service).await;
+        let request = Request::builder()
+            .uri(format!("/api/v1/items/{}/stock", first_item_uuid()))
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_create_stock_movement_route() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service
+            .expect_record_movement()
+            .returning(|_, _| Box::pin(async move { Ok(StockMovement::default()) }));
+        let app = app_v1_with_live_mock_stock_service(mock_stock_service).await;
+        let movement_request = CreateStockMovementRequest {
+            item_id: first_item_uuid(),
+            movement_type: MovementType::Receipt,
+            quantity: 10,
+            note: None,
+            created_by: "unit_test".to_string(),
+        };
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/items/{}/stock/movements",
+                first_item_uuid()
+            ))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(
+                serde_json::to_string(&movement_request).unwrap(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_create_stock_movement_insufficient_stock_route() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service
+            .expect_record_movement()
+            .returning(|_, _| {
+                Box::pin(async move { Err(ServiceError::InsufficientStock("short".to_string())) })
+            });
+        let app = app_v1_with_live_mock_stock_service(mock_stock_service).await;
+        let movement_request = CreateStockMovementRequest {
+            item_id: first_item_uuid(),
+            movement_type: MovementType::Sale,
+            quantity: 1000,
+            note: None,
+            created_by: "unit_test".to_string(),
+        };
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/items/{}/stock/movements",
+                first_item_uuid()
+            ))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(
+                serde_json::to_string(&movement_request).unwrap(),
+            ))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_get_stock_movements_route() {
+        let mut mock_stock_service = MockStockService::new();
+        mock_stock_service
+            .expect_get_movements()
+            .returning(|_, _| Box::pin(async move { Ok(Page::default()) }));
+        let app = app_v1_with_live_mock_stock_service(mock_stock_service).await;
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/items/{}/stock/movements?page_size=5",
+                first_item_uuid()
+            ))
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_create_payment_route() {
+        let mut mock_payment_service = MockPaymentService::new();
+        mock_payment_service
+            .expect_record_payment()
+            .withf(|_, actor| actor == "foo")
+            .returning(|_, _| Box::pin(async move { Ok(Payment::default()) }));
+        let app = app_v1_with_live_mock_payment_service(mock_payment_service).await;
+        let payment_request = CreatePaymentRequest {
+            invoice_id: first_invoice_uuid(),
+            amount: Money::new("40.00".parse().unwrap(), Currency::USD),
+            ..CreatePaymentRequest::default()
+        };
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/invoices/{}/payments",
+                first_invoice_uuid()
+            ))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(serde_json::to_string(&payment_request).unwrap()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_create_payment_exceeding_balance_route() {
+        let mut mock_payment_service = MockPaymentService::new();
+        mock_payment_service
+            .expect_record_payment()
+            .returning(|_, _| {
+                Box::pin(async move { Err(ServiceError::ExceedsBalance("too much".to_string())) })
+            });
+        let app = app_v1_with_live_mock_payment_service(mock_payment_service).await;
+        let payment_request = CreatePaymentRequest {
+            invoice_id: first_invoice_uuid(),
+            amount: Money::new("5000.00".parse().unwrap(), Currency::USD),
+            ..CreatePaymentRequest::default()
+        };
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/invoices/{}/payments",
+                first_invoice_uuid()
+            ))
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::POST)
+            .body(Body::from(serde_json::to_string(&payment_request).unwrap()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_get_payments_route() {
+        let mut mock_payment_service = MockPaymentService::new();
+        mock_payment_service
+            .expect_get_payments()
+            .returning(|_, _| Box::pin(async move { Ok(Page::default()) }));
+        let app = app_v1_with_live_mock_payment_service(mock_payment_service).await;
+        let request = Request::builder()
+            .uri(format!(
+                "/api/v1/invoices/{}/payments?page_size=5",
+                first_invoice_uuid()
+            ))
+            .header(http::header::AUTHORIZATION, mock_token())
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_history_routes() {
+        for (resource, resource_type) in [
+            ("persons", AuditResource::Person),
+            ("items", AuditResource::Item),
+            ("invoices", AuditResource::Invoice),
+        ] {
+            let mut mock_audit_service = MockAuditService::new();
+            mock_audit_service
+                .expect_get_history()
+                .withf(move |r, _, _| *r == resource_type)
+                .times(1)
+                .returning(|_, _, _| Box::pin(async move { Ok(Page::default()) }));
+            let app = app_v1_with_live_mock_audit_service(mock_audit_service).await;
+            let request = Request::builder()
+                .uri(format!("/api/v1/{}/{}/history", resource, Uuid::new_v4()))
+                .header(http::header::AUTHORIZATION, mock_token())
+                .method(http::Method::GET)
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), http::StatusCode::OK, "{}", resource);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_v1_item_history_route_requires_read_scope() {
+        let mut mock_audit_service = MockAuditService::new();
+        mock_audit_service.expect_get_history().never();
+        let app = app_v1_with_live_mock_audit_service(mock_audit_service).await;
+        let request = Request::builder()
+            .uri(format!("/api/v1/items/{}/history", first_item_uuid()))
+            .header(
+                http::header::AUTHORIZATION,
+                mock_token_with_scopes(vec![Scope::PersonsRead]),
+            )
+            .method(http::Method::GET)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
 }
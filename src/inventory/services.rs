@@ -1,9 +1,22 @@
+use crate::inventory::model::page::{Cursor, Page, Seek, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::inventory::model::sort::{Sort, SortField};
+use crate::inventory::model::Pagination;
 use crate::inventory::repositories::RepoError;
+use chrono::{DateTime, SecondsFormat, Utc};
 use garde::Report;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
 
+pub mod api_client;
+pub mod audit;
+pub mod idempotency;
 pub mod invoice;
 pub mod item;
+pub mod payment;
 pub mod person;
+pub mod stock;
+pub mod token;
 
 #[derive(Debug)]
 pub enum ServiceError {
@@ -11,8 +24,17 @@ pub enum ServiceError {
     InvalidUuid(String),
     UnexpectedError(String),
     UniqueViolation(String),
+    ForeignKeyViolation(String),
     InputValidationError(String),
     InvalidPrice(String),
+    InsufficientStock(String),
+    CurrencyMismatch(String),
+    InvalidStatus(String),
+    ExceedsBalance(String),
+    PreconditionFailed(String),
+    PreconditionRequired(String),
+    IdempotencyKeyInUse(String),
+    IdempotencyKeyReused(String),
     Other(String),
 }
 
@@ -23,6 +45,12 @@ impl From<RepoError> for ServiceError {
             RepoError::InvalidUuid(err) => ServiceError::InvalidUuid(err),
             RepoError::Other(err) => ServiceError::UnexpectedError(err),
             RepoError::UniqueViolation(err) => ServiceError::UniqueViolation(err),
+            RepoError::ForeignKeyViolation(err) => ServiceError::ForeignKeyViolation(err),
+            RepoError::InsufficientStock(err) => ServiceError::InsufficientStock(err),
+            RepoError::CurrencyMismatch(err) => ServiceError::CurrencyMismatch(err),
+            RepoError::InvalidStatus(err) => ServiceError::InvalidStatus(err),
+            RepoError::ExceedsBalance(err) => ServiceError::ExceedsBalance(err),
+            RepoError::VersionMismatch(err) => ServiceError::PreconditionFailed(err),
         }
     }
 }
@@ -33,10 +61,188 @@ impl From<Report> for ServiceError {
     }
 }
 
+/// Applies a JSON Merge Patch (RFC 7396) to `current`: each member of `patch` replaces the same
+/// member of `current`, objects are merged member by member, and a null removes the member
+pub(crate) fn merge_patch<T>(current: &T, patch: Value) -> Result<T, ServiceError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mut document = serde_json::to_value(current)
+        .map_err(|e| ServiceError::UnexpectedError(format!("Failed to patch: {}", e)))?;
+    merge(&mut document, patch);
+    serde_json::from_value(document)
+        .map_err(|e| ServiceError::InputValidationError(format!("Invalid patch: {}", e)))
+}
+
+fn merge(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        unreachable!("target was made an object above");
+    };
+    for (name, value) in patch {
+        if value.is_null() {
+            target.remove(&name);
+        } else {
+            merge(target.entry(name).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Decodes the page a client asked for. One row more than the page is read, so `into_page`
+/// can tell whether another page follows.
+pub(crate) fn seek(maybe_pagination: Option<Pagination>) -> Result<Seek, ServiceError> {
+    let pagination = maybe_pagination.unwrap_or_default();
+    let page_size = pagination.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if page_size < 1 {
+        return Err(ServiceError::InputValidationError(format!(
+            "page_size must be at least 1, not {}",
+            page_size
+        )));
+    }
+    let after = pagination
+        .cursor
+        .as_deref()
+        .map(Cursor::decode)
+        .transpose()
+        .map_err(ServiceError::InputValidationError)?;
+    Ok(Seek {
+        after,
+        limit: page_size.min(MAX_PAGE_SIZE) + 1,
+    })
+}
+
+/// Like `seek`, for a list read in `sort` order. The cursor must have come from a page sorted the
+/// same way, and hold a value of the right type for each key.
+pub(crate) fn sorted_seek<F: SortField>(
+    maybe_pagination: Option<Pagination>,
+    sort: &Sort<F>,
+) -> Result<Seek, ServiceError> {
+    let seek = seek(maybe_pagination)?;
+    if let Some(after) = &seek.after {
+        let fits = after.sort == sort.to_string()
+            && after.keys.len() == sort.keys().len()
+            && sort
+                .keys()
+                .iter()
+                .zip(&after.keys)
+                .all(|(key, value)| key.field.sort_type().accepts(value));
+        if !fits {
+            return Err(ServiceError::InputValidationError(
+                "Invalid cursor: it is from a list sorted differently".to_string(),
+            ));
+        }
+    }
+    Ok(seek)
+}
+
+/// The cursor after row `id` of a list in `sort` order, `value` giving the row's value of a field
+pub(crate) fn sort_cursor<F: SortField>(
+    id: i32,
+    sort: &Sort<F>,
+    value: impl Fn(F) -> String,
+) -> Cursor {
+    Cursor {
+        keys: sort.keys().iter().map(|key| value(key.field)).collect(),
+        sort: sort.to_string(),
+        ..Cursor::after_id(id)
+    }
+}
+
+/// A timestamp as a sort key, to the microsecond Postgres keeps so it compares equal to the row
+pub(crate) fn timestamp_key(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Builds the page for `rows` read with `seek`, pointing the next page after the last row kept
+pub(crate) fn into_page<R, T>(
+    mut rows: Vec<R>,
+    seek: &Seek,
+    cursor: impl Fn(&R) -> Cursor,
+    total: Option<i64>,
+) -> Page<T>
+where
+    T: From<R>,
+{
+    let has_more = rows.len() as i64 >= seek.limit;
+    rows.truncate(seek.limit as usize - 1);
+    let next_cursor = if has_more {
+        rows.last().map(|row| cursor(row).encode())
+    } else {
+        None
+    };
+    Page {
+        items: rows.into_iter().map(T::from).collect(),
+        next_cursor,
+        has_more,
+        total,
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::inventory::model::page::{Cursor, Seek};
+    use crate::inventory::model::sort::{PersonSortField, Sort};
+    use crate::inventory::model::Pagination;
     use crate::inventory::repositories::RepoError;
-    use crate::inventory::services::ServiceError;
+    use crate::inventory::services::{
+        into_page, merge_patch, seek, sort_cursor, sorted_seek, ServiceError,
+    };
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Patched {
+        name: String,
+        price: Price,
+        note: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Price {
+        amount: String,
+        currency: String,
+    }
+
+    fn patched() -> Patched {
+        Patched {
+            name: "Widget".to_string(),
+            price: Price {
+                amount: "1.00".to_string(),
+                currency: "USD".to_string(),
+            },
+            note: Some("fragile".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let result = merge_patch(
+            &patched(),
+            json!({"price": {"amount": "2.00"}, "note": null, "unknown": 1}),
+        )
+        .unwrap();
+        assert_eq!(result.name, "Widget");
+        assert_eq!(result.price.amount, "2.00");
+        assert_eq!(result.price.currency, "USD");
+        assert_eq!(result.note, None);
+
+        // an empty patch changes nothing
+        assert_eq!(merge_patch(&patched(), json!({})).unwrap(), patched());
+    }
+
+    #[test]
+    fn test_merge_patch_removing_required_member() {
+        let result = merge_patch(&patched(), json!({"name": null}));
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        let result = merge_patch(&patched(), json!(["not", "an", "object"]));
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
 
     #[test]
     fn test_from_repo_error() {
@@ -64,5 +270,133 @@ mod tests {
             ServiceError::UniqueViolation(err) => assert_eq!(err, "Unique violation"),
             _ => panic!("Expected UniqueViolation"),
         }
+        let repo_error = RepoError::ForeignKeyViolation("Foreign key violation".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::ForeignKeyViolation(err) => assert_eq!(err, "Foreign key violation"),
+            _ => panic!("Expected ForeignKeyViolation"),
+        }
+        let repo_error = RepoError::InsufficientStock("Insufficient stock".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::InsufficientStock(err) => assert_eq!(err, "Insufficient stock"),
+            _ => panic!("Expected InsufficientStock"),
+        }
+        let repo_error = RepoError::CurrencyMismatch("Currency mismatch".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::CurrencyMismatch(err) => assert_eq!(err, "Currency mismatch"),
+            _ => panic!("Expected CurrencyMismatch"),
+        }
+        let repo_error = RepoError::InvalidStatus("Invalid status".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::InvalidStatus(err) => assert_eq!(err, "Invalid status"),
+            _ => panic!("Expected InvalidStatus"),
+        }
+        let repo_error = RepoError::ExceedsBalance("Exceeds balance".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::ExceedsBalance(err) => assert_eq!(err, "Exceeds balance"),
+            _ => panic!("Expected ExceedsBalance"),
+        }
+        let repo_error = RepoError::VersionMismatch("Version mismatch".to_string());
+        let service_error = ServiceError::from(repo_error);
+        match service_error {
+            ServiceError::PreconditionFailed(err) => assert_eq!(err, "Version mismatch"),
+            _ => panic!("Expected PreconditionFailed"),
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let first_page = seek(None).unwrap();
+        assert_eq!(
+            first_page,
+            Seek {
+                after: None,
+                limit: 11
+            }
+        );
+        let next_page = seek(Some(Pagination {
+            cursor: Some(Cursor::after_id(42).encode()),
+            page_size: Some(1000),
+        }))
+        .unwrap();
+        assert_eq!(next_page.after, Some(Cursor::after_id(42)));
+        assert_eq!(next_page.limit, 101);
+    }
+
+    #[test]
+    fn test_seek_invalid() {
+        for pagination in [
+            Pagination {
+                cursor: Some("42".to_string()),
+                page_size: None,
+            },
+            Pagination {
+                cursor: None,
+                page_size: Some(0),
+            },
+        ] {
+            let result = seek(Some(pagination));
+            assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        }
+    }
+
+    #[test]
+    fn test_sorted_seek() {
+        let sort: Sort<PersonSortField> = "-created_at,name".parse().unwrap();
+        let cursor = sort_cursor(42, &sort, |field| match field {
+            PersonSortField::CreatedAt => "2024-01-01T00:00:00.000000Z".to_string(),
+            _ => "Ann".to_string(),
+        });
+        let pagination = |cursor: &Cursor| Pagination {
+            cursor: Some(cursor.encode()),
+            page_size: None,
+        };
+        let seek = sorted_seek(Some(pagination(&cursor)), &sort).unwrap();
+        assert_eq!(seek.after, Some(cursor.clone()));
+
+        // a cursor from a list sorted some other way is rejected
+        let other: Sort<PersonSortField> = "name,-created_at".parse().unwrap();
+        let result = sorted_seek(Some(pagination(&cursor)), &other);
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        let result = sorted_seek(
+            Some(pagination(&cursor)),
+            &Sort::<PersonSortField>::default(),
+        );
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+
+        // including one whose keys have the same types, so they'd be compared with other columns
+        let sort: Sort<PersonSortField> = "created_at".parse().unwrap();
+        let cursor = sort_cursor(42, &sort, |_| "2024-01-01T00:00:00.000000Z".to_string());
+        let other: Sort<PersonSortField> = "last_update".parse().unwrap();
+        let result = sorted_seek(Some(pagination(&cursor)), &other);
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        let other: Sort<PersonSortField> = "-created_at".parse().unwrap();
+        let result = sorted_seek(Some(pagination(&cursor)), &other);
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[test]
+    fn test_into_page() {
+        let seek = Seek {
+            after: None,
+            limit: 3,
+        };
+        let page = into_page::<i32, i64>(vec![1, 2, 3], &seek, |id| Cursor::after_id(*id), None);
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.has_more);
+        assert_eq!(
+            Cursor::decode(&page.next_cursor.unwrap()).unwrap(),
+            Cursor::after_id(2)
+        );
+
+        let page = into_page::<i32, i64>(vec![1, 2], &seek, |id| Cursor::after_id(*id), Some(2));
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.total, Some(2));
     }
 }
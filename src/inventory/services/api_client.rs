@@ -0,0 +1,463 @@
+use crate::inventory::model::{
+    ApiClient, ApiClientCredentials, AuditInfo, CreateApiClientRequest, Scope,
+};
+use crate::inventory::repositories::api_client::{ApiClientRepository, ApiClientRow};
+use crate::inventory::repositories::RepoError;
+use crate::inventory::services::ServiceError;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use garde::Validate;
+use mockall::automock;
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::instrument;
+
+const CLIENT_SECRET_LENGTH: usize = 40;
+
+/// Checked against when the client id doesn't exist, so an unknown id costs the same argon2
+/// verification as a wrong secret and the response time doesn't give away which ids exist
+static DUMMY_SECRET_HASH: Lazy<String> =
+    Lazy::new(|| hash_secret("not a client secret").expect("dummy client secret hashes"));
+
+#[async_trait]
+#[automock]
+pub trait ApiClientService: Debug + Send + Sync + 'static {
+    async fn list_clients(&self) -> Result<Vec<ApiClient>, ServiceError>;
+    async fn create_client(
+        &self,
+        create_api_client_request: CreateApiClientRequest,
+        created_by: String,
+    ) -> Result<ApiClientCredentials, ServiceError>;
+    async fn rotate_secret(
+        &self,
+        client_id: String,
+        changed_by: String,
+    ) -> Result<ApiClientCredentials, ServiceError>;
+    async fn revoke_client(
+        &self,
+        client_id: String,
+        changed_by: String,
+    ) -> Result<ApiClient, ServiceError>;
+    /// The client, if it is known, has not been revoked and the secret matches
+    async fn verify_credentials(
+        &self,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<Option<ApiClient>, ServiceError>;
+    /// Registers a client with a known secret and every scope unless the client id already exists
+    async fn bootstrap_client(
+        &self,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<(), ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct ApiClientServiceImpl {
+    api_client_repo: Arc<dyn ApiClientRepository + Send + Sync>,
+}
+
+impl ApiClientServiceImpl {
+    pub fn new(api_client_repo: Arc<dyn ApiClientRepository + Send + Sync>) -> Self {
+        Self { api_client_repo }
+    }
+}
+
+#[async_trait]
+impl ApiClientService for ApiClientServiceImpl {
+    #[instrument]
+    async fn list_clients(&self) -> Result<Vec<ApiClient>, ServiceError> {
+        let results = self.api_client_repo.get_all().await;
+        results
+            .map(|rows| rows.into_iter().map(ApiClient::from).collect())
+            .map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn create_client(
+        &self,
+        create_api_client_request: CreateApiClientRequest,
+        created_by: String,
+    ) -> Result<ApiClientCredentials, ServiceError> {
+        create_api_client_request.validate()?;
+        let client_secret = generate_secret();
+        let secret = client_secret.clone();
+        let secret_hash = run_blocking(move || hash_secret(&secret)).await?;
+        let row = self
+            .api_client_repo
+            .create(
+                &create_api_client_request.client_id,
+                &create_api_client_request.name,
+                &secret_hash,
+                &create_api_client_request.scopes,
+                &created_by,
+            )
+            .await?;
+        Ok(ApiClientCredentials {
+            client_id: row.client_id,
+            client_secret,
+        })
+    }
+
+    #[instrument]
+    async fn rotate_secret(
+        &self,
+        client_id: String,
+        changed_by: String,
+    ) -> Result<ApiClientCredentials, ServiceError> {
+        let client_secret = generate_secret();
+        let secret = client_secret.clone();
+        let secret_hash = run_blocking(move || hash_secret(&secret)).await?;
+        let row = self
+            .api_client_repo
+            .update_secret(&client_id, &secret_hash, &changed_by)
+            .await
+            .map_err(|e| match e {
+                RepoError::NotFound(_) => {
+                    ServiceError::NotFound(format!("No active API client with id {}", client_id))
+                }
+                e => ServiceError::from(e),
+            })?;
+        Ok(ApiClientCredentials {
+            client_id: row.client_id,
+            client_secret,
+        })
+    }
+
+    #[instrument]
+    async fn revoke_client(
+        &self,
+        client_id: String,
+        changed_by: String,
+    ) -> Result<ApiClient, ServiceError> {
+        let results = self.api_client_repo.revoke(&client_id, &changed_by).await;
+        results.map(ApiClient::from).map_err(ServiceError::from)
+    }
+
+    #[instrument(skip(client_secret))]
+    async fn verify_credentials(
+        &self,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<Option<ApiClient>, ServiceError> {
+        let row = match self.api_client_repo.get_by_client_id(&client_id).await {
+            Ok(row) => row,
+            Err(RepoError::NotFound(_)) => {
+                run_blocking(move || verify_secret(&client_secret, &DUMMY_SECRET_HASH)).await?;
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // the secret is checked even for a revoked client, for the same reason
+        let secret_hash = row.secret_hash.clone();
+        let verified = run_blocking(move || verify_secret(&client_secret, &secret_hash)).await?;
+        if row.revoked_at.is_some() || !verified {
+            return Ok(None);
+        }
+        Ok(Some(ApiClient::from(row)))
+    }
+
+    #[instrument(skip(client_secret))]
+    async fn bootstrap_client(
+        &self,
+        client_id: String,
+        client_secret: String,
+    ) -> Result<(), ServiceError> {
+        // an existing client is left alone so a restart never undoes a rotation or revocation
+        match self.api_client_repo.get_by_client_id(&client_id).await {
+            Ok(_) => return Ok(()),
+            Err(RepoError::NotFound(_)) => (),
+            Err(e) => return Err(e.into()),
+        }
+        let secret_hash = run_blocking(move || hash_secret(&client_secret)).await?;
+        self.api_client_repo
+            .create(
+                &client_id,
+                "Bootstrap client",
+                &secret_hash,
+                &Scope::ALL,
+                "system",
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CLIENT_SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Runs argon2 hashing or verification, which is slow on purpose, on the blocking pool so that a
+/// burst of requests to authorize can't tie up the runtime's workers
+async fn run_blocking<T, F>(f: F) -> Result<T, ServiceError>
+where
+    F: FnOnce() -> Result<T, ServiceError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        ServiceError::UnexpectedError(format!("Failed to check client secret: {}", e))
+    })?
+}
+
+fn hash_secret(client_secret: &str) -> Result<String, ServiceError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(client_secret.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ServiceError::UnexpectedError(format!("Failed to hash client secret: {}", e)))
+}
+
+fn verify_secret(client_secret: &str, secret_hash: &str) -> Result<bool, ServiceError> {
+    let parsed_hash = PasswordHash::new(secret_hash).map_err(|e| {
+        ServiceError::UnexpectedError(format!("Stored client secret hash is invalid: {}", e))
+    })?;
+    Ok(Argon2::default()
+        .verify_password(client_secret.as_bytes(), &parsed_hash)
+        .is_ok())
+}
+
+impl From<ApiClientRow> for ApiClient {
+    fn from(row: ApiClientRow) -> Self {
+        ApiClient {
+            seq: row.id,
+            id: row.alt_id.to_string(),
+            client_id: row.client_id,
+            name: row.name,
+            revoked_at: row.revoked_at,
+            scopes: row.scopes,
+            audit_info: AuditInfo {
+                created_by: row.created_by,
+                created_at: row.created_at,
+                changed_by: row.last_changed_by,
+                updated_at: row.last_update,
+                deleted_at: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::repositories::api_client::MockApiClientRepository;
+    use chrono::Utc;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    fn create_client_row(client_id: &str, secret_hash: &str) -> ApiClientRow {
+        ApiClientRow {
+            id: 1,
+            alt_id: Uuid::new_v4(),
+            client_id: client_id.to_string(),
+            name: "Reporting team".to_string(),
+            secret_hash: secret_hash.to_string(),
+            revoked_at: None,
+            scopes: vec![Scope::ItemsRead],
+            created_by: "testuser".to_string(),
+            created_at: Utc::now(),
+            last_changed_by: "testuser".to_string(),
+            last_update: Utc::now(),
+        }
+    }
+
+    fn expect_client(mock: &mut MockApiClientRepository, row: ApiClientRow) {
+        mock.expect_get_by_client_id().times(1).returning(move |_| {
+            let cloned_row = row.clone();
+            Box::pin(async move { Ok(cloned_row) })
+        });
+    }
+
+    #[test]
+    fn test_hash_and_verify_secret() {
+        let secret_hash = hash_secret("s3cret").unwrap();
+        assert!(secret_hash.starts_with("$argon2"));
+        assert!(!secret_hash.contains("s3cret"));
+        assert!(verify_secret("s3cret", &secret_hash).unwrap());
+        assert!(!verify_secret("wrong", &secret_hash).unwrap());
+        // the stand-in for unknown clients is a real hash that nothing sent will match
+        assert!(!verify_secret("s3cret", &DUMMY_SECRET_HASH).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_client() {
+        let mut mock = MockApiClientRepository::new();
+        let stored_hash = Arc::new(Mutex::new(String::new()));
+        let captured_hash = stored_hash.clone();
+        mock.expect_create()
+            .withf(|client_id, name, _, scopes, created_by| {
+                client_id == "reporting"
+                    && name == "Reporting team"
+                    && scopes == [Scope::ItemsRead]
+                    && created_by == "admin"
+            })
+            .times(1)
+            .returning(move |client_id, _, secret_hash, _, _| {
+                *captured_hash.lock().unwrap() = secret_hash.to_string();
+                let row = create_client_row(client_id, secret_hash);
+                Box::pin(async move { Ok(row) })
+            });
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .create_client(
+                CreateApiClientRequest {
+                    client_id: "reporting".to_string(),
+                    name: "Reporting team".to_string(),
+                    scopes: vec![Scope::ItemsRead],
+                },
+                "admin".to_string(),
+            )
+            .await;
+        let credentials = result.unwrap();
+        assert_eq!(credentials.client_id, "reporting");
+        assert_eq!(credentials.client_secret.len(), CLIENT_SECRET_LENGTH);
+        let stored_hash = stored_hash.lock().unwrap().clone();
+        assert_ne!(stored_hash, credentials.client_secret);
+        assert!(verify_secret(&credentials.client_secret, &stored_hash).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_client_invalid_client_id() {
+        let mut mock = MockApiClientRepository::new();
+        mock.expect_create().never();
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .create_client(
+                CreateApiClientRequest {
+                    client_id: "has spaces".to_string(),
+                    name: "Reporting team".to_string(),
+                    scopes: vec![Scope::ItemsRead],
+                },
+                "admin".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rotate_secret_revoked_client() {
+        let mut mock = MockApiClientRepository::new();
+        mock.expect_update_secret()
+            .times(1)
+            .returning(|_, _, _| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .rotate_secret("reporting".to_string(), "admin".to_string())
+            .await;
+        match result {
+            Err(ServiceError::NotFound(msg)) => {
+                assert_eq!(msg, "No active API client with id reporting")
+            }
+            other => panic!("Expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials() {
+        let mut mock = MockApiClientRepository::new();
+        expect_client(
+            &mut mock,
+            create_client_row("reporting", &hash_secret("s3cret").unwrap()),
+        );
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .verify_credentials("reporting".to_string(), "s3cret".to_string())
+            .await;
+        let client = result.unwrap().expect("credentials should be accepted");
+        assert_eq!(client.client_id, "reporting");
+        assert_eq!(client.scopes, vec![Scope::ItemsRead]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials_wrong_secret() {
+        let mut mock = MockApiClientRepository::new();
+        expect_client(
+            &mut mock,
+            create_client_row("reporting", &hash_secret("s3cret").unwrap()),
+        );
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .verify_credentials("reporting".to_string(), "guess".to_string())
+            .await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials_revoked_client() {
+        let mut mock = MockApiClientRepository::new();
+        let mut row = create_client_row("reporting", &hash_secret("s3cret").unwrap());
+        row.revoked_at = Some(Utc::now());
+        expect_client(&mut mock, row);
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .verify_credentials("reporting".to_string(), "s3cret".to_string())
+            .await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_client() {
+        let mut mock = MockApiClientRepository::new();
+        mock.expect_get_by_client_id()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+        mock.expect_create()
+            .withf(|client_id, _, secret_hash, scopes, _| {
+                client_id == "admin"
+                    && verify_secret("s3cret", secret_hash).unwrap()
+                    && scopes == Scope::ALL
+            })
+            .times(1)
+            .returning(|client_id, _, secret_hash, _, _| {
+                let row = create_client_row(client_id, secret_hash);
+                Box::pin(async move { Ok(row) })
+            });
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .bootstrap_client("admin".to_string(), "s3cret".to_string())
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_client_already_exists() {
+        let mut mock = MockApiClientRepository::new();
+        expect_client(&mut mock, create_client_row("admin", "$argon2id$existing"));
+        mock.expect_create().never();
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .bootstrap_client("admin".to_string(), "s3cret".to_string())
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_verify_credentials_unknown_client() {
+        let mut mock = MockApiClientRepository::new();
+        mock.expect_get_by_client_id()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+
+        let service = ApiClientServiceImpl::new(Arc::new(mock));
+        let result = service
+            .verify_credentials("nobody".to_string(), "s3cret".to_string())
+            .await;
+        assert!(result.unwrap().is_none());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::inventory::model::page::{Cursor, Page};
+use crate::inventory::model::{AuditEntry, AuditResource, Pagination};
+use crate::inventory::repositories::audit::{AuditRepository, AuditRow};
+use crate::inventory::services::{into_page, seek, ServiceError};
+use async_trait::async_trait;
+use mockall::automock;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[async_trait]
+#[automock]
+pub trait AuditService: Debug + Send + Sync + 'static {
+    async fn get_history(
+        &self,
+        resource_type: AuditResource,
+        resource_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<AuditEntry>, ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct AuditServiceImpl {
+    audit_repo: Arc<dyn AuditRepository + Send + Sync>,
+}
+
+impl AuditServiceImpl {
+    pub fn new(audit_repo: Arc<dyn AuditRepository + Send + Sync>) -> Self {
+        Self { audit_repo }
+    }
+}
+
+#[async_trait]
+impl AuditService for AuditServiceImpl {
+    #[instrument]
+    async fn get_history(
+        &self,
+        resource_type: AuditResource,
+        resource_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<AuditEntry>, ServiceError> {
+        let seek = seek(maybe_pagination)?;
+        let rows = self
+            .audit_repo
+            .get_history(resource_type, resource_id, &seek)
+            .await?;
+        // every record is created through the API, so one with no history at all never existed
+        if seek.after.is_none() && rows.is_empty() {
+            return Err(ServiceError::NotFound(format!(
+                "No history for {:?} with id {}",
+                resource_type, resource_id
+            )));
+        }
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &AuditRow| Cursor::after_id(row.id),
+            None,
+        ))
+    }
+}
+
+impl From<AuditRow> for AuditEntry {
+    fn from(row: AuditRow) -> Self {
+        AuditEntry {
+            seq: row.id,
+            resource_type: row.resource_type,
+            resource_id: row.resource_id.to_string(),
+            action: row.action,
+            actor: row.actor,
+            before: row.before,
+            after: row.after,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::model::page::Seek;
+    use crate::inventory::model::AuditAction;
+    use crate::inventory::repositories::audit::MockAuditRepository;
+    use mockall::predicate::*;
+    use serde_json::json;
+
+    fn create_audit_row(id: i32, resource_id: Uuid, action: AuditAction) -> AuditRow {
+        AuditRow {
+            id,
+            resource_type: AuditResource::Item,
+            resource_id,
+            action,
+            actor: "testuser".to_string(),
+            before: None,
+            after: Some(json!({ "name": "Widget" })),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_history() {
+        let mut mock = MockAuditRepository::new();
+        let resource_id = Uuid::new_v4();
+        let rows = vec![
+            create_audit_row(1, resource_id, AuditAction::Create),
+            create_audit_row(2, resource_id, AuditAction::Update),
+        ];
+        mock.expect_get_history()
+            .with(
+                eq(AuditResource::Item),
+                eq(resource_id),
+                eq(Seek {
+                    after: None,
+                    limit: 11,
+                }),
+            )
+            .times(1)
+            .returning(move |_, _, _| {
+                let cloned_rows = rows.clone();
+                Box::pin(async move { Ok(cloned_rows) })
+            });
+
+        let service = AuditServiceImpl::new(Arc::new(mock));
+        let history = service
+            .get_history(AuditResource::Item, resource_id, None)
+            .await
+            .unwrap();
+        assert!(!history.has_more);
+        let history = history.items;
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].resource_id, resource_id.to_string());
+        assert_eq!(history[0].action, AuditAction::Create);
+        assert_eq!(history[1].action, AuditAction::Update);
+        assert_eq!(history[1].after, Some(json!({ "name": "Widget" })));
+    }
+
+    #[tokio::test]
+    async fn test_get_history_not_found() {
+        let mut mock = MockAuditRepository::new();
+        mock.expect_get_history()
+            .times(1)
+            .returning(|_, _, _| Box::pin(async { Ok(vec![]) }));
+
+        let service = AuditServiceImpl::new(Arc::new(mock));
+        let result = service
+            .get_history(AuditResource::Person, Uuid::new_v4(), None)
+            .await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_history_past_last_page() {
+        let mut mock = MockAuditRepository::new();
+        mock.expect_get_history()
+            .times(1)
+            .returning(|_, _, _| Box::pin(async { Ok(vec![]) }));
+
+        let service = AuditServiceImpl::new(Arc::new(mock));
+        let pagination = Pagination {
+            cursor: Some(Cursor::after_id(42).encode()),
+            page_size: None,
+        };
+        let result = service
+            .get_history(AuditResource::Invoice, Uuid::new_v4(), Some(pagination))
+            .await;
+        assert_eq!(result.unwrap().items.len(), 0);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::inventory::model::{IdempotencyClaim, IdempotentResponse};
+use crate::inventory::repositories::idempotency::IdempotencyRepository;
+use crate::inventory::services::ServiceError;
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use mockall::automock;
+use serde_json::json;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::instrument;
+
+/// How long a response is replayed for when IDEMPOTENCY_KEY_TTL_SECS is not set
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// How long a key stays held by a request that never finishes, e.g. because the server stopped
+const IN_FLIGHT_HOLD_SECS: i64 = 60;
+
+const MAX_KEY_LENGTH: usize = 255;
+
+#[async_trait]
+#[automock]
+pub trait IdempotencyService: Debug + Send + Sync + 'static {
+    /// Starts a request sent with the claimed key. None if the request should go ahead, or the
+    /// response to be replayed if the same request has already succeeded with this key. The
+    /// claim's `request_hash` identifies the request, so that the key can't be reused for a
+    /// different one.
+    async fn begin(
+        &self,
+        claim: IdempotencyClaim,
+    ) -> Result<Option<IdempotentResponse>, ServiceError>;
+    /// Keeps the response to a request started with `begin` for its retries
+    async fn complete(
+        &self,
+        claim: IdempotencyClaim,
+        response: IdempotentResponse,
+    ) -> Result<(), ServiceError>;
+    /// Gives up the key of a request that failed, so that a retry runs it again
+    async fn release(&self, claim: IdempotencyClaim) -> Result<(), ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct IdempotencyServiceImpl {
+    idempotency_repo: Arc<dyn IdempotencyRepository + Send + Sync>,
+    ttl: Duration,
+}
+
+impl IdempotencyServiceImpl {
+    /// Responses are replayed for `ttl` after the request that produced them
+    pub fn new(
+        idempotency_repo: Arc<dyn IdempotencyRepository + Send + Sync>,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            idempotency_repo,
+            ttl,
+        }
+    }
+}
+
+#[async_trait]
+impl IdempotencyService for IdempotencyServiceImpl {
+    #[instrument]
+    async fn begin(
+        &self,
+        claim: IdempotencyClaim,
+    ) -> Result<Option<IdempotentResponse>, ServiceError> {
+        if claim.key.is_empty() || claim.key.len() > MAX_KEY_LENGTH {
+            return Err(ServiceError::InputValidationError(format!(
+                "Idempotency-Key must be between 1 and {} characters",
+                MAX_KEY_LENGTH
+            )));
+        }
+        let hold_until = Utc::now() + Duration::seconds(IN_FLIGHT_HOLD_SECS);
+        let earlier = self.idempotency_repo.claim(&claim, hold_until).await?;
+        let Some(earlier) = earlier else {
+            return Ok(None);
+        };
+        if earlier.request_hash != claim.request_hash {
+            return Err(ServiceError::IdempotencyKeyReused(format!(
+                "Idempotency-Key {} was already used for a different request",
+                claim.key
+            )));
+        }
+        match (earlier.response_status, earlier.response_body) {
+            (Some(status), Some(body)) => Ok(Some(IdempotentResponse {
+                status: status as u16,
+                headers: serde_json::from_value(earlier.response_headers).map_err(|e| {
+                    ServiceError::UnexpectedError(format!("Failed to replay headers: {}", e))
+                })?,
+                body,
+            })),
+            _ => Err(ServiceError::IdempotencyKeyInUse(format!(
+                "a request with Idempotency-Key {} is still being processed",
+                claim.key
+            ))),
+        }
+    }
+
+    #[instrument(skip(response))]
+    async fn complete(
+        &self,
+        claim: IdempotencyClaim,
+        response: IdempotentResponse,
+    ) -> Result<(), ServiceError> {
+        let expires_at = Utc::now() + self.ttl;
+        let results = self
+            .idempotency_repo
+            .complete(
+                &claim,
+                response.status as i16,
+                &response.body,
+                &json!(response.headers),
+                expires_at,
+            )
+            .await;
+        results.map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn release(&self, claim: IdempotencyClaim) -> Result<(), ServiceError> {
+        let results = self.idempotency_repo.release(&claim).await;
+        results.map_err(ServiceError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::repositories::idempotency::{
+        IdempotencyKeyRow, MockIdempotencyRepository,
+    };
+    use uuid::Uuid;
+
+    fn earlier_request(request_hash: &str, response: Option<(i16, &str)>) -> IdempotencyKeyRow {
+        IdempotencyKeyRow {
+            client_id: "test".to_string(),
+            key: "retry-1".to_string(),
+            request_hash: request_hash.to_string(),
+            response_status: response.map(|(status, _)| status),
+            response_body: response.map(|(_, body)| body.as_bytes().to_vec()),
+            response_headers: match response {
+                Some(_) => json!([["content-type", "application/json"], ["etag", "\"3\""]]),
+                None => json!([]),
+            },
+            expires_at: Utc::now(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn claim(key: &str, request_hash: &str) -> IdempotencyClaim {
+        IdempotencyClaim {
+            client_id: "test".to_string(),
+            key: key.to_string(),
+            request_hash: request_hash.to_string(),
+            claim_id: Uuid::nil(),
+        }
+    }
+
+    fn service(mock: MockIdempotencyRepository) -> IdempotencyServiceImpl {
+        IdempotencyServiceImpl::new(Arc::new(mock), Duration::hours(1))
+    }
+
+    #[tokio::test]
+    async fn test_begin_new_key() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_claim()
+            .withf(|claim, _| {
+                claim.client_id == "test" && claim.key == "retry-1" && claim.request_hash == "abc"
+            })
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(None) }));
+
+        let result = service(mock).begin(claim("retry-1", "abc")).await;
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_begin_replays_response() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_claim().times(1).returning(|_, _| {
+            Box::pin(async { Ok(Some(earlier_request("abc", Some((200, "{}"))))) })
+        });
+
+        let result = service(mock).begin(claim("retry-1", "abc")).await;
+        assert_eq!(
+            result.unwrap(),
+            Some(IdempotentResponse {
+                status: 200,
+                headers: vec![
+                    ("content-type".to_string(), "application/json".to_string()),
+                    ("etag".to_string(), "\"3\"".to_string()),
+                ],
+                body: b"{}".to_vec(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_keeps_headers() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_complete()
+            .withf(|_, status, body, headers, _| {
+                *status == 201
+                    && body == b"{}"
+                    && *headers == json!([["location", "/api/v1/items/1"]])
+            })
+            .times(1)
+            .returning(|_, _, _, _, _| Box::pin(async { Ok(()) }));
+
+        let response = IdempotentResponse {
+            status: 201,
+            headers: vec![("location".to_string(), "/api/v1/items/1".to_string())],
+            body: b"{}".to_vec(),
+        };
+        let result = service(mock)
+            .complete(claim("retry-1", "abc"), response)
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_begin_different_request() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_claim().times(1).returning(|_, _| {
+            Box::pin(async { Ok(Some(earlier_request("abc", Some((200, "{}"))))) })
+        });
+
+        let result = service(mock).begin(claim("retry-1", "xyz")).await;
+        assert!(matches!(result, Err(ServiceError::IdempotencyKeyReused(_))));
+    }
+
+    #[tokio::test]
+    async fn test_begin_while_in_flight() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_claim()
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(Some(earlier_request("abc", None))) }));
+
+        let result = service(mock).begin(claim("retry-1", "abc")).await;
+        assert!(matches!(result, Err(ServiceError::IdempotencyKeyInUse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_begin_rejects_long_key() {
+        let mut mock = MockIdempotencyRepository::new();
+        mock.expect_claim().never();
+
+        let result = service(mock).begin(claim(&"k".repeat(256), "abc")).await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+}
@@ -1,14 +1,22 @@
+use crate::inventory::model::money::{Currency, Money};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::sort::{InvoiceSortField, Sort};
 use crate::inventory::model::{
-    AuditInfo, CreateInvoiceRequest, DeleteResults, Invoice, Item, Pagination, ServiceResults,
-    UpdateInvoiceRequest,
+    AuditInfo, CreateInvoiceRequest, DeleteResults, ExpectedVersion, Invoice, InvoiceFilter,
+    InvoiceLine, InvoiceStatus, Pagination, ServiceResults, UpdateInvoiceRequest,
 };
 use crate::inventory::repositories::invoice::{
-    InvoiceItemRow, InvoiceRepository, InvoiceRow, InvoiceWithItemRow,
+    InvoiceItemRow, InvoiceRepository, InvoiceRow, InvoiceTotals, InvoiceWithItemRow,
+};
+use crate::inventory::repositories::unit_of_work::UnitOfWorkFactory;
+use crate::inventory::services::{
+    into_page, merge_patch, sort_cursor, sorted_seek, timestamp_key, ServiceError,
 };
-use crate::inventory::services::ServiceError;
 use async_trait::async_trait;
-use bigdecimal::ToPrimitive;
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+use garde::Validate;
 use mockall::automock;
+use serde_json::Value;
 use std::fmt::Debug;
 use std::sync::Arc;
 use tracing::instrument;
@@ -17,41 +25,105 @@ use uuid::Uuid;
 #[async_trait]
 #[automock]
 pub trait InvoiceService: Debug + Send + Sync + 'static {
+    /// Invoices matching every filter set in `filter`, in `sort` order
     async fn list_all_invoices(
         &self,
+        filter: InvoiceFilter,
         maybe_pagination: Option<Pagination>,
-    ) -> Result<Vec<Invoice>, ServiceError>;
-    async fn get_invoice(&self, id: Uuid, with_items: bool) -> Result<Invoice, ServiceError>;
-    async fn get_invoices_for_user(&self, user_id: Uuid) -> Result<Vec<Invoice>, ServiceError>;
+        sort: Sort<InvoiceSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Invoice>, ServiceError>;
+    async fn get_invoice(
+        &self,
+        id: Uuid,
+        with_items: bool,
+        include_deleted: bool,
+    ) -> Result<Invoice, ServiceError>;
+    /// The person's invoices matching `filter`; its user_id is replaced by `user_id`
+    async fn get_invoices_for_user(
+        &self,
+        user_id: Uuid,
+        filter: InvoiceFilter,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<InvoiceSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Invoice>, ServiceError>;
     async fn create_invoice(
         &self,
         create_invoice_request: CreateInvoiceRequest,
+        created_by: String,
     ) -> Result<Invoice, ServiceError>;
     async fn update_invoice(
         &self,
         update_invoice_request: UpdateInvoiceRequest,
+        changed_by: String,
+    ) -> Result<Invoice, ServiceError>;
+    /// Changes only what `patch`, a JSON Merge Patch of the invoice's UpdateInvoiceRequest, sets;
+    /// refused unless `version` accepts the invoice's current version
+    async fn patch_invoice(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
     ) -> Result<Invoice, ServiceError>;
-    async fn delete_invoice(&self, id: Uuid) -> Result<DeleteResults, ServiceError>;
+    async fn delete_invoice(
+        &self,
+        id: Uuid,
+        deleted_by: String,
+    ) -> Result<DeleteResults, ServiceError>;
+    async fn restore_invoice(&self, id: Uuid, restored_by: String)
+        -> Result<Invoice, ServiceError>;
+    /// Sends a draft invoice; its lines, tax rate and discount are fixed from then on
+    async fn issue_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError>;
+    /// Marks an issued invoice paid; only allowed once its payments cover the total, so in
+    /// practice for invoices with nothing to pay
+    async fn pay_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError>;
+    /// Cancels a draft or issued invoice that hasn't been paid
+    async fn void_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError>;
+    async fn refund_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError>;
     async fn add_item_to_invoice(
         &self,
         invoice_id: Uuid,
         item_id: Uuid,
+        quantity: i32,
+        changed_by: String,
     ) -> Result<ServiceResults, ServiceError>;
     async fn remove_item_from_invoice(
         &self,
         invoice_id: Uuid,
         item_id: Uuid,
+        changed_by: String,
     ) -> Result<DeleteResults, ServiceError>;
 }
 
 #[derive(Debug)]
 pub struct InvoiceServiceImpl {
     invoice_repo: Arc<dyn InvoiceRepository + Send + Sync>,
+    units: Arc<dyn UnitOfWorkFactory>,
 }
 
 impl InvoiceServiceImpl {
-    pub fn new(invoice_repo: Arc<dyn InvoiceRepository + Send + Sync>) -> Self {
-        Self { invoice_repo }
+    pub fn new(
+        invoice_repo: Arc<dyn InvoiceRepository + Send + Sync>,
+        units: Arc<dyn UnitOfWorkFactory>,
+    ) -> Self {
+        Self {
+            invoice_repo,
+            units,
+        }
+    }
+}
+
+/// An invoice's value of a sort field, as kept in a cursor
+fn sort_value(row: &InvoiceRow, field: InvoiceSortField) -> String {
+    match field {
+        InvoiceSortField::CreatedAt => timestamp_key(&row.created_at),
+        InvoiceSortField::LastUpdate => timestamp_key(&row.last_update),
+        InvoiceSortField::Total => row.total.to_string(),
+        InvoiceSortField::AmountPaid => row.amount_paid.to_string(),
     }
 }
 
@@ -60,171 +132,630 @@ impl InvoiceService for InvoiceServiceImpl {
     #[instrument]
     async fn list_all_invoices(
         &self,
+        filter: InvoiceFilter,
         maybe_pagination: Option<Pagination>,
-    ) -> Result<Vec<Invoice>, ServiceError> {
-        let results = self.invoice_repo.get_all_invoices(maybe_pagination).await;
-        results
-            .map(|i| i.into_iter().map(Invoice::from).collect())
-            .map_err(ServiceError::from)
+        sort: Sort<InvoiceSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Invoice>, ServiceError> {
+        check_filter(&filter)?;
+        let seek = sorted_seek(maybe_pagination, &sort)?;
+        let rows = self
+            .invoice_repo
+            .get_all_invoices(&filter, &sort, &seek, include_deleted)
+            .await?;
+        let total = if include_total {
+            Some(
+                self.invoice_repo
+                    .count_invoices(&filter, include_deleted)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &InvoiceRow| sort_cursor(row.id, &sort, |field| sort_value(row, field)),
+            total,
+        ))
     }
     #[instrument]
-    async fn get_invoice(&self, id: Uuid, with_items: bool) -> Result<Invoice, ServiceError> {
+    async fn get_invoice(
+        &self,
+        id: Uuid,
+        with_items: bool,
+        include_deleted: bool,
+    ) -> Result<Invoice, ServiceError> {
         if with_items {
-            let results = self.invoice_repo.get_with_items(id).await;
-            results.map(Invoice::from).map_err(ServiceError::from)
+            let rows = self
+                .invoice_repo
+                .get_with_items(id, include_deleted)
+                .await?;
+            Invoice::try_from(rows)
         } else {
-            let results = self.invoice_repo.get_by_uuid(id).await;
+            let results = self.invoice_repo.get_by_uuid(id, include_deleted).await;
             results.map(Invoice::from).map_err(ServiceError::from)
         }
     }
 
     #[instrument]
-    async fn get_invoices_for_user(&self, user_id: Uuid) -> Result<Vec<Invoice>, ServiceError> {
-        let results = self.invoice_repo.find_by_user_id(user_id).await;
-        results
-            .map(|i| i.into_iter().map(Invoice::from).collect())
-            .map_err(ServiceError::from)
+    async fn get_invoices_for_user(
+        &self,
+        user_id: Uuid,
+        mut filter: InvoiceFilter,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<InvoiceSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Invoice>, ServiceError> {
+        filter.user_id = Some(user_id);
+        self.list_all_invoices(
+            filter,
+            maybe_pagination,
+            sort,
+            include_deleted,
+            include_total,
+        )
+        .await
     }
     #[instrument]
     async fn create_invoice(
         &self,
-        create_invoice_request: CreateInvoiceRequest,
+        mut create_invoice_request: CreateInvoiceRequest,
+        created_by: String,
     ) -> Result<Invoice, ServiceError> {
-        let results = self.invoice_repo.create(create_invoice_request).await;
+        create_invoice_request.created_by = created_by;
+        create_invoice_request.validate()?;
+        check_currency(
+            "discount",
+            &create_invoice_request.discount,
+            create_invoice_request.currency,
+        )?;
+        if let Some(total) = &create_invoice_request.total {
+            check_currency("total", total, create_invoice_request.currency)?;
+        }
+        let lines = if create_invoice_request.items.is_empty() {
+            vec![]
+        } else {
+            self.invoice_repo
+                .price_lines(
+                    create_invoice_request.currency,
+                    &create_invoice_request.items,
+                )
+                .await?
+        };
+        let subtotal = lines
+            .iter()
+            .map(|line| &line.unit_price * BigDecimal::from(line.quantity))
+            .sum();
+        let totals = price(
+            subtotal,
+            create_invoice_request.tax_rate.clone(),
+            create_invoice_request.discount.amount.clone(),
+        );
+        check_client_total(create_invoice_request.total.as_ref(), &totals)?;
+        let results = self
+            .invoice_repo
+            .create(create_invoice_request, lines, totals)
+            .await;
         results.map(Invoice::from).map_err(ServiceError::from)
     }
     #[instrument]
     async fn update_invoice(
         &self,
-        update_invoice_request: UpdateInvoiceRequest,
+        mut update_invoice_request: UpdateInvoiceRequest,
+        changed_by: String,
     ) -> Result<Invoice, ServiceError> {
-        let results = self.invoice_repo.update(update_invoice_request).await;
+        update_invoice_request.changed_by = changed_by;
+        update_invoice_request.validate()?;
+        let current = self
+            .invoice_repo
+            .get_by_uuid(update_invoice_request.id, false)
+            .await?;
+        if current.status != InvoiceStatus::Draft {
+            return Err(ServiceError::InvalidStatus(format!(
+                "invoice {} is {}; it can only be changed while it is a draft",
+                update_invoice_request.id, current.status
+            )));
+        }
+        let lines = self
+            .invoice_repo
+            .get_items(update_invoice_request.id)
+            .await?;
+        for (field, money) in [
+            ("discount", &update_invoice_request.discount),
+            ("total", &update_invoice_request.total),
+        ] {
+            if let Some(money) = money {
+                check_currency(field, money, current.currency)?;
+            }
+        }
+        let tax_rate = update_invoice_request
+            .tax_rate
+            .clone()
+            .unwrap_or(current.tax_rate);
+        let discount = match &update_invoice_request.discount {
+            Some(discount) => discount.amount.clone(),
+            None => current.discount,
+        };
+        let totals = compute_totals(&lines, tax_rate, discount);
+        check_client_total(update_invoice_request.total.as_ref(), &totals)?;
+        let results = self
+            .invoice_repo
+            .update(update_invoice_request, totals)
+            .await;
         results.map(Invoice::from).map_err(ServiceError::from)
     }
+
+    #[instrument]
+    async fn patch_invoice(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
+    ) -> Result<Invoice, ServiceError> {
+        let current = self.invoice_repo.get_by_uuid(id, false).await?;
+        let current = UpdateInvoiceRequest {
+            id,
+            tax_rate: Some(current.tax_rate),
+            discount: Some(Money::new(current.discount, current.currency)),
+            ..UpdateInvoiceRequest::default()
+        };
+        let mut update_invoice_request = merge_patch(&current, patch)?;
+        if update_invoice_request.id != id {
+            return Err(ServiceError::InputValidationError(format!(
+                "id can't be changed. path: {}, patch: {}",
+                id, update_invoice_request.id
+            )));
+        }
+        update_invoice_request.version = version;
+        self.update_invoice(update_invoice_request, changed_by)
+            .await
+    }
+
     #[instrument]
-    async fn delete_invoice(&self, id: Uuid) -> Result<DeleteResults, ServiceError> {
-        let results = self.invoice_repo.delete(id).await;
+    async fn delete_invoice(
+        &self,
+        id: Uuid,
+        deleted_by: String,
+    ) -> Result<DeleteResults, ServiceError> {
+        let results = self.invoice_repo.delete(id, &deleted_by).await;
         results.map_err(ServiceError::from)
     }
     #[instrument]
+    async fn restore_invoice(
+        &self,
+        id: Uuid,
+        restored_by: String,
+    ) -> Result<Invoice, ServiceError> {
+        let results = self.invoice_repo.restore(id, &restored_by).await;
+        results.map(Invoice::from).map_err(ServiceError::from)
+    }
+    #[instrument]
+    async fn issue_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError> {
+        self.transition(id, InvoiceStatus::Issued, &changed_by)
+            .await
+    }
+    #[instrument]
+    async fn pay_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError> {
+        self.transition(id, InvoiceStatus::Paid, &changed_by).await
+    }
+    #[instrument]
+    async fn void_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError> {
+        self.transition(id, InvoiceStatus::Void, &changed_by).await
+    }
+    #[instrument]
+    async fn refund_invoice(&self, id: Uuid, changed_by: String) -> Result<Invoice, ServiceError> {
+        self.transition(id, InvoiceStatus::Refunded, &changed_by)
+            .await
+    }
+    #[instrument]
     async fn add_item_to_invoice(
         &self,
         invoice_id: Uuid,
         item_id: Uuid,
+        quantity: i32,
+        changed_by: String,
     ) -> Result<ServiceResults, ServiceError> {
-        let row = InvoiceItemRow {
-            invoice_id,
-            item_id,
-        };
-        let results = self.invoice_repo.add_item(row).await;
-        results
-            .map(|_| ServiceResults {
-                message: format!("Item {} added to invoice {}", item_id, invoice_id),
-                success: true,
-            })
-            .map_err(ServiceError::from)
+        if quantity < 1 {
+            return Err(ServiceError::InputValidationError(format!(
+                "quantity ({}) must be at least 1",
+                quantity
+            )));
+        }
+        // the line and the totals it changes are written together or not at all
+        let unit = self.units.begin().await?;
+        let invoices = unit.invoices();
+        let row = invoices
+            .add_item(invoice_id, item_id, quantity, &changed_by)
+            .await?;
+        recompute_totals(invoices.as_ref(), invoice_id, &changed_by).await?;
+        unit.commit().await?;
+        Ok(ServiceResults {
+            message: format!(
+                "Item {} added to invoice {}, quantity now {}",
+                item_id, invoice_id, row.quantity
+            ),
+            success: true,
+        })
     }
     #[instrument]
     async fn remove_item_from_invoice(
         &self,
         invoice_id: Uuid,
         item_id: Uuid,
+        changed_by: String,
     ) -> Result<DeleteResults, ServiceError> {
-        let row = InvoiceItemRow {
-            invoice_id,
-            item_id,
-        };
-        let results = self.invoice_repo.remove_item(row).await;
-        results.map_err(ServiceError::from)
+        let unit = self.units.begin().await?;
+        let invoices = unit.invoices();
+        let results = invoices
+            .remove_item(invoice_id, item_id, &changed_by)
+            .await?;
+        if results.deleted {
+            recompute_totals(invoices.as_ref(), invoice_id, &changed_by).await?;
+        }
+        unit.commit().await?;
+        Ok(results)
     }
 }
 
+impl InvoiceServiceImpl {
+    /// Moves an invoice to `to` if its current status allows it.
+    async fn transition(
+        &self,
+        id: Uuid,
+        to: InvoiceStatus,
+        changed_by: &str,
+    ) -> Result<Invoice, ServiceError> {
+        let current = self.invoice_repo.get_by_uuid(id, false).await?;
+        if !current.status.can_transition_to(to) {
+            return Err(ServiceError::InvalidStatus(format!(
+                "invoice {} is {} and cannot be moved to {}",
+                id, current.status, to
+            )));
+        }
+        if to == InvoiceStatus::Void && current.amount_paid > BigDecimal::zero() {
+            return Err(ServiceError::InvalidStatus(format!(
+                "invoice {} has payments recorded against it and cannot be voided",
+                id
+            )));
+        }
+        if to == InvoiceStatus::Paid && current.amount_paid < current.total {
+            return Err(ServiceError::InvalidStatus(format!(
+                "invoice {} has an outstanding balance of {} and cannot be marked paid",
+                id,
+                &current.total - &current.amount_paid
+            )));
+        }
+        self.invoice_repo
+            .set_status(id, current.status, to, changed_by)
+            .await
+            .map(Invoice::from)
+            .map_err(ServiceError::from)
+    }
+}
+
+/// Re-prices an invoice from its current lines, keeping its tax rate and discount.
+async fn recompute_totals(
+    invoice_repo: &(dyn InvoiceRepository + Send + Sync),
+    invoice_id: Uuid,
+    changed_by: &str,
+) -> Result<InvoiceRow, ServiceError> {
+    let current = invoice_repo.get_by_uuid(invoice_id, false).await?;
+    let lines = invoice_repo.get_items(invoice_id).await?;
+    let totals = compute_totals(&lines, current.tax_rate, current.discount);
+    invoice_repo
+        .update_totals(invoice_id, totals, changed_by)
+        .await
+        .map_err(ServiceError::from)
+}
+
+/// Prices an invoice from its lines.
+pub(crate) fn compute_totals(
+    lines: &[InvoiceItemRow],
+    tax_rate: BigDecimal,
+    discount: BigDecimal,
+) -> InvoiceTotals {
+    let subtotal = lines
+        .iter()
+        .map(|line| &line.unit_price * BigDecimal::from(line.quantity))
+        .sum();
+    price(subtotal, tax_rate, discount)
+}
+
+/// Prices an invoice from the sum of its lines. The discount comes off the subtotal before tax
+/// and cannot take it below zero; tax is rounded half-up to the cent.
+fn price(subtotal: BigDecimal, tax_rate: BigDecimal, discount: BigDecimal) -> InvoiceTotals {
+    let taxable = (&subtotal - &discount).max(BigDecimal::from(0));
+    let tax = (&taxable * &tax_rate).with_scale_round(2, RoundingMode::HalfUp);
+    let total = taxable + &tax;
+    InvoiceTotals {
+        subtotal,
+        tax_rate,
+        tax,
+        discount,
+        total,
+    }
+}
+
+fn check_currency(field: &str, money: &Money, currency: Currency) -> Result<(), ServiceError> {
+    if money.currency != currency {
+        return Err(ServiceError::CurrencyMismatch(format!(
+            "{} is in {} but the invoice is in {}",
+            field, money.currency, currency
+        )));
+    }
+    Ok(())
+}
+
+fn check_filter(filter: &InvoiceFilter) -> Result<(), ServiceError> {
+    let zero = BigDecimal::from(0);
+    if filter.min_total.as_ref().is_some_and(|total| *total < zero)
+        || filter.max_total.as_ref().is_some_and(|total| *total < zero)
+    {
+        return Err(ServiceError::InputValidationError(
+            "min_total and max_total can't be negative".to_string(),
+        ));
+    }
+    if let (Some(min_total), Some(max_total)) = (&filter.min_total, &filter.max_total) {
+        if min_total > max_total {
+            return Err(ServiceError::InputValidationError(format!(
+                "min_total ({}) is more than max_total ({})",
+                min_total, max_total
+            )));
+        }
+    }
+    if let (Some(created_from), Some(created_to)) = (filter.created_from, filter.created_to) {
+        if created_from > created_to {
+            return Err(ServiceError::InputValidationError(format!(
+                "created_from ({}) is after created_to ({})",
+                created_from, created_to
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_client_total(
+    client_total: Option<&Money>,
+    totals: &InvoiceTotals,
+) -> Result<(), ServiceError> {
+    let Some(client_total) = client_total else {
+        return Ok(());
+    };
+    let client_total = client_total
+        .amount
+        .with_scale_round(2, RoundingMode::HalfUp);
+    let expected_total = totals.total.with_scale_round(2, RoundingMode::HalfUp);
+    if client_total != expected_total {
+        return Err(ServiceError::InputValidationError(format!(
+            "total ({}) does not match the computed total ({})",
+            client_total.to_plain_string(),
+            expected_total.to_plain_string()
+        )));
+    }
+    Ok(())
+}
+
 impl From<InvoiceRow> for Invoice {
     fn from(row: InvoiceRow) -> Self {
         Invoice {
             seq: row.id,
             id: row.alt_id.to_string(),
             user_id: row.user_id.to_string(),
-            total: row.total.to_f64().unwrap(),
-            paid: row.paid,
+            currency: row.currency,
+            subtotal: Money::new(row.subtotal, row.currency),
+            discount: Money::new(row.discount, row.currency),
+            tax_rate: row.tax_rate,
+            tax: Money::new(row.tax, row.currency),
+            balance: Money::new(&row.total - &row.amount_paid, row.currency),
+            total: Money::new(row.total, row.currency),
+            amount_paid: Money::new(row.amount_paid, row.currency),
+            status: row.status,
             audit_info: AuditInfo {
                 created_by: row.created_by,
                 created_at: row.created_at,
                 changed_by: row.last_changed_by,
                 updated_at: row.last_update,
+                deleted_at: row.deleted_at,
             },
+            version: row.version,
             items: vec![],
         }
     }
 }
 
-impl From<Vec<InvoiceWithItemRow>> for Invoice {
-    fn from(rows: Vec<InvoiceWithItemRow>) -> Self {
-        let items: Vec<Item> = rows
+impl TryFrom<Vec<InvoiceWithItemRow>> for Invoice {
+    type Error = ServiceError;
+
+    /// Fails with NotFound when there are no rows, i.e. the invoice doesn't exist
+    fn try_from(rows: Vec<InvoiceWithItemRow>) -> Result<Self, Self::Error> {
+        // an invoice without lines comes back as one row with no line fields
+        let items: Vec<InvoiceLine> = rows
             .iter()
-            .map(|row| Item {
-                seq: 0,
-                id: row.item_alt_id.to_string(),
-                name: row.item_name.clone(),
-                description: row.item_description.clone(),
-                unit_price: row.item_unit_price.to_f64().unwrap(),
-                audit_info: AuditInfo::default(),
+            .filter_map(|row| {
+                Some(InvoiceLine {
+                    item_id: row.item_alt_id?.to_string(),
+                    name: row.item_name.clone()?,
+                    description: row.item_description.clone().unwrap_or_default(),
+                    quantity: row.quantity?,
+                    unit_price: Money::new(row.unit_price.clone()?, row.currency),
+                    subtotal: Money::new(row.line_subtotal.clone()?, row.currency),
+                })
             })
             .collect();
-        let row = rows.first().unwrap();
-        Invoice {
+        let Some(row) = rows.first() else {
+            return Err(ServiceError::NotFound("Invoice not found".to_string()));
+        };
+        Ok(Invoice {
             seq: row.id,
             id: row.alt_id.to_string(),
             user_id: row.user_id.to_string(),
-            total: row.total.to_f64().unwrap(),
-            paid: row.paid,
+            currency: row.currency,
+            subtotal: Money::new(row.subtotal.clone(), row.currency),
+            discount: Money::new(row.discount.clone(), row.currency),
+            tax_rate: row.tax_rate.clone(),
+            tax: Money::new(row.tax.clone(), row.currency),
+            total: Money::new(row.total.clone(), row.currency),
+            amount_paid: Money::new(row.amount_paid.clone(), row.currency),
+            balance: Money::new(&row.total - &row.amount_paid, row.currency),
+            status: row.status,
             audit_info: AuditInfo {
                 created_by: row.created_by.clone(),
                 created_at: row.created_at,
                 changed_by: row.last_changed_by.clone(),
                 updated_at: row.last_update,
+                deleted_at: row.deleted_at,
             },
-            items: items.clone(),
-        }
+            version: row.version,
+            items,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::inventory::repositories::invoice::MockInvoiceRepository;
+    use crate::inventory::model::page::{Cursor, Seek};
+    use crate::inventory::repositories::invoice::{MockInvoiceRepository, NewInvoiceLine};
+    use crate::inventory::repositories::unit_of_work::{
+        MockUnitOfWork, MockUnitOfWorkFactory, UnitOfWork,
+    };
+    use crate::inventory::repositories::RepoError;
     use mockall::predicate::*;
+    use serde_json::json;
+
+    fn invoice_service(mock: MockInvoiceRepository) -> InvoiceServiceImpl {
+        InvoiceServiceImpl::new(Arc::new(mock), Arc::new(MockUnitOfWorkFactory::new()))
+    }
+
+    /// A service whose one unit of work hands out `mock` and expects to be committed `commits`
+    /// times
+    fn invoice_service_in_unit(mock: MockInvoiceRepository, commits: usize) -> InvoiceServiceImpl {
+        let invoices: Arc<dyn InvoiceRepository + Send + Sync> = Arc::new(mock);
+        let mut unit = MockUnitOfWork::new();
+        unit.expect_invoices().returning(move || invoices.clone());
+        unit.expect_commit()
+            .times(commits)
+            .returning(|| Box::pin(async { Ok(()) }));
+        let mut units = MockUnitOfWorkFactory::new();
+        units.expect_begin().times(1).return_once(move || {
+            Box::pin(async move { Ok(Box::new(unit) as Box<dyn UnitOfWork>) })
+        });
+        InvoiceServiceImpl::new(Arc::new(MockInvoiceRepository::new()), Arc::new(units))
+    }
 
     fn create_invoice_row(uuid: Uuid, user_id: Uuid) -> InvoiceRow {
         InvoiceRow {
             id: 1,
             alt_id: uuid,
             user_id,
+            currency: Currency::USD,
+            subtotal: bigdecimal::BigDecimal::from(100),
+            tax_rate: bigdecimal::BigDecimal::from(0),
+            tax: bigdecimal::BigDecimal::from(0),
+            discount: bigdecimal::BigDecimal::from(0),
             total: bigdecimal::BigDecimal::from(100),
-            paid: false,
+            amount_paid: bigdecimal::BigDecimal::from(0),
+            status: InvoiceStatus::Draft,
             created_by: "testuser".to_string(),
             created_at: chrono::Utc::now(),
             last_changed_by: "testuser".to_string(),
             last_update: chrono::Utc::now(),
+            deleted_at: None,
+            version: 1,
         }
     }
+
+    fn create_line(invoice_id: Uuid, quantity: i32, unit_price: &str) -> InvoiceItemRow {
+        InvoiceItemRow {
+            invoice_id,
+            item_id: Uuid::new_v4(),
+            quantity,
+            unit_price: unit_price.parse().unwrap(),
+        }
+    }
+
+    fn expect_recompute(mock: &mut MockInvoiceRepository, id: Uuid, lines: Vec<InvoiceItemRow>) {
+        let current_row = create_invoice_row(id, Uuid::new_v4());
+        mock.expect_get_by_uuid()
+            .with(eq(id), eq(false))
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_row = current_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        mock.expect_get_items()
+            .with(eq(id))
+            .times(1)
+            .returning(move |_| {
+                let cloned_lines = lines.clone();
+                Box::pin(async move { Ok(cloned_lines) })
+            });
+    }
+
+    #[test]
+    fn test_compute_totals() {
+        let invoice_id = Uuid::new_v4();
+        let lines = vec![
+            create_line(invoice_id, 3, "19.99"),
+            create_line(invoice_id, 1, "5.00"),
+        ];
+        let totals = compute_totals(&lines, "0.0825".parse().unwrap(), "10".parse().unwrap());
+        assert_eq!(totals.subtotal, "64.97".parse::<BigDecimal>().unwrap());
+        assert_eq!(totals.tax, "4.54".parse::<BigDecimal>().unwrap());
+        assert_eq!(totals.total, "59.51".parse::<BigDecimal>().unwrap());
+    }
+
+    #[test]
+    fn test_compute_totals_discount_exceeds_subtotal() {
+        let lines = vec![create_line(Uuid::new_v4(), 1, "5.00")];
+        let totals = compute_totals(&lines, "0.2".parse().unwrap(), "8".parse().unwrap());
+        assert_eq!(totals.subtotal, "5".parse::<BigDecimal>().unwrap());
+        assert_eq!(totals.tax, BigDecimal::from(0));
+        assert_eq!(totals.total, BigDecimal::from(0));
+    }
+
+    #[test]
+    fn test_compute_totals_no_lines() {
+        let totals = compute_totals(&[], BigDecimal::from(0), BigDecimal::from(0));
+        assert_eq!(totals.total, BigDecimal::from(0));
+    }
+
     #[tokio::test]
     async fn test_list_all_invoices() {
         let mut mock = MockInvoiceRepository::new();
-        let no_pagination: Option<Pagination> = None;
         let expected_row = create_invoice_row(Uuid::new_v4(), Uuid::new_v4());
         mock.expect_get_all_invoices()
-            .with(eq(no_pagination))
+            .with(
+                eq(InvoiceFilter::default()),
+                eq(Sort::default()),
+                eq(Seek {
+                    after: None,
+                    limit: 11,
+                }),
+                eq(false),
+            )
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(vec![cloned_row]) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.list_all_invoices(None).await;
+        let service = invoice_service(mock);
+        let result = service
+            .list_all_invoices(
+                InvoiceFilter::default(),
+                None,
+                Sort::default(),
+                false,
+                false,
+            )
+            .await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().len(), 1);
+        assert_eq!(result.unwrap().items.len(), 1);
     }
 
     #[tokio::test]
@@ -232,21 +763,101 @@ mod tests {
         let mut mock = MockInvoiceRepository::new();
         let expected_row = create_invoice_row(Uuid::new_v4(), Uuid::new_v4());
         let pagination = Pagination {
-            last_id: None,
-            page_size: 10,
+            cursor: Some(Cursor::after_id(10).encode()),
+            page_size: Some(5),
+        };
+        let seek = Seek {
+            after: Some(Cursor::after_id(10)),
+            limit: 6,
         };
         mock.expect_get_all_invoices()
-            .with(eq(Some(pagination)))
+            .with(
+                eq(InvoiceFilter::default()),
+                eq(Sort::default()),
+                eq(seek),
+                eq(false),
+            )
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(vec![cloned_row]) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.list_all_invoices(Some(pagination)).await;
+        let service = invoice_service(mock);
+        let result = service
+            .list_all_invoices(
+                InvoiceFilter::default(),
+                Some(pagination),
+                Sort::default(),
+                false,
+                false,
+            )
+            .await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().len(), 1);
+        assert_eq!(result.unwrap().items.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_invoices_with_filter() {
+        let mut mock = MockInvoiceRepository::new();
+        let expected_row = create_invoice_row(Uuid::new_v4(), Uuid::new_v4());
+        let filter = InvoiceFilter {
+            status: Some(InvoiceStatus::Issued),
+            paid: Some(false),
+            min_total: Some(BigDecimal::from(100)),
+            max_total: Some(BigDecimal::from(100)),
+            ..InvoiceFilter::default()
+        };
+        mock.expect_get_all_invoices()
+            .withf(|filter, _, _, include_deleted| {
+                filter.status == Some(InvoiceStatus::Issued) && *include_deleted
+            })
+            .times(1)
+            .returning(move |_, _, _, _| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(vec![cloned_row]) })
+            });
+        mock.expect_count_invoices()
+            .with(eq(filter.clone()), eq(true))
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(1) }));
+
+        let service = invoice_service(mock);
+        let page = service
+            .list_all_invoices(filter, None, Sort::default(), true, true)
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.total, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_list_invoices_invalid_filter() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_get_all_invoices().never();
+        let service = invoice_service(mock);
+        let now = chrono::Utc::now();
+        for filter in [
+            InvoiceFilter {
+                max_total: Some(BigDecimal::from(-1)),
+                ..InvoiceFilter::default()
+            },
+            InvoiceFilter {
+                min_total: Some(BigDecimal::from(50)),
+                max_total: Some(BigDecimal::from(10)),
+                ..InvoiceFilter::default()
+            },
+            InvoiceFilter {
+                created_from: Some(now),
+                created_to: Some(now - chrono::Duration::days(1)),
+                ..InvoiceFilter::default()
+            },
+        ] {
+            let result = service
+                .list_all_invoices(filter, None, Sort::default(), false, false)
+                .await;
+            assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        }
     }
 
     #[tokio::test]
@@ -255,50 +866,108 @@ mod tests {
         let id = Uuid::new_v4();
         let expected_row = create_invoice_row(id, Uuid::new_v4());
         mock.expect_get_by_uuid()
-            .with(eq(id))
+            .with(eq(id), eq(false))
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.get_invoice(id, false).await;
+        let service = invoice_service(mock);
+        let result = service.get_invoice(id, false, false).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().id, id.to_string());
     }
 
-    #[tokio::test]
-    async fn test_get_invoice_with_items() {
-        let mut mock = MockInvoiceRepository::new();
-        let id = Uuid::new_v4();
-        let expected_item = InvoiceWithItemRow {
+    fn invoice_with_item_row(id: Uuid) -> InvoiceWithItemRow {
+        InvoiceWithItemRow {
             id: 1,
             alt_id: id,
             user_id: Uuid::new_v4(),
+            currency: Currency::EUR,
+            subtotal: bigdecimal::BigDecimal::from(100),
+            tax_rate: bigdecimal::BigDecimal::from(0),
+            tax: bigdecimal::BigDecimal::from(0),
+            discount: bigdecimal::BigDecimal::from(0),
             total: bigdecimal::BigDecimal::from(100),
-            paid: false,
+            amount_paid: bigdecimal::BigDecimal::from(0),
+            status: InvoiceStatus::Draft,
             created_by: "testuser".to_string(),
             created_at: chrono::Utc::now(),
             last_changed_by: "testuser".to_string(),
             last_update: chrono::Utc::now(),
-            item_alt_id: Uuid::new_v4(),
-            item_name: "Test Item".to_string(),
-            item_description: "Test Description".to_string(),
-            item_unit_price: bigdecimal::BigDecimal::from(100),
-        };
+            deleted_at: None,
+            item_alt_id: Some(Uuid::new_v4()),
+            item_name: Some("Test Item".to_string()),
+            item_description: Some("Test Description".to_string()),
+            quantity: Some(2),
+            unit_price: Some(bigdecimal::BigDecimal::from(50)),
+            line_subtotal: Some(bigdecimal::BigDecimal::from(100)),
+            version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_invoice_with_items() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        let expected_item = invoice_with_item_row(id);
         mock.expect_get_with_items()
-            .with(eq(id))
+            .with(eq(id), eq(false))
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = expected_item.clone();
                 Box::pin(async move { Ok(vec![cloned_row]) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.get_invoice(id, true).await;
+        let service = invoice_service(mock);
+        let result = service.get_invoice(id, true, false).await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().id, id.to_string());
+        let invoice = result.unwrap();
+        assert_eq!(invoice.id, id.to_string());
+        assert_eq!(invoice.items[0].quantity, 2);
+        assert_eq!(invoice.items[0].unit_price.amount, BigDecimal::from(50));
+        assert_eq!(invoice.currency, Currency::EUR);
+        assert_eq!(invoice.items[0].unit_price.currency, Currency::EUR);
+        assert_eq!(invoice.items[0].subtotal.amount, BigDecimal::from(100));
+    }
+
+    #[tokio::test]
+    async fn test_get_invoice_with_items_without_lines() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        mock.expect_get_with_items()
+            .with(eq(id), eq(false))
+            .times(1)
+            .returning(move |id, _| {
+                let row = InvoiceWithItemRow {
+                    item_alt_id: None,
+                    item_name: None,
+                    item_description: None,
+                    quantity: None,
+                    unit_price: None,
+                    line_subtotal: None,
+                    ..invoice_with_item_row(id)
+                };
+                Box::pin(async move { Ok(vec![row]) })
+            });
+
+        let service = invoice_service(mock);
+        let invoice = service.get_invoice(id, true, false).await.unwrap();
+        assert_eq!(invoice.id, id.to_string());
+        assert!(invoice.items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_invoice_with_items_not_found() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_get_with_items()
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok(vec![]) }));
+
+        let service = invoice_service(mock);
+        let result = service.get_invoice(Uuid::new_v4(), true, false).await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
     }
 
     #[tokio::test]
@@ -307,15 +976,15 @@ mod tests {
         let id = Uuid::new_v4();
         let expected_row = create_invoice_row(id, Uuid::new_v4());
         mock.expect_get_by_uuid()
-            .with(eq(id))
+            .with(eq(id), eq(false))
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.get_invoice(id, false).await;
+        let service = invoice_service(mock);
+        let result = service.get_invoice(id, false, false).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().id, id.to_string());
     }
@@ -325,18 +994,31 @@ mod tests {
         let mut mock = MockInvoiceRepository::new();
         let user_id = Uuid::new_v4();
         let expected_row = create_invoice_row(Uuid::new_v4(), user_id);
-        mock.expect_find_by_user_id()
-            .with(eq(user_id))
+        mock.expect_get_all_invoices()
+            .withf(move |filter, _, seek, include_deleted| {
+                filter.user_id == Some(user_id)
+                    && filter.status == Some(InvoiceStatus::Paid)
+                    && seek.after.is_none()
+                    && !include_deleted
+            })
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(vec![cloned_row]) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.get_invoices_for_user(user_id).await;
+        let service = invoice_service(mock);
+        // the user in the path wins over one in the filter
+        let filter = InvoiceFilter {
+            status: Some(InvoiceStatus::Paid),
+            user_id: Some(Uuid::new_v4()),
+            ..InvoiceFilter::default()
+        };
+        let result = service
+            .get_invoices_for_user(user_id, filter, None, Sort::default(), false, false)
+            .await;
         assert!(result.is_ok());
-        assert_eq!(result.unwrap().len(), 1);
+        assert_eq!(result.unwrap().items.len(), 1);
     }
 
     #[tokio::test]
@@ -345,64 +1027,331 @@ mod tests {
         let id = Uuid::new_v4();
         let user_id = Uuid::new_v4();
         let expected_row = create_invoice_row(id, user_id);
+        mock.expect_price_lines().never();
         mock.expect_create()
-            .withf(move |r| r.user_id == user_id)
+            .withf(move |r, lines, totals| {
+                r.user_id == user_id
+                    && r.created_by == "testuser"
+                    && lines.is_empty()
+                    && totals.total.is_zero()
+                    && totals.tax_rate == "0.2".parse::<BigDecimal>().unwrap()
+            })
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
+        let service = invoice_service(mock);
         let result = service
-            .create_invoice(CreateInvoiceRequest {
-                user_id,
-                total: 100.0,
-                paid: false,
-                created_by: "testuser".to_string(),
-                items: vec![],
-            })
+            .create_invoice(
+                CreateInvoiceRequest {
+                    user_id,
+                    currency: Currency::USD,
+                    total: None,
+                    tax_rate: "0.2".parse().unwrap(),
+                    discount: Money::zero(Currency::USD),
+                    items: vec![],
+                    ..CreateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
             .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().id, id.to_string());
     }
 
+    #[tokio::test]
+    async fn test_create_invoice_with_items() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        let (first_item, second_item) = (Uuid::new_v4(), Uuid::new_v4());
+        let expected_row = create_invoice_row(id, user_id);
+        let priced_lines = vec![
+            NewInvoiceLine {
+                item_id: first_item,
+                quantity: 2,
+                unit_price: "19.99".parse().unwrap(),
+            },
+            NewInvoiceLine {
+                item_id: second_item,
+                quantity: 1,
+                unit_price: "5.00".parse().unwrap(),
+            },
+        ];
+        let cloned_lines = priced_lines.clone();
+        mock.expect_price_lines()
+            .withf(move |currency, items| {
+                *currency == Currency::USD && items == [first_item, second_item, first_item]
+            })
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_lines = cloned_lines.clone();
+                Box::pin(async move { Ok(cloned_lines) })
+            });
+        mock.expect_create()
+            .withf(move |_, lines, totals| {
+                *lines == priced_lines && totals.total == "44.98".parse::<BigDecimal>().unwrap()
+            })
+            .times(1)
+            .returning(move |_, _, _| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = invoice_service(mock);
+        let result = service
+            .create_invoice(
+                CreateInvoiceRequest {
+                    user_id,
+                    currency: Currency::USD,
+                    total: Some(Money::new("44.98".parse().unwrap(), Currency::USD)),
+                    items: vec![first_item, second_item, first_item],
+                    ..CreateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_unknown_item() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_price_lines().times(1).returning(|_, _| {
+            Box::pin(async { Err(RepoError::NotFound("no such item".to_string())) })
+        });
+        mock.expect_create().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .create_invoice(
+                CreateInvoiceRequest {
+                    user_id: Uuid::new_v4(),
+                    items: vec![Uuid::new_v4()],
+                    ..CreateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_total_mismatch() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_create().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .create_invoice(
+                CreateInvoiceRequest {
+                    user_id: Uuid::new_v4(),
+                    total: Some(Money::new(BigDecimal::from(100), Currency::USD)),
+                    ..CreateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        match result {
+            Err(ServiceError::InputValidationError(msg)) => {
+                assert_eq!(
+                    msg,
+                    "total (100.00) does not match the computed total (0.00)"
+                )
+            }
+            other => panic!("Expected InputValidationError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_invoice_discount_in_other_currency() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_create().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .create_invoice(
+                CreateInvoiceRequest {
+                    user_id: Uuid::new_v4(),
+                    currency: Currency::GBP,
+                    discount: Money::new(BigDecimal::from(5), Currency::EUR),
+                    ..CreateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        match result {
+            Err(ServiceError::CurrencyMismatch(msg)) => {
+                assert_eq!(msg, "discount is in EUR but the invoice is in GBP")
+            }
+            other => panic!("Expected CurrencyMismatch, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_update_invoice() {
         let mut mock = MockInvoiceRepository::new();
         let id = Uuid::new_v4();
         let user_id = Uuid::new_v4();
         let expected_row = create_invoice_row(id, user_id);
+        expect_recompute(&mut mock, id, vec![create_line(id, 4, "25.00")]);
+        let (subtotal, discount, total) = (
+            BigDecimal::from(100),
+            BigDecimal::from(10),
+            BigDecimal::from(90),
+        );
         mock.expect_update()
-            .withf(move |r| r.id == id)
+            .withf(move |r, totals| {
+                r.id == id
+                    && r.changed_by == "testuser"
+                    && totals.subtotal == subtotal
+                    && totals.discount == discount
+                    && totals.total == total
+            })
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = expected_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
+        let service = invoice_service(mock);
         let result = service
-            .update_invoice(UpdateInvoiceRequest {
-                id,
-                total: 100.0,
-                paid: false,
-                changed_by: "testuser".to_string(),
-            })
+            .update_invoice(
+                UpdateInvoiceRequest {
+                    id,
+                    total: Some(Money::new(BigDecimal::from(90), Currency::USD)),
+                    tax_rate: None,
+                    discount: Some(Money::new(BigDecimal::from(10), Currency::USD)),
+                    ..UpdateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
             .await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap().id, id.to_string());
     }
 
+    #[tokio::test]
+    async fn test_patch_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        let expected_row = create_invoice_row(id, Uuid::new_v4());
+        let current_row = expected_row.clone();
+        mock.expect_get_by_uuid()
+            .with(eq(id), eq(false))
+            .times(2)
+            .returning(move |_, _| {
+                let cloned_row = current_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        mock.expect_get_items()
+            .returning(move |_| Box::pin(async move { Ok(vec![create_line(id, 4, "25.00")]) }));
+        let total = BigDecimal::from(90);
+        mock.expect_update()
+            .withf(move |r, totals| {
+                r.id == id
+                    && r.version == ExpectedVersion::OneOf(vec![5])
+                    && r.tax_rate == Some(BigDecimal::from(0))
+                    && r.discount == Some(Money::new(BigDecimal::from(10), Currency::USD))
+                    && totals.total == total
+            })
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = invoice_service(mock);
+        // the currency of the discount and the tax rate are kept from the invoice
+        let result = service
+            .patch_invoice(
+                id,
+                json!({"discount": {"amount": "10"}}),
+                ExpectedVersion::OneOf(vec![5]),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_invoice_total_mismatch() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_recompute(&mut mock, id, vec![create_line(id, 4, "25.00")]);
+        mock.expect_update().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .update_invoice(
+                UpdateInvoiceRequest {
+                    id,
+                    total: Some(Money::new(BigDecimal::from(200000), Currency::USD)),
+                    tax_rate: None,
+                    discount: None,
+                    ..UpdateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_invoice_total_in_other_currency() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_recompute(&mut mock, id, vec![create_line(id, 4, "25.00")]);
+        mock.expect_update().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .update_invoice(
+                UpdateInvoiceRequest {
+                    id,
+                    total: Some(Money::new(BigDecimal::from(100), Currency::GBP)),
+                    tax_rate: None,
+                    discount: None,
+                    ..UpdateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::CurrencyMismatch(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_invoice_not_found() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_get_by_uuid().times(1).returning(|_, _| {
+            Box::pin(async { Err(RepoError::NotFound("not found".to_string())) })
+        });
+        mock.expect_update().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .update_invoice(
+                UpdateInvoiceRequest {
+                    id: Uuid::new_v4(),
+                    ..UpdateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
     #[tokio::test]
     async fn test_delete_invoice() {
         let mut mock = MockInvoiceRepository::new();
         let id = Uuid::new_v4();
         mock.expect_delete()
-            .with(eq(id))
+            .with(eq(id), eq("testuser"))
             .times(1)
-            .returning(move |_| {
-                let cloned_id = id.clone().to_string();
+            .returning(move |_, _| {
+                let cloned_id = id.to_string();
                 Box::pin(async {
                     Ok(DeleteResults {
                         id: cloned_id,
@@ -411,8 +1360,8 @@ mod tests {
                 })
             });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.delete_invoice(id).await;
+        let service = invoice_service(mock);
+        let result = service.delete_invoice(id, "testuser".to_string()).await;
         assert!(result.is_ok());
     }
 
@@ -422,22 +1371,98 @@ mod tests {
         let invoice_id = Uuid::new_v4();
         let item_id = Uuid::new_v4();
         mock.expect_add_item()
-            .withf(move |r| r.invoice_id == invoice_id && r.item_id == item_id)
+            .withf(move |id, item, quantity, changed_by| {
+                *id == invoice_id && *item == item_id && *quantity == 3 && changed_by == "testuser"
+            })
             .times(1)
-            .returning(move |_| {
-                let cloned_invoice_id = invoice_id.clone();
-                let cloned_item_id = item_id.clone();
+            .returning(move |invoice_id, item_id, quantity, _| {
                 Box::pin(async move {
                     Ok(InvoiceItemRow {
-                        invoice_id: cloned_invoice_id,
-                        item_id: cloned_item_id,
+                        invoice_id,
+                        item_id,
+                        quantity,
+                        unit_price: bigdecimal::BigDecimal::from(10),
                     })
                 })
             });
+        expect_recompute(
+            &mut mock,
+            invoice_id,
+            vec![create_line(invoice_id, 3, "10.00")],
+        );
+        let total = BigDecimal::from(30);
+        mock.expect_update_totals()
+            .withf(move |id, totals, changed_by| {
+                *id == invoice_id && totals.total == total && changed_by == "testuser"
+            })
+            .times(1)
+            .returning(move |id, _, _| {
+                let row = create_invoice_row(id, Uuid::new_v4());
+                Box::pin(async move { Ok(row) })
+            });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.add_item_to_invoice(invoice_id, item_id).await;
+        let service = invoice_service_in_unit(mock, 1);
+        let result = service
+            .add_item_to_invoice(invoice_id, item_id, 3, "testuser".to_string())
+            .await;
         assert!(result.is_ok());
+        assert!(result.unwrap().message.ends_with("quantity now 3"));
+    }
+
+    #[tokio::test]
+    async fn test_add_item_to_invoice_zero_quantity() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_add_item().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .add_item_to_invoice(Uuid::new_v4(), Uuid::new_v4(), 0, "testuser".to_string())
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_add_item_to_invoice_insufficient_stock() {
+        let mut mock = MockInvoiceRepository::new();
+        mock.expect_add_item().times(1).returning(|_, _, _, _| {
+            Box::pin(async { Err(RepoError::InsufficientStock("short".to_string())) })
+        });
+        mock.expect_update_totals().never();
+
+        let service = invoice_service_in_unit(mock, 0);
+        let result = service
+            .add_item_to_invoice(Uuid::new_v4(), Uuid::new_v4(), 5, "testuser".to_string())
+            .await;
+        assert!(matches!(result, Err(ServiceError::InsufficientStock(_))));
+    }
+
+    #[tokio::test]
+    async fn test_add_item_to_invoice_rolled_back_when_totals_fail() {
+        let mut mock = MockInvoiceRepository::new();
+        let invoice_id = Uuid::new_v4();
+        mock.expect_add_item()
+            .times(1)
+            .returning(|invoice_id, item_id, quantity, _| {
+                Box::pin(async move {
+                    Ok(InvoiceItemRow {
+                        invoice_id,
+                        item_id,
+                        quantity,
+                        unit_price: BigDecimal::from(10),
+                    })
+                })
+            });
+        expect_recompute(&mut mock, invoice_id, vec![]);
+        mock.expect_update_totals().times(1).returning(|_, _, _| {
+            Box::pin(async { Err(RepoError::Other("connection lost".to_string())) })
+        });
+
+        // the unit is never committed, so the line is dropped along with it
+        let service = invoice_service_in_unit(mock, 0);
+        let result = service
+            .add_item_to_invoice(invoice_id, Uuid::new_v4(), 1, "testuser".to_string())
+            .await;
+        assert!(matches!(result, Err(ServiceError::UnexpectedError(_))));
     }
 
     #[tokio::test]
@@ -446,10 +1471,12 @@ mod tests {
         let invoice_id = Uuid::new_v4();
         let item_id = Uuid::new_v4();
         mock.expect_remove_item()
-            .withf(move |r| r.invoice_id == invoice_id && r.item_id == item_id)
+            .withf(move |id, item, changed_by| {
+                *id == invoice_id && *item == item_id && changed_by == "testuser"
+            })
             .times(1)
-            .returning(move |_| {
-                let cloned_item_id = item_id.clone().to_string();
+            .returning(move |_, _, _| {
+                let cloned_item_id = item_id.to_string();
                 Box::pin(async {
                     Ok(DeleteResults {
                         id: cloned_item_id,
@@ -457,9 +1484,168 @@ mod tests {
                     })
                 })
             });
+        expect_recompute(&mut mock, invoice_id, vec![]);
+        mock.expect_update_totals()
+            .withf(move |id, totals, changed_by| {
+                *id == invoice_id && totals.total.is_zero() && changed_by == "testuser"
+            })
+            .times(1)
+            .returning(move |id, _, _| {
+                let row = create_invoice_row(id, Uuid::new_v4());
+                Box::pin(async move { Ok(row) })
+            });
 
-        let service = InvoiceServiceImpl::new(Arc::new(mock));
-        let result = service.remove_item_from_invoice(invoice_id, item_id).await;
+        let service = invoice_service_in_unit(mock, 1);
+        let result = service
+            .remove_item_from_invoice(invoice_id, item_id, "testuser".to_string())
+            .await;
         assert!(result.is_ok());
     }
+
+    fn expect_current_status(mock: &mut MockInvoiceRepository, id: Uuid, status: InvoiceStatus) {
+        let mut current_row = create_invoice_row(id, Uuid::new_v4());
+        current_row.status = status;
+        mock.expect_get_by_uuid()
+            .with(eq(id), eq(false))
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_row = current_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+    }
+
+    #[tokio::test]
+    async fn test_issue_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_current_status(&mut mock, id, InvoiceStatus::Draft);
+        mock.expect_set_status()
+            .with(
+                eq(id),
+                eq(InvoiceStatus::Draft),
+                eq(InvoiceStatus::Issued),
+                eq("testuser"),
+            )
+            .times(1)
+            .returning(|id, _, to, _| {
+                let mut row = create_invoice_row(id, Uuid::new_v4());
+                row.status = to;
+                Box::pin(async move { Ok(row) })
+            });
+
+        let service = invoice_service(mock);
+        let result = service.issue_invoice(id, "testuser".to_string()).await;
+        assert_eq!(result.unwrap().status, InvoiceStatus::Issued);
+    }
+
+    #[tokio::test]
+    async fn test_pay_draft_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_current_status(&mut mock, id, InvoiceStatus::Draft);
+        mock.expect_set_status().never();
+
+        let service = invoice_service(mock);
+        let result = service.pay_invoice(id, "testuser".to_string()).await;
+        assert!(matches!(result, Err(ServiceError::InvalidStatus(_))));
+    }
+
+    #[tokio::test]
+    async fn test_pay_invoice_with_balance() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        // total 100, nothing paid yet
+        expect_current_status(&mut mock, id, InvoiceStatus::Issued);
+        mock.expect_set_status().never();
+
+        let service = invoice_service(mock);
+        let result = service.pay_invoice(id, "testuser".to_string()).await;
+        assert!(matches!(result, Err(ServiceError::InvalidStatus(_))));
+    }
+
+    #[tokio::test]
+    async fn test_pay_zero_total_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        let mut current_row = create_invoice_row(id, Uuid::new_v4());
+        current_row.status = InvoiceStatus::Issued;
+        current_row.total = BigDecimal::from(0);
+        mock.expect_get_by_uuid().times(1).returning(move |_, _| {
+            let cloned_row = current_row.clone();
+            Box::pin(async move { Ok(cloned_row) })
+        });
+        mock.expect_set_status()
+            .with(
+                eq(id),
+                eq(InvoiceStatus::Issued),
+                eq(InvoiceStatus::Paid),
+                eq("testuser"),
+            )
+            .times(1)
+            .returning(|id, _, to, _| {
+                let mut row = create_invoice_row(id, Uuid::new_v4());
+                row.status = to;
+                Box::pin(async move { Ok(row) })
+            });
+
+        let service = invoice_service(mock);
+        let result = service.pay_invoice(id, "testuser".to_string()).await;
+        assert_eq!(result.unwrap().status, InvoiceStatus::Paid);
+    }
+
+    #[tokio::test]
+    async fn test_void_paid_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_current_status(&mut mock, id, InvoiceStatus::Paid);
+        mock.expect_set_status().never();
+
+        let service = invoice_service(mock);
+        let result = service.void_invoice(id, "testuser".to_string()).await;
+        assert!(matches!(result, Err(ServiceError::InvalidStatus(_))));
+    }
+
+    #[tokio::test]
+    async fn test_update_issued_invoice() {
+        let mut mock = MockInvoiceRepository::new();
+        let id = Uuid::new_v4();
+        expect_current_status(&mut mock, id, InvoiceStatus::Issued);
+        mock.expect_update().never();
+
+        let service = invoice_service(mock);
+        let result = service
+            .update_invoice(
+                UpdateInvoiceRequest {
+                    id,
+                    discount: Some(Money::new(BigDecimal::from(10), Currency::USD)),
+                    ..UpdateInvoiceRequest::default()
+                },
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InvalidStatus(_))));
+    }
+
+    #[test]
+    fn test_invoice_status_transitions() {
+        use InvoiceStatus::*;
+        let allowed = [
+            (Draft, Issued),
+            (Draft, Void),
+            (Issued, Paid),
+            (Issued, Void),
+            (Paid, Refunded),
+        ];
+        for from in [Draft, Issued, Paid, Void, Refunded] {
+            for to in [Draft, Issued, Paid, Void, Refunded] {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from, to)),
+                    "{} -> {}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
 }
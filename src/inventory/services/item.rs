@@ -1,10 +1,21 @@
-use crate::inventory::model::{AuditInfo, CreateItemRequest, DeleteResults, Item, Pagination, UpdateItemRequest};
-use crate::inventory::repositories::item::{ItemRepository, ItemRow};
-use crate::inventory::services::ServiceError;
+use crate::inventory::model::money::Money;
+use crate::inventory::model::page::{Cursor, Page};
+use crate::inventory::model::sort::{ItemSortField, Sort};
+use crate::inventory::model::{
+    AuditInfo, CreateItemRequest, DeleteResults, ExpectedVersion, Item, ItemSearchQuery,
+    Pagination, SearchMatch, UpdateItemRequest,
+};
+use crate::inventory::repositories::item::{ItemRepository, ItemRow, ItemSearchRow};
+use crate::inventory::repositories::stock::StockRepository;
+use crate::inventory::services::{
+    into_page, merge_patch, sort_cursor, sorted_seek, timestamp_key, ServiceError,
+};
 use crate::test_helpers::string_to_uuid;
 use async_trait::async_trait;
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
 use garde::Validate;
+use serde_json::Value;
 use std::fmt::Debug;
 use std::sync::Arc;
 use tracing::instrument;
@@ -13,77 +24,324 @@ use uuid::Uuid;
 #[async_trait]
 #[mockall::automock]
 pub trait ItemService: Sync + Send + Debug + 'static {
-    async fn get_all_items(&self, maybe_pagination: Option<Pagination>) -> Result<Vec<Item>, ServiceError>;
-    async fn get_item_by_id(&self, id: Uuid) -> Result<Item, ServiceError>;
-    async fn create_item(&self, item: CreateItemRequest) -> Result<Item, ServiceError>;
-    async fn update_item(&self, item: UpdateItemRequest) -> Result<Item, ServiceError>;
-    async fn delete_item(&self, id: Uuid) -> Result<DeleteResults, ServiceError>;
+    async fn get_all_items(
+        &self,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<ItemSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Item>, ServiceError>;
+    /// Items matching every filter in `search` in `sort` order, or most relevant first when it
+    /// has keywords and there is no sort
+    async fn search_items(
+        &self,
+        search: ItemSearchQuery,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<ItemSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Item>, ServiceError>;
+    async fn get_item_by_id(&self, id: Uuid, include_deleted: bool) -> Result<Item, ServiceError>;
+    async fn create_item(
+        &self,
+        item: CreateItemRequest,
+        created_by: String,
+    ) -> Result<Item, ServiceError>;
+    async fn update_item(
+        &self,
+        item: UpdateItemRequest,
+        changed_by: String,
+    ) -> Result<Item, ServiceError>;
+    /// Changes only what `patch`, a JSON Merge Patch of the item's UpdateItemRequest, sets;
+    /// refused unless `version` accepts the item's current version
+    async fn patch_item(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
+    ) -> Result<Item, ServiceError>;
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        deleted_by: String,
+    ) -> Result<DeleteResults, ServiceError>;
+    async fn restore_item(&self, id: Uuid, restored_by: String) -> Result<Item, ServiceError>;
 }
 
 #[derive(Debug)]
 pub struct ItemServiceImpl {
     pub item_repository: Arc<dyn ItemRepository + Send + Sync>,
+    pub stock_repository: Arc<dyn StockRepository + Send + Sync>,
 }
 
 impl ItemServiceImpl {
-    pub fn new(item_repository: Arc<dyn ItemRepository + Send + Sync>) -> ItemServiceImpl {
-        ItemServiceImpl { item_repository }
+    pub fn new(
+        item_repository: Arc<dyn ItemRepository + Send + Sync>,
+        stock_repository: Arc<dyn StockRepository + Send + Sync>,
+    ) -> ItemServiceImpl {
+        ItemServiceImpl {
+            item_repository,
+            stock_repository,
+        }
+    }
+}
+
+/// An item's value of a sort field, as kept in a cursor
+fn sort_value(
+    field: ItemSortField,
+    name: &str,
+    unit_price: &BigDecimal,
+    created_at: &DateTime<Utc>,
+    last_update: &DateTime<Utc>,
+) -> String {
+    match field {
+        ItemSortField::Name => name.to_string(),
+        ItemSortField::UnitPrice => unit_price.to_string(),
+        ItemSortField::CreatedAt => timestamp_key(created_at),
+        ItemSortField::LastUpdate => timestamp_key(last_update),
     }
 }
 
 #[async_trait]
 impl ItemService for ItemServiceImpl {
     #[instrument]
-    async fn get_all_items(&self, maybe_pagination: Option<Pagination>) -> Result<Vec<Item>, ServiceError> {
-        self.item_repository.get_all_items(maybe_pagination).await
-            .map(|items| items.into_iter().map(Item::from).collect())
-            .map_err(ServiceError::from)
+    async fn get_all_items(
+        &self,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<ItemSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Item>, ServiceError> {
+        let seek = sorted_seek(maybe_pagination, &sort)?;
+        let rows = self
+            .item_repository
+            .get_all_items(&sort, &seek, include_deleted)
+            .await?;
+        let total = if include_total {
+            Some(
+                self.item_repository
+                    .count_items(&ItemSearchQuery::default(), include_deleted)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &ItemRow| {
+                sort_cursor(row.id, &sort, |field| {
+                    sort_value(
+                        field,
+                        &row.name,
+                        &row.unit_price,
+                        &row.created_at,
+                        &row.last_update,
+                    )
+                })
+            },
+            total,
+        ))
+    }
+
+    #[instrument]
+    async fn search_items(
+        &self,
+        mut search: ItemSearchQuery,
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<ItemSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Item>, ServiceError> {
+        search.q = search.q.map(|q| q.trim().to_string());
+        if search.q.as_ref().is_some_and(|q| q.is_empty()) {
+            return Err(ServiceError::InputValidationError(
+                "q must contain at least one keyword".to_string(),
+            ));
+        }
+        let zero = BigDecimal::from(0);
+        if search.min_price.as_ref().is_some_and(|price| *price < zero)
+            || search.max_price.as_ref().is_some_and(|price| *price < zero)
+        {
+            return Err(ServiceError::InputValidationError(
+                "min_price and max_price can't be negative".to_string(),
+            ));
+        }
+        if let (Some(min_price), Some(max_price)) = (&search.min_price, &search.max_price) {
+            if min_price > max_price {
+                return Err(ServiceError::InputValidationError(format!(
+                    "min_price ({}) is more than max_price ({})",
+                    min_price, max_price
+                )));
+            }
+        }
+        let seek = sorted_seek(maybe_pagination, &sort)?;
+        // only cursors from a keyword search carry a relevance, and keyword results are paged by
+        // it, so a cursor can only continue the kind of search it came from
+        if let Some(after) = &seek.after {
+            match (&search.q, after.rank) {
+                (Some(_), None) => {
+                    return Err(ServiceError::InputValidationError(
+                        "Invalid cursor: it is not from a keyword search".to_string(),
+                    ))
+                }
+                (None, Some(_)) => {
+                    return Err(ServiceError::InputValidationError(
+                        "Invalid cursor: it is from a keyword search".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        let rows = self
+            .item_repository
+            .search_items(&search, &sort, &seek, include_deleted)
+            .await?;
+        let total = if include_total {
+            Some(
+                self.item_repository
+                    .count_items(&search, include_deleted)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &ItemSearchRow| Cursor {
+                rank: row.rank,
+                ..sort_cursor(row.id, &sort, |field| {
+                    sort_value(
+                        field,
+                        &row.name,
+                        &row.unit_price,
+                        &row.created_at,
+                        &row.last_update,
+                    )
+                })
+            },
+            total,
+        ))
     }
 
     #[instrument]
-    async fn get_item_by_id(&self, id: Uuid) -> Result<Item, ServiceError> {
-        self.item_repository.get_item_by_uuid(id).await
+    async fn get_item_by_id(&self, id: Uuid, include_deleted: bool) -> Result<Item, ServiceError> {
+        let mut item = self
+            .item_repository
+            .get_item_by_uuid(id, include_deleted)
+            .await
             .map(Item::from)
-            .map_err(ServiceError::from)
+            .map_err(ServiceError::from)?;
+        let stock = self
+            .stock_repository
+            .get_stock(id)
+            .await
+            .map_err(ServiceError::from)?;
+        item.stock = Some(stock.into());
+        Ok(item)
     }
 
     #[instrument]
-    async fn create_item(&self, item: CreateItemRequest) -> Result<Item, ServiceError> {
-        if let None = BigDecimal::from_f64(item.unit_price) {
-            return Err(ServiceError::InvalidPrice(format!("Invalid unit price: {}", item.unit_price)));
-        }
+    async fn create_item(
+        &self,
+        mut item: CreateItemRequest,
+        created_by: String,
+    ) -> Result<Item, ServiceError> {
+        item.created_by = created_by;
         if let Err(e) = item.validate() {
-            return Err(ServiceError::InputValidationError(format!("Invalid input: {}", e)));
+            return Err(ServiceError::InputValidationError(format!(
+                "Invalid input: {}",
+                e
+            )));
         }
-        self.item_repository.create_item(&item).await
+        self.item_repository
+            .create_item(&item)
+            .await
             .map(Item::from)
             .map_err(ServiceError::from)
     }
 
     #[instrument]
-    async fn update_item(&self, item: UpdateItemRequest) -> Result<Item, ServiceError> {
-        if let None = BigDecimal::from_f64(item.unit_price) {
-            return Err(ServiceError::InvalidPrice(format!("Invalid unit price: {}", item.unit_price)));
-        }
+    async fn update_item(
+        &self,
+        mut item: UpdateItemRequest,
+        changed_by: String,
+    ) -> Result<Item, ServiceError> {
+        item.changed_by = changed_by;
         if let Err(e) = Uuid::parse_str(&item.id) {
-            return Err(ServiceError::InvalidUuid(format!("Invalid id: {} - details: {}", item.id, e.clone())));
+            return Err(ServiceError::InvalidUuid(format!(
+                "Invalid id: {} - details: {}",
+                item.id,
+                e.clone()
+            )));
         }
         if let Err(e) = item.validate() {
-            return Err(ServiceError::InputValidationError(format!("Invalid input: {}", e)));
+            return Err(ServiceError::InputValidationError(format!(
+                "Invalid input: {}",
+                e
+            )));
         }
-        self.item_repository.update_item(&item).await
+        self.item_repository
+            .update_item(&item)
+            .await
             .map(Item::from)
             .map_err(ServiceError::from)
     }
 
     #[instrument]
-    async fn delete_item(&self, id: Uuid) -> Result<DeleteResults, ServiceError> {
-        self.item_repository.delete_item(id).await
-            .map(|row| DeleteResults { id: String::from(row.alt_id), deleted: true })
+    async fn patch_item(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
+    ) -> Result<Item, ServiceError> {
+        let current = self.item_repository.get_item_by_uuid(id, false).await?;
+        let current = UpdateItemRequest {
+            id: current.alt_id.to_string(),
+            name: current.name,
+            description: current.description,
+            unit_price: Money::new(current.unit_price, current.currency),
+            ..UpdateItemRequest::default()
+        };
+        let mut item = merge_patch(&current, patch)?;
+        if item.id != current.id {
+            return Err(ServiceError::InputValidationError(format!(
+                "id can't be changed. path: {}, patch: {}",
+                current.id, item.id
+            )));
+        }
+        item.version = version;
+        self.update_item(item, changed_by).await
+    }
+
+    #[instrument]
+    async fn delete_item(
+        &self,
+        id: Uuid,
+        deleted_by: String,
+    ) -> Result<DeleteResults, ServiceError> {
+        self.item_repository
+            .delete_item(id, &deleted_by)
+            .await
+            .map(|row| DeleteResults {
+                id: String::from(row.alt_id),
+                deleted: true,
+            })
             .map_err(ServiceError::from)
     }
-}
 
+    #[instrument]
+    async fn restore_item(&self, id: Uuid, restored_by: String) -> Result<Item, ServiceError> {
+        self.item_repository
+            .restore_item(id, &restored_by)
+            .await
+            .map(Item::from)
+            .map_err(ServiceError::from)
+    }
+}
 
 impl From<ItemRow> for Item {
     fn from(item_row: ItemRow) -> Self {
@@ -92,13 +350,47 @@ impl From<ItemRow> for Item {
             id: String::from(item_row.alt_id),
             name: item_row.name,
             description: item_row.description,
-            unit_price: item_row.unit_price.to_f64().unwrap(),
+            unit_price: Money::new(item_row.unit_price, item_row.currency),
             audit_info: AuditInfo {
                 created_by: item_row.created_by,
                 created_at: item_row.created_at,
                 changed_by: item_row.last_changed_by,
                 updated_at: item_row.last_update,
+                deleted_at: item_row.deleted_at,
             },
+            version: item_row.version,
+            stock: None,
+            search_match: None,
+        }
+    }
+}
+
+impl From<ItemSearchRow> for Item {
+    fn from(row: ItemSearchRow) -> Self {
+        let search_match = match (row.rank, row.name_highlight, row.description_highlight) {
+            (Some(rank), Some(name), Some(description)) => Some(SearchMatch {
+                rank,
+                name,
+                description,
+            }),
+            _ => None,
+        };
+        Item {
+            seq: row.id,
+            id: String::from(row.alt_id),
+            name: row.name,
+            description: row.description,
+            unit_price: Money::new(row.unit_price, row.currency),
+            audit_info: AuditInfo {
+                created_by: row.created_by,
+                created_at: row.created_at,
+                changed_by: row.last_changed_by,
+                updated_at: row.last_update,
+                deleted_at: row.deleted_at,
+            },
+            version: row.version,
+            stock: None,
+            search_match,
         }
     }
 }
@@ -110,25 +402,38 @@ impl From<Item> for ItemRow {
             alt_id: string_to_uuid(&item.id),
             name: item.name,
             description: item.description,
-            unit_price: BigDecimal::from_f64(item.unit_price).unwrap(),
+            unit_price: item.unit_price.amount,
+            currency: item.unit_price.currency,
             created_by: item.audit_info.created_by,
             created_at: item.audit_info.created_at,
             last_changed_by: item.audit_info.changed_by,
             last_update: item.audit_info.updated_at,
+            deleted_at: item.audit_info.deleted_at,
+            version: item.version,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::inventory::model::{AuditInfo, CreateItemRequest, Item, UpdateItemRequest};
-    use crate::inventory::repositories::item::{ItemRow, MockItemRepository};
+    use crate::inventory::model::money::{Currency, Money};
+    use crate::inventory::model::page::Cursor;
+    use crate::inventory::model::sort::Sort;
+    use crate::inventory::model::{
+        AuditInfo, CreateItemRequest, ExpectedVersion, Item, ItemSearchQuery, Pagination,
+        UpdateItemRequest,
+    };
+    use crate::inventory::repositories::item::{ItemRow, ItemSearchRow, MockItemRepository};
+    use crate::inventory::repositories::stock::{MockStockRepository, StockRow};
+    use crate::inventory::repositories::RepoError;
     use crate::inventory::services::item::{ItemService, ItemServiceImpl};
+    use crate::inventory::services::ServiceError;
     use crate::inventory::services::ServiceError::InputValidationError;
     use crate::test_helpers::{init, string_to_uuid};
-    use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+    use bigdecimal::BigDecimal;
     use chrono::Utc;
     use mockall::predicate::eq;
+    use serde_json::json;
     use std::sync::Arc;
     use uuid::Uuid;
 
@@ -138,13 +443,17 @@ mod tests {
             id: uuid.to_string(),
             name: "item".to_string(),
             description: "item description".to_string(),
-            unit_price: 10.0,
+            unit_price: Money::new(BigDecimal::from(10), Currency::USD),
             audit_info: AuditInfo {
                 created_by: "unit_test".to_string(),
                 created_at: Utc::now(),
                 changed_by: "unit_test".to_string(),
                 updated_at: Utc::now(),
+                deleted_at: None,
             },
+            stock: None,
+            version: 1,
+            search_match: None,
         }
     }
     #[tokio::test]
@@ -154,20 +463,26 @@ mod tests {
         let item = CreateItemRequest {
             name: "item".to_string(),
             description: "item description".to_string(),
-            unit_price: 10.0,
+            unit_price: Money::new(BigDecimal::from(10), Currency::USD),
             created_by: "user".to_string(),
         };
-        let item_clone = item.clone();
+        let item_clone = CreateItemRequest {
+            created_by: "forged".to_string(),
+            ..item.clone()
+        };
         let item_row = ItemRow {
             id: 1,
             alt_id: string_to_uuid("00000000-0000-0000-0000-000000000001"),
             name: item.name.clone(),
             description: item.description.clone(),
-            unit_price: BigDecimal::from_f64(item.unit_price).unwrap(),
+            unit_price: item.unit_price.amount.clone(),
+            currency: Currency::USD,
             created_by: item.created_by.clone(),
             created_at: Utc::now(),
             last_changed_by: item.created_by.clone(),
             last_update: Utc::now(),
+            deleted_at: None,
+            version: 1,
         };
         mock.expect_create_item()
             .with(eq(item))
@@ -176,13 +491,13 @@ mod tests {
                 let cloned_row = item_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
-        let service = ItemServiceImpl::new(Arc::new(mock));
-        let result = service.create_item(item_clone).await;
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let result = service.create_item(item_clone, "user".to_string()).await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.name, "item");
         assert_eq!(item.description, "item description");
-        assert_eq!(item.unit_price, 10.0);
+        assert_eq!(item.unit_price.amount, BigDecimal::from(10));
         assert_eq!(item.audit_info.created_by, "user");
         assert_eq!(item.audit_info.changed_by, "user");
     }
@@ -196,22 +511,67 @@ mod tests {
         let expected_results = create_item(uuid, seq);
         let item_row = ItemRow::from(expected_results.clone());
         mock.expect_get_item_by_uuid()
-            .with(eq(uuid))
+            .with(eq(uuid), eq(false))
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = item_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
-        let service = ItemServiceImpl::new(Arc::new(mock));
-        let result = service.get_item_by_id(uuid).await;
+        let mut stock_mock = MockStockRepository::new();
+        stock_mock
+            .expect_get_stock()
+            .with(eq(uuid))
+            .times(1)
+            .returning(move |item_id| {
+                Box::pin(async move {
+                    Ok(StockRow {
+                        item_id,
+                        on_hand: 12,
+                        reserved: 2,
+                        available: 10,
+                        last_changed_by: None,
+                        last_update: None,
+                    })
+                })
+            });
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(stock_mock));
+        let result = service.get_item_by_id(uuid, false).await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.id, expected_results.id);
         assert_eq!(item.name, expected_results.name);
         assert_eq!(item.description, expected_results.description);
-        assert_eq!(item.unit_price, expected_results.unit_price.to_f64().unwrap());
-        assert_eq!(item.audit_info.created_by, expected_results.audit_info.created_by);
-        assert_eq!(item.audit_info.changed_by, expected_results.audit_info.changed_by);
+        assert_eq!(item.unit_price, expected_results.unit_price);
+        assert_eq!(
+            item.audit_info.created_by,
+            expected_results.audit_info.created_by
+        );
+        assert_eq!(
+            item.audit_info.changed_by,
+            expected_results.audit_info.changed_by
+        );
+        let stock = item.stock.expect("stock should be populated");
+        assert_eq!(stock.on_hand, 12);
+        assert_eq!(stock.available, 10);
+    }
+
+    #[tokio::test]
+    async fn test_get_item_by_id_stock_error() {
+        init();
+        let mut mock = MockItemRepository::new();
+        let uuid = Uuid::new_v4();
+        let item_row = ItemRow::from(create_item(uuid, 1));
+        mock.expect_get_item_by_uuid().returning(move |_, _| {
+            let cloned_row = item_row.clone();
+            Box::pin(async move { Ok(cloned_row) })
+        });
+        let mut stock_mock = MockStockRepository::new();
+        stock_mock
+            .expect_get_stock()
+            .returning(|_| Box::pin(async { Err(RepoError::Other("boom".to_string())) }));
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(stock_mock));
+        let result = service.get_item_by_id(uuid, false).await;
+        assert!(matches!(result, Err(ServiceError::UnexpectedError(_))));
     }
 
     #[tokio::test]
@@ -222,28 +582,38 @@ mod tests {
             id: "00000000-0000-0000-0000-000000000001".to_string(),
             name: "item".to_string(),
             description: "item description".to_string(),
-            unit_price: 10.0,
+            unit_price: Money::new(BigDecimal::from(10), Currency::USD),
             changed_by: "unit_test".to_string(),
+            version: ExpectedVersion::Any,
+        };
+        let item_req_clone = UpdateItemRequest {
+            changed_by: "forged".to_string(),
+            ..item_request.clone()
         };
-        let item_req_clone = item_request.clone();
         let item_row = ItemRow {
             id: 1,
             alt_id: string_to_uuid("00000000-0000-0000-0000-000000000001"),
             name: item_request.name.clone(),
             description: item_request.description.clone(),
-            unit_price: BigDecimal::from_f64(item_request.unit_price).unwrap(),
+            unit_price: item_request.unit_price.amount.clone(),
+            currency: Currency::USD,
             created_by: "unit_test".to_string(),
             created_at: Utc::now(),
             last_changed_by: "unit_test".to_string(),
             last_update: Utc::now(),
+            deleted_at: None,
+            version: 1,
         };
         mock.expect_update_item()
+            .withf(|item| item.changed_by == "unit_test")
             .returning(move |_| {
                 let cloned_row = item_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
-        let service = ItemServiceImpl::new(Arc::new(mock));
-        let result = service.update_item(item_req_clone).await;
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let result = service
+            .update_item(item_req_clone, "unit_test".to_string())
+            .await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.name, item_request.name);
@@ -253,6 +623,196 @@ mod tests {
         assert_eq!(item.audit_info.changed_by, item_request.changed_by);
     }
 
+    #[tokio::test]
+    async fn test_patch_item() {
+        init();
+        let mut mock = MockItemRepository::new();
+        let uuid = Uuid::new_v4();
+        let current = create_item(uuid, 1);
+        let item_row = ItemRow::from(current.clone());
+        let name = current.name.clone();
+        mock.expect_get_item_by_uuid()
+            .with(eq(uuid), eq(false))
+            .returning(move |_, _| {
+                let cloned_row = item_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        let updated_row = ItemRow::from(current);
+        mock.expect_update_item()
+            .withf(move |item| {
+                item.name == name
+                    && item.unit_price == Money::new("9.99".parse().unwrap(), Currency::USD)
+                    && item.version == ExpectedVersion::OneOf(vec![2])
+                    && item.changed_by == "unit_test"
+            })
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = updated_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        // the currency is kept, as only the amount of the price is in the patch
+        let result = service
+            .patch_item(
+                uuid,
+                json!({"unit_price": {"amount": "9.99"}}),
+                ExpectedVersion::OneOf(vec![2]),
+                "unit_test".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_search_items() {
+        init();
+        let mut mock = MockItemRepository::new();
+        let uuid = Uuid::new_v4();
+        mock.expect_search_items()
+            .withf(|search, sort, seek, _| {
+                search.q.as_deref() == Some("widget")
+                    && sort.is_empty()
+                    && seek.after.is_none()
+                    && seek.limit == 2
+            })
+            .times(1)
+            .returning(move |_, _, _, _| {
+                let row = ItemSearchRow {
+                    id: 1,
+                    alt_id: uuid,
+                    name: "Blue widget".to_string(),
+                    description: "A small widget".to_string(),
+                    unit_price: BigDecimal::from(15),
+                    currency: Currency::USD,
+                    created_by: "unit_test".to_string(),
+                    created_at: Utc::now(),
+                    last_changed_by: "unit_test".to_string(),
+                    last_update: Utc::now(),
+                    deleted_at: None,
+                    version: 1,
+                    rank: Some(0.6),
+                    name_highlight: Some("Blue <mark>widget</mark>".to_string()),
+                    description_highlight: Some("A small <mark>widget</mark>".to_string()),
+                };
+                let next_row = ItemSearchRow {
+                    id: 2,
+                    rank: Some(0.3),
+                    ..row.clone()
+                };
+                Box::pin(async move { Ok(vec![row, next_row]) })
+            });
+        mock.expect_count_items()
+            .withf(|search, _| search.q.as_deref() == Some("widget"))
+            .times(1)
+            .returning(|_, _| Box::pin(async move { Ok(2) }));
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let search = ItemSearchQuery {
+            q: Some("  widget ".to_string()),
+            ..ItemSearchQuery::default()
+        };
+        let pagination = Pagination {
+            cursor: None,
+            page_size: Some(1),
+        };
+        let page = service
+            .search_items(search, Some(pagination), Sort::default(), false, true)
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, uuid.to_string());
+        let search_match = page.items[0].search_match.as_ref().unwrap();
+        assert_eq!(search_match.name, "Blue <mark>widget</mark>");
+        assert!(page.has_more);
+        assert_eq!(page.total, Some(2));
+        // the next page carries on from the last item's relevance
+        let next = Cursor::decode(&page.next_cursor.unwrap()).unwrap();
+        assert_eq!(
+            next,
+            Cursor {
+                rank: Some(0.6),
+                ..Cursor::after_id(1)
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_items_invalid_filters() {
+        init();
+        let mut mock = MockItemRepository::new();
+        mock.expect_search_items().never();
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        for search in [
+            ItemSearchQuery {
+                q: Some(" ".to_string()),
+                ..ItemSearchQuery::default()
+            },
+            ItemSearchQuery {
+                min_price: Some(BigDecimal::from(-1)),
+                ..ItemSearchQuery::default()
+            },
+            ItemSearchQuery {
+                min_price: Some(BigDecimal::from(50)),
+                max_price: Some(BigDecimal::from(10)),
+                ..ItemSearchQuery::default()
+            },
+        ] {
+            let result = service
+                .search_items(search, None, Sort::default(), false, false)
+                .await;
+            assert!(matches!(result, Err(InputValidationError(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_items_cursor_without_rank() {
+        init();
+        let mut mock = MockItemRepository::new();
+        mock.expect_search_items().never();
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let search = ItemSearchQuery {
+            q: Some("widget".to_string()),
+            ..ItemSearchQuery::default()
+        };
+        // a cursor from a list that isn't ranked by relevance
+        let pagination = Pagination {
+            cursor: Some(Cursor::after_id(1).encode()),
+            page_size: None,
+        };
+        let result = service
+            .search_items(search, Some(pagination), Sort::default(), false, false)
+            .await;
+        assert!(matches!(result, Err(InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_search_items_cursor_with_rank_and_no_keywords() {
+        init();
+        let mut mock = MockItemRepository::new();
+        mock.expect_search_items().never();
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        // a cursor from a keyword search, sent back without the keywords
+        let pagination = Pagination {
+            cursor: Some(
+                Cursor {
+                    rank: Some(0.6),
+                    ..Cursor::after_id(1)
+                }
+                .encode(),
+            ),
+            page_size: None,
+        };
+        let result = service
+            .search_items(
+                ItemSearchQuery::default(),
+                Some(pagination),
+                Sort::default(),
+                false,
+                false,
+            )
+            .await;
+        assert!(matches!(result, Err(InputValidationError(_))));
+    }
+
     #[tokio::test]
     async fn test_delete_item() {
         init();
@@ -262,25 +822,36 @@ mod tests {
             alt_id: string_to_uuid("00000000-0000-0000-0000-000000000001"),
             name: "item".to_string(),
             description: "item description".to_string(),
-            unit_price: BigDecimal::from_f64(10.0).unwrap(),
+            unit_price: BigDecimal::from(10),
+            currency: Currency::USD,
             created_by: "unit_test".to_string(),
             created_at: Utc::now(),
             last_changed_by: "unit_test".to_string(),
             last_update: Utc::now(),
+            deleted_at: None,
+            version: 1,
         };
         mock.expect_delete_item()
-            .with(eq(string_to_uuid("00000000-0000-0000-0000-000000000001")))
+            .withf(|id, deleted_by| {
+                *id == string_to_uuid("00000000-0000-0000-0000-000000000001")
+                    && deleted_by == "user"
+            })
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _| {
                 let cloned_row = item_row.clone();
                 Box::pin(async move { Ok(cloned_row) })
             });
-        let service = ItemServiceImpl::new(Arc::new(mock));
-        let result = service.delete_item(string_to_uuid("00000000-0000-0000-0000-000000000001")).await;
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let result = service
+            .delete_item(
+                string_to_uuid("00000000-0000-0000-0000-000000000001"),
+                "user".to_string(),
+            )
+            .await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.id, "00000000-0000-0000-0000-000000000001");
-        assert_eq!(item.deleted, true);
+        assert!(item.deleted);
     }
 
     #[tokio::test]
@@ -293,22 +864,34 @@ mod tests {
         let item_row = ItemRow::from(expected_results.clone());
         mock.expect_get_all_items()
             .times(1)
-            .returning(move |_| {
+            .returning(move |_, _, _| {
                 let cloned_row = item_row.clone();
                 Box::pin(async move { Ok(vec![cloned_row]) })
             });
-        let service = ItemServiceImpl::new(Arc::new(mock));
-        let result = service.get_all_items(None).await;
+        mock.expect_count_items().never();
+        let service = ItemServiceImpl::new(Arc::new(mock), Arc::new(MockStockRepository::new()));
+        let result = service
+            .get_all_items(None, Sort::default(), false, false)
+            .await;
         assert!(result.is_ok());
-        let items = result.unwrap();
+        let page = result.unwrap();
+        assert!(!page.has_more);
+        assert_eq!(page.total, None);
+        let items = page.items;
         assert_eq!(items.len(), 1);
         let item = &items[0];
         assert_eq!(item.id, expected_results.id);
         assert_eq!(item.name, expected_results.name);
         assert_eq!(item.description, expected_results.description);
-        assert_eq!(item.unit_price, expected_results.unit_price.to_f64().unwrap());
-        assert_eq!(item.audit_info.created_by, expected_results.audit_info.created_by);
-        assert_eq!(item.audit_info.changed_by, expected_results.audit_info.changed_by);
+        assert_eq!(item.unit_price, expected_results.unit_price);
+        assert_eq!(
+            item.audit_info.created_by,
+            expected_results.audit_info.created_by
+        );
+        assert_eq!(
+            item.audit_info.changed_by,
+            expected_results.audit_info.changed_by
+        );
     }
 
     #[tokio::test]
@@ -318,17 +901,19 @@ mod tests {
         let item = CreateItemRequest {
             name: "item".to_string(),
             description: "item description".to_string(),
-            unit_price: -10.0,
+            unit_price: Money::new(BigDecimal::from(-10), Currency::USD),
             created_by: "user".to_string(),
         };
         let item_clone = item.clone();
-        let service = ItemServiceImpl::new(Arc::new(_mock));
-        let result = service.create_item(item_clone).await;
+        let service = ItemServiceImpl::new(Arc::new(_mock), Arc::new(MockStockRepository::new()));
+        let result = service.create_item(item_clone, "user".to_string()).await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            InputValidationError(msg) => assert_eq!(msg, "Invalid input: unit_price: lower than 0\n"),
-            _ => assert!(false, "Expected InvalidPrice")
+            InputValidationError(msg) => {
+                assert_eq!(msg, "Invalid input: unit_price: lower than 0\n")
+            }
+            _ => panic!("Expected InvalidPrice"),
         };
     }
 
@@ -339,17 +924,19 @@ mod tests {
         let item = CreateItemRequest {
             name: "".to_string(),
             description: "item description".to_string(),
-            unit_price: 10.0,
+            unit_price: Money::new(BigDecimal::from(10), Currency::USD),
             created_by: "user".to_string(),
         };
         let item_clone = item.clone();
-        let service = ItemServiceImpl::new(Arc::new(_mock));
-        let result = service.create_item(item_clone).await;
+        let service = ItemServiceImpl::new(Arc::new(_mock), Arc::new(MockStockRepository::new()));
+        let result = service.create_item(item_clone, "user".to_string()).await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            InputValidationError(msg) => assert_eq!(msg, "Invalid input: name: length is lower than 3\n"),
-            _ => assert!(false, "Expected InputValidationError")
+            InputValidationError(msg) => {
+                assert_eq!(msg, "Invalid input: name: length is lower than 3\n")
+            }
+            _ => panic!("Expected InputValidationError"),
         };
     }
-}
\ No newline at end of file
+}
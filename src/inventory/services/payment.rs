@@ -0,0 +1,218 @@
+use crate::inventory::model::money::Money;
+use crate::inventory::model::page::{Cursor, Page};
+use crate::inventory::model::{CreatePaymentRequest, Pagination, Payment};
+use crate::inventory::repositories::payment::{PaymentRepository, PaymentRow};
+use crate::inventory::services::{into_page, seek, ServiceError};
+use async_trait::async_trait;
+use garde::Validate;
+use mockall::automock;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[async_trait]
+#[automock]
+pub trait PaymentService: Debug + Send + Sync + 'static {
+    async fn record_payment(
+        &self,
+        create_payment_request: CreatePaymentRequest,
+        created_by: String,
+    ) -> Result<Payment, ServiceError>;
+    async fn get_payments(
+        &self,
+        invoice_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<Payment>, ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct PaymentServiceImpl {
+    payment_repo: Arc<dyn PaymentRepository + Send + Sync>,
+}
+
+impl PaymentServiceImpl {
+    pub fn new(payment_repo: Arc<dyn PaymentRepository + Send + Sync>) -> Self {
+        Self { payment_repo }
+    }
+}
+
+#[async_trait]
+impl PaymentService for PaymentServiceImpl {
+    #[instrument]
+    async fn record_payment(
+        &self,
+        mut create_payment_request: CreatePaymentRequest,
+        created_by: String,
+    ) -> Result<Payment, ServiceError> {
+        create_payment_request.created_by = created_by;
+        create_payment_request.validate()?;
+        let results = self
+            .payment_repo
+            .record_payment(&create_payment_request)
+            .await;
+        results.map(Payment::from).map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn get_payments(
+        &self,
+        invoice_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<Payment>, ServiceError> {
+        let seek = seek(maybe_pagination)?;
+        let rows = self.payment_repo.get_payments(invoice_id, &seek).await?;
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &PaymentRow| Cursor::after_id(row.id),
+            None,
+        ))
+    }
+}
+
+impl From<PaymentRow> for Payment {
+    fn from(row: PaymentRow) -> Self {
+        Payment {
+            seq: row.id,
+            id: row.alt_id.to_string(),
+            invoice_id: row.invoice_id.to_string(),
+            amount: Money::new(row.amount, row.currency),
+            method: row.method,
+            reference: row.reference,
+            paid_at: row.paid_at,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::model::money::Currency;
+    use crate::inventory::model::page::Seek;
+    use crate::inventory::model::PaymentMethod;
+    use crate::inventory::repositories::payment::MockPaymentRepository;
+    use crate::inventory::repositories::RepoError;
+    use bigdecimal::BigDecimal;
+    use mockall::predicate::*;
+
+    fn create_payment_row(invoice_id: Uuid, amount: &str) -> PaymentRow {
+        PaymentRow {
+            id: 1,
+            alt_id: Uuid::new_v4(),
+            invoice_id,
+            amount: amount.parse().unwrap(),
+            currency: Currency::USD,
+            method: PaymentMethod::Card,
+            reference: Some("auth-123".to_string()),
+            paid_at: chrono::Utc::now(),
+            created_by: "testuser".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn create_payment_request(invoice_id: Uuid, amount: &str) -> CreatePaymentRequest {
+        CreatePaymentRequest {
+            invoice_id,
+            amount: Money::new(amount.parse().unwrap(), Currency::USD),
+            method: PaymentMethod::Card,
+            reference: Some("auth-123".to_string()),
+            ..CreatePaymentRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_payment() {
+        let mut mock = MockPaymentRepository::new();
+        let invoice_id = Uuid::new_v4();
+        let row = create_payment_row(invoice_id, "40.00");
+        mock.expect_record_payment()
+            .withf(move |payment| {
+                payment.invoice_id == invoice_id && payment.created_by == "testuser"
+            })
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = PaymentServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_payment(
+                create_payment_request(invoice_id, "40.00"),
+                "testuser".to_string(),
+            )
+            .await;
+        let payment = result.unwrap();
+        assert_eq!(payment.invoice_id, invoice_id.to_string());
+        assert_eq!(
+            payment.amount,
+            Money::new("40.00".parse().unwrap(), Currency::USD)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_zero_payment() {
+        let mut mock = MockPaymentRepository::new();
+        mock.expect_record_payment().never();
+
+        let service = PaymentServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_payment(
+                create_payment_request(Uuid::new_v4(), "0"),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_record_payment_exceeding_balance() {
+        let mut mock = MockPaymentRepository::new();
+        mock.expect_record_payment().times(1).returning(|_| {
+            Box::pin(async { Err(RepoError::ExceedsBalance("too much".to_string())) })
+        });
+
+        let service = PaymentServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_payment(
+                create_payment_request(Uuid::new_v4(), "500.00"),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::ExceedsBalance(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_payments() {
+        let mut mock = MockPaymentRepository::new();
+        let invoice_id = Uuid::new_v4();
+        let rows = vec![
+            create_payment_row(invoice_id, "40.00"),
+            create_payment_row(invoice_id, "60.00"),
+        ];
+        mock.expect_get_payments()
+            .with(
+                eq(invoice_id),
+                eq(Seek {
+                    after: None,
+                    limit: 11,
+                }),
+            )
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_rows = rows.clone();
+                Box::pin(async move { Ok(cloned_rows) })
+            });
+
+        let service = PaymentServiceImpl::new(Arc::new(mock));
+        let payments = service.get_payments(invoice_id, None).await.unwrap();
+        assert_eq!(payments.items.len(), 2);
+        assert_eq!(
+            payments.items[1].amount.amount,
+            "60.00".parse::<BigDecimal>().unwrap()
+        );
+    }
+}
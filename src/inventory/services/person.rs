@@ -1,9 +1,16 @@
-use crate::inventory::model::{AuditInfo, CreatePersonRequest, Person, UpdatePersonRequest};
+use crate::inventory::model::page::Page;
+use crate::inventory::model::sort::{PersonSortField, Sort};
+use crate::inventory::model::{
+    AuditInfo, CreatePersonRequest, ExpectedVersion, Pagination, Person, UpdatePersonRequest,
+};
 use crate::inventory::repositories::person::{PersonRepository, PersonRow};
-use crate::inventory::services::ServiceError;
+use crate::inventory::services::{
+    into_page, merge_patch, sort_cursor, sorted_seek, timestamp_key, ServiceError,
+};
 use crate::test_helpers::string_to_uuid;
 use async_trait::async_trait;
 use garde::Validate;
+use serde_json::Value;
 use std::fmt::Debug;
 use std::sync::Arc;
 use tracing::instrument;
@@ -12,21 +19,45 @@ use uuid::Uuid;
 #[async_trait]
 #[mockall::automock]
 pub trait PersonService: Sync + Send + Debug + 'static {
-    async fn get_person(&self, id: Uuid) -> Result<Person, ServiceError>;
+    async fn get_person(&self, id: Uuid, include_deleted: bool) -> Result<Person, ServiceError>;
     async fn get_persons(
         &self,
-        last_id: Option<i32>,
-        page_size: i64,
-    ) -> Result<Vec<Person>, ServiceError>;
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<PersonSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Person>, ServiceError>;
     async fn create_person(
         &self,
         create_person_request: CreatePersonRequest,
+        created_by: String,
     ) -> Result<Person, ServiceError>;
     async fn update_person(
         &self,
         update_person_request: UpdatePersonRequest,
+        changed_by: String,
     ) -> Result<Person, ServiceError>;
-    async fn delete_person(&self, id: Uuid) -> Result<(), ServiceError>;
+    /// Changes only what `patch`, a JSON Merge Patch of the person's UpdatePersonRequest, sets;
+    /// refused unless `version` accepts the person's current version
+    async fn patch_person(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
+    ) -> Result<Person, ServiceError>;
+    async fn delete_person(&self, id: Uuid, deleted_by: String) -> Result<(), ServiceError>;
+    async fn restore_person(&self, id: Uuid, restored_by: String) -> Result<Person, ServiceError>;
+}
+
+/// A person's value of a sort field, as kept in a cursor
+fn sort_value(row: &PersonRow, field: PersonSortField) -> String {
+    match field {
+        PersonSortField::Name => row.name.clone(),
+        PersonSortField::Email => row.email.clone(),
+        PersonSortField::CreatedAt => timestamp_key(&row.created_at),
+        PersonSortField::LastUpdate => timestamp_key(&row.last_update),
+    }
 }
 
 #[derive(Debug)]
@@ -43,8 +74,11 @@ impl PersonServiceImpl {
 #[async_trait]
 impl PersonService for PersonServiceImpl {
     #[instrument]
-    async fn get_person(&self, id: Uuid) -> Result<Person, ServiceError> {
-        let results = self.person_repo.get_person_by_uuid(id).await;
+    async fn get_person(&self, id: Uuid, include_deleted: bool) -> Result<Person, ServiceError> {
+        let results = self
+            .person_repo
+            .get_person_by_uuid(id, include_deleted)
+            .await;
         match results {
             Ok(person) => Ok(person.into()),
             Err(e) => Err(e.into()),
@@ -54,21 +88,36 @@ impl PersonService for PersonServiceImpl {
     #[instrument]
     async fn get_persons(
         &self,
-        last_id: Option<i32>,
-        page_size: i64,
-    ) -> Result<Vec<Person>, ServiceError> {
-        let results = self.person_repo.get_all_persons(last_id, page_size).await;
-        match results {
-            Ok(persons) => Ok(persons.into_iter().map(Person::from).collect()),
-            Err(e) => Err(e.into()),
-        }
+        maybe_pagination: Option<Pagination>,
+        sort: Sort<PersonSortField>,
+        include_deleted: bool,
+        include_total: bool,
+    ) -> Result<Page<Person>, ServiceError> {
+        let seek = sorted_seek(maybe_pagination, &sort)?;
+        let rows = self
+            .person_repo
+            .get_all_persons(&sort, &seek, include_deleted)
+            .await?;
+        let total = if include_total {
+            Some(self.person_repo.count_persons(include_deleted).await?)
+        } else {
+            None
+        };
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &PersonRow| sort_cursor(row.id, &sort, |field| sort_value(row, field)),
+            total,
+        ))
     }
 
     #[instrument]
     async fn create_person(
         &self,
-        create_person_request: CreatePersonRequest,
+        mut create_person_request: CreatePersonRequest,
+        created_by: String,
     ) -> Result<Person, ServiceError> {
+        create_person_request.created_by = created_by;
         // validate CreatePersonRequest.email
         if let Err(e) = create_person_request.validate() {
             // convert the error to a ServiceError and return it
@@ -84,8 +133,10 @@ impl PersonService for PersonServiceImpl {
     #[instrument]
     async fn update_person(
         &self,
-        update_person_request: UpdatePersonRequest,
+        mut update_person_request: UpdatePersonRequest,
+        changed_by: String,
     ) -> Result<Person, ServiceError> {
+        update_person_request.changed_by = changed_by;
         if let Err(e) = update_person_request.validate() {
             return Err(e.into());
         }
@@ -97,13 +148,49 @@ impl PersonService for PersonServiceImpl {
     }
 
     #[instrument]
-    async fn delete_person(&self, id: Uuid) -> Result<(), ServiceError> {
-        let results = self.person_repo.delete_person(id).await;
+    async fn patch_person(
+        &self,
+        id: Uuid,
+        patch: Value,
+        version: ExpectedVersion,
+        changed_by: String,
+    ) -> Result<Person, ServiceError> {
+        let current = self.person_repo.get_person_by_uuid(id, false).await?;
+        let current = UpdatePersonRequest {
+            id: current.alt_id.to_string(),
+            name: current.name,
+            email: current.email,
+            changed_by: String::new(),
+            version: ExpectedVersion::Any,
+        };
+        let mut update_person_request = merge_patch(&current, patch)?;
+        if update_person_request.id != current.id {
+            return Err(ServiceError::InputValidationError(format!(
+                "id can't be changed. path: {}, patch: {}",
+                current.id, update_person_request.id
+            )));
+        }
+        update_person_request.version = version;
+        self.update_person(update_person_request, changed_by).await
+    }
+
+    #[instrument]
+    async fn delete_person(&self, id: Uuid, deleted_by: String) -> Result<(), ServiceError> {
+        let results = self.person_repo.delete_person(id, &deleted_by).await;
         match results {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
         }
     }
+
+    #[instrument]
+    async fn restore_person(&self, id: Uuid, restored_by: String) -> Result<Person, ServiceError> {
+        let results = self.person_repo.restore_person(id, &restored_by).await;
+        match results {
+            Ok(person) => Ok(person.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
 }
 
 impl From<PersonRow> for Person {
@@ -118,7 +205,9 @@ impl From<PersonRow> for Person {
                 created_at: person_row.created_at,
                 changed_by: person_row.last_changed_by,
                 updated_at: person_row.last_update,
+                deleted_at: person_row.deleted_at,
             },
+            version: person_row.version,
         }
     }
 }
@@ -134,17 +223,25 @@ impl From<Person> for PersonRow {
             created_at: person.audit_info.created_at,
             last_changed_by: person.audit_info.changed_by,
             last_update: person.audit_info.updated_at,
+            deleted_at: person.audit_info.deleted_at,
+            version: person.version,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::inventory::model::{AuditInfo, CreatePersonRequest, Person, UpdatePersonRequest};
+    use crate::inventory::model::page::Cursor;
+    use crate::inventory::model::sort::{PersonSortField, Sort, SortKey};
+    use crate::inventory::model::{
+        AuditInfo, CreatePersonRequest, ExpectedVersion, Pagination, Person, UpdatePersonRequest,
+    };
     use crate::inventory::repositories::person::{MockPersonRepository, PersonRow};
     use crate::inventory::services::person::{PersonService, PersonServiceImpl};
     use crate::inventory::services::ServiceError;
     use crate::test_helpers::init;
+    use mockall::predicate::eq;
+    use serde_json::json;
     use std::sync::Arc;
     use uuid::Uuid;
 
@@ -152,16 +249,18 @@ mod tests {
         let name = format!("Test Person {}", uuid);
         let email = format!("{}@testing.com", uuid);
         Person {
-            seq: seq,
+            seq,
             id: uuid.to_string(),
-            name: name,
+            name,
             email: email.clone(),
             audit_info: AuditInfo {
                 created_by: "testuser".to_string(),
                 created_at: chrono::Utc::now(),
                 changed_by: "testuser".to_string(),
                 updated_at: chrono::Utc::now(),
+                deleted_at: None,
             },
+            version: 1,
         }
     }
     #[tokio::test]
@@ -172,12 +271,14 @@ mod tests {
         let seq = 1;
         let expected_results = create_person(uuid, seq);
         let mock_expected_results = PersonRow::from(expected_results.clone());
-        mock_repo.expect_get_person_by_uuid().returning(move |_| {
-            let cloned_results = mock_expected_results.clone();
-            Box::pin(async move { Ok(cloned_results) })
-        });
+        mock_repo
+            .expect_get_person_by_uuid()
+            .returning(move |_, _| {
+                let cloned_results = mock_expected_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
         let service = super::PersonServiceImpl::new(Arc::new(mock_repo));
-        let result = service.get_person(uuid).await;
+        let result = service.get_person(uuid, false).await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.id, expected_results.id);
@@ -191,16 +292,101 @@ mod tests {
         let seq = 1;
         let expected_results = create_person(uuid, seq);
         let mock_results = vec![PersonRow::from(expected_results.clone())];
-        mock_repo.expect_get_all_persons().returning(move |_, _| {
-            let cloned_results = mock_results.clone();
-            Box::pin(async move { Ok(cloned_results) })
-        });
+        mock_repo
+            .expect_get_all_persons()
+            .withf(|sort, seek, _| sort.is_empty() && seek.after.is_none() && seek.limit == 101)
+            .returning(move |_, _, _| {
+                let cloned_results = mock_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
+        mock_repo
+            .expect_count_persons()
+            .with(eq(false))
+            .returning(|_| Box::pin(async move { Ok(1) }));
         let service = PersonServiceImpl::new(Arc::new(mock_repo));
-        let result = service.get_persons(None, 100).await;
+        let pagination = Pagination {
+            cursor: None,
+            page_size: Some(100),
+        };
+        let result = service
+            .get_persons(Some(pagination), Sort::default(), false, true)
+            .await;
         assert!(result.is_ok());
-        let persons = result.unwrap();
-        assert_eq!(persons.len(), 1);
-        assert_eq!(persons[0].id, expected_results.id);
+        let page = result.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, expected_results.id);
+        assert!(!page.has_more);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.total, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_get_persons_next_page() {
+        init();
+        let mut mock_repo = MockPersonRepository::new();
+        let rows: Vec<PersonRow> = (1..=3)
+            .map(|seq| PersonRow::from(create_person(Uuid::new_v4(), seq)))
+            .collect();
+        mock_repo
+            .expect_get_all_persons()
+            .withf(|_, seek, _| seek.after == Some(Cursor::after_id(7)) && seek.limit == 3)
+            .returning(move |_, _, _| {
+                let cloned_rows = rows.clone();
+                Box::pin(async move { Ok(cloned_rows) })
+            });
+        mock_repo.expect_count_persons().never();
+        let service = PersonServiceImpl::new(Arc::new(mock_repo));
+        let pagination = Pagination {
+            cursor: Some(Cursor::after_id(7).encode()),
+            page_size: Some(2),
+        };
+        let page = service
+            .get_persons(Some(pagination), Sort::default(), false, false)
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 2);
+        assert!(page.has_more);
+        assert_eq!(page.total, None);
+        // the next page starts after the last person on this one
+        let next = Cursor::decode(&page.next_cursor.unwrap()).unwrap();
+        assert_eq!(next, Cursor::after_id(2));
+    }
+
+    #[tokio::test]
+    async fn test_get_persons_sorted() {
+        init();
+        let mut mock_repo = MockPersonRepository::new();
+        let rows: Vec<PersonRow> = (1..=3)
+            .map(|seq| PersonRow::from(create_person(Uuid::new_v4(), seq)))
+            .collect();
+        let last_kept = rows[1].clone();
+        mock_repo
+            .expect_get_all_persons()
+            .withf(|sort, seek, _| {
+                sort.keys()
+                    == [SortKey {
+                        field: PersonSortField::Name,
+                        descending: true,
+                    }]
+                    && seek.after.is_none()
+            })
+            .returning(move |_, _, _| {
+                let cloned_rows = rows.clone();
+                Box::pin(async move { Ok(cloned_rows) })
+            });
+        let service = PersonServiceImpl::new(Arc::new(mock_repo));
+        let pagination = Pagination {
+            cursor: None,
+            page_size: Some(2),
+        };
+        let page = service
+            .get_persons(Some(pagination), "-name".parse().unwrap(), false, false)
+            .await
+            .unwrap();
+        // the cursor carries the sort key of the last person, to continue from it
+        let next = Cursor::decode(&page.next_cursor.unwrap()).unwrap();
+        assert_eq!(next.id, last_kept.id);
+        assert_eq!(next.keys, vec![last_kept.name]);
     }
 
     #[tokio::test]
@@ -211,17 +397,20 @@ mod tests {
         let seq = 1;
         let expected_results = create_person(uuid, seq);
         let mock_expected_results = PersonRow::from(expected_results.clone());
-        mock_repo.expect_create_person().returning(move |_| {
-            let cloned_results = mock_expected_results.clone();
-            Box::pin(async move { Ok(cloned_results) })
-        });
+        mock_repo
+            .expect_create_person()
+            .withf(|person| person.created_by == "test")
+            .returning(move |_| {
+                let cloned_results = mock_expected_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
         let service = PersonServiceImpl::new(Arc::new(mock_repo));
         let request = CreatePersonRequest {
             name: expected_results.name.clone(),
             email: expected_results.email.clone(),
-            created_by: "test".to_string(),
+            created_by: "forged".to_string(),
         };
-        let result = service.create_person(request).await;
+        let result = service.create_person(request, "test".to_string()).await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.id, expected_results.id);
@@ -238,12 +427,12 @@ mod tests {
             email: "test".to_string(),
             created_by: "test".to_string(),
         };
-        let result = service.create_person(request).await;
+        let result = service.create_person(request, "test".to_string()).await;
         assert!(result.is_err());
         match result {
             Err(e) => match e {
-                ServiceError::InputValidationError(_) => assert!(true),
-                _ => assert!(false, "Expected InputValidationError, got {:?}", e),
+                ServiceError::InputValidationError(_) => (),
+                _ => panic!("Expected InputValidationError, got {:?}", e),
             },
             _ => panic!("Expected an error"),
         }
@@ -257,23 +446,102 @@ mod tests {
         let seq = 1;
         let expected_results = create_person(uuid, seq);
         let mock_expected_results = PersonRow::from(expected_results.clone());
-        mock_repo.expect_update_person().returning(move |_| {
-            let cloned_results = mock_expected_results.clone();
-            Box::pin(async move { Ok(cloned_results) })
-        });
+        mock_repo
+            .expect_update_person()
+            .withf(|person| person.changed_by == "test")
+            .returning(move |_| {
+                let cloned_results = mock_expected_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
         let service = PersonServiceImpl::new(Arc::new(mock_repo));
         let request = UpdatePersonRequest {
             id: expected_results.id.clone(),
             name: expected_results.name.clone(),
             email: expected_results.email.clone(),
-            changed_by: "test".to_string(),
+            changed_by: "forged".to_string(),
+            version: ExpectedVersion::Any,
         };
-        let result = service.update_person(request).await;
+        let result = service.update_person(request, "test".to_string()).await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.id, expected_results.id);
     }
 
+    #[tokio::test]
+    async fn test_patch_person() {
+        init();
+        let mut mock_repo = MockPersonRepository::new();
+        let uuid = Uuid::new_v4();
+        let current = create_person(uuid, 1);
+        let current_row = PersonRow::from(current.clone());
+        let name = current.name.clone();
+        mock_repo
+            .expect_get_person_by_uuid()
+            .returning(move |_, _| {
+                let cloned_row = current_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        mock_repo
+            .expect_update_person()
+            .withf(move |person| {
+                person.id == uuid.to_string()
+                    && person.name == name
+                    && person.email == "patched@testing.com"
+                    && person.version == ExpectedVersion::OneOf(vec![3])
+                    && person.changed_by == "test"
+            })
+            .times(1)
+            .returning(move |_| {
+                let row = PersonRow::from(create_person(uuid, 1));
+                Box::pin(async move { Ok(row) })
+            });
+        let service = PersonServiceImpl::new(Arc::new(mock_repo));
+        let result = service
+            .patch_person(
+                uuid,
+                json!({"email": "patched@testing.com"}),
+                ExpectedVersion::OneOf(vec![3]),
+                "test".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_patch_person_cannot_change_id() {
+        init();
+        let mut mock_repo = MockPersonRepository::new();
+        let uuid = Uuid::new_v4();
+        let current_row = PersonRow::from(create_person(uuid, 1));
+        mock_repo
+            .expect_get_person_by_uuid()
+            .returning(move |_, _| {
+                let cloned_row = current_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+        mock_repo.expect_update_person().never();
+        let service = PersonServiceImpl::new(Arc::new(mock_repo));
+        let result = service
+            .patch_person(
+                uuid,
+                json!({"id": Uuid::new_v4().to_string()}),
+                ExpectedVersion::OneOf(vec![1]),
+                "test".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+        // removing a field that every person has is refused too
+        let result = service
+            .patch_person(
+                uuid,
+                json!({"name": null}),
+                ExpectedVersion::OneOf(vec![1]),
+                "test".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
     #[tokio::test]
     async fn test_update_person_invalid_email() {
         init();
@@ -285,13 +553,14 @@ mod tests {
             name: "Test Person".to_string(),
             email: "test".to_string(),
             changed_by: "test".to_string(),
+            version: ExpectedVersion::Any,
         };
-        let result = service.update_person(request).await;
+        let result = service.update_person(request, "test".to_string()).await;
         assert!(result.is_err());
         match result {
             Err(e) => match e {
-                ServiceError::InputValidationError(_) => assert!(true),
-                _ => assert!(false, "Expected InputValidationError, got {:?}", e),
+                ServiceError::InputValidationError(_) => (),
+                _ => panic!("Expected InputValidationError, got {:?}", e),
             },
             _ => panic!("Expected an error"),
         }
@@ -305,15 +574,37 @@ mod tests {
         let seq = 1;
         let expected_results = create_person(uuid, seq);
         let mock_expected_results = PersonRow::from(expected_results.clone());
-        mock_repo.expect_delete_person().returning(move |_| {
-            let cloned_results = mock_expected_results.clone();
-            Box::pin(async move { Ok(cloned_results) })
-        });
+        mock_repo
+            .expect_delete_person()
+            .withf(move |id, deleted_by| *id == uuid && deleted_by == "test")
+            .returning(move |_, _| {
+                let cloned_results = mock_expected_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
         let service = PersonServiceImpl::new(Arc::new(mock_repo));
-        let result = service.delete_person(uuid).await;
+        let result = service.delete_person(uuid, "test".to_string()).await;
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_restore_person() {
+        init();
+        let mut mock_repo = MockPersonRepository::new();
+        let uuid = Uuid::new_v4();
+        let expected_results = create_person(uuid, 1);
+        let mock_expected_results = PersonRow::from(expected_results.clone());
+        mock_repo
+            .expect_restore_person()
+            .withf(move |id, restored_by| *id == uuid && restored_by == "test")
+            .returning(move |_, _| {
+                let cloned_results = mock_expected_results.clone();
+                Box::pin(async move { Ok(cloned_results) })
+            });
+        let service = PersonServiceImpl::new(Arc::new(mock_repo));
+        let result = service.restore_person(uuid, "test".to_string()).await;
+        assert_eq!(result.unwrap().id, expected_results.id);
+    }
+
     #[tokio::test]
     async fn test_person_from_row() {
         let row = PersonRow {
@@ -325,6 +616,8 @@ mod tests {
             created_at: chrono::Utc::now(),
             last_changed_by: "testuser".to_string(),
             last_update: chrono::Utc::now(),
+            deleted_at: None,
+            version: 1,
         };
 
         let person = Person::from(row.clone());
@@ -350,7 +643,9 @@ mod tests {
                 created_at: chrono::Utc::now(),
                 changed_by: "testuser".to_string(),
                 updated_at: chrono::Utc::now(),
+                deleted_at: None,
             },
+            version: 1,
         };
 
         let row = PersonRow::from(person.clone());
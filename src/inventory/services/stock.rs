@@ -0,0 +1,365 @@
+use crate::inventory::model::page::{Cursor, Page};
+use crate::inventory::model::{CreateStockMovementRequest, Pagination, StockLevel, StockMovement};
+use crate::inventory::repositories::stock::{StockMovementRow, StockRepository, StockRow};
+use crate::inventory::services::{into_page, seek, ServiceError};
+use async_trait::async_trait;
+use garde::Validate;
+use mockall::automock;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[async_trait]
+#[automock]
+pub trait StockService: Debug + Send + Sync + 'static {
+    async fn get_stock(&self, item_id: Uuid) -> Result<StockLevel, ServiceError>;
+    async fn record_movement(
+        &self,
+        create_stock_movement_request: CreateStockMovementRequest,
+        created_by: String,
+    ) -> Result<StockMovement, ServiceError>;
+    async fn get_movements(
+        &self,
+        item_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<StockMovement>, ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct StockServiceImpl {
+    stock_repo: Arc<dyn StockRepository + Send + Sync>,
+}
+
+impl StockServiceImpl {
+    pub fn new(stock_repo: Arc<dyn StockRepository + Send + Sync>) -> Self {
+        Self { stock_repo }
+    }
+}
+
+#[async_trait]
+impl StockService for StockServiceImpl {
+    #[instrument]
+    async fn get_stock(&self, item_id: Uuid) -> Result<StockLevel, ServiceError> {
+        let results = self.stock_repo.get_stock(item_id).await;
+        results.map(StockLevel::from).map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn record_movement(
+        &self,
+        mut create_stock_movement_request: CreateStockMovementRequest,
+        created_by: String,
+    ) -> Result<StockMovement, ServiceError> {
+        create_stock_movement_request.created_by = created_by;
+        create_stock_movement_request.validate()?;
+        let quantity = create_stock_movement_request.quantity;
+        let movement_type = create_stock_movement_request.movement_type;
+        if quantity == 0 {
+            return Err(ServiceError::InputValidationError(
+                "quantity cannot be zero".to_string(),
+            ));
+        }
+        // receipts, sales, returns and write-offs are given as unsigned counts; the ledger stores signed deltas
+        create_stock_movement_request.quantity = match movement_type.is_inbound() {
+            None => quantity,
+            Some(_) if quantity < 0 => {
+                return Err(ServiceError::InputValidationError(format!(
+                    "quantity ({}) must be positive for {:?} movements",
+                    quantity, movement_type
+                )))
+            }
+            Some(true) => quantity,
+            Some(false) => -quantity,
+        };
+        let results = self
+            .stock_repo
+            .record_movement(&create_stock_movement_request)
+            .await;
+        results.map(StockMovement::from).map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn get_movements(
+        &self,
+        item_id: Uuid,
+        maybe_pagination: Option<Pagination>,
+    ) -> Result<Page<StockMovement>, ServiceError> {
+        let seek = seek(maybe_pagination)?;
+        let rows = self.stock_repo.get_movements(item_id, &seek).await?;
+        Ok(into_page(
+            rows,
+            &seek,
+            |row: &StockMovementRow| Cursor::after_id(row.id),
+            None,
+        ))
+    }
+}
+
+impl From<StockRow> for StockLevel {
+    fn from(row: StockRow) -> Self {
+        StockLevel {
+            item_id: row.item_id.to_string(),
+            on_hand: row.on_hand,
+            reserved: row.reserved,
+            available: row.available,
+            changed_by: row.last_changed_by,
+            updated_at: row.last_update,
+        }
+    }
+}
+
+impl From<StockMovementRow> for StockMovement {
+    fn from(row: StockMovementRow) -> Self {
+        StockMovement {
+            seq: row.id,
+            id: row.alt_id.to_string(),
+            item_id: row.item_id.to_string(),
+            movement_type: row.movement_type,
+            quantity: row.quantity,
+            note: row.note,
+            created_by: row.created_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::model::page::Seek;
+    use crate::inventory::model::MovementType;
+    use crate::inventory::repositories::stock::MockStockRepository;
+    use crate::inventory::repositories::RepoError;
+    use mockall::predicate::*;
+
+    fn create_stock_row(item_id: Uuid, on_hand: i32, reserved: i32) -> StockRow {
+        StockRow {
+            item_id,
+            on_hand,
+            reserved,
+            available: on_hand - reserved,
+            last_changed_by: Some("testuser".to_string()),
+            last_update: Some(chrono::Utc::now()),
+        }
+    }
+
+    fn create_movement_row(
+        item_id: Uuid,
+        movement_type: MovementType,
+        quantity: i32,
+    ) -> StockMovementRow {
+        StockMovementRow {
+            id: 1,
+            alt_id: Uuid::new_v4(),
+            item_id,
+            movement_type,
+            quantity,
+            note: None,
+            created_by: "testuser".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn create_movement_request(
+        item_id: Uuid,
+        movement_type: MovementType,
+        quantity: i32,
+    ) -> CreateStockMovementRequest {
+        CreateStockMovementRequest {
+            item_id,
+            movement_type,
+            quantity,
+            note: None,
+            created_by: "forged".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_stock() {
+        let mut mock = MockStockRepository::new();
+        let item_id = Uuid::new_v4();
+        let expected_row = create_stock_row(item_id, 10, 4);
+        mock.expect_get_stock()
+            .with(eq(item_id))
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service.get_stock(item_id).await;
+        assert!(result.is_ok());
+        let stock = result.unwrap();
+        assert_eq!(stock.item_id, item_id.to_string());
+        assert_eq!(stock.on_hand, 10);
+        assert_eq!(stock.reserved, 4);
+        assert_eq!(stock.available, 6);
+    }
+
+    #[tokio::test]
+    async fn test_get_stock_not_found() {
+        let mut mock = MockStockRepository::new();
+        mock.expect_get_stock()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("not found".to_string())) }));
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service.get_stock(Uuid::new_v4()).await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_receipt() {
+        let mut mock = MockStockRepository::new();
+        let item_id = Uuid::new_v4();
+        let expected_row = create_movement_row(item_id, MovementType::Receipt, 25);
+        mock.expect_record_movement()
+            .withf(move |r| r.item_id == item_id && r.quantity == 25 && r.created_by == "testuser")
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(item_id, MovementType::Receipt, 25),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+        let movement = result.unwrap();
+        assert_eq!(movement.quantity, 25);
+        assert_eq!(movement.movement_type, MovementType::Receipt);
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_sale_is_negated() {
+        let mut mock = MockStockRepository::new();
+        let item_id = Uuid::new_v4();
+        let expected_row = create_movement_row(item_id, MovementType::Sale, -3);
+        mock.expect_record_movement()
+            .withf(|r| r.quantity == -3)
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(item_id, MovementType::Sale, 3),
+                "testuser".to_string(),
+            )
+            .await;
+        assert_eq!(result.unwrap().quantity, -3);
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_negative_adjustment() {
+        let mut mock = MockStockRepository::new();
+        let item_id = Uuid::new_v4();
+        let expected_row = create_movement_row(item_id, MovementType::Adjustment, -2);
+        mock.expect_record_movement()
+            .withf(|r| r.quantity == -2)
+            .times(1)
+            .returning(move |_| {
+                let cloned_row = expected_row.clone();
+                Box::pin(async move { Ok(cloned_row) })
+            });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(item_id, MovementType::Adjustment, -2),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_negative_receipt() {
+        let mut mock = MockStockRepository::new();
+        mock.expect_record_movement().never();
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(Uuid::new_v4(), MovementType::Receipt, -5),
+                "testuser".to_string(),
+            )
+            .await;
+        match result {
+            Err(ServiceError::InputValidationError(msg)) => {
+                assert_eq!(msg, "quantity (-5) must be positive for Receipt movements")
+            }
+            other => panic!("Expected InputValidationError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_zero_quantity() {
+        let mut mock = MockStockRepository::new();
+        mock.expect_record_movement().never();
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(Uuid::new_v4(), MovementType::Adjustment, 0),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InputValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_record_movement_insufficient_stock() {
+        let mut mock = MockStockRepository::new();
+        mock.expect_record_movement().times(1).returning(|_| {
+            Box::pin(async { Err(RepoError::InsufficientStock("insufficient".to_string())) })
+        });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service
+            .record_movement(
+                create_movement_request(Uuid::new_v4(), MovementType::WriteOff, 50),
+                "testuser".to_string(),
+            )
+            .await;
+        assert!(matches!(result, Err(ServiceError::InsufficientStock(_))));
+    }
+
+    #[tokio::test]
+    async fn test_get_movements() {
+        let mut mock = MockStockRepository::new();
+        let item_id = Uuid::new_v4();
+        let rows = vec![
+            create_movement_row(item_id, MovementType::Receipt, 10),
+            create_movement_row(item_id, MovementType::Sale, -4),
+        ];
+        mock.expect_get_movements()
+            .with(
+                eq(item_id),
+                eq(Seek {
+                    after: None,
+                    limit: 11,
+                }),
+            )
+            .times(1)
+            .returning(move |_, _| {
+                let cloned_rows = rows.clone();
+                Box::pin(async move { Ok(cloned_rows) })
+            });
+
+        let service = StockServiceImpl::new(Arc::new(mock));
+        let result = service.get_movements(item_id, None).await;
+        let movements = result.unwrap().items;
+        assert_eq!(movements.len(), 2);
+        assert_eq!(movements[1].quantity, -4);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::inventory::model::RedeemedRefreshToken;
+use crate::inventory::repositories::token::{RedeemedRefreshTokenRow, TokenRepository};
+use crate::inventory::repositories::RepoError;
+use crate::inventory::services::ServiceError;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use mockall::automock;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+const REFRESH_TOKEN_LENGTH: usize = 48;
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+#[async_trait]
+#[automock]
+pub trait TokenService: Debug + Send + Sync + 'static {
+    async fn record_access_token(
+        &self,
+        jti: Uuid,
+        family: Uuid,
+        client_id: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), ServiceError>;
+    async fn is_access_token_active(&self, jti: Uuid) -> Result<bool, ServiceError>;
+    /// Issues a new opaque refresh token in `family`. Only its digest is kept.
+    async fn issue_refresh_token(
+        &self,
+        client_id: String,
+        family: Uuid,
+    ) -> Result<String, ServiceError>;
+    /// Spends a refresh token. None if it is unknown, expired or revoked; presenting a token that
+    /// was already spent also revokes every token in its family, as it has probably been stolen.
+    async fn redeem_refresh_token(
+        &self,
+        refresh_token: String,
+    ) -> Result<Option<RedeemedRefreshToken>, ServiceError>;
+    async fn revoke_client_tokens(&self, client_id: String) -> Result<i64, ServiceError>;
+}
+
+#[derive(Debug)]
+pub struct TokenServiceImpl {
+    token_repo: Arc<dyn TokenRepository + Send + Sync>,
+}
+
+impl TokenServiceImpl {
+    pub fn new(token_repo: Arc<dyn TokenRepository + Send + Sync>) -> Self {
+        Self { token_repo }
+    }
+}
+
+#[async_trait]
+impl TokenService for TokenServiceImpl {
+    #[instrument]
+    async fn record_access_token(
+        &self,
+        jti: Uuid,
+        family: Uuid,
+        client_id: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), ServiceError> {
+        let results = self
+            .token_repo
+            .record_access_token(jti, family, &client_id, expires_at)
+            .await;
+        results.map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn is_access_token_active(&self, jti: Uuid) -> Result<bool, ServiceError> {
+        let results = self.token_repo.is_access_token_active(jti).await;
+        results.map_err(ServiceError::from)
+    }
+
+    #[instrument]
+    async fn issue_refresh_token(
+        &self,
+        client_id: String,
+        family: Uuid,
+    ) -> Result<String, ServiceError> {
+        let refresh_token = generate_refresh_token();
+        let expires_at = Utc::now() + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+        self.token_repo
+            .create_refresh_token(&digest(&refresh_token), family, &client_id, expires_at)
+            .await?;
+        Ok(refresh_token)
+    }
+
+    #[instrument(skip(refresh_token))]
+    async fn redeem_refresh_token(
+        &self,
+        refresh_token: String,
+    ) -> Result<Option<RedeemedRefreshToken>, ServiceError> {
+        let token_hash = digest(&refresh_token);
+        match self.token_repo.use_refresh_token(&token_hash).await {
+            Ok(row) => return Ok(Some(RedeemedRefreshToken::from(row))),
+            Err(RepoError::NotFound(_)) => (),
+            Err(e) => return Err(e.into()),
+        }
+        match self.token_repo.get_refresh_token(&token_hash).await {
+            Ok(row) if row.used_at.is_some() => {
+                warn!(
+                    "Refresh token of client {} was reused; revoking token family {}",
+                    row.client_id, row.family
+                );
+                self.token_repo.revoke_family(row.family).await?;
+                Ok(None)
+            }
+            Ok(_) | Err(RepoError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    #[instrument]
+    async fn revoke_client_tokens(&self, client_id: String) -> Result<i64, ServiceError> {
+        let results = self.token_repo.revoke_client_tokens(&client_id).await;
+        results.map_err(ServiceError::from)
+    }
+}
+
+fn generate_refresh_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REFRESH_TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Refresh tokens are long and random, so a plain digest is enough to keep them out of the database
+/// while still letting them be looked up
+fn digest(refresh_token: &str) -> String {
+    hex::encode(Sha256::digest(refresh_token.as_bytes()))
+}
+
+impl From<RedeemedRefreshTokenRow> for RedeemedRefreshToken {
+    fn from(row: RedeemedRefreshTokenRow) -> Self {
+        RedeemedRefreshToken {
+            client_id: row.client_id,
+            scopes: row.scopes,
+            family: row.family,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::model::Scope;
+    use crate::inventory::repositories::token::{MockTokenRepository, RefreshTokenRow};
+    use mockall::predicate::*;
+    use std::sync::Mutex;
+
+    fn create_refresh_token_row(token_hash: &str, family: Uuid) -> RefreshTokenRow {
+        RefreshTokenRow {
+            id: 1,
+            token_hash: token_hash.to_string(),
+            family,
+            client_id: "reporting".to_string(),
+            expires_at: Utc::now() + Duration::days(1),
+            used_at: None,
+            revoked_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_digest() {
+        let hash = digest("s3cret");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, digest("s3cret"));
+        assert_ne!(hash, digest("s3cret2"));
+    }
+
+    #[tokio::test]
+    async fn test_issue_refresh_token() {
+        let mut mock = MockTokenRepository::new();
+        let family = Uuid::new_v4();
+        let stored_hash = Arc::new(Mutex::new(String::new()));
+        let captured_hash = stored_hash.clone();
+        mock.expect_create_refresh_token()
+            .withf(move |_, f, client_id, expires_at| {
+                *f == family && client_id == "reporting" && *expires_at > Utc::now()
+            })
+            .times(1)
+            .returning(move |token_hash, family, _, _| {
+                *captured_hash.lock().unwrap() = token_hash.to_string();
+                let row = create_refresh_token_row(token_hash, family);
+                Box::pin(async move { Ok(row) })
+            });
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service
+            .issue_refresh_token("reporting".to_string(), family)
+            .await;
+        let refresh_token = result.unwrap();
+        assert_eq!(refresh_token.len(), REFRESH_TOKEN_LENGTH);
+        assert_eq!(*stored_hash.lock().unwrap(), digest(&refresh_token));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_refresh_token() {
+        let mut mock = MockTokenRepository::new();
+        let family = Uuid::new_v4();
+        mock.expect_use_refresh_token()
+            .with(eq(digest("refresh")))
+            .times(1)
+            .returning(move |_| {
+                Box::pin(async move {
+                    Ok(RedeemedRefreshTokenRow {
+                        client_id: "reporting".to_string(),
+                        scopes: vec![Scope::ItemsRead],
+                        family,
+                    })
+                })
+            });
+        mock.expect_revoke_family().never();
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service.redeem_refresh_token("refresh".to_string()).await;
+        let redeemed = result.unwrap().expect("refresh token should be redeemed");
+        assert_eq!(redeemed.client_id, "reporting");
+        assert_eq!(redeemed.scopes, vec![Scope::ItemsRead]);
+        assert_eq!(redeemed.family, family);
+    }
+
+    #[tokio::test]
+    async fn test_redeem_unknown_refresh_token() {
+        let mut mock = MockTokenRepository::new();
+        mock.expect_use_refresh_token()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+        mock.expect_get_refresh_token()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+        mock.expect_revoke_family().never();
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service.redeem_refresh_token("unknown".to_string()).await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_redeem_reused_refresh_token_revokes_family() {
+        let mut mock = MockTokenRepository::new();
+        let family = Uuid::new_v4();
+        mock.expect_use_refresh_token()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+        mock.expect_get_refresh_token()
+            .times(1)
+            .returning(move |token_hash| {
+                let mut row = create_refresh_token_row(token_hash, family);
+                row.used_at = Some(Utc::now());
+                Box::pin(async move { Ok(row) })
+            });
+        mock.expect_revoke_family()
+            .with(eq(family))
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(2) }));
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service.redeem_refresh_token("stolen".to_string()).await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_redeem_revoked_refresh_token() {
+        let mut mock = MockTokenRepository::new();
+        mock.expect_use_refresh_token()
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+        mock.expect_get_refresh_token()
+            .times(1)
+            .returning(|token_hash| {
+                let mut row = create_refresh_token_row(token_hash, Uuid::new_v4());
+                row.revoked_at = Some(Utc::now());
+                Box::pin(async move { Ok(row) })
+            });
+        mock.expect_revoke_family().never();
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service.redeem_refresh_token("revoked".to_string()).await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_client_tokens_not_found() {
+        let mut mock = MockTokenRepository::new();
+        mock.expect_revoke_client_tokens()
+            .with(eq("nobody"))
+            .times(1)
+            .returning(|_| Box::pin(async { Err(RepoError::NotFound("none".to_string())) }));
+
+        let service = TokenServiceImpl::new(Arc::new(mock));
+        let result = service.revoke_client_tokens("nobody".to_string()).await;
+        assert!(matches!(result, Err(ServiceError::NotFound(_))));
+    }
+}
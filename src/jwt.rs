@@ -1,53 +1,69 @@
+mod keys;
+
 use std::fmt::Display;
 
+use crate::inventory::model::Scope;
 use crate::AppContext;
 use async_trait::async_trait;
-use axum::extract::FromRequestParts;
+use axum::extract::{FromRef, FromRequestParts, State};
+use axum::http::header::CACHE_CONTROL;
 use axum::http::request::Parts;
 use axum::http::{HeaderValue, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
-use axum::routing::post;
+use axum::routing::{get, post};
 use axum::{Json, RequestPartsExt, Router};
 use axum_extra::headers::authorization::Bearer;
 use axum_extra::headers::Authorization;
 use axum_extra::TypedHeader;
 use axum_macros::debug_handler;
-use jsonwebtoken::{decode, DecodingKey, EncodingKey, Header, Validation};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::jwk::JwkSet;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tower_http::cors::CorsLayer;
+use tracing::{error, warn};
+use uuid::Uuid;
 
-struct Keys {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
-}
+pub use keys::{KeyError, Keys};
 
-impl Keys {
-    fn new(secret: &[u8]) -> Keys {
-        let encoding_key = EncodingKey::from_secret(secret);
-        let decoding_key = DecodingKey::from_secret(secret);
-        Keys {
-            encoding_key,
-            decoding_key,
-        }
-    }
-}
+/// Access tokens are short lived; clients keep working past this by redeeming their refresh token
+const ACCESS_TOKEN_TTL_SECS: u64 = 60 * 60;
 
-static KEYS: Lazy<Keys> = Lazy::new(|| {
-    let secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    Keys::new(secret.as_bytes())
-});
+/// How long relying services may cache the JWKS. Keep a retired key published for at least this
+/// long plus the access token TTL.
+const JWKS_MAX_AGE_SECS: u64 = 5 * 60;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+static KEYS: Lazy<Keys> = Lazy::new(Keys::from_env);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    #[default]
+    ClientCredentials,
+    RefreshToken,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AuthRequest {
+    #[serde(default)]
+    pub(crate) grant_type: GrantType,
+    #[serde(default)]
     pub(crate) client_id: String,
+    #[serde(default)]
     pub(crate) client_secret: String,
+    /// Required for the refresh_token grant
+    #[serde(default)]
+    pub(crate) refresh_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResponse {
     token: String,
+    /// Single use; redeeming it returns a new access token and a new refresh token
+    refresh_token: String,
+    /// Seconds until the access token expires
+    expires_in: u64,
 }
 
 #[derive(Debug)]
@@ -56,6 +72,9 @@ pub enum AuthError {
     MissingCredentials,
     TokenCreation,
     InvalidToken,
+    RevokedToken,
+    CredentialLookup,
+    InsufficientScope,
 }
 
 impl IntoResponse for AuthError {
@@ -65,6 +84,11 @@ impl IntoResponse for AuthError {
             AuthError::MissingCredentials => (StatusCode::BAD_REQUEST, "Missing credentials"),
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error"),
             AuthError::InvalidToken => (StatusCode::BAD_REQUEST, "Invalid token"),
+            AuthError::RevokedToken => (StatusCode::UNAUTHORIZED, "Token has been revoked"),
+            AuthError::CredentialLookup => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Credential lookup error")
+            }
+            AuthError::InsufficientScope => (StatusCode::FORBIDDEN, "Insufficient scope"),
         };
         let body = Json(json!({
             "error": error_message,
@@ -77,6 +101,15 @@ impl IntoResponse for AuthError {
 pub struct Claims {
     pub(crate) sub: String,
     pub(crate) exp: usize,
+    pub(crate) jti: Uuid,
+    #[serde(default)]
+    pub(crate) scopes: Vec<Scope>,
+}
+
+impl Claims {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
 }
 
 impl Display for Claims {
@@ -85,55 +118,159 @@ impl Display for Claims {
     }
 }
 
+/// The scope a route requires. Routes add it as a request extension and the `Claims` extractor
+/// rejects tokens that do not carry it.
+#[derive(Clone, Copy, Debug)]
+pub struct RequiredScope(pub Scope);
+
 #[async_trait]
-impl<S> FromRequestParts<S> for Claims {
+impl<S> FromRequestParts<S> for Claims
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
     type Rejection = AuthError;
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, AuthError> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, AuthError> {
         // Extract the token from the authorization header
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| AuthError::InvalidToken)?;
         // Decode the user data
-        let token_data =
-            decode::<Claims>(bearer.token(), &KEYS.decoding_key, &Validation::default())
-                .map_err(|_| AuthError::InvalidToken)?;
+        let claims = KEYS
+            .decode::<Claims>(bearer.token())
+            .map_err(|_| AuthError::InvalidToken)?
+            .claims;
 
-        Ok(token_data.claims)
+        let active = AppContext::from_ref(state)
+            .token_service
+            .is_access_token_active(claims.jti)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up access token {}: {:?}", claims.jti, e);
+                AuthError::CredentialLookup
+            })?;
+        if !active {
+            return Err(AuthError::RevokedToken);
+        }
+
+        if let Some(RequiredScope(scope)) = parts.extensions.get::<RequiredScope>() {
+            if !claims.has_scope(*scope) {
+                warn!(
+                    "{} {} requires {} which {} was not granted",
+                    parts.method, parts.uri, scope, claims.sub
+                );
+                return Err(AuthError::InsufficientScope);
+            }
+        }
+        Ok(claims)
     }
 }
 
 #[debug_handler]
-pub async fn authorize(Json(payload): Json<AuthRequest>) -> Result<Json<AuthResponse>, AuthError> {
-    // TODO - replace with call to lookup user/pass from db
-    if payload.client_id == "foo" && payload.client_secret == "bar" {
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap();
-        let exp = now + std::time::Duration::from_secs(60 * 60 * 24);
-        let claims = Claims {
-            sub: payload.client_id,
-            exp: exp.as_secs() as usize,
-        };
-        let token = jsonwebtoken::encode(&Header::default(), &claims, &KEYS.encoding_key)
-            .map_err(|_e| AuthError::InvalidToken)?;
-        Ok(Json(AuthResponse { token }))
-    } else {
-        Err(AuthError::WrongCredentials)
-    }
+pub async fn authorize(
+    State(app_context): State<AppContext>,
+    Json(payload): Json<AuthRequest>,
+) -> Result<Json<AuthResponse>, AuthError> {
+    let (client_id, scopes, family) = match payload.grant_type {
+        GrantType::ClientCredentials => {
+            if payload.client_id.is_empty() || payload.client_secret.is_empty() {
+                return Err(AuthError::MissingCredentials);
+            }
+            let client = app_context
+                .api_client_service
+                .verify_credentials(payload.client_id.clone(), payload.client_secret)
+                .await
+                .map_err(|e| {
+                    error!(
+                        "Failed to look up API client {}: {:?}",
+                        payload.client_id, e
+                    );
+                    AuthError::CredentialLookup
+                })?
+                .ok_or(AuthError::WrongCredentials)?;
+            (client.client_id, client.scopes, Uuid::new_v4())
+        }
+        GrantType::RefreshToken => {
+            let refresh_token = payload
+                .refresh_token
+                .filter(|refresh_token| !refresh_token.is_empty())
+                .ok_or(AuthError::MissingCredentials)?;
+            let redeemed = app_context
+                .token_service
+                .redeem_refresh_token(refresh_token)
+                .await
+                .map_err(|e| {
+                    error!("Failed to redeem refresh token: {:?}", e);
+                    AuthError::CredentialLookup
+                })?
+                .ok_or(AuthError::WrongCredentials)?;
+            (redeemed.client_id, redeemed.scopes, redeemed.family)
+        }
+    };
+
+    let (claims, expires_at) = new_claims(client_id, scopes);
+    let token = KEYS.encode(&claims).map_err(|e| {
+        error!("Failed to sign access token: {:?}", e);
+        AuthError::TokenCreation
+    })?;
+    app_context
+        .token_service
+        .record_access_token(claims.jti, family, claims.sub.clone(), expires_at)
+        .await
+        .map_err(|e| {
+            error!("Failed to record access token {}: {:?}", claims.jti, e);
+            AuthError::TokenCreation
+        })?;
+    let refresh_token = app_context
+        .token_service
+        .issue_refresh_token(claims.sub, family)
+        .await
+        .map_err(|e| {
+            error!("Failed to issue refresh token: {:?}", e);
+            AuthError::TokenCreation
+        })?;
+    Ok(Json(AuthResponse {
+        token,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_TTL_SECS,
+    }))
 }
 
-pub fn gen_token(auth_request: AuthRequest) -> String {
+fn new_claims(client_id: String, scopes: Vec<Scope>) -> (Claims, DateTime<Utc>) {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap();
-    let exp = now + std::time::Duration::from_secs(60 * 60 * 24);
+    let exp = now + std::time::Duration::from_secs(ACCESS_TOKEN_TTL_SECS);
     let claims = Claims {
-        sub: auth_request.client_id,
+        sub: client_id,
         exp: exp.as_secs() as usize,
+        jti: Uuid::new_v4(),
+        scopes,
     };
-    jsonwebtoken::encode(&Header::default(), &claims, &KEYS.encoding_key)
-        .expect("Failed to generate token")
+    let expires_at = DateTime::from_timestamp(exp.as_secs() as i64, 0).unwrap_or_default();
+    (claims, expires_at)
+}
+
+/// Signs a token without recording it, so it is only accepted where token lookups are mocked
+pub fn gen_token(auth_request: AuthRequest, scopes: Vec<Scope>) -> String {
+    let (claims, _) = new_claims(auth_request.client_id, scopes);
+    KEYS.encode(&claims).expect("Failed to generate token")
+}
+
+/// Publishes the public keys access tokens may be signed with, so other services can verify them
+/// without sharing a secret
+#[debug_handler]
+pub async fn jwks() -> impl IntoResponse {
+    let cache_control = format!("public, max-age={}", JWKS_MAX_AGE_SECS);
+    (
+        [(CACHE_CONTROL, cache_control)],
+        Json::<JwkSet>(KEYS.jwks()),
+    )
+}
+
+pub fn well_known_route() -> Router<AppContext> {
+    Router::new().route("/jwks.json", get(jwks))
 }
 
 pub fn route() -> Router<AppContext> {
@@ -146,40 +283,83 @@ pub fn route() -> Router<AppContext> {
 
 #[cfg(test)]
 mod tests {
-    use crate::jwt::{Claims, KEYS};
-    use crate::test_helpers::body_to_string;
-    use axum::extract::FromRequestParts;
+    use crate::inventory::model::{ApiClient, RedeemedRefreshToken, Scope};
+    use crate::inventory::services::api_client::MockApiClientService;
+    use crate::inventory::services::token::MockTokenService;
+    use crate::inventory::services::ServiceError;
+    use crate::jwt::{AuthError, AuthRequest, Claims, GrantType, RequiredScope, KEYS};
+    use crate::test_helpers::{body_to_string, mock_active_token_service};
+    use crate::AppContext;
+    use axum::extract::{FromRequestParts, State};
     use axum::response::IntoResponse;
-    use jsonwebtoken::{decode, Validation};
+    use axum::Extension;
+    use mockall::predicate::eq;
+    use std::sync::Arc;
     use tower::ServiceExt;
+    use uuid::Uuid;
+
+    fn client_credentials(client_id: &str, client_secret: &str) -> AuthRequest {
+        AuthRequest {
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            ..AuthRequest::default()
+        }
+    }
+
+    fn refresh_token_grant(refresh_token: &str) -> AuthRequest {
+        AuthRequest {
+            grant_type: GrantType::RefreshToken,
+            refresh_token: Some(refresh_token.to_string()),
+            ..AuthRequest::default()
+        }
+    }
 
     #[test]
     fn test_gen_token() {
-        let auth_request = crate::jwt::AuthRequest {
-            client_id: "foo".to_string(),
-            client_secret: "bar".to_string(),
-        };
-        let token = crate::jwt::gen_token(auth_request);
+        let token = crate::jwt::gen_token(client_credentials("foo", "bar"), vec![Scope::ItemsRead]);
         assert!(!token.is_empty());
-        let token_data =
-            decode::<Claims>(token.as_str(), &KEYS.decoding_key, &Validation::default())
-                .expect("Failed to decode token");
+        let token_data = KEYS
+            .decode::<Claims>(token.as_str())
+            .expect("Failed to decode token");
         assert_eq!(token_data.claims.sub, "foo");
         assert!(token_data.claims.exp > 0);
+        assert!(!token_data.claims.jti.is_nil());
+        assert_eq!(token_data.claims.scopes, vec![Scope::ItemsRead]);
+    }
+
+    #[test]
+    fn test_deserialize_auth_request() {
+        let auth_request: AuthRequest =
+            serde_json::from_str(r#"{"client_id":"foo","client_secret":"bar"}"#).unwrap();
+        assert_eq!(auth_request.grant_type, GrantType::ClientCredentials);
+        let auth_request: AuthRequest =
+            serde_json::from_str(r#"{"grant_type":"refresh_token","refresh_token":"abc"}"#)
+                .unwrap();
+        assert_eq!(auth_request.grant_type, GrantType::RefreshToken);
+        assert_eq!(auth_request.refresh_token, Some("abc".to_string()));
     }
 
     #[tokio::test]
     async fn test_into_response() {
-        let error = crate::jwt::AuthError::WrongCredentials;
+        let error = AuthError::WrongCredentials;
         let response = error.into_response();
         assert_eq!(response.status(), 401);
-        let error = crate::jwt::AuthError::MissingCredentials;
+        let error = AuthError::MissingCredentials;
         let response = error.into_response();
         assert_eq!(response.status(), 400);
-        let error = crate::jwt::AuthError::TokenCreation;
+        let error = AuthError::TokenCreation;
         let response = error.into_response();
         assert_eq!(response.status(), 500);
-        let error = crate::jwt::AuthError::InvalidToken;
+        let error = AuthError::CredentialLookup;
+        let response = error.into_response();
+        assert_eq!(response.status(), 500);
+        let error = AuthError::RevokedToken;
+        let response = error.into_response();
+        assert_eq!(response.status(), 401);
+        let error = AuthError::InsufficientScope;
+        let response = error.into_response();
+        assert_eq!(response.status(), 403);
+        let error = AuthError::InvalidToken;
         let response = error.into_response();
         assert_eq!(response.status(), 400);
         let response_str = body_to_string(response.into_body()).await.unwrap();
@@ -194,88 +374,271 @@ mod tests {
         let claims = Claims {
             sub: "test".to_string(),
             exp: 0,
+            ..Claims::default()
         };
         let display = format!("{}", claims);
         assert_eq!(display, "Subject: test\nExpiration: 0");
     }
 
+    fn test_app_context(
+        mock_api_client_service: MockApiClientService,
+        mock_token_service: MockTokenService,
+    ) -> AppContext {
+        AppContext {
+            api_client_service: Arc::new(mock_api_client_service),
+            token_service: Arc::new(mock_token_service),
+            ..crate::test_helpers::test_app_context(
+                crate::inventory::services::person::MockPersonService::new(),
+                crate::inventory::services::item::MockItemService::new(),
+                crate::inventory::services::invoice::MockInvoiceService::new(),
+            )
+        }
+    }
+
+    fn mock_verify_credentials(verified: bool) -> MockApiClientService {
+        let client = verified.then(|| ApiClient {
+            client_id: "foo".to_string(),
+            scopes: vec![Scope::ItemsRead, Scope::InvoicesWrite],
+            ..ApiClient::default()
+        });
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_verify_credentials()
+            .with(eq("foo".to_string()), eq("bar".to_string()))
+            .returning(move |_, _| {
+                let cloned_client = client.clone();
+                Box::pin(async move { Ok(cloned_client) })
+            });
+        mock_api_client_service
+    }
+
+    /// Expects a single access token and refresh token to be issued to "foo"
+    fn mock_issue_tokens(mock_token_service: &mut MockTokenService) {
+        mock_token_service
+            .expect_record_access_token()
+            .withf(|_, _, client_id, _| client_id == "foo")
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async { Ok(()) }));
+        mock_token_service
+            .expect_issue_refresh_token()
+            .withf(|client_id, _| client_id == "foo")
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok("refresh".to_string()) }));
+    }
+
     #[tokio::test]
     async fn test_authorize() {
-        let auth_request = crate::jwt::AuthRequest {
-            client_id: "foo".to_string(),
-            client_secret: "bar".to_string(),
-        };
-        let response = crate::jwt::authorize(axum::Json(auth_request)).await;
+        let mut mock_token_service = MockTokenService::new();
+        mock_issue_tokens(&mut mock_token_service);
+        let app_context = test_app_context(mock_verify_credentials(true), mock_token_service);
+        let response = crate::jwt::authorize(
+            State(app_context),
+            axum::Json(client_credentials("foo", "bar")),
+        )
+        .await;
         assert!(response.is_ok());
         let response = response.unwrap();
         assert!(!response.0.token.is_empty());
+        assert_eq!(response.0.refresh_token, "refresh");
+        assert_eq!(response.0.expires_in, crate::jwt::ACCESS_TOKEN_TTL_SECS);
+        let token_data = KEYS
+            .decode::<Claims>(response.0.token.as_str())
+            .expect("Failed to decode token");
+        assert_eq!(token_data.claims.sub, "foo");
+        assert_eq!(
+            token_data.claims.scopes,
+            vec![Scope::ItemsRead, Scope::InvoicesWrite]
+        );
     }
 
     #[tokio::test]
     async fn test_authorize_wrong_credentials() {
-        let auth_request = crate::jwt::AuthRequest {
-            client_id: "foo".to_string(),
-            client_secret: "baz".to_string(),
-        };
-        let response = crate::jwt::authorize(axum::Json(auth_request)).await;
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service.expect_record_access_token().never();
+        mock_token_service.expect_issue_refresh_token().never();
+        let app_context = test_app_context(mock_verify_credentials(false), mock_token_service);
+        let response = crate::jwt::authorize(
+            State(app_context),
+            axum::Json(client_credentials("foo", "bar")),
+        )
+        .await;
         assert!(response.is_err());
         let error = response.unwrap_err();
         match error {
-            crate::jwt::AuthError::WrongCredentials => assert!(true),
-            _ => assert!(false),
+            AuthError::WrongCredentials => (),
+            _ => panic!("Expected WrongCredentials"),
         }
     }
 
     #[tokio::test]
-    async fn test_from_request_parts() {
-        let token = crate::jwt::gen_token(crate::jwt::AuthRequest {
-            client_id: "foo".to_string(),
-            client_secret: "bar".to_string(),
-        });
+    async fn test_authorize_missing_credentials() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service.expect_verify_credentials().never();
+        let app_context = test_app_context(mock_api_client_service, MockTokenService::new());
+        let response = crate::jwt::authorize(
+            State(app_context),
+            axum::Json(client_credentials("foo", "")),
+        )
+        .await;
+        assert!(matches!(
+            response.unwrap_err(),
+            AuthError::MissingCredentials
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_lookup_error() {
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service
+            .expect_verify_credentials()
+            .returning(|_, _| {
+                Box::pin(async { Err(ServiceError::UnexpectedError("db down".to_string())) })
+            });
+        let app_context = test_app_context(mock_api_client_service, MockTokenService::new());
+        let response = crate::jwt::authorize(
+            State(app_context),
+            axum::Json(client_credentials("foo", "bar")),
+        )
+        .await;
+        assert!(matches!(response.unwrap_err(), AuthError::CredentialLookup));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_refresh_token() {
+        let family = Uuid::new_v4();
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service
+            .expect_redeem_refresh_token()
+            .with(eq("old_refresh".to_string()))
+            .times(1)
+            .returning(move |_| {
+                Box::pin(async move {
+                    Ok(Some(RedeemedRefreshToken {
+                        client_id: "foo".to_string(),
+                        scopes: vec![Scope::PersonsRead],
+                        family,
+                    }))
+                })
+            });
+        mock_token_service
+            .expect_record_access_token()
+            .withf(move |_, f, client_id, _| *f == family && client_id == "foo")
+            .times(1)
+            .returning(|_, _, _, _| Box::pin(async { Ok(()) }));
+        mock_token_service
+            .expect_issue_refresh_token()
+            .with(eq("foo".to_string()), eq(family))
+            .times(1)
+            .returning(|_, _| Box::pin(async { Ok("new_refresh".to_string()) }));
+        let mut mock_api_client_service = MockApiClientService::new();
+        mock_api_client_service.expect_verify_credentials().never();
+        let app_context = test_app_context(mock_api_client_service, mock_token_service);
+        let response = crate::jwt::authorize(
+            State(app_context),
+            axum::Json(refresh_token_grant("old_refresh")),
+        )
+        .await;
+        let response = response.unwrap();
+        assert_eq!(response.0.refresh_token, "new_refresh");
+        let token_data = KEYS
+            .decode::<Claims>(response.0.token.as_str())
+            .expect("Failed to decode token");
+        assert_eq!(token_data.claims.sub, "foo");
+        assert_eq!(token_data.claims.scopes, vec![Scope::PersonsRead]);
+    }
+
+    #[tokio::test]
+    async fn test_authorize_invalid_refresh_token() {
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service
+            .expect_redeem_refresh_token()
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(None) }));
+        mock_token_service.expect_record_access_token().never();
+        let app_context = test_app_context(MockApiClientService::new(), mock_token_service);
+        let response =
+            crate::jwt::authorize(State(app_context), axum::Json(refresh_token_grant("spent")))
+                .await;
+        assert!(matches!(response.unwrap_err(), AuthError::WrongCredentials));
+    }
+
+    #[tokio::test]
+    async fn test_authorize_missing_refresh_token() {
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service.expect_redeem_refresh_token().never();
+        let app_context = test_app_context(MockApiClientService::new(), mock_token_service);
+        let response =
+            crate::jwt::authorize(State(app_context), axum::Json(refresh_token_grant(""))).await;
+        assert!(matches!(
+            response.unwrap_err(),
+            AuthError::MissingCredentials
+        ));
+    }
+
+    fn request_parts(token: &str) -> axum::http::request::Parts {
         let request = axum::http::Request::builder()
             .header("Authorization", format!("Bearer {}", token))
             .body(())
             .unwrap();
-        let mut parts = request.into_parts().0;
-        let claims = crate::jwt::Claims::from_request_parts(&mut parts, &())
+        request.into_parts().0
+    }
+
+    #[tokio::test]
+    async fn test_from_request_parts() {
+        let token = crate::jwt::gen_token(client_credentials("foo", "bar"), vec![]);
+        let mut parts = request_parts(&token);
+        let app_context =
+            test_app_context(MockApiClientService::new(), mock_active_token_service());
+        let claims = Claims::from_request_parts(&mut parts, &app_context)
             .await
             .unwrap();
         assert_eq!(claims.sub, "foo");
     }
 
+    #[tokio::test]
+    async fn test_from_request_parts_revoked_token() {
+        let token = crate::jwt::gen_token(client_credentials("foo", "bar"), vec![]);
+        let jti = KEYS.decode::<Claims>(&token).unwrap().claims.jti;
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service
+            .expect_is_access_token_active()
+            .with(eq(jti))
+            .times(1)
+            .returning(|_| Box::pin(async { Ok(false) }));
+        let mut parts = request_parts(&token);
+        let app_context = test_app_context(MockApiClientService::new(), mock_token_service);
+        let result = Claims::from_request_parts(&mut parts, &app_context).await;
+        assert!(matches!(result.unwrap_err(), AuthError::RevokedToken));
+    }
+
     #[tokio::test]
     async fn test_from_request_parts_invalid_token() {
-        let request = axum::http::Request::builder()
-            .header("Authorization", "Bearer invalid_token")
-            .body(())
-            .unwrap();
-        let mut parts = request.into_parts().0;
-        let result = crate::jwt::Claims::from_request_parts(&mut parts, &()).await;
+        let mut mock_token_service = MockTokenService::new();
+        mock_token_service.expect_is_access_token_active().never();
+        let mut parts = request_parts("invalid_token");
+        let app_context = test_app_context(MockApiClientService::new(), mock_token_service);
+        let result = Claims::from_request_parts(&mut parts, &app_context).await;
         assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            crate::jwt::AuthError::InvalidToken
-        ));
+        assert!(matches!(result.unwrap_err(), AuthError::InvalidToken));
     }
 
     #[tokio::test]
     async fn test_from_request_parts_missing_token() {
         let request = axum::http::Request::builder().body(()).unwrap();
         let mut parts = request.into_parts().0;
-        let result = crate::jwt::Claims::from_request_parts(&mut parts, &()).await;
+        let app_context = test_app_context(MockApiClientService::new(), MockTokenService::new());
+        let result = Claims::from_request_parts(&mut parts, &app_context).await;
         assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            crate::jwt::AuthError::InvalidToken
-        ));
+        assert!(matches!(result.unwrap_err(), AuthError::InvalidToken));
     }
 
     #[tokio::test]
     async fn test_authorize_route() {
-        let app = crate::jwt::route().with_state(crate::test_helpers::test_app_context(
-            crate::inventory::services::person::MockPersonService::new(),
-            crate::inventory::services::item::MockItemService::new(),
-            crate::inventory::services::invoice::MockInvoiceService::new(),
+        let mut mock_token_service = MockTokenService::new();
+        mock_issue_tokens(&mut mock_token_service);
+        let app = crate::jwt::route().with_state(test_app_context(
+            mock_verify_credentials(true),
+            mock_token_service,
         ));
         let request = axum::http::Request::builder()
             .uri("/")
@@ -286,6 +649,79 @@ mod tests {
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), 200);
         let body = body_to_string(response.into_body()).await.unwrap();
-        assert!(!body.is_empty());
+        assert!(body.contains(r#""refresh_token":"refresh""#));
+    }
+
+    #[tokio::test]
+    async fn test_jwks_route() {
+        let app = crate::jwt::well_known_route().with_state(test_app_context(
+            MockApiClientService::new(),
+            MockTokenService::new(),
+        ));
+        let request = axum::http::Request::builder()
+            .uri("/jwks.json")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers().get("cache-control").unwrap(),
+            "public, max-age=300"
+        );
+        // tests sign with JWT_SECRET, which is never published
+        let body = body_to_string(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"keys":[]}"#);
+    }
+
+    fn scoped_app(scope: Scope) -> axum::Router {
+        axum::Router::new()
+            .route(
+                "/",
+                axum::routing::get(|_claims: Claims| async { "ok" })
+                    .route_layer(Extension(RequiredScope(scope))),
+            )
+            .with_state(test_app_context(
+                MockApiClientService::new(),
+                mock_active_token_service(),
+            ))
+    }
+
+    fn scoped_request(scopes: Vec<Scope>) -> axum::http::Request<axum::body::Body> {
+        let token = crate::jwt::gen_token(client_credentials("foo", "bar"), scopes);
+        axum::http::Request::builder()
+            .uri("/")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(axum::body::Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_required_scope() {
+        let response = scoped_app(Scope::ItemsRead)
+            .oneshot(scoped_request(vec![Scope::PersonsRead, Scope::ItemsRead]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_required_scope_missing_scope() {
+        let response = scoped_app(Scope::ItemsWrite)
+            .oneshot(scoped_request(vec![Scope::ItemsRead]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 403);
+        let body = body_to_string(response.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"error":"Insufficient scope"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_required_scope_missing_token() {
+        let request = axum::http::Request::builder()
+            .uri("/")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = scoped_app(Scope::ItemsRead).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), 400);
     }
 }
@@ -0,0 +1,402 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    decode, decode_header, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use ring::rand::SystemRandom;
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{EcdsaKeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// kid given to the shared `JWT_SECRET` key. It is never published in the JWKS.
+pub(crate) const SECRET_KEY_ID: &str = "secret";
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(String),
+    InvalidKey(String),
+    UnknownSigningKey(String),
+    NoKeys,
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyError::Io(msg) => write!(f, "failed to read signing keys: {}", msg),
+            KeyError::InvalidKey(msg) => write!(f, "invalid signing key: {}", msg),
+            KeyError::UnknownSigningKey(kid) => write!(f, "no signing key with kid {}", kid),
+            KeyError::NoKeys => write!(f, "no signing keys found"),
+        }
+    }
+}
+
+struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    /// The public half, for asymmetric keys only
+    jwk: Option<Jwk>,
+}
+
+impl SigningKey {
+    fn from_secret(kid: &str, secret: &[u8]) -> SigningKey {
+        SigningKey {
+            kid: kid.to_string(),
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    /// Parses a PKCS#8 PEM private key, which must be a P-256 EC key (ES256) or an RSA key (RS256)
+    fn from_pem(kid: &str, contents: &str) -> Result<SigningKey, KeyError> {
+        let invalid = |msg: String| KeyError::InvalidKey(format!("{}: {}", kid, msg));
+        let parsed = pem::parse(contents).map_err(|e| invalid(e.to_string()))?;
+        if parsed.tag() != "PRIVATE KEY" {
+            return Err(invalid(format!(
+                "expected a PKCS#8 PRIVATE KEY, found {}",
+                parsed.tag()
+            )));
+        }
+        let der = parsed.contents();
+        let (algorithm, encoding_key, parameters) = if let Ok(key_pair) =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, der, &SystemRandom::new())
+        {
+            // uncompressed point: 0x04 || x || y
+            let point = key_pair.public_key().as_ref();
+            let parameters = AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+                y: URL_SAFE_NO_PAD.encode(&point[33..65]),
+            });
+            let encoding_key = EncodingKey::from_ec_pem(contents.as_bytes())
+                .map_err(|e| invalid(e.to_string()))?;
+            (Algorithm::ES256, encoding_key, parameters)
+        } else if let Ok(key_pair) = RsaKeyPair::from_pkcs8(der) {
+            let public = PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+            let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(&public.n),
+                e: URL_SAFE_NO_PAD.encode(&public.e),
+            });
+            let encoding_key = EncodingKey::from_rsa_pem(contents.as_bytes())
+                .map_err(|e| invalid(e.to_string()))?;
+            (Algorithm::RS256, encoding_key, parameters)
+        } else {
+            return Err(invalid(
+                "only P-256 EC (ES256) and RSA (RS256) keys are supported".to_string(),
+            ));
+        };
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_id: Some(kid.to_string()),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::ES256 => KeyAlgorithm::ES256,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+        // verify with the published key so the JWKS and our own checks can never disagree
+        let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|e| invalid(e.to_string()))?;
+        Ok(SigningKey {
+            kid: kid.to_string(),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+}
+
+/// The keys access tokens are signed and verified with. New tokens are signed with a single key;
+/// every key is accepted for verification, so a new key can be rolled out before it signs
+/// anything and an old one kept until the last token it signed has expired.
+pub struct Keys {
+    signing_kid: String,
+    keys: Vec<SigningKey>,
+}
+
+impl Keys {
+    /// Loads `<kid>.pem` files from `JWT_KEYS_DIR`, signing with `JWT_SIGNING_KEY_ID`. Falls back
+    /// to HS256 with `JWT_SECRET` when no key directory is configured.
+    pub fn from_env() -> Keys {
+        match std::env::var("JWT_KEYS_DIR") {
+            Ok(dir) => {
+                let signing_kid = std::env::var("JWT_SIGNING_KEY_ID").ok();
+                Keys::from_dir(Path::new(&dir), signing_kid)
+                    .unwrap_or_else(|e| panic!("JWT_KEYS_DIR {}: {}", dir, e))
+            }
+            Err(_) => {
+                let secret = std::env::var("JWT_SECRET")
+                    .expect("JWT_SECRET must be set when JWT_KEYS_DIR is not");
+                Keys::from_secret(secret.as_bytes())
+            }
+        }
+    }
+
+    pub fn from_secret(secret: &[u8]) -> Keys {
+        Keys {
+            signing_kid: SECRET_KEY_ID.to_string(),
+            keys: vec![SigningKey::from_secret(SECRET_KEY_ID, secret)],
+        }
+    }
+
+    pub fn from_dir(dir: &Path, signing_kid: Option<String>) -> Result<Keys, KeyError> {
+        let entries = std::fs::read_dir(dir).map_err(|e| KeyError::Io(e.to_string()))?;
+        let mut pems = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| KeyError::Io(e.to_string()))?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("pem") {
+                continue;
+            }
+            let kid = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| KeyError::InvalidKey(format!("{:?}", path)))?
+                .to_string();
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| KeyError::Io(format!("{:?}: {}", path, e)))?;
+            pems.push((kid, contents));
+        }
+        pems.sort();
+        Keys::from_pems(pems, signing_kid)
+    }
+
+    /// Builds a key set from `(kid, pem)` pairs. The signing kid may be omitted when there is
+    /// only one key.
+    pub fn from_pems(
+        pems: Vec<(String, String)>,
+        signing_kid: Option<String>,
+    ) -> Result<Keys, KeyError> {
+        let keys = pems
+            .iter()
+            .map(|(kid, contents)| SigningKey::from_pem(kid, contents))
+            .collect::<Result<Vec<_>, _>>()?;
+        let signing_kid = match (signing_kid, keys.as_slice()) {
+            (_, []) => return Err(KeyError::NoKeys),
+            (Some(kid), _) => kid,
+            (None, [key]) => key.kid.clone(),
+            (None, _) => {
+                return Err(KeyError::UnknownSigningKey(
+                    "(JWT_SIGNING_KEY_ID must be set when there is more than one key)".to_string(),
+                ))
+            }
+        };
+        if !keys.iter().any(|key| key.kid == signing_kid) {
+            return Err(KeyError::UnknownSigningKey(signing_kid));
+        }
+        Ok(Keys { signing_kid, keys })
+    }
+
+    fn signing_key(&self) -> &SigningKey {
+        self.keys
+            .iter()
+            .find(|key| key.kid == self.signing_kid)
+            .expect("signing key is validated on construction")
+    }
+
+    pub fn encode<T: serde::Serialize>(
+        &self,
+        claims: &T,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        let key = self.signing_key();
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        jsonwebtoken::encode(&header, claims, &key.encoding_key)
+    }
+
+    /// Verifies a token with the key named by its `kid`. The key's own algorithm is enforced,
+    /// whatever the header claims, so a public key can never be used as an HMAC secret.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key = header
+            .kid
+            .and_then(|kid| self.keys.iter().find(|key| key.kid == kid))
+            .ok_or(ErrorKind::InvalidToken)?;
+        decode(token, &key.decoding_key, &Validation::new(key.algorithm))
+    }
+
+    /// The public keys, for publishing at `/.well-known/jwks.json`
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    const RS256_PEM: &str = include_str!("../../tests/fixtures/keys/rs256-test.pem");
+    const ES256_PEM: &str = include_str!("../../tests/fixtures/keys/es256-test.pem");
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "foo".to_string(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        }
+    }
+
+    fn pem(kid: &str, contents: &str) -> (String, String) {
+        (kid.to_string(), contents.to_string())
+    }
+
+    #[test]
+    fn test_rs256_round_trip() {
+        let keys = Keys::from_pems(vec![pem("rsa-1", RS256_PEM)], None).unwrap();
+        let token = keys.encode(&claims()).unwrap();
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid, Some("rsa-1".to_string()));
+        assert_eq!(keys.decode::<TestClaims>(&token).unwrap().claims.sub, "foo");
+    }
+
+    #[test]
+    fn test_es256_round_trip() {
+        let keys = Keys::from_pems(vec![pem("ec-1", ES256_PEM)], None).unwrap();
+        let token = keys.encode(&claims()).unwrap();
+        assert_eq!(decode_header(&token).unwrap().alg, Algorithm::ES256);
+        assert_eq!(keys.decode::<TestClaims>(&token).unwrap().claims, claims());
+    }
+
+    #[test]
+    fn test_rotation_accepts_tokens_from_previous_key() {
+        let pems = vec![pem("old", RS256_PEM), pem("new", ES256_PEM)];
+        let before = Keys::from_pems(pems.clone(), Some("old".to_string())).unwrap();
+        let after = Keys::from_pems(pems, Some("new".to_string())).unwrap();
+        let old_token = before.encode(&claims()).unwrap();
+        let new_token = after.encode(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid,
+            Some("new".to_string())
+        );
+        assert!(after.decode::<TestClaims>(&old_token).is_ok());
+        assert!(before.decode::<TestClaims>(&new_token).is_ok());
+
+        let retired = Keys::from_pems(vec![pem("new", ES256_PEM)], None).unwrap();
+        assert!(retired.decode::<TestClaims>(&old_token).is_err());
+    }
+
+    #[test]
+    fn test_decode_unknown_or_missing_kid() {
+        let keys = Keys::from_pems(vec![pem("ec-1", ES256_PEM)], None).unwrap();
+        let other = Keys::from_pems(vec![pem("ec-2", ES256_PEM)], None).unwrap();
+        let token = other.encode(&claims()).unwrap();
+        let error = keys.decode::<TestClaims>(&token).unwrap_err();
+        assert_eq!(*error.kind(), ErrorKind::InvalidToken);
+
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::ES256),
+            &claims(),
+            &keys.signing_key().encoding_key,
+        )
+        .unwrap();
+        assert!(keys.decode::<TestClaims>(&token).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_algorithm_confusion() {
+        let keys = Keys::from_pems(vec![pem("rsa-1", RS256_PEM)], None).unwrap();
+        // an HS256 token claiming the RSA kid, "signed" with the public modulus as the secret
+        let jwk = keys.jwks().keys.remove(0);
+        let AlgorithmParameters::RSA(rsa) = jwk.algorithm else {
+            panic!("expected an RSA key");
+        };
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("rsa-1".to_string());
+        let token = jsonwebtoken::encode(
+            &header,
+            &claims(),
+            &EncodingKey::from_secret(rsa.n.as_bytes()),
+        )
+        .unwrap();
+        let error = keys.decode::<TestClaims>(&token).unwrap_err();
+        assert_eq!(*error.kind(), ErrorKind::InvalidAlgorithm);
+    }
+
+    #[test]
+    fn test_jwks_publishes_only_public_keys() {
+        let keys = Keys::from_pems(
+            vec![pem("rsa-1", RS256_PEM), pem("ec-1", ES256_PEM)],
+            Some("ec-1".to_string()),
+        )
+        .unwrap();
+        let jwks = keys.jwks();
+        assert_eq!(jwks.keys.len(), 2);
+        assert!(jwks.find("rsa-1").is_some());
+        assert!(jwks.find("ec-1").is_some());
+        let json = serde_json::to_string(&jwks).unwrap();
+        assert!(json.contains(r#""kty":"RSA""#));
+        assert!(json.contains(r#""crv":"P-256""#));
+        assert!(!json.contains(r#""d":"#));
+
+        assert!(Keys::from_secret(b"secret").jwks().keys.is_empty());
+    }
+
+    #[test]
+    fn test_from_secret() {
+        let keys = Keys::from_secret(b"secret");
+        let token = keys.encode(&claims()).unwrap();
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::HS256);
+        assert_eq!(header.kid, Some(SECRET_KEY_ID.to_string()));
+        assert!(keys.decode::<TestClaims>(&token).is_ok());
+        assert!(Keys::from_secret(b"other")
+            .decode::<TestClaims>(&token)
+            .is_err());
+    }
+
+    #[test]
+    fn test_from_pems_signing_key_must_exist() {
+        let result = Keys::from_pems(vec![pem("ec-1", ES256_PEM)], Some("ec-2".to_string()));
+        assert!(matches!(result, Err(KeyError::UnknownSigningKey(_))));
+        let result = Keys::from_pems(vec![pem("a", ES256_PEM), pem("b", RS256_PEM)], None);
+        assert!(matches!(result, Err(KeyError::UnknownSigningKey(_))));
+        assert!(matches!(
+            Keys::from_pems(vec![], None),
+            Err(KeyError::NoKeys)
+        ));
+    }
+
+    #[test]
+    fn test_from_pems_invalid_key() {
+        let result = Keys::from_pems(vec![pem("bad", "not a pem")], None);
+        assert!(matches!(result, Err(KeyError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_from_dir() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/keys");
+        let keys = Keys::from_dir(&dir, Some("rs256-test".to_string())).unwrap();
+        let token = keys.encode(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&token).unwrap().kid,
+            Some("rs256-test".to_string())
+        );
+        assert_eq!(keys.jwks().keys.len(), 2);
+    }
+}
@@ -5,9 +5,17 @@ pub mod test_helpers;
 use crate::inventory::db::initialize_db_pool;
 use crate::inventory::repositories::person::PersonRepositoryImpl;
 use crate::inventory::routes::ApiDoc;
+use crate::inventory::services::api_client::ApiClientService;
+use crate::inventory::services::audit::AuditService;
+use crate::inventory::services::idempotency::{
+    IdempotencyService, DEFAULT_IDEMPOTENCY_KEY_TTL_SECS,
+};
 use crate::inventory::services::invoice::InvoiceService;
 use crate::inventory::services::item::ItemService;
+use crate::inventory::services::payment::PaymentService;
 use crate::inventory::services::person::{PersonService, PersonServiceImpl};
+use crate::inventory::services::stock::StockService;
+use crate::inventory::services::token::TokenService;
 use axum::extract::MatchedPath;
 use axum::extract::Request;
 use axum::middleware::Next;
@@ -30,6 +38,12 @@ pub struct AppContext {
     pub person_service: Arc<dyn PersonService + Send + 'static>,
     pub item_service: Arc<dyn ItemService + Send + 'static>,
     pub invoice_service: Arc<dyn InvoiceService + Send + 'static>,
+    pub stock_service: Arc<dyn StockService + Send + 'static>,
+    pub payment_service: Arc<dyn PaymentService + Send + 'static>,
+    pub api_client_service: Arc<dyn ApiClientService + Send + 'static>,
+    pub token_service: Arc<dyn TokenService + Send + 'static>,
+    pub audit_service: Arc<dyn AuditService + Send + 'static>,
+    pub idempotency_service: Arc<dyn IdempotencyService + Send + 'static>,
 }
 
 impl AppContext {
@@ -38,10 +52,22 @@ impl AppContext {
         let person_service = Self::init_person_service(&db_pool).await;
         let item_service = Self::init_item_service(&db_pool).await;
         let invoice_service = Self::init_invoice_service(&db_pool).await;
+        let stock_service = Self::init_stock_service(&db_pool).await;
+        let payment_service = Self::init_payment_service(&db_pool).await;
+        let api_client_service = Self::init_api_client_service(&db_pool).await;
+        let token_service = Self::init_token_service(&db_pool).await;
+        let audit_service = Self::init_audit_service(&db_pool).await;
+        let idempotency_service = Self::init_idempotency_service(&db_pool).await;
         AppContext {
             person_service,
             item_service,
             invoice_service,
+            stock_service,
+            payment_service,
+            api_client_service,
+            token_service,
+            audit_service,
+            idempotency_service,
         }
     }
 
@@ -53,18 +79,86 @@ impl AppContext {
     async fn init_item_service(db_pool: &PgPool) -> Arc<dyn ItemService> {
         let item_repo =
             inventory::repositories::item::ItemRepositoryImpl::new(db_pool.clone()).await;
-        Arc::new(inventory::services::item::ItemServiceImpl::new(Arc::new(
-            item_repo,
-        )))
+        let stock_repo =
+            inventory::repositories::stock::StockRepositoryImpl::new(db_pool.clone()).await;
+        Arc::new(inventory::services::item::ItemServiceImpl::new(
+            Arc::new(item_repo),
+            Arc::new(stock_repo),
+        ))
     }
 
     async fn init_invoice_service(db_pool: &PgPool) -> Arc<dyn InvoiceService> {
         let invoice_repo =
             inventory::repositories::invoice::InvoiceRepositoryImpl::new(db_pool.clone()).await;
+        let units =
+            inventory::repositories::unit_of_work::UnitOfWorkFactoryImpl::new(db_pool.clone())
+                .await;
         Arc::new(inventory::services::invoice::InvoiceServiceImpl::new(
             Arc::new(invoice_repo),
+            Arc::new(units),
+        ))
+    }
+
+    async fn init_stock_service(db_pool: &PgPool) -> Arc<dyn StockService> {
+        let stock_repo =
+            inventory::repositories::stock::StockRepositoryImpl::new(db_pool.clone()).await;
+        Arc::new(inventory::services::stock::StockServiceImpl::new(Arc::new(
+            stock_repo,
+        )))
+    }
+
+    async fn init_payment_service(db_pool: &PgPool) -> Arc<dyn PaymentService> {
+        let payment_repo =
+            inventory::repositories::payment::PaymentRepositoryImpl::new(db_pool.clone()).await;
+        Arc::new(inventory::services::payment::PaymentServiceImpl::new(
+            Arc::new(payment_repo),
+        ))
+    }
+
+    async fn init_api_client_service(db_pool: &PgPool) -> Arc<dyn ApiClientService> {
+        let api_client_repo =
+            inventory::repositories::api_client::ApiClientRepositoryImpl::new(db_pool.clone())
+                .await;
+        Arc::new(inventory::services::api_client::ApiClientServiceImpl::new(
+            Arc::new(api_client_repo),
         ))
     }
+
+    async fn init_token_service(db_pool: &PgPool) -> Arc<dyn TokenService> {
+        let token_repo =
+            inventory::repositories::token::TokenRepositoryImpl::new(db_pool.clone()).await;
+        Arc::new(inventory::services::token::TokenServiceImpl::new(Arc::new(
+            token_repo,
+        )))
+    }
+
+    async fn init_audit_service(db_pool: &PgPool) -> Arc<dyn AuditService> {
+        let audit_repo =
+            inventory::repositories::audit::AuditRepositoryImpl::new(db_pool.clone()).await;
+        Arc::new(inventory::services::audit::AuditServiceImpl::new(Arc::new(
+            audit_repo,
+        )))
+    }
+
+    /// Responses to requests sent with an Idempotency-Key are replayed for IDEMPOTENCY_KEY_TTL_SECS
+    async fn init_idempotency_service(db_pool: &PgPool) -> Arc<dyn IdempotencyService> {
+        let ttl_secs = std::env::var("IDEMPOTENCY_KEY_TTL_SECS")
+            .ok()
+            .map(|secs| {
+                secs.parse()
+                    .expect("IDEMPOTENCY_KEY_TTL_SECS must be a number of seconds")
+            })
+            .unwrap_or(DEFAULT_IDEMPOTENCY_KEY_TTL_SECS);
+        let idempotency_repo =
+            inventory::repositories::idempotency::IdempotencyRepositoryImpl::new(db_pool.clone())
+                .await;
+        Arc::new(
+            inventory::services::idempotency::IdempotencyServiceImpl::new(
+                Arc::new(idempotency_repo),
+                chrono::Duration::seconds(ttl_secs),
+            ),
+        )
+    }
 }
 
 fn setup_metrics_recorder() -> PrometheusHandle {
@@ -89,9 +183,11 @@ fn metrics_app() -> Router {
 
 pub async fn start_server() {
     let app_context = AppContext::new().await;
+    bootstrap_api_client(&app_context).await;
     let app = Router::new()
         .merge(Redoc::with_url("/redoc", ApiDoc::openapi()))
         .nest("/api/v1/authorize", jwt::route())
+        .nest("/.well-known", jwt::well_known_route())
         .merge(inventory::routes::api_routes_with_status_routes())
         .with_state(app_context)
         .route_layer(middleware::from_fn(track_metrics))
@@ -119,6 +215,21 @@ pub async fn start_server() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Registers the client given by BOOTSTRAP_CLIENT_ID and BOOTSTRAP_CLIENT_SECRET, if both are set,
+/// so a fresh database has a client that can obtain the first token and create the others.
+async fn bootstrap_api_client(app_context: &AppContext) {
+    if let (Ok(client_id), Ok(client_secret)) = (
+        std::env::var("BOOTSTRAP_CLIENT_ID"),
+        std::env::var("BOOTSTRAP_CLIENT_SECRET"),
+    ) {
+        app_context
+            .api_client_service
+            .bootstrap_client(client_id, client_secret)
+            .await
+            .expect("Failed to register the bootstrap API client");
+    }
+}
+
 pub async fn start_metrics_server() {
     let app = metrics_app();
 
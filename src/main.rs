@@ -1,5 +1,4 @@
 use inventory_service::{start_metrics_server, start_server};
-use opentelemetry::logs::LoggerProvider;
 use opentelemetry::trace::TracerProvider as _;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
@@ -18,7 +17,6 @@ async fn main() {
 async fn init() {
     let otlp_endpoint =
         std::env::var("OTLP_ENDPOINT").unwrap_or_else(|_| "http://localhost:4317".to_string());
-    let log_endpoint = otlp_endpoint.clone();
     let provider = opentelemetry_sdk::trace::TracerProvider::builder()
         .with_batch_exporter(
             opentelemetry_otlp::SpanExporter::builder()
@@ -1,11 +1,17 @@
+use crate::inventory::model::Scope;
+use crate::inventory::services::api_client::MockApiClientService;
+use crate::inventory::services::audit::MockAuditService;
+use crate::inventory::services::idempotency::MockIdempotencyService;
 use crate::inventory::services::invoice::MockInvoiceService;
 use crate::inventory::services::item::MockItemService;
+use crate::inventory::services::payment::MockPaymentService;
 use crate::inventory::services::person::MockPersonService;
+use crate::inventory::services::stock::MockStockService;
+use crate::inventory::services::token::MockTokenService;
 use crate::jwt::{AuthRequest, Claims};
 use crate::{jwt, AppContext};
 use axum::body::Body;
 use futures::StreamExt;
-use std::cell::OnceCell;
 use std::string::FromUtf8Error;
 use std::sync::{Arc, Once};
 use tracing::Level;
@@ -18,29 +24,20 @@ pub const INVALID_UUID: &str = "00000000-0000-0000-0000-000000000000";
 pub const FIRST_PERSON_ID: i32 = 1;
 pub const FIRST_ITEM_ID: i32 = 1;
 pub const FIRST_INVOICE_ID: i32 = 1;
-pub const FIRST_INVOICE_UUID_CELL: OnceCell<Uuid> = OnceCell::new();
-pub const FIRST_ITEM_UUID_CELL: OnceCell<Uuid> = OnceCell::new();
-pub const INVALID_UUID_CELL: OnceCell<Uuid> = OnceCell::new();
 
 pub fn first_person_uuid() -> Uuid {
     Uuid::parse_str(FIRST_PERSON_UUID).unwrap()
 }
 
 pub fn first_item_uuid() -> Uuid {
-    FIRST_ITEM_UUID_CELL
-        .get_or_init(|| Uuid::parse_str(FIRST_ITEM_UUID).unwrap())
-        .clone()
+    Uuid::parse_str(FIRST_ITEM_UUID).unwrap()
 }
 pub fn invalid_uuid() -> Uuid {
-    INVALID_UUID_CELL
-        .get_or_init(|| Uuid::parse_str(INVALID_UUID).unwrap())
-        .clone()
+    Uuid::parse_str(INVALID_UUID).unwrap()
 }
 
 pub fn first_invoice_uuid() -> Uuid {
-    FIRST_INVOICE_UUID_CELL
-        .get_or_init(|| Uuid::parse_str(FIRST_INVOICE_UUID).unwrap())
-        .clone()
+    Uuid::parse_str(FIRST_INVOICE_UUID).unwrap()
 }
 
 pub fn string_to_uuid(s: &str) -> Uuid {
@@ -59,22 +56,44 @@ pub fn test_app_context(
         person_service,
         item_service,
         invoice_service,
+        stock_service: Arc::new(MockStockService::new()),
+        payment_service: Arc::new(MockPaymentService::new()),
+        api_client_service: Arc::new(MockApiClientService::new()),
+        token_service: Arc::new(mock_active_token_service()),
+        audit_service: Arc::new(MockAuditService::new()),
+        idempotency_service: Arc::new(MockIdempotencyService::new()),
     }
 }
 
+/// A token service accepting every access token, as tokens from `mock_token` are never recorded
+pub fn mock_active_token_service() -> MockTokenService {
+    let mut mock_token_service = MockTokenService::new();
+    mock_token_service
+        .expect_is_access_token_active()
+        .returning(|_| Box::pin(async { Ok(true) }));
+    mock_token_service
+}
+
 pub fn mock_claims() -> Claims {
     Claims {
         sub: "test".to_string(),
         exp: 0,
+        jti: Uuid::new_v4(),
+        scopes: Scope::ALL.to_vec(),
     }
 }
 
 pub fn mock_token() -> String {
+    mock_token_with_scopes(Scope::ALL.to_vec())
+}
+
+pub fn mock_token_with_scopes(scopes: Vec<Scope>) -> String {
     let auth_request = AuthRequest {
         client_id: "foo".to_string(),
         client_secret: "bar".to_string(),
+        ..AuthRequest::default()
     };
-    let token = jwt::gen_token(auth_request.clone());
+    let token = jwt::gen_token(auth_request, scopes);
     format!("Bearer {}", token)
 }
 
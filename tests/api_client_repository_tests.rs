@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use inventory_service::inventory::model::Scope;
+    use inventory_service::inventory::repositories::api_client::{
+        ApiClientRepository, ApiClientRepositoryImpl,
+    };
+    use inventory_service::inventory::repositories::RepoError;
+    use inventory_service::test_helpers::init;
+    use sqlx::PgPool;
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_get_all(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let rows = repository.get_all().await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].client_id, "reporting");
+        assert_eq!(rows[0].scopes, vec![Scope::ItemsRead, Scope::InvoicesRead]);
+        assert!(rows[0].revoked_at.is_none());
+        assert!(rows[1].revoked_at.is_some());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_create(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository
+            .create(
+                "billing",
+                "Billing team",
+                "$argon2id$hash",
+                &[Scope::InvoicesRead, Scope::InvoicesWrite],
+                "admin",
+            )
+            .await;
+        let row = result.unwrap();
+        assert_eq!(row.client_id, "billing");
+        assert_eq!(row.name, "Billing team");
+        assert_eq!(row.secret_hash, "$argon2id$hash");
+        assert_eq!(row.scopes, vec![Scope::InvoicesRead, Scope::InvoicesWrite]);
+        assert_eq!(row.created_by, "admin");
+        assert_eq!(row.last_changed_by, "admin");
+        assert!(row.revoked_at.is_none());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_create_duplicate_client_id(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository
+            .create(
+                "reporting",
+                "Another team",
+                "$argon2id$hash",
+                &[Scope::ItemsRead],
+                "admin",
+            )
+            .await;
+        assert!(matches!(result, Err(RepoError::UniqueViolation(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_get_by_client_id_not_found(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository.get_by_client_id("nobody").await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_update_secret(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository
+            .update_secret("reporting", "$argon2id$rotated", "admin")
+            .await;
+        let row = result.unwrap();
+        assert_eq!(row.secret_hash, "$argon2id$rotated");
+        assert_eq!(row.last_changed_by, "admin");
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_update_secret_revoked_client(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository
+            .update_secret("legacy", "$argon2id$rotated", "admin")
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_revoke(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let row = repository.revoke("reporting", "admin").await.unwrap();
+        assert!(row.revoked_at.is_some());
+        assert_eq!(row.last_changed_by, "admin");
+        let again = repository.revoke("reporting", "admin").await.unwrap();
+        assert_eq!(again.revoked_at, row.revoked_at);
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_revoke_not_found(pool: PgPool) {
+        init();
+        let repository = ApiClientRepositoryImpl::new(pool).await;
+        let result = repository.revoke("nobody", "admin").await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use inventory_service::inventory::model::money::{Currency, Money};
+    use inventory_service::inventory::model::page::{Cursor, Seek};
+    use inventory_service::inventory::model::{
+        AuditAction, AuditResource, CreateItemRequest, CreatePersonRequest, ExpectedVersion,
+        UpdateItemRequest,
+    };
+    use inventory_service::inventory::repositories::audit::{AuditRepository, AuditRepositoryImpl};
+    use inventory_service::inventory::repositories::item::{ItemRepository, ItemRepositoryImpl};
+    use inventory_service::inventory::repositories::person::{
+        PersonRepository, PersonRepositoryImpl,
+    };
+    use inventory_service::test_helpers::{first_item_uuid, first_person_uuid};
+    use sqlx::PgPool;
+    use std::sync::Once;
+    use tracing::Level;
+
+    static TRACING: Once = Once::new();
+    pub fn init() {
+        TRACING.call_once(|| {
+            tracing_subscriber::fmt()
+                .with_max_level(Level::DEBUG)
+                .init();
+        });
+    }
+
+    #[sqlx::test]
+    async fn test_item_history(pool: PgPool) {
+        init();
+        let item_repository = ItemRepositoryImpl::new(pool.clone()).await;
+        let audit_repository = AuditRepositoryImpl::new(pool).await;
+        let created = item_repository
+            .create_item(&CreateItemRequest {
+                name: "Widget".to_string(),
+                description: "A widget".to_string(),
+                unit_price: Money::new(BigDecimal::from(10), Currency::USD),
+                created_by: "alice".to_string(),
+            })
+            .await
+            .unwrap();
+        item_repository
+            .update_item(&UpdateItemRequest {
+                id: created.alt_id.to_string(),
+                name: "Gadget".to_string(),
+                description: "A widget".to_string(),
+                unit_price: Money::new(BigDecimal::from(12), Currency::USD),
+                changed_by: "bob".to_string(),
+                version: ExpectedVersion::OneOf(vec![created.version]),
+            })
+            .await
+            .unwrap();
+        item_repository
+            .delete_item(created.alt_id, "carol")
+            .await
+            .unwrap();
+
+        let history = audit_repository
+            .get_history(
+                AuditResource::Item,
+                created.alt_id,
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 3);
+        let actions: Vec<AuditAction> = history.iter().map(|entry| entry.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Create,
+                AuditAction::Update,
+                AuditAction::Delete
+            ]
+        );
+        let actors: Vec<&str> = history.iter().map(|entry| entry.actor.as_str()).collect();
+        assert_eq!(actors, vec!["alice", "bob", "carol"]);
+
+        assert!(history[0].before.is_none());
+        assert_eq!(history[0].after.as_ref().unwrap()["name"], "Widget");
+        assert_eq!(history[1].before.as_ref().unwrap()["name"], "Widget");
+        assert_eq!(history[1].after.as_ref().unwrap()["name"], "Gadget");
+        assert_eq!(history[2].before.as_ref().unwrap()["name"], "Gadget");
+        // deletes are soft, so the record after a delete is still there with deleted_at set
+        assert_eq!(history[2].after.as_ref().unwrap()["name"], "Gadget");
+        assert!(!history[2].after.as_ref().unwrap()["deleted_at"].is_null());
+
+        // the history is paged by seq like every other list
+        let page = Seek {
+            after: Some(Cursor::after_id(history[0].id)),
+            limit: 1,
+        };
+        let next_page = audit_repository
+            .get_history(AuditResource::Item, created.alt_id, &page)
+            .await
+            .unwrap();
+        assert_eq!(next_page.len(), 1);
+        assert_eq!(next_page[0].id, history[1].id);
+    }
+
+    #[sqlx::test(fixtures("people"))]
+    async fn test_person_history_outlives_delete(pool: PgPool) {
+        init();
+        let person_repository = PersonRepositoryImpl::new(pool.clone()).await;
+        let audit_repository = AuditRepositoryImpl::new(pool).await;
+        person_repository
+            .delete_person(first_person_uuid(), "alice")
+            .await
+            .unwrap();
+        let history = audit_repository
+            .get_history(
+                AuditResource::Person,
+                first_person_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        // the fixture rows were inserted directly, so only the delete is recorded
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].action, AuditAction::Delete);
+        assert_eq!(history[0].before.as_ref().unwrap()["name"], "John Doe");
+    }
+
+    #[sqlx::test(fixtures("people"))]
+    async fn test_restore_is_recorded(pool: PgPool) {
+        init();
+        let person_repository = PersonRepositoryImpl::new(pool.clone()).await;
+        let audit_repository = AuditRepositoryImpl::new(pool).await;
+        person_repository
+            .delete_person(first_person_uuid(), "alice")
+            .await
+            .unwrap();
+        person_repository
+            .restore_person(first_person_uuid(), "bob")
+            .await
+            .unwrap();
+        let history = audit_repository
+            .get_history(
+                AuditResource::Person,
+                first_person_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].action, AuditAction::Restore);
+        assert_eq!(history[1].actor, "bob");
+        assert!(!history[1].before.as_ref().unwrap()["deleted_at"].is_null());
+        assert!(history[1].after.as_ref().unwrap()["deleted_at"].is_null());
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_failed_change_is_not_recorded(pool: PgPool) {
+        init();
+        let item_repository = ItemRepositoryImpl::new(pool.clone()).await;
+        let audit_repository = AuditRepositoryImpl::new(pool).await;
+        let missing_item_uuid = uuid::Uuid::new_v4();
+        let result = item_repository
+            .delete_item(missing_item_uuid, "alice")
+            .await;
+        assert!(result.is_err());
+        let history = audit_repository
+            .get_history(
+                AuditResource::Item,
+                missing_item_uuid,
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(history.is_empty());
+        let history = audit_repository
+            .get_history(
+                AuditResource::Item,
+                first_item_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(history.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn test_audit_log_is_append_only(pool: PgPool) {
+        init();
+        let person_repository = PersonRepositoryImpl::new(pool.clone()).await;
+        person_repository
+            .create_person(&CreatePersonRequest {
+                name: "Audit Person".to_string(),
+                email: "audit.person@test.com".to_string(),
+                created_by: "alice".to_string(),
+            })
+            .await
+            .unwrap();
+        let update = sqlx::query("UPDATE audit_log SET actor = 'mallory'")
+            .execute(&pool)
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_log").execute(&pool).await;
+        assert!(delete.is_err());
+    }
+}
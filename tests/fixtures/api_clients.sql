@@ -0,0 +1,3 @@
+INSERT INTO api_clients (client_id, name, secret_hash, scopes, revoked_at, created_by, last_changed_by)
+VALUES ('reporting', 'Reporting team', '$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$aGFzaGhhc2g', '{items:read,invoices:read}', NULL, 'unit_test', 'unit_test'),
+       ('legacy', 'Legacy integration', '$argon2id$v=19$m=19456,t=2,p=1$c2FsdHNhbHQ$b2xkaGFzaA', '{items:write}', now(), 'unit_test', 'unit_test');
@@ -0,0 +1,4 @@
+-- reservations for the first two items from items.sql; all other items have never been counted
+INSERT INTO stock_levels (item_id, reserved, created_by, last_changed_by)
+VALUES ('6f4bdd88-d12e-421a-bac7-92ed2d9035aa', 10, 'unit_test', 'unit_test'),
+       ('2492b388-e0b9-47ca-97a1-8f5ba75441ea', 5, 'unit_test', 'unit_test');
@@ -0,0 +1,6 @@
+-- ledger for the first two items from items.sql: item 1 nets to 100 on hand, item 2 to 5
+INSERT INTO stock_movements (item_id, movement_type, quantity, note, created_by)
+VALUES ('6f4bdd88-d12e-421a-bac7-92ed2d9035aa', 'receipt', 120, 'PO-1001', 'unit_test'),
+       ('6f4bdd88-d12e-421a-bac7-92ed2d9035aa', 'sale', -15, NULL, 'unit_test'),
+       ('6f4bdd88-d12e-421a-bac7-92ed2d9035aa', 'write_off', -5, 'damaged in transit', 'unit_test'),
+       ('2492b388-e0b9-47ca-97a1-8f5ba75441ea', 'receipt', 5, 'PO-1002', 'unit_test');
@@ -0,0 +1,11 @@
+-- one login of the reporting client from api_clients.sql, refreshed once, plus a token of the revoked legacy client.
+-- refresh tokens are stored as the SHA-256 of 'spent-refresh-token', 'live-refresh-token' and 'legacy-refresh-token'
+INSERT INTO access_tokens (jti, family, client_id, expires_at, revoked_at)
+VALUES ('5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0001', 'f0000000-0000-0000-0000-000000000001', 'reporting', now() + interval '1 hour', NULL),
+       ('5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0002', 'f0000000-0000-0000-0000-000000000001', 'reporting', now() + interval '1 hour', now()),
+       ('5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0003', 'f0000000-0000-0000-0000-000000000002', 'legacy', now() + interval '1 hour', NULL);
+
+INSERT INTO refresh_tokens (token_hash, family, client_id, expires_at, used_at)
+VALUES (encode(sha256('spent-refresh-token'), 'hex'), 'f0000000-0000-0000-0000-000000000001', 'reporting', now() + interval '30 days', now()),
+       (encode(sha256('live-refresh-token'), 'hex'), 'f0000000-0000-0000-0000-000000000001', 'reporting', now() + interval '30 days', NULL),
+       (encode(sha256('legacy-refresh-token'), 'hex'), 'f0000000-0000-0000-0000-000000000002', 'legacy', now() + interval '30 days', NULL);
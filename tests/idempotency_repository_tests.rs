@@ -0,0 +1,167 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use inventory_service::inventory::model::IdempotencyClaim;
+    use inventory_service::inventory::repositories::idempotency::{
+        IdempotencyRepository, IdempotencyRepositoryImpl,
+    };
+    use inventory_service::test_helpers::init;
+    use serde_json::json;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    fn claim(client_id: &str, request_hash: &str) -> IdempotencyClaim {
+        IdempotencyClaim {
+            client_id: client_id.to_string(),
+            key: "key-1".to_string(),
+            request_hash: request_hash.to_string(),
+            claim_id: Uuid::new_v4(),
+        }
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_completed_key_returns_stored_response(pool: PgPool) {
+        init();
+        let repository = IdempotencyRepositoryImpl::new(pool).await;
+        let hold_until = Utc::now() + Duration::seconds(60);
+        let first = claim("reporting", "hash-1");
+
+        let claimed = repository.claim(&first, hold_until).await.unwrap();
+        assert!(claimed.is_none());
+        // while the first request runs the key is held without a response
+        let held = repository
+            .claim(&claim("reporting", "hash-1"), hold_until)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(held.request_hash, "hash-1");
+        assert!(held.response_status.is_none());
+
+        repository
+            .complete(
+                &first,
+                201,
+                b"{\"id\":1}",
+                &json!([["content-type", "application/json"], ["etag", "\"1\""]]),
+                Utc::now() + Duration::hours(24),
+            )
+            .await
+            .unwrap();
+        let completed = repository
+            .claim(&claim("reporting", "hash-1"), hold_until)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(completed.response_status, Some(201));
+        assert_eq!(completed.response_body.unwrap(), b"{\"id\":1}");
+        assert_eq!(
+            completed.response_headers,
+            json!([["content-type", "application/json"], ["etag", "\"1\""]])
+        );
+
+        // a completed key is kept even if released
+        repository.release(&first).await.unwrap();
+        let completed = repository
+            .claim(&claim("reporting", "hash-1"), hold_until)
+            .await
+            .unwrap();
+        assert!(completed.is_some());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_released_key_can_be_claimed_again(pool: PgPool) {
+        init();
+        let repository = IdempotencyRepositoryImpl::new(pool).await;
+        let hold_until = Utc::now() + Duration::seconds(60);
+        let first = claim("reporting", "hash-1");
+
+        repository.claim(&first, hold_until).await.unwrap();
+        repository.release(&first).await.unwrap();
+        let claimed = repository
+            .claim(&claim("reporting", "hash-2"), hold_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_none());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_expired_key_can_be_claimed_again(pool: PgPool) {
+        init();
+        let repository = IdempotencyRepositoryImpl::new(pool).await;
+        let first = claim("reporting", "hash-1");
+
+        repository.claim(&first, Utc::now()).await.unwrap();
+        repository
+            .complete(
+                &first,
+                201,
+                b"{}",
+                &json!([]),
+                Utc::now() - Duration::seconds(1),
+            )
+            .await
+            .unwrap();
+        let claimed = repository
+            .claim(
+                &claim("reporting", "hash-2"),
+                Utc::now() + Duration::seconds(60),
+            )
+            .await
+            .unwrap();
+        assert!(claimed.is_none());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_request_that_lost_its_hold_leaves_key_alone(pool: PgPool) {
+        init();
+        let repository = IdempotencyRepositoryImpl::new(pool).await;
+        let stale = claim("reporting", "hash-1");
+        let retry = claim("reporting", "hash-1");
+
+        // the first request outlives its hold and a retry takes the key over
+        repository.claim(&stale, Utc::now()).await.unwrap();
+        let claimed = repository
+            .claim(&retry, Utc::now() + Duration::seconds(60))
+            .await
+            .unwrap();
+        assert!(claimed.is_none());
+
+        repository.release(&stale).await.unwrap();
+        repository
+            .complete(
+                &stale,
+                201,
+                b"{}",
+                &json!([]),
+                Utc::now() + Duration::hours(24),
+            )
+            .await
+            .unwrap();
+        let held = repository
+            .claim(
+                &claim("reporting", "hash-1"),
+                Utc::now() + Duration::seconds(60),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(held.response_status.is_none());
+    }
+
+    #[sqlx::test(fixtures("api_clients"))]
+    async fn test_keys_are_per_client(pool: PgPool) {
+        init();
+        let repository = IdempotencyRepositoryImpl::new(pool).await;
+        let hold_until = Utc::now() + Duration::seconds(60);
+
+        repository
+            .claim(&claim("reporting", "hash-1"), hold_until)
+            .await
+            .unwrap();
+        let claimed = repository
+            .claim(&claim("legacy", "hash-1"), hold_until)
+            .await
+            .unwrap();
+        assert!(claimed.is_none());
+    }
+}
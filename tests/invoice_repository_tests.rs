@@ -1,15 +1,21 @@
 #[cfg(test)]
 mod tests {
     use bigdecimal::{BigDecimal, FromPrimitive};
+    use inventory_service::inventory::model::money::{Currency, Money};
+    use inventory_service::inventory::model::page::{Cursor, Seek};
+    use inventory_service::inventory::model::sort::{InvoiceSortField, Sort};
     use inventory_service::inventory::model::{
-        CreateInvoiceRequest, Pagination, UpdateInvoiceRequest,
+        CreateInvoiceRequest, CreateStockMovementRequest, ExpectedVersion, InvoiceFilter,
+        InvoiceStatus, MovementType, UpdateInvoiceRequest,
     };
     use inventory_service::inventory::repositories::invoice::{
-        InvoiceItemRow, InvoiceRepository, InvoiceRepositoryImpl,
+        InvoiceRepository, InvoiceRepositoryImpl, InvoiceTotals, NewInvoiceLine,
     };
+    use inventory_service::inventory::repositories::item::{ItemRepository, ItemRepositoryImpl};
     use inventory_service::inventory::repositories::person::{
         PersonRepository, PersonRepositoryImpl,
     };
+    use inventory_service::inventory::repositories::stock::{StockRepository, StockRepositoryImpl};
     use inventory_service::inventory::repositories::RepoError;
     use inventory_service::test_helpers::{
         first_invoice_uuid, first_item_uuid, first_person_uuid, init, FIRST_INVOICE_ID,
@@ -17,51 +23,159 @@ mod tests {
     use sqlx::PgPool;
     use uuid::Uuid;
 
+    fn totals(total: f64) -> InvoiceTotals {
+        InvoiceTotals {
+            subtotal: BigDecimal::from_f64(total).unwrap(),
+            total: BigDecimal::from_f64(total).unwrap(),
+            ..InvoiceTotals::default()
+        }
+    }
+
+    async fn create_empty_invoice(repository: &InvoiceRepositoryImpl) -> Uuid {
+        let create_invoice_request = CreateInvoiceRequest {
+            user_id: first_person_uuid(),
+            currency: Currency::USD,
+            total: None,
+            tax_rate: BigDecimal::from(0),
+            discount: Money::zero(Currency::USD),
+            created_by: "unit_test".to_string(),
+            items: vec![],
+        };
+        repository
+            .create(create_invoice_request, vec![], totals(0.0))
+            .await
+            .unwrap()
+            .alt_id
+    }
+
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_get_all_invoices(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
         let result = repository
-            .get_all_invoices(Some(Pagination::default()))
+            .get_all_invoices(
+                &InvoiceFilter::default(),
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
             .await;
         assert!(result.is_ok());
         let invoices = result.unwrap();
         assert_eq!(invoices.len(), 10);
         // get the next page
-        let mut page = Pagination {
-            last_id: Some(invoices[9].id),
-            page_size: 10,
+        let mut page = Seek {
+            after: Some(Cursor::after_id(invoices[9].id)),
+            limit: 10,
         };
-        let result = repository.get_all_invoices(Some(page)).await;
+        let result = repository
+            .get_all_invoices(&InvoiceFilter::default(), &Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let invoices_page2 = result.unwrap();
         assert_eq!(invoices_page2.len(), 10);
         // get the final page
-        page.last_id = Some(invoices_page2[9].id);
-        let result = repository.get_all_invoices(Some(page)).await;
+        page.after = Some(Cursor::after_id(invoices_page2[9].id));
+        let result = repository
+            .get_all_invoices(&InvoiceFilter::default(), &Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let invoices_page3 = result.unwrap();
         assert_eq!(invoices_page3.len(), 3);
         // test there are no further pages
-        page.last_id = Some(invoices_page3[2].id);
-        let result = repository.get_all_invoices(Some(page)).await;
+        page.after = Some(Cursor::after_id(invoices_page3[2].id));
+        let result = repository
+            .get_all_invoices(&InvoiceFilter::default(), &Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let invoices_page4 = result.unwrap();
         assert_eq!(invoices_page4.len(), 0);
     }
 
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_get_invoices_sorted(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let filter = InvoiceFilter {
+            user_id: Some(first_person_uuid()),
+            ..InvoiceFilter::default()
+        };
+        let sort: Sort<InvoiceSortField> = "-total".parse().unwrap();
+        let mut page = Seek {
+            after: None,
+            limit: 5,
+        };
+        let mut invoices = Vec::new();
+        loop {
+            let rows = repository
+                .get_all_invoices(&filter, &sort, &page, false)
+                .await
+                .unwrap();
+            let Some(last) = rows.last() else { break };
+            page.after = Some(Cursor {
+                keys: vec![last.total.to_string()],
+                ..Cursor::after_id(last.id)
+            });
+            invoices.extend(rows);
+        }
+        // every page stays within the filter, and the pages carry on in order
+        assert_eq!(invoices.len(), 13);
+        assert!(invoices.iter().all(|i| i.user_id == first_person_uuid()));
+        assert!(invoices
+            .windows(2)
+            .all(|pair| pair[0].total > pair[1].total));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_count_invoices(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let count = repository
+            .count_invoices(&InvoiceFilter::default(), false)
+            .await
+            .unwrap();
+        assert_eq!(count, 23);
+        // the count applies the same filters as the list
+        let filter = InvoiceFilter {
+            user_id: Some(first_person_uuid()),
+            min_total: Some(BigDecimal::from(1000)),
+            ..InvoiceFilter::default()
+        };
+        let count = repository.count_invoices(&filter, false).await.unwrap();
+        let invoices = repository
+            .get_all_invoices(
+                &filter,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 30,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, invoices.len() as i64);
+    }
+
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_create_invoice(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
         let invoice_request = CreateInvoiceRequest {
             user_id: first_person_uuid(),
-            total: 100.0,
-            paid: false,
+            currency: Currency::USD,
+            total: None,
+            tax_rate: BigDecimal::from(0),
+            discount: Money::zero(Currency::USD),
             created_by: "unit_test".to_string(),
             items: vec![],
         };
-        let result = repository.create(invoice_request).await;
+        let result = repository
+            .create(invoice_request, vec![], totals(100.0))
+            .await;
         assert!(result.is_ok());
         let invoice = result.unwrap();
         assert_eq!(invoice.total, BigDecimal::from_f64(100.0).unwrap());
@@ -81,7 +195,7 @@ mod tests {
     async fn test_get_invoice_by_uuid(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let result = repository.get_by_uuid(first_invoice_uuid()).await;
+        let result = repository.get_by_uuid(first_invoice_uuid(), false).await;
         assert!(result.is_ok());
         let invoice = result.unwrap();
         assert_eq!(invoice.total, BigDecimal::from_f64(100.0).unwrap());
@@ -93,27 +207,72 @@ mod tests {
         let repository = InvoiceRepositoryImpl::new(pool).await;
         let update_invoice_request = UpdateInvoiceRequest {
             id: first_invoice_uuid(),
-            total: 200000.0,
-            paid: true,
+            total: None,
+            tax_rate: Some(BigDecimal::from_f64(0.1).unwrap()),
+            discount: Some(Money::zero(Currency::USD)),
             changed_by: "unit_test".to_string(),
+            version: ExpectedVersion::OneOf(vec![1]),
         };
-        let result = repository.update(update_invoice_request).await;
+        let invoice_totals = InvoiceTotals {
+            subtotal: BigDecimal::from(100),
+            tax_rate: BigDecimal::from_f64(0.1).unwrap(),
+            tax: BigDecimal::from(10),
+            discount: BigDecimal::from(0),
+            total: BigDecimal::from(110),
+        };
+        let result = repository
+            .update(update_invoice_request.clone(), invoice_totals.clone())
+            .await;
+        assert!(result.is_ok());
+        let updated_invoice = result.unwrap();
+        assert_eq!(updated_invoice.status, InvoiceStatus::Draft);
+        assert_eq!(updated_invoice.tax, BigDecimal::from(10));
+        assert_eq!(updated_invoice.total, BigDecimal::from(110));
+        assert_eq!(updated_invoice.version, 2);
+
+        // the same change made against the version it has just replaced is refused
+        let result = repository
+            .update(update_invoice_request, invoice_totals)
+            .await;
+        assert!(matches!(result, Err(RepoError::VersionMismatch(_))));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_update_invoice_totals(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let result = repository
+            .update_totals(first_invoice_uuid(), totals(75.5), "test")
+            .await;
         assert!(result.is_ok());
         let updated_invoice = result.unwrap();
         assert_eq!(
-            updated_invoice.total,
-            BigDecimal::from_f64(200000.0).unwrap()
+            updated_invoice.subtotal,
+            BigDecimal::from_f64(75.5).unwrap()
         );
+        assert_eq!(updated_invoice.total, BigDecimal::from_f64(75.5).unwrap());
+        assert_eq!(updated_invoice.last_changed_by, "test");
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_find_invoices_by_user_id(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let result = repository.find_by_user_id(first_person_uuid()).await;
+        let filter = InvoiceFilter {
+            user_id: Some(first_person_uuid()),
+            ..InvoiceFilter::default()
+        };
+        let page = Seek {
+            after: None,
+            limit: 20,
+        };
+        let result = repository
+            .get_all_invoices(&filter, &Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let invoices = result.unwrap();
         assert_eq!(invoices.len(), 13);
+        assert!(invoices.iter().all(|i| i.user_id == first_person_uuid()));
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
@@ -121,148 +280,784 @@ mod tests {
         init();
         let cloned_pool = pool.clone();
         let person_repository = PersonRepositoryImpl::new(pool).await;
-        let result = person_repository.get_all_persons(None, 5).await;
+        let result = person_repository
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 5,
+                },
+                false,
+            )
+            .await;
         assert!(result.is_ok());
         let persons = result.unwrap();
         assert_eq!(persons.len(), 5);
         let person_id = persons[2].alt_id;
         let repository = InvoiceRepositoryImpl::new(cloned_pool).await;
-        let result = repository.find_by_user_id(person_id).await;
+        let filter = InvoiceFilter {
+            user_id: Some(person_id),
+            ..InvoiceFilter::default()
+        };
+        let result = repository
+            .get_all_invoices(
+                &filter,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await;
         assert!(result.is_ok());
         let invoices = result.unwrap();
         assert_eq!(invoices.len(), 0);
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_get_invoices_with_filters(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let page = Seek {
+            after: None,
+            limit: 30,
+        };
+        let count = |filter: InvoiceFilter| {
+            let repository = &repository;
+            let page = &page;
+            async move {
+                repository
+                    .get_all_invoices(&filter, &Sort::default(), page, false)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        let totals = InvoiceFilter {
+            user_id: Some(first_person_uuid()),
+            min_total: Some(BigDecimal::from(500)),
+            max_total: Some(BigDecimal::from(1400)),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(totals).await, 6);
+
+        repository
+            .set_status(
+                first_invoice_uuid(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "unit_test",
+            )
+            .await
+            .unwrap();
+        let issued = InvoiceFilter {
+            status: Some(InvoiceStatus::Issued),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(issued).await, 1);
+
+        // nothing has been paid on any invoice yet
+        let paid = InvoiceFilter {
+            paid: Some(true),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(paid).await, 0);
+        let unpaid = InvoiceFilter {
+            paid: Some(false),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(unpaid).await, 23);
+
+        let an_hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+        let created_since = InvoiceFilter {
+            created_from: Some(an_hour_ago),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(created_since).await, 23);
+        let created_before = InvoiceFilter {
+            created_to: Some(an_hour_ago),
+            ..InvoiceFilter::default()
+        };
+        assert_eq!(count(created_before).await, 0);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
     async fn test_delete_invoice(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let result = repository.delete(first_invoice_uuid()).await;
+        let result = repository.delete(first_invoice_uuid(), "test").await;
         assert!(result.is_ok());
         let delete_resutls = result.unwrap();
         assert_eq!(delete_resutls.id, first_invoice_uuid().to_string());
-        assert_eq!(delete_resutls.deleted, true);
+        assert!(delete_resutls.deleted);
     }
 
-    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
     async fn test_delete_invoice_not_found(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let result = repository.delete(first_invoice_uuid()).await;
+        let result = repository.delete(first_invoice_uuid(), "test").await;
         assert!(result.is_ok());
         let delete_results = result.unwrap();
         assert_eq!(delete_results.id, first_invoice_uuid().to_string());
-        assert_eq!(delete_results.deleted, true);
-        let result = repository.delete(first_invoice_uuid()).await;
+        assert!(delete_results.deleted);
+        let result = repository.delete(first_invoice_uuid(), "test").await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            RepoError::NotFound(_) => assert!(true),
-            _ => assert!(false, "Expected NotFound error"),
+            RepoError::NotFound(_) => (),
+            _ => panic!("Expected NotFound error"),
         }
     }
 
-    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
     async fn test_add_item_to_invoice(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
         let create_invoice_request = CreateInvoiceRequest {
             user_id: first_person_uuid(),
-            total: 100.0,
-            paid: false,
+            currency: Currency::USD,
+            total: None,
+            tax_rate: BigDecimal::from(0),
+            discount: Money::zero(Currency::USD),
             created_by: "unit_test".to_string(),
             items: vec![],
         };
-        let result = repository.create(create_invoice_request).await;
+        let result = repository
+            .create(create_invoice_request, vec![], totals(100.0))
+            .await;
         assert!(result.is_ok());
         let invoice = result.unwrap();
         assert_eq!(invoice.total, BigDecimal::from_f64(100.0).unwrap());
-        let cloned_invoice_id = invoice.alt_id.clone();
-        let invoice_item = InvoiceItemRow {
-            invoice_id: invoice.alt_id,
-            item_id: first_item_uuid(),
-        };
-        let result = repository.add_item(invoice_item).await;
+        let cloned_invoice_id = invoice.alt_id;
+        let result = repository
+            .add_item(invoice.alt_id, first_item_uuid(), 3, "unit_test")
+            .await;
         assert!(result.is_ok());
         let invoice_item = result.unwrap();
         assert_eq!(invoice_item.invoice_id, cloned_invoice_id);
         assert_eq!(invoice_item.item_id, first_item_uuid());
+        assert_eq!(invoice_item.quantity, 3);
+        assert_eq!(invoice_item.unit_price, BigDecimal::from_f64(10.0).unwrap());
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_add_item_already_on_invoice(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let result = repository
+            .add_item(first_invoice_uuid(), first_item_uuid(), 3, "unit_test")
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().quantity, 5);
+        let items = repository.get_items(first_invoice_uuid()).await.unwrap();
+        assert_eq!(items.len(), 2);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_add_item_keeps_captured_price(pool: PgPool) {
+        init();
+        sqlx::query("UPDATE items SET unit_price = 99 WHERE alt_id = $1")
+            .bind(first_item_uuid())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let result = repository
+            .add_item(first_invoice_uuid(), first_item_uuid(), 1, "unit_test")
+            .await;
+        assert_eq!(
+            result.unwrap().unit_price,
+            BigDecimal::from_f64(10.0).unwrap()
+        );
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_add_item_to_invoice_invalid_uuid(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let invoice_item = InvoiceItemRow {
-            invoice_id: Uuid::new_v4(),
-            item_id: first_item_uuid(),
-        };
-        let result = repository.add_item(invoice_item).await;
+        let result = repository
+            .add_item(Uuid::new_v4(), first_item_uuid(), 1, "unit_test")
+            .await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            RepoError::Other(_) => assert!(true),
-            e => assert!(false, "Expected Other error, received: {:?}", e),
+            RepoError::NotFound(_) => (),
+            e => panic!("Expected NotFound error, received: {:?}", e),
+        }
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_add_item_in_other_currency(pool: PgPool) {
+        init();
+        sqlx::query("UPDATE items SET currency = 'EUR' WHERE alt_id = $1")
+            .bind(first_item_uuid())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let result = repository
+            .add_item(first_invoice_uuid(), first_item_uuid(), 1, "unit_test")
+            .await;
+        match result {
+            Err(RepoError::CurrencyMismatch(_)) => (),
+            other => panic!("Expected CurrencyMismatch error, received: {:?}", other),
         }
+        let items = repository.get_items(first_invoice_uuid()).await.unwrap();
+        assert_eq!(items.iter().map(|item| item.quantity).sum::<i32>(), 6);
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_add_item_to_invoice_invalid_item_uuid(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let invoice_item = InvoiceItemRow {
-            invoice_id: first_invoice_uuid(),
-            item_id: Uuid::new_v4(),
-        };
-        let result = repository.add_item(invoice_item).await;
+        let result = repository
+            .add_item(first_invoice_uuid(), Uuid::new_v4(), 1, "unit_test")
+            .await;
         assert!(result.is_err());
         let error = result.unwrap_err();
         match error {
-            RepoError::Other(_) => assert!(true),
-            e => assert!(false, "Expected Other error, received: {:?}", e),
+            RepoError::NotFound(_) => (),
+            e => panic!("Expected NotFound error, received: {:?}", e),
         }
     }
 
-    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
     async fn test_remove_item_from_invoice(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
         let create_invoice_request = CreateInvoiceRequest {
             user_id: first_person_uuid(),
-            total: 100.0,
-            paid: false,
+            currency: Currency::USD,
+            total: None,
+            tax_rate: BigDecimal::from(0),
+            discount: Money::zero(Currency::USD),
             created_by: "unit_test".to_string(),
             items: vec![],
         };
-        let result = repository.create(create_invoice_request).await;
+        let result = repository
+            .create(create_invoice_request, vec![], totals(100.0))
+            .await;
         assert!(result.is_ok());
         let invoice = result.unwrap();
         assert_eq!(invoice.total, BigDecimal::from_f64(100.0).unwrap());
-        let cloned_invoice_id = invoice.alt_id.clone();
-        let invoice_item = InvoiceItemRow {
-            invoice_id: invoice.alt_id,
-            item_id: first_item_uuid(),
-        };
-        let result = repository.add_item(invoice_item).await;
+        let cloned_invoice_id = invoice.alt_id;
+        let result = repository
+            .add_item(invoice.alt_id, first_item_uuid(), 1, "unit_test")
+            .await;
         assert!(result.is_ok());
         let invoice_item = result.unwrap();
         assert_eq!(invoice_item.invoice_id, cloned_invoice_id);
         assert_eq!(invoice_item.item_id, first_item_uuid());
-        let result = repository.remove_item(invoice_item).await;
+        let result = repository
+            .remove_item(invoice_item.invoice_id, invoice_item.item_id, "unit_test")
+            .await;
         assert!(result.is_ok());
         let delete_results = result.unwrap();
-        assert_eq!(delete_results.deleted, true);
+        assert!(delete_results.deleted);
     }
 
     #[sqlx::test(fixtures("people", "items", "invoices"))]
     async fn test_get_with_items(pool: PgPool) {
         init();
         let repository = InvoiceRepositoryImpl::new(pool).await;
-        let result = repository.get_with_items(first_invoice_uuid()).await;
+        let result = repository.get_with_items(first_invoice_uuid(), false).await;
         assert!(result.is_ok());
         let invoice_rows = result.unwrap();
         assert_eq!(invoice_rows.len(), 2);
         assert_eq!(invoice_rows[0].total, BigDecimal::from_f64(100.0).unwrap());
-        assert_eq!(invoice_rows[0].item_alt_id, first_item_uuid());
+        assert_eq!(invoice_rows[0].item_alt_id, Some(first_item_uuid()));
+        assert_eq!(invoice_rows[0].quantity, Some(2));
+        assert_eq!(
+            invoice_rows[0].line_subtotal,
+            Some(BigDecimal::from_f64(20.0).unwrap())
+        );
+        let line_total: BigDecimal = invoice_rows
+            .iter()
+            .filter_map(|row| row.line_subtotal.as_ref())
+            .sum();
+        assert_eq!(line_total, invoice_rows[0].subtotal);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_get_with_items_without_lines(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        // the invoice still comes back, on a single row without a line
+        let rows = repository.get_with_items(invoice_id, false).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].alt_id, invoice_id);
+        assert!(rows[0].item_alt_id.is_none());
+        assert!(rows[0].line_subtotal.is_none());
+        let rows = repository
+            .get_with_items(Uuid::new_v4(), false)
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_deleted_invoice_is_hidden_until_restored(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        repository
+            .delete(first_invoice_uuid(), "test")
+            .await
+            .unwrap();
+        let result = repository.get_by_uuid(first_invoice_uuid(), false).await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+        let invoice = repository
+            .get_by_uuid(first_invoice_uuid(), true)
+            .await
+            .unwrap();
+        assert!(invoice.deleted_at.is_some());
+        let filter = InvoiceFilter {
+            user_id: Some(first_person_uuid()),
+            ..InvoiceFilter::default()
+        };
+        let page = Seek {
+            after: None,
+            limit: 20,
+        };
+        let invoices = repository
+            .get_all_invoices(&filter, &Sort::default(), &page, false)
+            .await
+            .unwrap();
+        assert!(invoices.iter().all(|i| i.alt_id != first_invoice_uuid()));
+        // a deleted invoice takes no new lines
+        let result = repository
+            .add_item(first_invoice_uuid(), first_item_uuid(), 1, "unit_test")
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+
+        let restored = repository
+            .restore(first_invoice_uuid(), "restorer")
+            .await
+            .unwrap();
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(restored.last_changed_by, "restorer");
+        // its lines were kept, so it comes back whole
+        let rows = repository
+            .get_with_items(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_deleting_an_item_keeps_invoice_lines(pool: PgPool) {
+        init();
+        let item_repository = ItemRepositoryImpl::new(pool.clone()).await;
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        item_repository
+            .delete_item(first_item_uuid(), "test")
+            .await
+            .unwrap();
+        let rows = repository
+            .get_with_items(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows
+            .iter()
+            .any(|row| row.item_alt_id == Some(first_item_uuid())));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_set_invoice_status(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let invoice = repository
+            .set_status(
+                first_invoice_uuid(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "test",
+            )
+            .await
+            .unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Issued);
+        assert_eq!(invoice.last_changed_by, "test");
+        // a caller working from a stale status is turned away
+        let result = repository
+            .set_status(
+                first_invoice_uuid(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Void,
+                "test",
+            )
+            .await;
+        assert!(matches!(result, Err(RepoError::InvalidStatus(_))));
+        let result = repository
+            .set_status(
+                Uuid::new_v4(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "test",
+            )
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_issued_invoice_lines_are_fixed(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        repository
+            .set_status(
+                first_invoice_uuid(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "test",
+            )
+            .await
+            .unwrap();
+        let result = repository
+            .add_item(first_invoice_uuid(), first_item_uuid(), 1, "unit_test")
+            .await;
+        assert!(matches!(result, Err(RepoError::InvalidStatus(_))));
+        let result = repository
+            .remove_item(first_invoice_uuid(), first_item_uuid(), "unit_test")
+            .await;
+        assert!(matches!(result, Err(RepoError::InvalidStatus(_))));
+        let update_invoice_request = UpdateInvoiceRequest {
+            id: first_invoice_uuid(),
+            changed_by: "test".to_string(),
+            ..UpdateInvoiceRequest::default()
+        };
+        let result = repository
+            .update(update_invoice_request, totals(100.0))
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+        let rows = repository
+            .get_with_items(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_invoice_lines_reserve_stock(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let stock = StockRepositoryImpl::new(pool).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        repository
+            .add_item(invoice_id, first_item_uuid(), 3, "unit_test")
+            .await
+            .unwrap();
+        let level = stock.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(level.reserved, 13);
+        assert_eq!(level.available, 87);
+        // more than is available is turned away and reserves nothing
+        let result = repository
+            .add_item(invoice_id, first_item_uuid(), 88, "unit_test")
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        assert_eq!(
+            stock.get_stock(first_item_uuid()).await.unwrap().reserved,
+            13
+        );
+        let result = repository
+            .remove_item(invoice_id, first_item_uuid(), "unit_test")
+            .await
+            .unwrap();
+        assert!(result.deleted);
+        let level = stock.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(level.reserved, 10);
+        assert_eq!(level.on_hand, 100);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_paying_invoice_takes_reserved_stock(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let stock = StockRepositoryImpl::new(pool).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        repository
+            .add_item(invoice_id, first_item_uuid(), 5, "unit_test")
+            .await
+            .unwrap();
+        for (from, to) in [
+            (InvoiceStatus::Draft, InvoiceStatus::Issued),
+            (InvoiceStatus::Issued, InvoiceStatus::Paid),
+        ] {
+            repository
+                .set_status(invoice_id, from, to, "unit_test")
+                .await
+                .unwrap();
+        }
+        let level = stock.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(level.on_hand, 95);
+        assert_eq!(level.reserved, 10);
+        let movements = stock
+            .get_movements(
+                first_item_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await
+            .unwrap();
+        let sale = movements.last().unwrap();
+        assert_eq!(sale.quantity, -5);
+        assert_eq!(sale.note, Some(format!("invoice {}", invoice_id)));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_voiding_invoice_releases_stock(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let stock = StockRepositoryImpl::new(pool).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        repository
+            .add_item(invoice_id, first_item_uuid(), 5, "unit_test")
+            .await
+            .unwrap();
+        repository
+            .set_status(
+                invoice_id,
+                InvoiceStatus::Draft,
+                InvoiceStatus::Void,
+                "unit_test",
+            )
+            .await
+            .unwrap();
+        let level = stock.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(level.on_hand, 100);
+        assert_eq!(level.reserved, 10);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_releasing_more_than_reserved_is_refused(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        repository
+            .add_item(invoice_id, first_item_uuid(), 5, "unit_test")
+            .await
+            .unwrap();
+        // the line's reservation has already been handed back
+        sqlx::query("UPDATE stock_levels SET reserved = 2 WHERE item_id = $1")
+            .bind(first_item_uuid())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let result = repository
+            .set_status(
+                invoice_id,
+                InvoiceStatus::Draft,
+                InvoiceStatus::Void,
+                "unit_test",
+            )
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        let stock = StockRepositoryImpl::new(pool).await;
+        assert_eq!(
+            stock.get_stock(first_item_uuid()).await.unwrap().reserved,
+            2
+        );
+        let invoice = repository.get_by_uuid(invoice_id, false).await.unwrap();
+        assert_eq!(invoice.status, InvoiceStatus::Draft);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_paying_invoice_without_stock_on_hand_is_refused(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let invoice_id = create_empty_invoice(&repository).await;
+        repository
+            .add_item(invoice_id, first_item_uuid(), 5, "unit_test")
+            .await
+            .unwrap();
+        repository
+            .set_status(
+                invoice_id,
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "unit_test",
+            )
+            .await
+            .unwrap();
+        // stock written off behind the ledger's back, leaving less on hand than the line
+        sqlx::query(
+            "INSERT INTO stock_movements (item_id, movement_type, quantity, created_by) VALUES ($1, 'write_off', -97, 'unit_test')",
+        )
+        .bind(first_item_uuid())
+        .execute(&pool)
+        .await
+        .unwrap();
+        let result = repository
+            .set_status(
+                invoice_id,
+                InvoiceStatus::Issued,
+                InvoiceStatus::Paid,
+                "unit_test",
+            )
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        let stock = StockRepositoryImpl::new(pool).await;
+        let level = stock.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(level.on_hand, 3);
+        assert_eq!(level.reserved, 15);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_create_invoice_with_lines(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let stock = StockRepositoryImpl::new(pool).await;
+        let second_item = Uuid::parse_str("2492b388-e0b9-47ca-97a1-8f5ba75441ea").unwrap();
+        stock
+            .record_movement(&CreateStockMovementRequest {
+                item_id: second_item,
+                movement_type: MovementType::Receipt,
+                quantity: 1,
+                note: None,
+                created_by: "unit_test".to_string(),
+            })
+            .await
+            .unwrap();
+        let lines = repository
+            .price_lines(
+                Currency::USD,
+                &[first_item_uuid(), second_item, first_item_uuid()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].item_id, first_item_uuid());
+        assert_eq!(lines[0].quantity, 2);
+        assert_eq!(lines[1].quantity, 1);
+        let invoice_request = CreateInvoiceRequest {
+            user_id: first_person_uuid(),
+            currency: Currency::USD,
+            created_by: "unit_test".to_string(),
+            ..CreateInvoiceRequest::default()
+        };
+        let invoice = repository
+            .create(invoice_request, lines, totals(40.0))
+            .await
+            .unwrap();
+        let items = repository.get_items(invoice.alt_id).await.unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(
+            stock.get_stock(first_item_uuid()).await.unwrap().reserved,
+            12
+        );
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_concurrent_creates_with_lines_in_opposite_order(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let stock = StockRepositoryImpl::new(pool).await;
+        let second_item = Uuid::parse_str("2492b388-e0b9-47ca-97a1-8f5ba75441ea").unwrap();
+        stock
+            .record_movement(&CreateStockMovementRequest {
+                item_id: second_item,
+                movement_type: MovementType::Receipt,
+                quantity: 20,
+                note: None,
+                created_by: "unit_test".to_string(),
+            })
+            .await
+            .unwrap();
+        let line = |item_id| NewInvoiceLine {
+            item_id,
+            quantity: 1,
+            unit_price: BigDecimal::from(10),
+        };
+        let invoice_request = CreateInvoiceRequest {
+            user_id: first_person_uuid(),
+            currency: Currency::USD,
+            created_by: "unit_test".to_string(),
+            ..CreateInvoiceRequest::default()
+        };
+        // stock levels are locked in item order whatever order the lines come in, so the two
+        // creates queue on the first lock instead of deadlocking
+        for _ in 0..10 {
+            let (first, second) = tokio::join!(
+                repository.create(
+                    invoice_request.clone(),
+                    vec![line(first_item_uuid()), line(second_item)],
+                    totals(20.0)
+                ),
+                repository.create(
+                    invoice_request.clone(),
+                    vec![line(second_item), line(first_item_uuid())],
+                    totals(20.0)
+                )
+            );
+            assert!(first.is_ok());
+            assert!(second.is_ok());
+        }
+        assert_eq!(
+            stock.get_stock(first_item_uuid()).await.unwrap().reserved,
+            30
+        );
+        assert_eq!(stock.get_stock(second_item).await.unwrap().reserved, 25);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_create_invoice_rolls_back_when_a_line_fails(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let invoice_request = CreateInvoiceRequest {
+            user_id: first_person_uuid(),
+            currency: Currency::USD,
+            created_by: "unit_test".to_string(),
+            ..CreateInvoiceRequest::default()
+        };
+        // the first line can be reserved, the second item has none available
+        let lines = vec![
+            NewInvoiceLine {
+                item_id: first_item_uuid(),
+                quantity: 5,
+                unit_price: BigDecimal::from(10),
+            },
+            NewInvoiceLine {
+                item_id: Uuid::parse_str("2492b388-e0b9-47ca-97a1-8f5ba75441ea").unwrap(),
+                quantity: 1,
+                unit_price: BigDecimal::from(20),
+            },
+        ];
+        let result = repository
+            .create(invoice_request.clone(), lines, totals(70.0))
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        let unknown_item = vec![NewInvoiceLine {
+            item_id: Uuid::new_v4(),
+            quantity: 1,
+            unit_price: BigDecimal::from(10),
+        }];
+        let result = repository
+            .create(invoice_request, unknown_item, totals(10.0))
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+
+        let invoices: i64 = sqlx::query_scalar("SELECT count(*) FROM invoices")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(invoices, 23);
+        let stock = StockRepositoryImpl::new(pool).await;
+        assert_eq!(
+            stock.get_stock(first_item_uuid()).await.unwrap().reserved,
+            10
+        );
+    }
+
+    #[sqlx::test(fixtures("people", "items"))]
+    async fn test_price_lines_unknown_item(pool: PgPool) {
+        init();
+        let repository = InvoiceRepositoryImpl::new(pool).await;
+        let result = repository
+            .price_lines(Currency::USD, &[first_item_uuid(), Uuid::new_v4()])
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
     }
 }
@@ -1,7 +1,13 @@
 #[cfg(test)]
 mod tests {
     use bigdecimal::{BigDecimal, FromPrimitive};
-    use inventory_service::inventory::model::{CreateItemRequest, Pagination, UpdateItemRequest};
+    use chrono::SecondsFormat;
+    use inventory_service::inventory::model::money::{Currency, Money};
+    use inventory_service::inventory::model::page::{Cursor, Seek};
+    use inventory_service::inventory::model::sort::{ItemSortField, Sort};
+    use inventory_service::inventory::model::{
+        CreateItemRequest, ExpectedVersion, ItemSearchQuery, UpdateItemRequest,
+    };
     use inventory_service::inventory::repositories::item::{ItemRepository, ItemRepositoryImpl};
     use inventory_service::inventory::repositories::RepoError;
     use inventory_service::test_helpers::{
@@ -24,33 +30,88 @@ mod tests {
     async fn test_get_all_items(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
-        let result = repository.get_all_items(Some(Pagination::default())).await;
+        let result = repository
+            .get_all_items(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await;
         assert!(result.is_ok());
         let items = result.unwrap();
         assert_eq!(items.len(), 10);
         // get the next page
-        let mut page = Pagination {
-            last_id: Some(items[9].id),
-            page_size: 10,
+        let mut page = Seek {
+            after: Some(Cursor::after_id(items[9].id)),
+            limit: 10,
         };
-        let result = repository.get_all_items(Some(page)).await;
+        let result = repository
+            .get_all_items(&Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let items_page2 = result.unwrap();
         assert_eq!(items_page2.len(), 10);
         // get the final page
-        page.last_id = Some(items_page2[9].id);
-        let result = repository.get_all_items(Some(page)).await;
+        page.after = Some(Cursor::after_id(items_page2[9].id));
+        let result = repository
+            .get_all_items(&Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let items_page3 = result.unwrap();
         assert_eq!(items_page3.len(), 3);
         // test there are no further pages
-        page.last_id = Some(items_page3[2].id);
-        let result = repository.get_all_items(Some(page)).await;
+        page.after = Some(Cursor::after_id(items_page3[2].id));
+        let result = repository
+            .get_all_items(&Sort::default(), &page, false)
+            .await;
         assert!(result.is_ok());
         let items_page4 = result.unwrap();
         assert_eq!(items_page4.len(), 0);
     }
 
+    #[sqlx::test(fixtures("items"))]
+    async fn test_get_all_items_sorted(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        // the fixture items are created together, so they tie on created_at and the price decides
+        let sort: Sort<ItemSortField> = "created_at,-unit_price".parse().unwrap();
+        let mut page = Seek {
+            after: None,
+            limit: 10,
+        };
+        let mut items = Vec::new();
+        loop {
+            let rows = repository.get_all_items(&sort, &page, false).await.unwrap();
+            let Some(last) = rows.last() else { break };
+            page.after = Some(Cursor {
+                keys: vec![
+                    last.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    last.unit_price.to_string(),
+                ],
+                ..Cursor::after_id(last.id)
+            });
+            items.extend(rows);
+        }
+        let all = repository
+            .get_all_items(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(items.len(), all.len());
+        assert!(items
+            .windows(2)
+            .all(|pair| pair[0].unit_price > pair[1].unit_price));
+    }
+
     // test all functions on ItemRepositoryImpl
     #[sqlx::test(fixtures("items"))]
     async fn test_get_item_by_id(pool: PgPool) {
@@ -65,7 +126,7 @@ mod tests {
     async fn test_get_item_by_uuid(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
-        let result = repository.get_item_by_uuid(first_item_uuid()).await;
+        let result = repository.get_item_by_uuid(first_item_uuid(), false).await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.name, "Item 1");
@@ -78,7 +139,7 @@ mod tests {
         let item_request = CreateItemRequest {
             name: "Test Item".to_string(),
             description: "Test Item Description".to_string(),
-            unit_price: 100.0,
+            unit_price: Money::new(BigDecimal::from(100), Currency::USD),
             created_by: "testuser".to_string(),
         };
         let result = repository.create_item(&item_request).await;
@@ -87,6 +148,7 @@ mod tests {
         assert_eq!(item.name, "Test Item");
         assert_eq!(item.description, "Test Item Description");
         assert_eq!(item.unit_price, BigDecimal::from_f64(100.0).unwrap());
+        assert_eq!(item.currency, Currency::USD);
     }
 
     #[sqlx::test(fixtures("items"))]
@@ -97,8 +159,9 @@ mod tests {
             id: FIRST_ITEM_UUID.to_string(),
             name: "Updated Item".to_string(),
             description: "Updated Item Description".to_string(),
-            unit_price: 200.0,
+            unit_price: Money::new(BigDecimal::from(200), Currency::EUR),
             changed_by: "testuser".to_string(),
+            version: ExpectedVersion::OneOf(vec![1]),
         };
         let result = repository.update_item(&item_request).await;
         assert!(result.is_ok());
@@ -106,27 +169,139 @@ mod tests {
         assert_eq!(item.name, "Updated Item");
         assert_eq!(item.description, "Updated Item Description");
         assert_eq!(item.unit_price, BigDecimal::from_f64(200.0).unwrap());
+        assert_eq!(item.currency, Currency::EUR);
+        assert_eq!(item.version, 2);
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_update_item_stale_version(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        // someone else's change moves the item on to version 2
+        repository
+            .delete_item(first_item_uuid(), "someone_else")
+            .await
+            .unwrap();
+        repository
+            .restore_item(first_item_uuid(), "someone_else")
+            .await
+            .unwrap();
+        let item_request = UpdateItemRequest {
+            id: FIRST_ITEM_UUID.to_string(),
+            name: "Updated Item".to_string(),
+            description: "Updated Item Description".to_string(),
+            unit_price: Money::new(BigDecimal::from(200), Currency::USD),
+            changed_by: "testuser".to_string(),
+            version: ExpectedVersion::OneOf(vec![1]),
+        };
+        let result = repository.update_item(&item_request).await;
+        assert!(matches!(result, Err(RepoError::VersionMismatch(_))));
+        let item = repository
+            .get_item_by_uuid(first_item_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(item.name, "Item 1");
+        assert_eq!(item.version, 3);
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_update_item_any_listed_or_any_version(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        let item_request = |version| UpdateItemRequest {
+            id: FIRST_ITEM_UUID.to_string(),
+            name: "Updated Item".to_string(),
+            description: "Updated Item Description".to_string(),
+            unit_price: Money::new(BigDecimal::from(200), Currency::USD),
+            changed_by: "testuser".to_string(),
+            version,
+        };
+        let item = repository
+            .update_item(&item_request(ExpectedVersion::OneOf(vec![5, 1])))
+            .await
+            .unwrap();
+        assert_eq!(item.version, 2);
+        let item = repository
+            .update_item(&item_request(ExpectedVersion::Any))
+            .await
+            .unwrap();
+        assert_eq!(item.version, 3);
+        let result = repository
+            .update_item(&item_request(ExpectedVersion::OneOf(vec![1, 2])))
+            .await;
+        assert!(matches!(result, Err(RepoError::VersionMismatch(_))));
     }
 
     #[sqlx::test(fixtures("items"))]
     async fn test_delete_item(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
-        let result = repository.delete_item(first_item_uuid()).await;
+        let result = repository.delete_item(first_item_uuid(), "test").await;
         assert!(result.is_ok());
         let item = result.unwrap();
         assert_eq!(item.name, "Item 1");
     }
 
+    #[sqlx::test(fixtures("items"))]
+    async fn test_deleted_item_is_hidden_until_restored(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        let deleted = repository
+            .delete_item(first_item_uuid(), "test")
+            .await
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        let result = repository.get_item_by_uuid(first_item_uuid(), false).await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+        let item = repository
+            .get_item_by_uuid(first_item_uuid(), true)
+            .await
+            .unwrap();
+        assert!(item.deleted_at.is_some());
+        let items = repository
+            .get_all_items(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(items.iter().all(|item| item.alt_id != first_item_uuid()));
+        let items = repository
+            .get_all_items(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+                true,
+            )
+            .await
+            .unwrap();
+        assert!(items.iter().any(|item| item.alt_id == first_item_uuid()));
+
+        let restored = repository
+            .restore_item(first_item_uuid(), "restorer")
+            .await
+            .unwrap();
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(restored.last_changed_by, "restorer");
+        let result = repository.restore_item(first_item_uuid(), "restorer").await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
     #[sqlx::test(fixtures("items"))]
     async fn test_get_item_by_id_not_found(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
         let result = repository.get_item_by_id(999).await;
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
         match result.unwrap_err() {
             RepoError::NotFound(_) => (),
-            _ => assert!(false, "Expected NotFound error"),
+            _ => panic!("Expected NotFound error"),
         }
     }
 
@@ -134,11 +309,11 @@ mod tests {
     async fn test_get_item_by_uuid_not_found(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
-        let result = repository.get_item_by_uuid(invalid_uuid()).await;
-        assert_eq!(result.is_err(), true);
+        let result = repository.get_item_by_uuid(invalid_uuid(), false).await;
+        assert!(result.is_err());
         match result.unwrap_err() {
             RepoError::NotFound(_) => (),
-            _ => assert!(false, "Expected NotFound error"),
+            _ => panic!("Expected NotFound error"),
         }
     }
 
@@ -150,14 +325,15 @@ mod tests {
             id: "00000000-0000-0000-0000-000000000000".to_string(),
             name: "Updated Item".to_string(),
             description: "Updated Item Description".to_string(),
-            unit_price: 200.0,
+            unit_price: Money::new(BigDecimal::from(200), Currency::USD),
             changed_by: "testuser".to_string(),
+            version: ExpectedVersion::Any,
         };
         let result = repository.update_item(&item_request).await;
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
         match result.unwrap_err() {
             RepoError::NotFound(_) => (),
-            _ => assert!(false, "Expected NotFound error"),
+            _ => panic!("Expected NotFound error"),
         }
     }
 
@@ -165,11 +341,213 @@ mod tests {
     async fn test_delete_item_not_found(pool: PgPool) {
         init();
         let repository = ItemRepositoryImpl::new(pool).await;
-        let result = repository.delete_item(invalid_uuid()).await;
-        assert_eq!(result.is_err(), true);
+        let result = repository.delete_item(invalid_uuid(), "test").await;
+        assert!(result.is_err());
         match result.unwrap_err() {
             RepoError::NotFound(_) => (),
-            _ => assert!(false, "Expected NotFound error"),
+            _ => panic!("Expected NotFound error"),
         }
     }
+
+    async fn create_items_to_search(repository: &ItemRepositoryImpl) {
+        for (name, description, price, created_by) in [
+            ("Blue widget", "A small widget", 15, "warehouse"),
+            ("Red gadget", "A gadget with blue trim", 25, "warehouse"),
+            ("Green widget", "A large widget", 35, "catalogue"),
+        ] {
+            repository
+                .create_item(&CreateItemRequest {
+                    name: name.to_string(),
+                    description: description.to_string(),
+                    unit_price: Money::new(BigDecimal::from(price), Currency::USD),
+                    created_by: created_by.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_search_items_by_keyword(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        create_items_to_search(&repository).await;
+        let search = ItemSearchQuery {
+            q: Some("blue".to_string()),
+            ..ItemSearchQuery::default()
+        };
+        let items = repository
+            .search_items(
+                &search,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        // a match in the name ranks above one in the description
+        let names: Vec<&str> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["Blue widget", "Red gadget"]);
+        assert!(items[0].rank.unwrap() > items[1].rank.unwrap());
+        assert_eq!(
+            items[0].name_highlight.as_deref(),
+            Some("<mark>Blue</mark> widget")
+        );
+        assert!(items[1]
+            .description_highlight
+            .as_ref()
+            .unwrap()
+            .contains("<mark>blue</mark>"));
+
+        // keyword results are paged by rank, so the cursor carries it
+        let page = Seek {
+            after: Some(Cursor {
+                rank: items[0].rank,
+                ..Cursor::after_id(items[0].id)
+            }),
+            limit: 10,
+        };
+        let next = repository
+            .search_items(&search, &Sort::default(), &page, false)
+            .await
+            .unwrap();
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].name, "Red gadget");
+
+        // a sort replaces the order by relevance, and its cursor continues in that order
+        let sort: Sort<ItemSortField> = "-unit_price".parse().unwrap();
+        let page = Seek {
+            after: None,
+            limit: 1,
+        };
+        let items = repository
+            .search_items(&search, &sort, &page, false)
+            .await
+            .unwrap();
+        assert_eq!(items[0].name, "Red gadget");
+        let page = Seek {
+            after: Some(Cursor {
+                keys: vec![items[0].unit_price.to_string()],
+                ..Cursor::after_id(items[0].id)
+            }),
+            limit: 1,
+        };
+        let next = repository
+            .search_items(&search, &sort, &page, false)
+            .await
+            .unwrap();
+        assert_eq!(next[0].name, "Blue widget");
+
+        // words are stemmed, and other filters still apply
+        let search = ItemSearchQuery {
+            q: Some("widgets".to_string()),
+            min_price: Some(BigDecimal::from(20)),
+            ..ItemSearchQuery::default()
+        };
+        let items = repository
+            .search_items(
+                &search,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "Green widget");
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_search_items_highlight_is_escaped(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        repository
+            .create_item(&CreateItemRequest {
+                name: "<script>alert(1)</script> teapot".to_string(),
+                description: "Tom & Jerry's \"teapot\"".to_string(),
+                unit_price: Money::new(BigDecimal::from(10), Currency::USD),
+                created_by: "warehouse".to_string(),
+            })
+            .await
+            .unwrap();
+        let search = ItemSearchQuery {
+            q: Some("teapot".to_string()),
+            ..ItemSearchQuery::default()
+        };
+        let items = repository
+            .search_items(
+                &search,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 1);
+        // only the highlight markup is left as HTML
+        assert_eq!(
+            items[0].name_highlight.as_deref(),
+            Some("&lt;script&gt;alert(1)&lt;/script&gt; <mark>teapot</mark>")
+        );
+        assert!(items[0]
+            .description_highlight
+            .as_ref()
+            .unwrap()
+            .contains("Jerry&#39;s &quot;<mark>teapot</mark>"));
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_search_items_by_filters(pool: PgPool) {
+        init();
+        let repository = ItemRepositoryImpl::new(pool).await;
+        create_items_to_search(&repository).await;
+        let search = ItemSearchQuery {
+            min_price: Some(BigDecimal::from(20)),
+            max_price: Some(BigDecimal::from(40)),
+            ..ItemSearchQuery::default()
+        };
+        let items = repository
+            .search_items(
+                &search,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        // Item 2, 3 and 4 from the fixture and two of the new items, in id order
+        assert_eq!(items.len(), 5);
+        assert!(items.windows(2).all(|pair| pair[0].id < pair[1].id));
+        assert!(items.iter().all(|item| item.rank.is_none()));
+
+        let search = ItemSearchQuery {
+            created_by: Some("warehouse".to_string()),
+            ..ItemSearchQuery::default()
+        };
+        let items = repository
+            .search_items(
+                &search,
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 2);
+    }
 }
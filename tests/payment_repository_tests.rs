@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use inventory_service::inventory::model::money::{Currency, Money};
+    use inventory_service::inventory::model::page::Seek;
+    use inventory_service::inventory::model::{CreatePaymentRequest, InvoiceStatus, PaymentMethod};
+    use inventory_service::inventory::repositories::invoice::{
+        InvoiceRepository, InvoiceRepositoryImpl,
+    };
+    use inventory_service::inventory::repositories::payment::{
+        PaymentRepository, PaymentRepositoryImpl,
+    };
+    use inventory_service::inventory::repositories::RepoError;
+    use inventory_service::test_helpers::{first_invoice_uuid, init};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    fn payment(invoice_id: Uuid, amount: &str, currency: Currency) -> CreatePaymentRequest {
+        CreatePaymentRequest {
+            invoice_id,
+            amount: Money::new(amount.parse().unwrap(), currency),
+            method: PaymentMethod::BankTransfer,
+            reference: Some("TRF-0001".to_string()),
+            paid_at: None,
+            created_by: "unit_test".to_string(),
+        }
+    }
+
+    async fn issue_first_invoice(pool: &PgPool) {
+        InvoiceRepositoryImpl::new(pool.clone())
+            .await
+            .set_status(
+                first_invoice_uuid(),
+                InvoiceStatus::Draft,
+                InvoiceStatus::Issued,
+                "unit_test",
+            )
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_partial_payments_settle_invoice(pool: PgPool) {
+        init();
+        issue_first_invoice(&pool).await;
+        let invoices = InvoiceRepositoryImpl::new(pool.clone()).await;
+        let repository = PaymentRepositoryImpl::new(pool).await;
+
+        let first = repository
+            .record_payment(&payment(first_invoice_uuid(), "40.00", Currency::USD))
+            .await
+            .unwrap();
+        assert_eq!(first.amount, "40.00".parse::<BigDecimal>().unwrap());
+        let invoice = invoices
+            .get_by_uuid(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(invoice.amount_paid, "40.00".parse::<BigDecimal>().unwrap());
+        assert_eq!(invoice.status, InvoiceStatus::Issued);
+
+        // the remaining 60.00 clears the balance and marks the invoice paid
+        repository
+            .record_payment(&payment(first_invoice_uuid(), "60.00", Currency::USD))
+            .await
+            .unwrap();
+        let invoice = invoices
+            .get_by_uuid(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(invoice.amount_paid, invoice.total);
+        assert_eq!(invoice.status, InvoiceStatus::Paid);
+
+        let payments = repository
+            .get_payments(
+                first_invoice_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0].id, first.id);
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_overpayment_is_rejected(pool: PgPool) {
+        init();
+        issue_first_invoice(&pool).await;
+        let repository = PaymentRepositoryImpl::new(pool.clone()).await;
+        repository
+            .record_payment(&payment(first_invoice_uuid(), "75.00", Currency::USD))
+            .await
+            .unwrap();
+        let result = repository
+            .record_payment(&payment(first_invoice_uuid(), "25.01", Currency::USD))
+            .await;
+        assert!(matches!(result, Err(RepoError::ExceedsBalance(_))));
+        let invoice = InvoiceRepositoryImpl::new(pool)
+            .await
+            .get_by_uuid(first_invoice_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(invoice.amount_paid, "75.00".parse::<BigDecimal>().unwrap());
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_payment_against_draft_is_rejected(pool: PgPool) {
+        init();
+        let repository = PaymentRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_payment(&payment(first_invoice_uuid(), "10.00", Currency::USD))
+            .await;
+        assert!(matches!(result, Err(RepoError::InvalidStatus(_))));
+        let result = repository
+            .record_payment(&payment(Uuid::new_v4(), "10.00", Currency::USD))
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_payment_in_other_currency_is_rejected(pool: PgPool) {
+        init();
+        issue_first_invoice(&pool).await;
+        let repository = PaymentRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_payment(&payment(first_invoice_uuid(), "10.00", Currency::EUR))
+            .await;
+        assert!(matches!(result, Err(RepoError::CurrencyMismatch(_))));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_payments_are_append_only(pool: PgPool) {
+        init();
+        issue_first_invoice(&pool).await;
+        let repository = PaymentRepositoryImpl::new(pool.clone()).await;
+        let recorded = repository
+            .record_payment(&payment(first_invoice_uuid(), "10.00", Currency::USD))
+            .await
+            .unwrap();
+        let result = sqlx::query("UPDATE payments SET amount = 1 WHERE id = $1")
+            .bind(recorded.id)
+            .execute(&pool)
+            .await;
+        assert!(result.is_err());
+        let result = sqlx::query("DELETE FROM payments WHERE id = $1")
+            .bind(recorded.id)
+            .execute(&pool)
+            .await;
+        assert!(result.is_err());
+    }
+}
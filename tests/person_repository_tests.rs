@@ -1,42 +1,93 @@
 #[cfg(test)]
 mod tests {
-    use inventory_service::inventory::model::{CreatePersonRequest, UpdatePersonRequest};
+    use inventory_service::inventory::model::page::{Cursor, Seek};
+    use inventory_service::inventory::model::sort::Sort;
+    use inventory_service::inventory::model::{
+        CreatePersonRequest, ExpectedVersion, UpdatePersonRequest,
+    };
     use inventory_service::inventory::repositories::person::{
         PersonRepository, PersonRepositoryImpl, PersonRow,
     };
     use inventory_service::inventory::repositories::RepoError;
-    use inventory_service::test_helpers::{first_person_uuid, init, invalid_uuid, FIRST_PERSON_ID, FIRST_PERSON_UUID};
+    use inventory_service::test_helpers::{
+        first_person_uuid, init, invalid_uuid, FIRST_PERSON_ID, FIRST_PERSON_UUID,
+    };
     use sqlx::PgPool;
 
     #[sqlx::test(migrations = "./migrations", fixtures("people"))]
     async fn test_get_all_persons(pool: PgPool) {
         init();
         let repository = PersonRepositoryImpl::new(pool).await;
-        let result = repository.get_all_persons(None, 10).await;
+        let result = repository
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 10,
+                },
+                false,
+            )
+            .await;
         assert!(result.is_ok());
         let people = result.unwrap();
         assert_eq!(people.len(), 10);
         // get the next page
-        let result = repository.get_all_persons(Some(people[9].id), 10).await;
+        let result = repository
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: Some(Cursor::after_id(people[9].id)),
+                    limit: 10,
+                },
+                false,
+            )
+            .await;
         assert!(result.is_ok());
         let people_page2 = result.unwrap();
         assert_eq!(people_page2.len(), 10);
         // get the final page
         let result = repository
-            .get_all_persons(Some(people_page2[9].id), 10)
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: Some(Cursor::after_id(people_page2[9].id)),
+                    limit: 10,
+                },
+                false,
+            )
             .await;
         assert!(result.is_ok());
         let people_page3 = result.unwrap();
         assert_eq!(people_page3.len(), 3);
         // test there are no further pages
         let result = repository
-            .get_all_persons(Some(people_page3[2].id), 10)
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: Some(Cursor::after_id(people_page3[2].id)),
+                    limit: 10,
+                },
+                false,
+            )
             .await;
         assert!(result.is_ok());
         let people_page4 = result.unwrap();
         assert_eq!(people_page4.len(), 0);
     }
 
+    #[sqlx::test(migrations = "./migrations", fixtures("people"))]
+    async fn test_count_persons(pool: PgPool) {
+        init();
+        let repository = PersonRepositoryImpl::new(pool).await;
+        assert_eq!(repository.count_persons(false).await.unwrap(), 23);
+        repository
+            .delete_person(first_person_uuid(), "test")
+            .await
+            .unwrap();
+        assert_eq!(repository.count_persons(false).await.unwrap(), 22);
+        assert_eq!(repository.count_persons(true).await.unwrap(), 23);
+    }
+
     // test all functions on PersonRepositoryImpl
     #[sqlx::test(fixtures("people"))]
     async fn test_get_person_by_id(pool: PgPool) {
@@ -75,29 +126,105 @@ mod tests {
             email: "updated.person@test.com".to_string(),
             changed_by: "testuser".to_string(),
             id: FIRST_PERSON_UUID.to_string(),
+            version: ExpectedVersion::OneOf(vec![1]),
         };
         let result = repository.update_person(&person_request).await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.name, "Updated Person");
         assert_eq!(person.email, "updated.person@test.com");
+        assert_eq!(person.version, 2);
+
+        // a second change made against version 1 would undo the first
+        let result = repository.update_person(&person_request).await;
+        assert!(matches!(result, Err(RepoError::VersionMismatch(_))));
     }
 
     #[sqlx::test(fixtures("people"))]
     async fn test_delete_person(pool: PgPool) {
         init();
         let repository = PersonRepositoryImpl::new(pool).await;
-        let result = repository.delete_person(first_person_uuid()).await;
+        let result = repository.delete_person(first_person_uuid(), "test").await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.name, "John Doe");
     }
 
+    #[sqlx::test(fixtures("people"))]
+    async fn test_deleted_person_is_hidden_until_restored(pool: PgPool) {
+        init();
+        let repository = PersonRepositoryImpl::new(pool).await;
+        let deleted = repository
+            .delete_person(first_person_uuid(), "test")
+            .await
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.last_changed_by, "test");
+        let result = repository
+            .get_person_by_uuid(first_person_uuid(), false)
+            .await;
+        assert_not_found(result);
+        let person = repository
+            .get_person_by_uuid(first_person_uuid(), true)
+            .await
+            .unwrap();
+        assert!(person.deleted_at.is_some());
+        let persons = repository
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(persons.iter().all(|p| p.alt_id != first_person_uuid()));
+        let persons = repository
+            .get_all_persons(
+                &Sort::default(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+                true,
+            )
+            .await
+            .unwrap();
+        assert!(persons.iter().any(|p| p.alt_id == first_person_uuid()));
+        // a second delete finds nothing to delete
+        let result = repository.delete_person(first_person_uuid(), "test").await;
+        assert_not_found(result);
+
+        let restored = repository
+            .restore_person(first_person_uuid(), "restorer")
+            .await
+            .unwrap();
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(restored.last_changed_by, "restorer");
+        let person = repository
+            .get_person_by_uuid(first_person_uuid(), false)
+            .await
+            .unwrap();
+        assert_eq!(person.name, "John Doe");
+    }
+
+    #[sqlx::test(fixtures("people"))]
+    async fn test_restore_person_not_deleted(pool: PgPool) {
+        init();
+        let repository = PersonRepositoryImpl::new(pool).await;
+        let result = repository.restore_person(first_person_uuid(), "test").await;
+        assert_not_found(result);
+    }
+
     #[sqlx::test(fixtures("people"))]
     async fn test_get_person_by_uuid(pool: PgPool) {
         init();
         let repository = PersonRepositoryImpl::new(pool).await;
-        let result = repository.get_person_by_uuid(first_person_uuid()).await;
+        let result = repository
+            .get_person_by_uuid(first_person_uuid(), false)
+            .await;
         assert!(result.is_ok());
         let person = result.unwrap();
         assert_eq!(person.name, "John Doe");
@@ -109,7 +236,7 @@ mod tests {
     async fn test_get_person_by_uuid_not_found(pool: PgPool) {
         init();
         let repository = PersonRepositoryImpl::new(pool).await;
-        let result = repository.get_person_by_uuid(invalid_uuid()).await;
+        let result = repository.get_person_by_uuid(invalid_uuid(), false).await;
         assert!(result.is_err());
         assert_not_found(result);
     }
@@ -132,6 +259,7 @@ mod tests {
             email: "update.person@test.com".to_string(),
             changed_by: "testuser".to_string(),
             id: invalid_uuid().to_string(),
+            version: ExpectedVersion::Any,
         };
         let result = repository.update_person(&person_request).await;
         assert!(result.is_err());
@@ -142,7 +270,7 @@ mod tests {
     async fn test_delete_person_not_found(pool: PgPool) {
         init();
         let repository = PersonRepositoryImpl::new(pool).await;
-        let result = repository.delete_person(invalid_uuid()).await;
+        let result = repository.delete_person(invalid_uuid(), "test").await;
         assert!(result.is_err());
         assert_not_found(result);
     }
@@ -156,14 +284,15 @@ mod tests {
             email: "foo@bar.com".to_string(),
             changed_by: "testuser".to_string(),
             id: "invalid-uuid".to_string(),
+            version: ExpectedVersion::Any,
         };
         let result = repository.update_person(&person_request).await;
         assert!(result.is_err());
         match result {
-            Ok(_) => assert!(false),
+            Ok(_) => panic!("Expected an error"),
             Err(e) => match e {
-                RepoError::InvalidUuid(_) => assert!(true),
-                re => assert!(false, "Expected InvalidUuid, got {:?}", re),
+                RepoError::InvalidUuid(_) => (),
+                re => panic!("Expected InvalidUuid, got {:?}", re),
             },
         }
     }
@@ -180,12 +309,10 @@ mod tests {
         let result = repository.create_person(&person_request).await;
         assert!(result.is_err());
         match result {
-            Ok(_) => assert!(false),
+            Ok(_) => panic!("Expected an error"),
             Err(e) => match e {
-                RepoError::UniqueViolation(msg) => {
-                    assert!(true, "Got UniqueViolation with message: {:?}", msg)
-                }
-                re => assert!(false, "Expected UniqueViolation, got {:?}", re),
+                RepoError::UniqueViolation(msg) => assert!(!msg.is_empty()),
+                re => panic!("Expected UniqueViolation, got {:?}", re),
             },
         }
     }
@@ -193,10 +320,10 @@ mod tests {
     // TODO - update to use generics so we can put into helpers
     fn assert_not_found(result: Result<PersonRow, RepoError>) {
         match result {
-            Ok(_) => assert!(false),
+            Ok(_) => panic!("Expected an error"),
             Err(e) => match e {
-                RepoError::NotFound(_) => assert!(true),
-                _ => assert!(false),
+                RepoError::NotFound(_) => (),
+                _ => panic!("Expected NotFound"),
             },
         }
     }
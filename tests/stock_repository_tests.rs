@@ -0,0 +1,229 @@
+#[cfg(test)]
+mod tests {
+    use inventory_service::inventory::model::page::{Cursor, Seek};
+    use inventory_service::inventory::model::{CreateStockMovementRequest, MovementType};
+    use inventory_service::inventory::repositories::stock::{StockRepository, StockRepositoryImpl};
+    use inventory_service::inventory::repositories::RepoError;
+    use inventory_service::test_helpers::{first_item_uuid, init, invalid_uuid, string_to_uuid};
+    use sqlx::PgPool;
+
+    const SECOND_ITEM_UUID: &str = "2492b388-e0b9-47ca-97a1-8f5ba75441ea";
+
+    fn movement(
+        item_id: uuid::Uuid,
+        movement_type: MovementType,
+        quantity: i32,
+    ) -> CreateStockMovementRequest {
+        CreateStockMovementRequest {
+            item_id,
+            movement_type,
+            quantity,
+            note: None,
+            created_by: "testuser".to_string(),
+        }
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_get_stock(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository.get_stock(first_item_uuid()).await;
+        assert!(result.is_ok());
+        let stock = result.unwrap();
+        assert_eq!(stock.item_id, first_item_uuid());
+        assert_eq!(stock.on_hand, 100);
+        assert_eq!(stock.reserved, 10);
+        assert_eq!(stock.available, 90);
+        assert_eq!(stock.last_changed_by, Some("unit_test".to_string()));
+        assert!(stock.last_update.is_some());
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_get_stock_fully_reserved(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository.get_stock(string_to_uuid(SECOND_ITEM_UUID)).await;
+        assert!(result.is_ok());
+        let stock = result.unwrap();
+        assert_eq!(stock.on_hand, 5);
+        assert_eq!(stock.available, 0);
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_get_stock_never_counted(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository.get_stock(first_item_uuid()).await;
+        assert!(result.is_ok());
+        let stock = result.unwrap();
+        assert_eq!(stock.on_hand, 0);
+        assert_eq!(stock.reserved, 0);
+        assert_eq!(stock.available, 0);
+        assert!(stock.last_changed_by.is_none());
+        assert!(stock.last_update.is_none());
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_get_stock_not_found(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository.get_stock(invalid_uuid()).await;
+        match result {
+            Err(RepoError::NotFound(_)) => (),
+            other => panic!("Expected NotFound error, got {:?}", other),
+        }
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_record_movement_first_receipt(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_movement(&movement(first_item_uuid(), MovementType::Receipt, 42))
+            .await;
+        assert!(result.is_ok());
+        let row = result.unwrap();
+        assert_eq!(row.item_id, first_item_uuid());
+        assert_eq!(row.movement_type, MovementType::Receipt);
+        assert_eq!(row.quantity, 42);
+        let stock = repository.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(stock.on_hand, 42);
+        assert_eq!(stock.reserved, 0);
+        assert_eq!(stock.last_changed_by, Some("testuser".to_string()));
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_record_movement_updates_on_hand(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_movement(&movement(first_item_uuid(), MovementType::Sale, -30))
+            .await;
+        assert!(result.is_ok());
+        let stock = repository.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(stock.on_hand, 70);
+        assert_eq!(stock.available, 60);
+        assert_eq!(stock.last_changed_by, Some("testuser".to_string()));
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_record_movement_below_reserved(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_movement(&movement(first_item_uuid(), MovementType::WriteOff, -91))
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        let stock = repository.get_stock(first_item_uuid()).await.unwrap();
+        assert_eq!(stock.on_hand, 100);
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_record_movement_item_not_found(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .record_movement(&movement(invalid_uuid(), MovementType::Receipt, 1))
+            .await;
+        match result {
+            Err(RepoError::NotFound(_)) => (),
+            other => panic!("Expected NotFound error, got {:?}", other),
+        }
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_movements_are_append_only(pool: PgPool) {
+        init();
+        let result = sqlx::query("UPDATE stock_movements SET quantity = 1")
+            .execute(&pool)
+            .await;
+        assert!(result.is_err());
+        let result = sqlx::query("DELETE FROM stock_movements")
+            .execute(&pool)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_get_movements(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .get_movements(
+                first_item_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await;
+        assert!(result.is_ok());
+        let rows = result.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].movement_type, MovementType::Receipt);
+        assert_eq!(rows[0].note, Some("PO-1001".to_string()));
+        assert_eq!(rows[2].movement_type, MovementType::WriteOff);
+        assert_eq!(rows.iter().map(|row| row.quantity).sum::<i32>(), 100);
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_get_movements_paginated(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let first_page = repository
+            .get_movements(
+                first_item_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 2,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(first_page.len(), 2);
+        let second_page = repository
+            .get_movements(
+                first_item_uuid(),
+                &Seek {
+                    after: Some(Cursor::after_id(first_page[1].id)),
+                    limit: 2,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(second_page.len(), 1);
+        assert_eq!(second_page[0].movement_type, MovementType::WriteOff);
+    }
+
+    #[sqlx::test(fixtures("items"))]
+    async fn test_get_movements_none_recorded(pool: PgPool) {
+        init();
+        let repository = StockRepositoryImpl::new(pool).await;
+        let result = repository
+            .get_movements(
+                first_item_uuid(),
+                &Seek {
+                    after: None,
+                    limit: 100,
+                },
+            )
+            .await;
+        assert!(result.unwrap().is_empty());
+    }
+
+    #[sqlx::test(fixtures("items", "stock_levels", "stock_movements"))]
+    async fn test_item_with_movements_is_never_hard_deleted(pool: PgPool) {
+        init();
+        let result = sqlx::query("DELETE FROM items WHERE alt_id = $1")
+            .bind(first_item_uuid())
+            .execute(&pool)
+            .await
+            .map_err(RepoError::from);
+        match result {
+            Err(RepoError::ForeignKeyViolation(err)) => {
+                assert!(err.contains("fk_stock_movements_item_alt_id"))
+            }
+            other => panic!("Expected ForeignKeyViolation error, got {:?}", other),
+        }
+    }
+}
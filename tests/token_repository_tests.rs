@@ -0,0 +1,149 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use inventory_service::inventory::model::Scope;
+    use inventory_service::inventory::repositories::token::{TokenRepository, TokenRepositoryImpl};
+    use inventory_service::inventory::repositories::RepoError;
+    use inventory_service::test_helpers::{init, string_to_uuid};
+    use sha2::{Digest, Sha256};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    const ACTIVE_JTI: &str = "5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0001";
+    const REVOKED_JTI: &str = "5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0002";
+    const REVOKED_CLIENT_JTI: &str = "5b6a1f0e-9a55-4b63-8a1a-1e0e0c1c0003";
+    const REPORTING_FAMILY: &str = "f0000000-0000-0000-0000-000000000001";
+
+    fn digest(refresh_token: &str) -> String {
+        hex::encode(Sha256::digest(refresh_token.as_bytes()))
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_is_access_token_active(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let active = repository
+            .is_access_token_active(string_to_uuid(ACTIVE_JTI))
+            .await;
+        assert!(active.unwrap());
+        let revoked = repository
+            .is_access_token_active(string_to_uuid(REVOKED_JTI))
+            .await;
+        assert!(!revoked.unwrap());
+        let revoked_client = repository
+            .is_access_token_active(string_to_uuid(REVOKED_CLIENT_JTI))
+            .await;
+        assert!(!revoked_client.unwrap());
+        let unknown = repository.is_access_token_active(Uuid::new_v4()).await;
+        assert!(!unknown.unwrap());
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_record_access_token(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let jti = Uuid::new_v4();
+        let result = repository
+            .record_access_token(
+                jti,
+                Uuid::new_v4(),
+                "reporting",
+                Utc::now() + Duration::hours(1),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert!(repository.is_access_token_active(jti).await.unwrap());
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_use_refresh_token(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let result = repository
+            .use_refresh_token(&digest("live-refresh-token"))
+            .await;
+        let redeemed = result.unwrap();
+        assert_eq!(redeemed.client_id, "reporting");
+        assert_eq!(redeemed.scopes, vec![Scope::ItemsRead, Scope::InvoicesRead]);
+        assert_eq!(redeemed.family, string_to_uuid(REPORTING_FAMILY));
+        let again = repository
+            .use_refresh_token(&digest("live-refresh-token"))
+            .await;
+        assert!(matches!(again, Err(RepoError::NotFound(_))));
+        let row = repository
+            .get_refresh_token(&digest("live-refresh-token"))
+            .await
+            .unwrap();
+        assert!(row.used_at.is_some());
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_use_refresh_token_of_revoked_client(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let result = repository
+            .use_refresh_token(&digest("legacy-refresh-token"))
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_use_expired_refresh_token(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        repository
+            .create_refresh_token(
+                &digest("expired-refresh-token"),
+                Uuid::new_v4(),
+                "reporting",
+                Utc::now() - Duration::minutes(1),
+            )
+            .await
+            .unwrap();
+        let result = repository
+            .use_refresh_token(&digest("expired-refresh-token"))
+            .await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_revoke_family(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let result = repository
+            .revoke_family(string_to_uuid(REPORTING_FAMILY))
+            .await;
+        // the live access token and the unused refresh token
+        assert_eq!(result.unwrap(), 2);
+        assert!(!repository
+            .is_access_token_active(string_to_uuid(ACTIVE_JTI))
+            .await
+            .unwrap());
+        let refresh = repository
+            .use_refresh_token(&digest("live-refresh-token"))
+            .await;
+        assert!(matches!(refresh, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_revoke_client_tokens(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let result = repository.revoke_client_tokens("reporting").await;
+        assert_eq!(result.unwrap(), 2);
+        assert!(!repository
+            .is_access_token_active(string_to_uuid(ACTIVE_JTI))
+            .await
+            .unwrap());
+        let again = repository.revoke_client_tokens("reporting").await;
+        assert_eq!(again.unwrap(), 0);
+    }
+
+    #[sqlx::test(fixtures("api_clients", "tokens"))]
+    async fn test_revoke_client_tokens_not_found(pool: PgPool) {
+        init();
+        let repository = TokenRepositoryImpl::new(pool).await;
+        let result = repository.revoke_client_tokens("nobody").await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+}
@@ -0,0 +1,146 @@
+#[cfg(test)]
+mod tests {
+    use inventory_service::inventory::model::money::{Currency, Money};
+    use inventory_service::inventory::model::{CreateInvoiceRequest, CreatePersonRequest};
+    use inventory_service::inventory::repositories::invoice::{
+        InvoiceRepository, InvoiceRepositoryImpl, InvoiceTotals,
+    };
+    use inventory_service::inventory::repositories::person::{
+        PersonRepository, PersonRepositoryImpl,
+    };
+    use inventory_service::inventory::repositories::unit_of_work::{
+        UnitOfWorkFactory, UnitOfWorkFactoryImpl,
+    };
+    use inventory_service::inventory::repositories::RepoError;
+    use inventory_service::inventory::services::invoice::{InvoiceService, InvoiceServiceImpl};
+    use inventory_service::test_helpers::{first_invoice_uuid, first_item_uuid, init};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+
+    fn person_request() -> CreatePersonRequest {
+        CreatePersonRequest {
+            name: "Unit Of Work".to_string(),
+            email: "unit.of.work@example.com".to_string(),
+            created_by: "unit_test".to_string(),
+        }
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_commit_writes_every_step(pool: PgPool) {
+        init();
+        let units = UnitOfWorkFactoryImpl::new(pool.clone()).await;
+        let persons = PersonRepositoryImpl::new(pool.clone()).await;
+        let invoices = InvoiceRepositoryImpl::new(pool).await;
+
+        let unit = units.begin().await.unwrap();
+        let person = unit
+            .persons()
+            .create_person(&person_request())
+            .await
+            .unwrap();
+        let invoice_request = CreateInvoiceRequest {
+            user_id: person.alt_id,
+            currency: Currency::USD,
+            discount: Money::zero(Currency::USD),
+            created_by: "unit_test".to_string(),
+            ..CreateInvoiceRequest::default()
+        };
+        let invoice = unit
+            .invoices()
+            .create(invoice_request, vec![], InvoiceTotals::default())
+            .await
+            .unwrap();
+        // nothing is visible outside the unit until it commits
+        let result = persons.get_person_by_uuid(person.alt_id, false).await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+
+        unit.commit().await.unwrap();
+        assert!(persons
+            .get_person_by_uuid(person.alt_id, false)
+            .await
+            .is_ok());
+        let invoice = invoices.get_by_uuid(invoice.alt_id, false).await.unwrap();
+        assert_eq!(invoice.user_id, person.alt_id);
+        // a committed unit can't be used again
+        let result = unit.persons().create_person(&person_request()).await;
+        assert!(matches!(result, Err(RepoError::Other(_))));
+        assert!(unit.commit().await.is_err());
+    }
+
+    #[sqlx::test(fixtures("people"))]
+    async fn test_dropped_unit_is_rolled_back(pool: PgPool) {
+        init();
+        let units = UnitOfWorkFactoryImpl::new(pool.clone()).await;
+        let persons = PersonRepositoryImpl::new(pool).await;
+
+        let unit = units.begin().await.unwrap();
+        let person = unit
+            .persons()
+            .create_person(&person_request())
+            .await
+            .unwrap();
+        drop(unit);
+
+        let result = persons.get_person_by_uuid(person.alt_id, false).await;
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices"))]
+    async fn test_failed_step_keeps_earlier_steps(pool: PgPool) {
+        init();
+        let units = UnitOfWorkFactoryImpl::new(pool.clone()).await;
+        let persons = PersonRepositoryImpl::new(pool).await;
+
+        let unit = units.begin().await.unwrap();
+        let person = unit
+            .persons()
+            .create_person(&person_request())
+            .await
+            .unwrap();
+        // no stock has been received for the item, so the line is refused
+        let result = unit
+            .invoices()
+            .add_item(first_invoice_uuid(), first_item_uuid(), 1, "unit_test")
+            .await;
+        assert!(matches!(result, Err(RepoError::InsufficientStock(_))));
+        unit.commit().await.unwrap();
+
+        assert!(persons
+            .get_person_by_uuid(person.alt_id, false)
+            .await
+            .is_ok());
+    }
+
+    #[sqlx::test(fixtures("people", "items", "invoices", "stock_levels", "stock_movements"))]
+    async fn test_concurrent_adds_to_one_invoice(pool: PgPool) {
+        init();
+        let invoices = Arc::new(InvoiceRepositoryImpl::new(pool.clone()).await);
+        let units = Arc::new(UnitOfWorkFactoryImpl::new(pool).await);
+        let service = InvoiceServiceImpl::new(invoices.clone(), units);
+
+        // each add locks the invoice before rewriting its totals, so the second waits on the
+        // first instead of deadlocking with it
+        let (first, second) = tokio::join!(
+            service.add_item_to_invoice(
+                first_invoice_uuid(),
+                first_item_uuid(),
+                1,
+                "unit_test".to_string()
+            ),
+            service.add_item_to_invoice(
+                first_invoice_uuid(),
+                first_item_uuid(),
+                1,
+                "unit_test".to_string()
+            )
+        );
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        let lines = invoices.get_items(first_invoice_uuid()).await.unwrap();
+        let line = lines
+            .iter()
+            .find(|line| line.item_id == first_item_uuid())
+            .unwrap();
+        assert_eq!(line.quantity, 4);
+    }
+}